@@ -17,13 +17,17 @@ pub fn derive_scatter(tokens: TokenStream) -> TokenStream {
 
 	let func_names = [
 		(
-			quote!(scatter_ray(&self, __one: &mut Ray, __two: &Hit) -> bool),
-			quote!(scatter_ray(__one, __two)),
+			quote!(scatter_ray<R: rand::Rng>(&self, __one: &mut Ray, __two: &Hit, __three: &mut R) -> bool),
+			quote!(scatter_ray(__one, __two, __three)),
 		),
 		(quote!(requires_uv(&self) -> bool), quote!(requires_uv())),
 		(quote!(is_light(&self) -> bool), quote!(is_light())),
 		(quote!(ls_chance(&self) -> Float), quote!(ls_chance())),
 		(quote!(is_delta(&self) -> bool), quote!(is_delta())),
+		(
+			quote!(is_shadow_catcher(&self) -> bool),
+			quote!(is_shadow_catcher()),
+		),
 		(
 			quote!(scattering_pdf(&self, __one: &Hit, __two: Vec3, __three: Vec3) -> Float),
 			quote!(scattering_pdf(__one, __two, __three)),
@@ -40,6 +44,11 @@ pub fn derive_scatter(tokens: TokenStream) -> TokenStream {
 			quote!(get_emission(&self, __one: &Hit, __two: Vec3) -> Vec3),
 			quote!(get_emission(__one, __two)),
 		),
+		(
+			quote!(base_colour(&self, __one: &Hit, __two: Vec3) -> Vec3),
+			quote!(base_colour(__one, __two)),
+		),
+		(quote!(is_valid(&self) -> bool), quote!(is_valid())),
 	]
 	.into_iter();
 
@@ -82,7 +91,12 @@ pub fn derive_texture(tokens: TokenStream) -> TokenStream {
 			quote!(colour_value(&self, __one: Vec3, __two: Vec3) -> Vec3),
 			quote!(colour_value(__one, __two)),
 		),
+		(
+			quote!(uv_colour_value(&self, __one: Vec2, __two: Vec3, __three: Vec3) -> Vec3),
+			quote!(uv_colour_value(__one, __two, __three)),
+		),
 		(quote!(requires_uv(&self) -> bool), quote!(requires_uv())),
+		(quote!(is_valid(&self) -> bool), quote!(is_valid())),
 	]
 	.into_iter();
 
@@ -133,10 +147,17 @@ pub fn derive_primitive(tokens: TokenStream) -> TokenStream {
 			quote!(get_uv(&self, __one: Vec3) -> Option<Vec2>),
 			quote!(get_uv(__one)),
 		),
-		(quote!(get_sample(&self) -> Vec3), quote!(get_sample())),
 		(
-			quote!(sample_visible_from_point(&self, __one: Vec3) -> Vec3),
-			quote!(sample_visible_from_point(__one)),
+			quote!(get_sample<R: rand::Rng>(&self, __one: &mut R) -> Vec3),
+			quote!(get_sample(__one)),
+		),
+		(
+			quote!(sample_surface<R: rand::Rng>(&self, __one: &mut R) -> (Vec3, Vec3)),
+			quote!(sample_surface(__one)),
+		),
+		(
+			quote!(sample_visible_from_point<R: rand::Rng>(&self, __one: Vec3, __two: Float, __three: &mut R) -> Vec3),
+			quote!(sample_visible_from_point(__one, __two, __three)),
 		),
 		(quote!(area(&self) -> Float), quote!(area())),
 		(
@@ -147,6 +168,9 @@ pub fn derive_primitive(tokens: TokenStream) -> TokenStream {
 			quote!(material_is_light(&self) -> bool),
 			quote!(material_is_light()),
 		),
+		(quote!(object_id(&self) -> u32), quote!(object_id())),
+		(quote!(layers(&self) -> u32), quote!(layers())),
+		(quote!(material(&self) -> Option<&M>), quote!(material())),
 	]
 	.into_iter();
 
@@ -20,6 +20,16 @@ impl AABB {
 	}
 
 	pub fn does_int(&self, ray: &Ray) -> bool {
+		self.entry_distance(ray).is_some()
+	}
+
+	// distance along the ray at which it enters the box, None on a miss
+	pub fn entry_distance(&self, ray: &Ray) -> Option<Float> {
+		self.overlap(ray).map(|(tmin, _)| tmin)
+	}
+
+	// distances along the ray at which it enters and leaves the box, None on a miss
+	pub fn overlap(&self, ray: &Ray) -> Option<(Float, Float)> {
 		let mut t1 = (self.min.x - ray.origin.x) * ray.d_inverse.x;
 		let mut t2 = (self.max.x - ray.origin.x) * ray.d_inverse.x;
 
@@ -53,7 +63,12 @@ impl AABB {
 		let tmin = tmin.max(t1.min(t2));
 		let tmax = tmax.min(t1.max(t2));
 
-		tmax > tmin.max(0.0)
+		let tmin = tmin.max(0.0);
+		if tmax > tmin {
+			Some((tmin, tmax))
+		} else {
+			None
+		}
 	}
 
 	pub fn merge(aabb: &mut Option<Self>, second: Self) {
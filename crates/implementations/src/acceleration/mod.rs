@@ -1,13 +1,15 @@
 use crate::{
 	aabb::{AABound, AABB},
-	acceleration::split::{Split, SplitType},
+	acceleration::split::{Split, SplitType, TRAVERSAL_COST},
 	utility::sort_by_indices,
 	Axis,
 };
 use region::RegionResSlice;
 
+use clap::ValueEnum;
+use rayon::prelude::*;
 use rt_core::*;
-use std::{collections::VecDeque, marker::PhantomData};
+use std::marker::PhantomData;
 
 #[cfg(all(feature = "f64"))]
 use std::f64::EPSILON;
@@ -40,12 +42,25 @@ impl PrimitiveInfo {
 	}
 }
 
+// order in which the children of a node are visited
+#[derive(Debug, ValueEnum, Copy, Clone)]
+pub enum TraversalOrder {
+	// nearest child by the sign of the ray direction along the split axis
+	Axis,
+	// nearest child by the distance at which the ray enters its bounds
+	Distance,
+}
+
 pub struct Bvh<P: Primitive, M: Scatter, S: NoHit<M>> {
 	split_type: SplitType,
+	traversal_order: TraversalOrder,
 	nodes: Vec<Node>,
 	sky: S,
 	pub primitives: RegionResSlice<P>,
 	pub lights: Vec<usize>,
+	// update rebuilds the tree once refitting has raised its SAH cost by this factor
+	pub rebuild_threshold: Float,
+	build_cost: Float,
 	phantom: PhantomData<M>,
 }
 
@@ -56,40 +71,116 @@ where
 	S: NoHit<M>,
 {
 	pub fn new(
-		mut primitives: region::RegionUniqSlice<'_, P>,
+		primitives: region::RegionUniqSlice<'_, P>,
 		sky: S,
 		split_type: SplitType,
+		traversal_order: TraversalOrder,
 	) -> Self {
 		let mut bvh = Self {
 			split_type,
+			traversal_order,
 			nodes: Vec::new(),
 			sky,
-			primitives: primitives.zero_slice(),
+			primitives: primitives.shared(),
 			lights: Vec::new(),
+			rebuild_threshold: 1.5,
+			build_cost: 0.0,
 			phantom: PhantomData,
 		};
-		let mut primitives_info: Vec<PrimitiveInfo> = primitives
+		bvh.build();
+		bvh
+	}
+
+	pub fn with_rebuild_threshold(mut self, threshold: Float) -> Self {
+		self.rebuild_threshold = threshold;
+		self
+	}
+
+	// builds the tree from scratch, reordering the primitives to match it
+	fn build(&mut self) {
+		self.nodes.clear();
+		self.lights.clear();
+		let mut primitives_info: Vec<PrimitiveInfo> = self
+			.primitives
 			.iter()
 			.enumerate()
 			.map(|(index, primitive)| PrimitiveInfo::new::<P, M>(index, primitive))
 			.collect();
 
-		bvh.build_bvh(&mut Vec::new(), 0, &mut primitives_info);
+		// without primitives there are no nodes and every ray misses
+		if !primitives_info.is_empty() {
+			self.build_bvh(&mut Vec::new(), 0, &mut primitives_info);
+		}
 
 		sort_by_indices(
-			&mut primitives,
+			&mut self.primitives,
 			primitives_info.iter().map(|&info| info.index).collect(),
 		);
 
-		for (i, prim) in primitives.iter().enumerate() {
-			if prim.material_is_light() {
-				bvh.lights.push(i);
+		// lights in no layer can't be seen by any ray, leaving them out means rays in every
+		// layer can sample every light
+		for (i, prim) in self.primitives.iter().enumerate() {
+			if prim.material_is_light() && prim.layers() != 0 {
+				self.lights.push(i);
 			}
 		}
+		self.build_cost = self.sah_cost();
+	}
+
+	// expected cost of a ray through the tree, each node's cost weighted by the chance a
+	// ray through the root passes through its bounds
+	pub fn sah_cost(&self) -> Float {
+		let Some(root) = self.nodes.first() else {
+			return 0.0;
+		};
+		let root_area = root.bounds.surface_area().max(EPSILON);
+		self.nodes
+			.iter()
+			.map(|node| {
+				let cost = match node.children {
+					Some(_) => TRAVERSAL_COST,
+					None => node.number_primitives as Float,
+				};
+				cost * node.bounds.surface_area() / root_area
+			})
+			.sum()
+	}
 
-		bvh.primitives = primitives.shared();
+	// fits the bounds of every node around the primitives again after they've moved,
+	// keeping the tree's structure
+	pub fn refit(&mut self) {
+		// children always come after their parent
+		for i in (0..self.nodes.len()).rev() {
+			let node = &self.nodes[i];
+			let mut bounds = None;
+			match node.children {
+				Some(children) => {
+					for child in children {
+						AABB::merge(&mut bounds, self.nodes[child].bounds);
+					}
+				}
+				None => {
+					let range =
+						node.primitive_offset..(node.primitive_offset + node.number_primitives);
+					for primitive in &self.primitives[range] {
+						AABB::merge(&mut bounds, primitive.get_aabb());
+					}
+				}
+			}
+			self.nodes[i].bounds = bounds.unwrap();
+		}
+	}
 
-		bvh
+	// brings the tree up to date with primitives that have moved, refitting it unless
+	// that would leave it rebuild_threshold times as costly as when it was last built.
+	// Returns whether it was rebuilt, which reorders the primitives and lights.
+	pub fn update(&mut self) -> bool {
+		self.refit();
+		if self.sah_cost() > self.rebuild_threshold * self.build_cost {
+			self.build();
+			return true;
+		}
+		false
 	}
 	pub fn number_nodes(&self) -> usize {
 		self.nodes.len()
@@ -126,9 +217,11 @@ where
 
 			let axis = Axis::get_max_axis(&center_bounds.get_extent());
 
-			if (axis.get_axis_value(center_bounds.min) - axis.get_axis_value(center_bounds.max))
-				.abs() < 100.0 * EPSILON
-			{
+			// SAH still divides primitives with the same centroid, their bounds can differ
+			let coincident = (axis.get_axis_value(center_bounds.min)
+				- axis.get_axis_value(center_bounds.max))
+			.abs() < 100.0 * EPSILON;
+			if coincident && !matches!(self.split_type, SplitType::Sah) {
 				for primitive in primitives_info {
 					ordered_primitives.push(primitive.index);
 				}
@@ -142,6 +235,7 @@ where
 					children = Some((
 						self.build_bvh(ordered_primitives, offset, left),
 						self.build_bvh(ordered_primitives, offset + left.len(), right),
+						axis,
 					));
 				} else {
 					for primitive in primitives_info {
@@ -154,74 +248,165 @@ where
 		if let Some(children) = children {
 			self.nodes[node_index].set_child(children.0, 0);
 			self.nodes[node_index].set_child(children.1, 1);
+			self.nodes[node_index].axis = children.2;
 		}
 
 		node_index
 	}
+}
 
-	pub fn get_intersection_candidates(&self, ray: &Ray) -> Vec<(usize, usize)> {
-		let mut offset_len = Vec::new();
+impl<P, M, S> Bvh<P, M, S>
+where
+	P: Primitive,
+	M: Scatter,
+	S: NoHit<M>,
+{
+	// returns the children of a node ordered nearest first
+	fn order_children(&self, node: &Node, children: [usize; 2], ray: &Ray) -> [usize; 2] {
+		let swap = match self.traversal_order {
+			TraversalOrder::Axis => node.axis.get_axis_value(ray.direction) < 0.0,
+			TraversalOrder::Distance => {
+				let distance = |index: usize| {
+					self.nodes[index]
+						.bounds
+						.entry_distance(ray)
+						.unwrap_or(Float::INFINITY)
+				};
+				distance(children[1]) < distance(children[0])
+			}
+		};
+		if swap {
+			[children[1], children[0]]
+		} else {
+			children
+		}
+	}
 
-		let mut node_stack = VecDeque::new();
-		node_stack.push_back(0);
-		while !node_stack.is_empty() {
-			let index = node_stack.pop_front().unwrap();
+	// visits leaves intersected by the ray nearest first, leaves that the ray enters
+	// further than the value returned by the closure are skipped and a negative value
+	// ends the traversal
+	fn traverse<F: FnMut(usize, usize) -> Float>(&self, ray: &Ray, mut leaf: F) {
+		if self.nodes.is_empty() {
+			return;
+		}
+		let mut max_t = Float::INFINITY;
 
+		let mut node_stack = vec![0];
+		while let Some(index) = node_stack.pop() {
 			let node = &self.nodes[index];
 
-			if !node.bounds.does_int(ray) {
-				continue;
+			match node.bounds.entry_distance(ray) {
+				Some(t) if t <= max_t => (),
+				_ => continue,
 			}
 
 			match node.children {
 				Some(children) => {
-					node_stack.push_back(children[0]);
-					node_stack.push_back(children[1]);
+					let [near, far] = self.order_children(node, children, ray);
+					node_stack.push(far);
+					node_stack.push(near);
 				}
 				None => {
-					offset_len.push((node.primitive_offset, node.number_primitives));
+					max_t = max_t.min(leaf(node.primitive_offset, node.number_primitives));
+					if max_t < 0.0 {
+						return;
+					}
 				}
 			}
 		}
-		offset_len
 	}
-}
 
-impl<P, M, S> AccelerationStructure for Bvh<P, M, S>
-where
-	P: Primitive<Material = M>,
-	M: Scatter,
-	S: NoHit<M>,
-{
-	type Object = P;
-	type Material = M;
-	type Sky = S;
-	fn get_intersection_candidates(&self, ray: &Ray) -> Vec<(usize, usize)> {
-		let mut offset_len = Vec::new();
-
-		let mut node_stack = VecDeque::new();
-		node_stack.push_back(0);
-		while !node_stack.is_empty() {
-			let index = node_stack.pop_front().unwrap();
+	// closest hit of up to 64 rays traversing the tree together, a node is visited while
+	// any ray in the packet can still find a closer hit inside it and children are
+	// ordered using the first such ray
+	fn check_hit_packet_masked(
+		&self,
+		rays: &[Ray],
+	) -> Vec<Option<(SurfaceIntersection<P::Material>, usize)>> {
+		debug_assert!(rays.len() <= 64);
+		let mut hits: Vec<Option<(SurfaceIntersection<P::Material>, usize)>> =
+			rays.iter().map(|_| None).collect();
+		let mut max_t = vec![Float::INFINITY; rays.len()];
+		if self.nodes.is_empty() {
+			return hits;
+		}
 
+		let all: u64 = if rays.len() == 64 {
+			u64::MAX
+		} else {
+			(1 << rays.len()) - 1
+		};
+		let mut node_stack = vec![(0, all)];
+		while let Some((index, mask)) = node_stack.pop() {
 			let node = &self.nodes[index];
 
-			if !node.bounds.does_int(ray) {
+			let mut active: u64 = 0;
+			for (i, ray) in rays.iter().enumerate() {
+				if mask & (1 << i) == 0 {
+					continue;
+				}
+				match node.bounds.entry_distance(ray) {
+					Some(t) if t <= max_t[i] => active |= 1 << i,
+					_ => (),
+				}
+			}
+			if active == 0 {
 				continue;
 			}
 
 			match node.children {
 				Some(children) => {
-					node_stack.push_back(children[0]);
-					node_stack.push_back(children[1]);
+					let first = &rays[active.trailing_zeros() as usize];
+					let [near, far] = self.order_children(node, children, first);
+					node_stack.push((far, active));
+					node_stack.push((near, active));
 				}
 				None => {
-					offset_len.push((node.primitive_offset, node.number_primitives));
+					let (offset, len) = (node.primitive_offset, node.number_primitives);
+					for (i, ray) in rays.iter().enumerate() {
+						if active & (1 << i) == 0 {
+							continue;
+						}
+						for index in offset..(offset + len) {
+							if self.primitives[index].layers() & ray.layers == 0 {
+								continue;
+							}
+							if let Some(current_hit) = self.primitives[index].get_int(ray) {
+								if current_hit.hit.t > 0.0 && current_hit.hit.t < max_t[i] {
+									max_t[i] = current_hit.hit.t;
+									hits[i] = Some((current_hit, index));
+								}
+							}
+						}
+					}
 				}
 			}
 		}
+		hits
+	}
+
+	pub fn get_intersection_candidates(&self, ray: &Ray) -> Vec<(usize, usize)> {
+		let mut offset_len = Vec::new();
+		self.traverse(ray, |offset, len| {
+			offset_len.push((offset, len));
+			Float::INFINITY
+		});
 		offset_len
 	}
+}
+
+impl<P, M, S> AccelerationStructure for Bvh<P, M, S>
+where
+	P: Primitive<Material = M>,
+	M: Scatter,
+	S: NoHit<M>,
+{
+	type Object = P;
+	type Material = M;
+	type Sky = S;
+	fn get_intersection_candidates(&self, ray: &Ray) -> Vec<(usize, usize)> {
+		Bvh::get_intersection_candidates(self, ray)
+	}
 
 	fn check_hit_index(&self, ray: &Ray, index: usize) -> Option<SurfaceIntersection<M>> {
 		let object = &self.primitives[index];
@@ -250,6 +435,9 @@ where
 					continue;
 				}
 				let tobject = &self.primitives[current_index];
+				if tobject.layers() & ray.layers == 0 {
+					continue;
+				}
 				// check for hit
 				if let Some(current_hit) = tobject.get_int(ray) {
 					// make sure ray is going forwards
@@ -263,15 +451,14 @@ where
 	}
 
 	fn check_hit(&self, ray: &Ray) -> (SurfaceIntersection<M>, usize) {
-		let offset_lens = self.get_intersection_candidates(ray);
-
 		let mut hit: Option<(SurfaceIntersection<M>, usize)> = None;
 
-		for offset_len in offset_lens {
-			let offset = offset_len.0;
-			let len = offset_len.1;
+		self.traverse(ray, |offset, len| {
 			for index in offset..(offset + len) {
 				let object = &self.primitives[index];
+				if object.layers() & ray.layers == 0 {
+					continue;
+				}
 				// check for hit
 				if let Some(current_hit) = object.get_int(ray) {
 					// make sure ray is going forwards
@@ -290,28 +477,82 @@ where
 					}
 				}
 			}
-		}
+			// nodes entered after the closest hit so far can't contain a closer one
+			match &hit {
+				Some((hit, _)) => hit.hit.t,
+				None => Float::INFINITY,
+			}
+		});
+
 		match hit {
 			None => (self.sky.get_si(ray), usize::MAX),
 			Some(hit) => hit,
 		}
 	}
+	fn occluded(&self, ray: &Ray, max_dist: Float) -> bool {
+		let mut occluded = false;
+		self.traverse(ray, |offset, len| {
+			occluded = self.primitives[offset..(offset + len)]
+				.iter()
+				.any(|object| {
+					object.layers() & ray.layers != 0
+						&& object
+							.get_int(ray)
+							.is_some_and(|hit| hit.hit.t > 0.0 && hit.hit.t < max_dist)
+				});
+			if occluded {
+				-1.0
+			} else {
+				max_dist
+			}
+		});
+		occluded
+	}
+	fn check_hit_packet(&self, rays: &[Ray]) -> Vec<(SurfaceIntersection<M>, usize)> {
+		rays.chunks(64)
+			.flat_map(|rays| {
+				self.check_hit_packet_masked(rays)
+					.into_iter()
+					.zip(rays.iter())
+					.map(|(hit, ray)| match hit {
+						Some(hit) => hit,
+						None => (self.sky.get_si(ray), usize::MAX),
+					})
+					.collect::<Vec<_>>()
+			})
+			.collect()
+	}
+
+	fn intersect_batch(&self, rays: &[Ray]) -> Vec<Option<Hit>> {
+		rays.par_iter()
+			.map(|ray| match self.check_hit(ray) {
+				(_, usize::MAX) => None,
+				(intersection, _) => Some(intersection.hit),
+			})
+			.collect()
+	}
+
 	fn get_pdf_from_index(
 		&self,
 		last_hit: &Hit,
 		light_hit: &Hit,
 		sampled_dir: Vec3,
 		index: usize,
+		layers: u32,
 	) -> Float {
-		let sky_samplable = self.sky.can_sample();
-		let divisor = if sky_samplable {
-			self.lights.len() + 1
-		} else {
+		let lights = if layers == u32::MAX {
 			self.lights.len()
-		} as Float;
+		} else {
+			self.lights
+				.iter()
+				.filter(|&&light| self.primitives[light].layers() & layers != 0)
+				.count()
+		};
+		let sky_samplable = self.sky.can_sample();
+		let divisor = if sky_samplable { lights + 1 } else { lights } as Float;
 
 		if index == usize::MAX {
-			self.sky.pdf(sampled_dir) / divisor
+			self.sky.pdf(last_hit.point, sampled_dir) / divisor
 		} else {
 			self.primitives[index].scattering_pdf(last_hit.point, sampled_dir, light_hit) / divisor
 		}
@@ -322,6 +563,9 @@ where
 	fn get_object(&self, index: usize) -> Option<&P> {
 		self.primitives.get(index)
 	}
+	fn get_object_mut(&mut self, index: usize) -> Option<&mut P> {
+		self.primitives.get_mut(index)
+	}
 	fn sky(&self) -> &S {
 		&self.sky
 	}
@@ -331,6 +575,7 @@ where
 pub struct Node {
 	bounds: AABB,
 	children: Option<[usize; 2]>,
+	axis: Axis,
 	primitive_offset: usize,
 	number_primitives: usize,
 }
@@ -340,6 +585,7 @@ impl Node {
 		Node {
 			bounds,
 			children: None,
+			axis: Axis::X,
 			primitive_offset,
 			number_primitives,
 		}
@@ -359,3 +605,442 @@ impl Node {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		random_sampler::RandomSampler,
+		sphere::Sphere,
+		utility::{random_float, random_unit_vector, test_scene::*},
+		AllPrimitives, RenderMethod, RenderOptions,
+	};
+	use rand::thread_rng;
+	use region::Region;
+
+	#[test]
+	fn traversal_orders_agree() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let primitives: Vec<TestPrimitive> = (0..200)
+			.map(|_| {
+				let center =
+					10.0 * (Vec3::new(random_float(), random_float(), random_float()) - 0.5);
+				AllPrimitives::Sphere(Sphere::new(center, 0.1 + random_float(), material))
+			})
+			.collect();
+
+		let region = Box::leak(Box::new(Region::new()));
+		let axis: TestBvh = Bvh::new(
+			region.alloc_slice(&primitives),
+			sky(Vec3::zero()),
+			SplitType::Sah,
+			TraversalOrder::Axis,
+		);
+		let distance: TestBvh = Bvh::new(
+			region.alloc_slice(&primitives),
+			sky(Vec3::zero()),
+			SplitType::Sah,
+			TraversalOrder::Distance,
+		);
+
+		for _ in 0..10000 {
+			let origin = 12.0 * random_unit_vector();
+			let ray = Ray::new(origin, random_unit_vector(), 0.0);
+
+			let (axis_hit, axis_index) = axis.check_hit(&ray);
+			let (distance_hit, distance_index) = distance.check_hit(&ray);
+			assert_eq!(axis_index, distance_index);
+			if axis_index != usize::MAX {
+				assert_eq!(axis_hit.hit.t, distance_hit.hit.t);
+			}
+		}
+	}
+
+	#[test]
+	fn packets_agree_with_single_rays() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let primitives: Vec<TestPrimitive> = (0..200)
+			.map(|_| {
+				let center =
+					10.0 * (Vec3::new(random_float(), random_float(), random_float()) - 0.5);
+				AllPrimitives::Sphere(Sphere::new(center, 0.1 + random_float(), material))
+			})
+			.collect();
+		let bvh = bvh(primitives, sky(Vec3::zero()));
+
+		for _ in 0..100 {
+			// a spread of rays from one origin, more than fit in a single packet
+			let origin = 12.0 * random_unit_vector();
+			let direction = -origin.normalised();
+			let rays: Vec<Ray> = (0..100)
+				.map(|_| {
+					Ray::new(
+						origin,
+						(direction + 0.3 * random_unit_vector()).normalised(),
+						0.0,
+					)
+				})
+				.collect();
+
+			for (ray, (packet_hit, packet_index)) in rays.iter().zip(bvh.check_hit_packet(&rays)) {
+				let (hit, index) = bvh.check_hit(ray);
+				assert_eq!(index, packet_index);
+				if index != usize::MAX {
+					assert_eq!(hit.hit.t, packet_hit.hit.t);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn intersect_batch() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let primitives: Vec<TestPrimitive> = (0..50)
+			.map(|_| {
+				let center =
+					10.0 * (Vec3::new(random_float(), random_float(), random_float()) - 0.5);
+				AllPrimitives::Sphere(Sphere::new(center, 0.1 + random_float(), material))
+			})
+			.collect();
+		let bvh = bvh(primitives.clone(), sky(Vec3::zero()));
+
+		let rays: Vec<Ray> = (0..1000)
+			.map(|_| Ray::new(12.0 * random_unit_vector(), random_unit_vector(), 0.0))
+			.collect();
+		let hits = bvh.intersect_batch(&rays);
+		assert_eq!(hits.len(), rays.len());
+
+		for (ray, hit) in rays.iter().zip(hits) {
+			let closest = primitives
+				.iter()
+				.filter_map(|p| p.get_int(ray))
+				.map(|si| si.hit.t)
+				.filter(|&t| t > 0.0)
+				.reduce(Float::min);
+			assert_eq!(hit.map(|hit| hit.t), closest);
+		}
+
+		assert!(bvh.intersect_batch(&[]).is_empty());
+	}
+
+	#[test]
+	fn layer_masks() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let mut front = Sphere::new(Vec3::zero(), 1.0, material);
+		front.layers = 0b01;
+		let mut back = Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0, material);
+		back.layers = 0b10;
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(front), AllPrimitives::Sphere(back)],
+			sky(Vec3::zero()),
+		);
+
+		let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::z(), 0.0);
+		let t = |ray: &Ray| {
+			let (si, index) = bvh.check_hit(ray);
+			let packet = bvh.check_hit_packet(&[*ray]);
+			assert_eq!(packet[0].1, index);
+			(index != usize::MAX).then_some(si.hit.t)
+		};
+		assert_eq!(t(&ray), Some(4.0));
+		assert_eq!(t(&ray.with_layers(0b01)), Some(4.0));
+		// the front sphere is passed through
+		assert_eq!(t(&ray.with_layers(0b10)), Some(9.0));
+		assert_eq!(t(&ray.with_layers(0b100)), None);
+
+		// continued rays keep their layers
+		assert_eq!(
+			ray.with_layers(0b10).spawn(Vec3::zero(), Vec3::x()).layers,
+			0b10
+		);
+	}
+
+	#[test]
+	fn empty_scene() {
+		let colour = Vec3::new(0.2, 0.4, 0.6);
+		let bvh = bvh(Vec::new(), sky(colour));
+		assert_eq!(bvh.number_nodes(), 0);
+
+		let ray = Ray::new(Vec3::zero(), Vec3::z(), 0.0);
+		assert_eq!(bvh.check_hit(&ray).1, usize::MAX);
+		assert_eq!(bvh.check_hit_packet(&[ray, ray])[1].1, usize::MAX);
+		assert!(bvh.get_intersection_candidates(&ray).is_empty());
+
+		// every ray sees the sky
+		let camera = camera(Vec3::new(0.0, 0.0, -5.0), Vec3::zero(), 1.0);
+		for render_method in [RenderMethod::Naive, RenderMethod::MIS] {
+			let options = RenderOptions {
+				width: 4,
+				height: 4,
+				samples_per_pixel: 2,
+				render_method,
+				..Default::default()
+			};
+			let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+			for pixel in image.chunks(3) {
+				assert!((Vec3::new(pixel[0], pixel[1], pixel[2]) - colour).mag() < 0.0001);
+			}
+		}
+	}
+
+	#[test]
+	fn occluded() {
+		// a row of spheres along the z axis
+		let material = lambertian(Vec3::one(), 0.5);
+		let spheres = (0..20)
+			.map(|i| {
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 0.0, 2.0 * i as Float),
+					0.5,
+					material,
+				))
+			})
+			.collect();
+		let bvh = bvh(spheres, sky(Vec3::zero()));
+
+		// the nearest sphere is 1.5 away
+		let ray = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::z(), 0.0);
+		assert!(bvh.occluded(&ray, 1.6));
+		assert!(!bvh.occluded(&ray, 1.4));
+		assert!(!bvh.occluded(&ray.with_layers(0), Float::INFINITY));
+		assert!(!bvh.occluded(&Ray::new(ray.origin, -Vec3::z(), 0.0), Float::INFINITY));
+
+		// the traversal stops at the first leaf with a hit
+		let mut leaves = 0;
+		bvh.traverse(&ray, |_, _| {
+			leaves += 1;
+			-1.0
+		});
+		assert_eq!(leaves, 1);
+
+		for _ in 0..1000 {
+			let origin = Vec3::new(random_float(), random_float(), 40.0 * random_float() - 2.0);
+			let ray = Ray::new(origin, random_unit_vector(), 0.0);
+			let max_dist = 10.0 * random_float();
+			let (si, index) = bvh.check_hit(&ray);
+			assert_eq!(
+				bvh.occluded(&ray, max_dist),
+				index != usize::MAX && si.hit.t < max_dist
+			);
+		}
+	}
+
+	// nodes whose bounds the ray passes through, as a traversal looking for any hit
+	fn nodes_visited(bvh: &TestBvh, ray: &Ray) -> usize {
+		let (mut visited, mut node_stack) = (0, vec![0]);
+		while let Some(index) = node_stack.pop() {
+			let node = &bvh.nodes[index];
+			if !node.bounds.does_int(ray) {
+				continue;
+			}
+			visited += 1;
+			if let Some(children) = node.children {
+				node_stack.extend(children);
+			}
+		}
+		visited
+	}
+
+	#[test]
+	fn sah_visits_fewer_nodes() {
+		// marbles: clusters of small spheres of different sizes on a large ground sphere
+		let material = lambertian(Vec3::one(), 0.5);
+		let mut primitives: Vec<TestPrimitive> = vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::new(0.0, -1000.0, 0.0),
+			1000.0,
+			material,
+		))];
+		for _ in 0..20 {
+			let cluster = Vec3::new(40.0 * random_float() - 20.0, 0.0, 40.0 * random_float());
+			let spread = 0.5 + 3.0 * random_float();
+			for _ in 0..50 {
+				let offset = spread * random_unit_vector() * random_float();
+				let radius = 0.05 + 0.2 * random_float() * random_float();
+				let center = cluster + Vec3::new(offset.x, radius, offset.z);
+				primitives.push(AllPrimitives::Sphere(Sphere::new(center, radius, material)));
+			}
+		}
+
+		let region = Box::leak(Box::new(Region::new()));
+		let mut build = |split_type| -> TestBvh {
+			Bvh::new(
+				region.alloc_slice(&primitives),
+				sky(Vec3::zero()),
+				split_type,
+				TraversalOrder::Axis,
+			)
+		};
+		let (sah, middle) = (build(SplitType::Sah), build(SplitType::Middle));
+
+		let rays: Vec<Ray> = (0..2000)
+			.map(|_| {
+				// aimed at the marbles rather than the empty floor between them
+				let origin = Vec3::new(0.0, 5.0, -10.0);
+				let target = primitives[(random_float() * primitives.len() as Float) as usize]
+					.get_sample(&mut thread_rng());
+				Ray::new(origin, target - origin, 0.0)
+			})
+			.collect();
+		let average = |bvh: &TestBvh| {
+			rays.iter()
+				.map(|ray| nodes_visited(bvh, ray))
+				.sum::<usize>() as Float
+				/ rays.len() as Float
+		};
+		let (sah_visited, middle_visited) = (average(&sah), average(&middle));
+		assert!(
+			sah_visited < middle_visited,
+			"{sah_visited} {middle_visited}"
+		);
+	}
+
+	#[test]
+	fn refit_or_rebuild() {
+		// a 10 x 10 grid of spheres
+		let material = lambertian(Vec3::one(), 0.5);
+		let grid = |i: usize| Vec3::new((i % 10) as Float, 0.0, (i / 10) as Float);
+		let primitives = (0..100)
+			.map(|i| AllPrimitives::Sphere(Sphere::new(grid(i), 0.3, material)))
+			.collect();
+		let mut bvh = bvh(primitives, sky(Vec3::zero()));
+		let (built, nodes) = (bvh.sah_cost(), bvh.number_nodes());
+		let moved = |bvh: &mut TestBvh, to: &dyn Fn(usize, Vec3) -> Vec3| {
+			for i in 0..100 {
+				if let Some(AllPrimitives::Sphere(sphere)) = bvh.get_object_mut(i) {
+					sphere.center = to(i, sphere.center);
+				}
+			}
+		};
+
+		// nudging every sphere keeps the tree
+		moved(&mut bvh, &|i, center| {
+			center + 0.05 * (i % 3) as Float * Vec3::y()
+		});
+		assert!(!bvh.update());
+		assert!(bvh.sah_cost() < 1.5 * built);
+		let ray = Ray::new(Vec3::new(0.0, 10.0, 0.0), -Vec3::y(), 0.0);
+		let hit = bvh.check_hit(&ray).0.hit;
+		assert!((hit.t - 9.7).abs() < 0.0001, "{}", hit.t);
+
+		// shuffling them around the grid leaves nodes spanning all of it
+		moved(&mut bvh, &|i, center| {
+			grid((37 * i) % 100) + center.y * Vec3::y()
+		});
+		bvh.refit();
+		let refitted = bvh.sah_cost();
+		assert!(refitted > 1.5 * built);
+		assert!(bvh.update());
+		assert!(bvh.sah_cost() < 0.5 * refitted);
+		assert_eq!(bvh.number_nodes(), nodes);
+		// the rebuilt tree still finds every sphere
+		for i in 0..100 {
+			let center = match bvh.get_object(i) {
+				Some(AllPrimitives::Sphere(sphere)) => sphere.center,
+				_ => unreachable!(),
+			};
+			let ray = Ray::new(center + 5.0 * Vec3::y(), -Vec3::y(), 0.0);
+			assert!((bvh.check_hit(&ray).0.hit.t - 4.7).abs() < 0.0001);
+		}
+	}
+
+	#[test]
+	fn coincident_centroids() {
+		// nested spheres all share a centre
+		let material = lambertian(Vec3::one(), 0.5);
+		let primitives: Vec<TestPrimitive> = (1..=300)
+			.map(|i| AllPrimitives::Sphere(Sphere::new(Vec3::zero(), i as Float, material)))
+			.collect();
+		let region = Box::leak(Box::new(Region::new()));
+		let mut build = |split_type| -> TestBvh {
+			Bvh::new(
+				region.alloc_slice(&primitives),
+				sky(Vec3::zero()),
+				split_type,
+				TraversalOrder::Axis,
+			)
+		};
+
+		assert_eq!(build(SplitType::Middle).number_nodes(), 1);
+		let sah = build(SplitType::Sah);
+		assert!(sah.number_nodes() > 1);
+		let ray = Ray::new(Vec3::new(0.0, 0.0, -500.0), Vec3::z(), 0.0);
+		let (hit, _) = sah.check_hit(&ray);
+		assert!((hit.hit.t - 200.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn reproducible_builds() {
+		// clusters of nested spheres whose centroids tie
+		let material = lambertian(Vec3::one(), 0.5);
+		let primitives: Vec<TestPrimitive> = (0..240)
+			.map(|i| {
+				let centre = Vec3::new((i * 7 % 4) as Float, 0.0, (i % 3) as Float);
+				AllPrimitives::Sphere(Sphere::new(centre, 0.1 + 0.01 * i as Float, material))
+			})
+			.collect();
+		let region = Box::leak(Box::new(Region::new()));
+		for split_type in [SplitType::Sah, SplitType::Middle, SplitType::EqualCounts] {
+			let mut build = || -> TestBvh {
+				Bvh::new(
+					region.alloc_slice(&primitives),
+					sky(Vec3::zero()),
+					split_type,
+					TraversalOrder::Axis,
+				)
+			};
+			let (first, second) = (build(), build());
+			assert_eq!(first.number_nodes(), second.number_nodes());
+			assert_eq!(format!("{:?}", first.nodes), format!("{:?}", second.nodes));
+			let radii = |bvh: &TestBvh| -> Vec<Float> {
+				bvh.primitives
+					.iter()
+					.map(|primitive| match primitive {
+						AllPrimitives::Sphere(sphere) => sphere.radius,
+						_ => unreachable!(),
+					})
+					.collect()
+			};
+			assert_eq!(radii(&first), radii(&second));
+		}
+	}
+
+	#[test]
+	fn lights_found_on_build() {
+		// lights are told apart by their radius
+		let (diffuse, light) = (lambertian(Vec3::one(), 0.5), emissive(Vec3::one(), 4.0));
+		let primitives: Vec<TestPrimitive> = [
+			(0.5, diffuse),
+			(0.2, light),
+			(0.6, diffuse),
+			(0.7, diffuse),
+			(0.4, light),
+			(0.3, light),
+		]
+		.into_iter()
+		.enumerate()
+		.map(|(i, (radius, material))| {
+			let mut sphere = Sphere::new(Vec3::new(2.0 * i as Float, 0.0, 0.0), radius, material);
+			// a light no ray can see
+			if radius == 0.3 {
+				sphere.layers = 0;
+			}
+			AllPrimitives::Sphere(sphere)
+		})
+		.collect();
+		let bvh = bvh(primitives, sky(Vec3::zero()));
+
+		let mut radii: Vec<Float> = bvh
+			.get_samplable()
+			.iter()
+			.map(|&i| match bvh.get_object(i) {
+				Some(AllPrimitives::Sphere(sphere)) => {
+					assert!(sphere.material_is_light());
+					sphere.radius
+				}
+				_ => unreachable!(),
+			})
+			.collect();
+		radii.sort_by(Float::total_cmp);
+		assert_eq!(radii, [0.2, 0.4]);
+	}
+}
@@ -1,9 +1,12 @@
 use crate::{aabb::AABB, acceleration::PrimitiveInfo, Axis};
 use clap::ValueEnum;
 use rt_core::*;
+use std::cmp::Ordering;
 
 const NUM_BUCKETS: usize = 12;
 const MAX_IN_NODE: usize = 255;
+// cost of visiting a node relative to intersecting a primitive
+pub(super) const TRAVERSAL_COST: Float = 0.125;
 
 #[macro_export]
 macro_rules! partition {
@@ -97,11 +100,7 @@ impl Split for SplitType {
 				let mid_index = partition!(primitives_info, closure);
 
 				if mid_index == 0 || mid_index == (len - 1) {
-					primitives_info[0..len].sort_by(|a, b| {
-						axis.get_axis_value(a.center)
-							.partial_cmp(&axis.get_axis_value(b.center))
-							.unwrap()
-					});
+					primitives_info[0..len].sort_by(|a, b| centre_order(axis, a, b));
 				}
 				mid_index
 			}
@@ -109,17 +108,18 @@ impl Split for SplitType {
 			SplitType::Sah => {
 				let len = primitives_info.len();
 
-				if len <= 4 {
-					return split_equal(axis, primitives_info);
-				}
-
-				let mut buckets = [BucketInfo::new(); NUM_BUCKETS];
-
 				let max_val = axis.get_axis_value(center_bounds.max);
 				let min_val = axis.get_axis_value(center_bounds.min);
 
 				let centroid_extent = max_val - min_val;
 
+				// coinciding centroids can't be told apart by bucket
+				if len <= 4 || centroid_extent < 100.0 * EPSILON {
+					return split_equal(axis, primitives_info);
+				}
+
+				let mut buckets = [BucketInfo::new(); NUM_BUCKETS];
+
 				for primitive_info in primitives_info.iter() {
 					let b = calculate_b(axis, primitive_info, min_val, centroid_extent);
 
@@ -131,36 +131,15 @@ impl Split for SplitType {
 					);
 				}
 
+				let (left, right) = (sweep(buckets.iter()), sweep(buckets.iter().rev()));
+				let bounds_sa = bounds.surface_area();
 				let mut costs = [0.0; NUM_BUCKETS - 1];
-				for (i, cost) in costs.iter_mut().enumerate().take(NUM_BUCKETS - 1) {
-					let (mut bounds_left, mut bounds_right) = (None, None);
-					let (mut count_left, mut count_right) = (0, 0);
-
-					for bucket in buckets.iter().take(i + 1) {
-						if bucket.bounds.is_some() {
-							AABB::merge(&mut bounds_left, bucket.bounds.unwrap());
-							count_left += bucket.count;
-						}
-					}
-
-					for bucket in buckets.iter().take(NUM_BUCKETS).skip(i + 1) {
-						if bucket.bounds.is_some() {
-							AABB::merge(&mut bounds_right, bucket.bounds.unwrap());
-							count_right += bucket.count;
-						}
-					}
-
-					let left_sa = bounds_left
-						.map(|bounds: AABB| bounds.surface_area())
-						.unwrap_or(0.0);
-
-					let right_sa = bounds_right
-						.map(|bounds: AABB| bounds.surface_area())
-						.unwrap_or(0.0);
-
-					*cost = 0.125
+				for (i, cost) in costs.iter_mut().enumerate() {
+					let ((count_left, left_sa), (count_right, right_sa)) =
+						(left[i], right[NUM_BUCKETS - 2 - i]);
+					*cost = TRAVERSAL_COST
 						+ (count_left as Float * left_sa + count_right as Float * right_sa)
-							/ bounds.surface_area();
+							/ bounds_sa;
 				}
 
 				let mut min_cost = costs[0];
@@ -186,6 +165,24 @@ impl Split for SplitType {
 	}
 }
 
+// primitive count and surface area of the union of the first one, two, ... buckets,
+// built up in a single pass rather than merging every bucket again for each split
+fn sweep<'a>(buckets: impl Iterator<Item = &'a BucketInfo>) -> [(u32, Float); NUM_BUCKETS - 1] {
+	let mut sweep = [(0, 0.0); NUM_BUCKETS - 1];
+	let (mut bounds, mut count) = (None, 0);
+	for (bucket, side) in buckets.zip(sweep.iter_mut()) {
+		if let Some(bucket_bounds) = bucket.bounds {
+			AABB::merge(&mut bounds, bucket_bounds);
+			count += bucket.count;
+		}
+		*side = (
+			count,
+			bounds.map_or(0.0, |bounds: AABB| bounds.surface_area()),
+		);
+	}
+	sweep
+}
+
 fn calculate_b(axis: &Axis, primitive_info: &PrimitiveInfo, min: Float, extent: Float) -> usize {
 	let absolute_value = axis.get_axis_value(primitive_info.center);
 
@@ -201,10 +198,53 @@ fn calculate_b(axis: &Axis, primitive_info: &PrimitiveInfo, min: Float, extent:
 fn split_equal(axis: &Axis, primitives_info: &mut [PrimitiveInfo]) -> usize {
 	let len = primitives_info.len();
 	let point_mid = len / 2;
-	primitives_info[0..len].sort_by(|a, b| {
-		axis.get_axis_value(a.center)
-			.partial_cmp(&axis.get_axis_value(b.center))
-			.unwrap()
-	});
+	primitives_info[0..len].sort_by(|a, b| centre_order(axis, a, b));
 	point_mid
 }
+
+// primitives with the same centre along the axis keep the order they were given to the
+// BVH in, so the tree doesn't depend on where earlier partitions happened to leave them
+fn centre_order(axis: &Axis, a: &PrimitiveInfo, b: &PrimitiveInfo) -> Ordering {
+	axis.get_axis_value(a.center)
+		.total_cmp(&axis.get_axis_value(b.center))
+		.then(a.index.cmp(&b.index))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::random_float;
+
+	#[test]
+	fn swept_areas() {
+		let random_vec = || Vec3::new(random_float(), random_float(), random_float());
+		for _ in 0..100 {
+			let mut buckets = [BucketInfo::new(); NUM_BUCKETS];
+			for bucket in buckets.iter_mut().filter(|_| random_float() < 0.7) {
+				let min = random_vec();
+				bucket.count = 1 + (random_float() * 10.0) as u32;
+				bucket.bounds = Some(AABB::new(min, min + random_vec()));
+			}
+
+			// areas recomputed from the union of the buckets on each side of every split
+			let union = |buckets: &[BucketInfo]| {
+				let mut bounds = None;
+				for bounds_b in buckets.iter().filter_map(|b| b.bounds) {
+					AABB::merge(&mut bounds, bounds_b);
+				}
+				let count = buckets.iter().map(|b| b.count).sum::<u32>();
+				(count, bounds.map_or(0.0, |b: AABB| b.surface_area()))
+			};
+			let (left, right) = (sweep(buckets.iter()), sweep(buckets.iter().rev()));
+			for i in 0..(NUM_BUCKETS - 1) {
+				let (expected_left, expected_right) =
+					(union(&buckets[..=i]), union(&buckets[(i + 1)..]));
+				let swept_right = right[NUM_BUCKETS - 2 - i];
+				assert_eq!(left[i].0, expected_left.0);
+				assert_eq!(swept_right.0, expected_right.0);
+				assert!((left[i].1 - expected_left.1).abs() < 0.0001);
+				assert!((swept_right.1 - expected_right.1).abs() < 0.0001);
+			}
+		}
+	}
+}
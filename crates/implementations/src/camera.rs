@@ -1,5 +1,5 @@
-use crate::utility::random_float;
 use crate::Camera;
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug)]
@@ -14,6 +14,11 @@ pub struct SimpleCamera {
 	pub v: Vec3,
 	pub lower_left: Vec3,
 	pub lens_radius: Float,
+	// offset of the right eye from the origin, the left eye is offset the other way
+	pub stereo_offset: Option<Vec3>,
+	// ray times are uniform between the shutter opening and closing
+	pub shutter_open: Float,
+	pub shutter_close: Float,
 }
 
 impl SimpleCamera {
@@ -49,16 +54,304 @@ impl SimpleCamera {
 			v,
 			lower_left,
 			lens_radius: aperture / 2.0,
+			stereo_offset: None,
+			shutter_open: 0.0,
+			shutter_close: 1.0,
 		}
 	}
+
+	// the same lens looking from another position, focused on the point looked at
+	pub fn looking_from(&self, origin: Vec3, lookat: Vec3, vup: Vec3) -> Self {
+		let fov = (2.0 * (self.viewport_width / 2.0).atan()).to_degrees();
+		let mut camera = SimpleCamera::new(
+			origin,
+			lookat,
+			vup,
+			fov,
+			self.aspect_ratio,
+			2.0 * self.lens_radius,
+			(origin - lookat).mag(),
+		);
+		camera.stereo_offset = self.stereo_offset.map(|offset| offset.mag() * camera.u);
+		camera.with_shutter(self.shutter_open, self.shutter_close)
+	}
+
+	// renders a side by side stereo pair with the left eye on the left half of the
+	// image, both eyes converge on the focus plane
+	pub fn stereo(mut self, ipd: Float) -> Self {
+		self.stereo_offset = Some(0.5 * ipd * self.u);
+		self
+	}
+
+	// a shorter window blurs less of an object's motion
+	pub fn with_shutter(mut self, open: Float, close: Float) -> Self {
+		self.shutter_open = open;
+		self.shutter_close = close;
+		self
+	}
+
+	// origin of the eye and the horizontal position within its half of the image
+	fn eye(&self, u: Float) -> (Vec3, Float) {
+		match self.stereo_offset {
+			Some(offset) if u < 0.5 => (self.origin - offset, 2.0 * u),
+			Some(offset) => (self.origin + offset, 2.0 * u - 1.0),
+			None => (self.origin, u),
+		}
+	}
+
+	// position of the point on the image and its depth, 1 being the focus plane, the
+	// lens and stereo eyes are ignored and points behind the camera return None
+	pub fn project(&self, point: Vec3) -> Option<(Vec2, Float)> {
+		let to_centre = self.lower_left + 0.5 * (self.horizontal + self.vertical) - self.origin;
+		let offset = point - self.origin;
+		let depth = offset.dot(to_centre) / to_centre.mag_sq();
+		if depth <= 0.0 {
+			return None;
+		}
+		let on_plane = offset / depth - (self.lower_left - self.origin);
+		Some((
+			Vec2::new(
+				on_plane.dot(self.horizontal) / self.horizontal.mag_sq(),
+				on_plane.dot(self.vertical) / self.vertical.mag_sq(),
+			),
+			depth,
+		))
+	}
 }
 
 impl Camera for SimpleCamera {
-	fn get_ray(&self, u: Float, v: Float) -> Ray {
+	fn get_ray<R: Rng>(&self, u: Float, v: Float, rng: &mut R) -> Ray {
+		let (origin, u) = self.eye(u);
+		Ray::new(
+			origin,
+			self.lower_left + self.horizontal * u + self.vertical * v - origin,
+			self.shutter_open + (self.shutter_close - self.shutter_open) * rng.gen::<Float>(),
+		)
+	}
+}
+
+// camera defined by a closure from the position on the image, (0, 0) being the lower
+// left corner, and a lens sample uniform in [0, 1)^2 to a ray, for trying out lens
+// models such as distortion without a type for each
+pub struct CustomCamera<F> {
+	pub get_ray: F,
+}
+
+impl<F> CustomCamera<F>
+where
+	F: Fn(Float, Float, Vec2) -> Ray + Sync,
+{
+	pub fn new(get_ray: F) -> Self {
+		CustomCamera { get_ray }
+	}
+}
+
+impl<F> Camera for CustomCamera<F>
+where
+	F: Fn(Float, Float, Vec2) -> Ray + Sync,
+{
+	fn get_ray<R: Rng>(&self, u: Float, v: Float, rng: &mut R) -> Ray {
+		(self.get_ray)(u, v, Vec2::new(rng.gen(), rng.gen()))
+	}
+}
+
+// 360 degree panorama from a single point, u is the longitude with the centre of the
+// image looking at lookat and v the latitude from straight down to straight up. The
+// left and right edges meet behind the camera and the aspect ratio is ignored.
+#[derive(Debug)]
+pub struct EquirectangularCamera {
+	pub origin: Vec3,
+	pub forward: Vec3,
+	pub right: Vec3,
+	pub up: Vec3,
+}
+
+impl EquirectangularCamera {
+	pub fn new(origin: Vec3, lookat: Vec3, vup: Vec3) -> Self {
+		let forward = (lookat - origin).normalised();
+		let right = forward.cross(vup).normalised();
+		EquirectangularCamera {
+			origin,
+			forward,
+			right,
+			up: right.cross(forward),
+		}
+	}
+}
+
+impl Camera for EquirectangularCamera {
+	fn get_ray<R: Rng>(&self, u: Float, v: Float, rng: &mut R) -> Ray {
+		let longitude = 2.0 * PI * (u - 0.5);
+		let latitude = PI * (v.clamp(0.0, 1.0) - 0.5);
+		let around = longitude.sin() * self.right + longitude.cos() * self.forward;
 		Ray::new(
 			self.origin,
-			self.lower_left + self.horizontal * u + self.vertical * v - self.origin,
-			random_float(),
+			latitude.cos() * around + latitude.sin() * self.up,
+			rng.gen(),
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*, *};
+	use rand::thread_rng;
+
+	#[test]
+	fn stereo_eyes() {
+		let ipd = 0.064;
+		let camera = SimpleCamera::new(
+			Vec3::new(0.0, 1.0, -5.0),
+			Vec3::zero(),
+			Vec3::y(),
+			40.0,
+			1.0,
+			0.0,
+			4.0,
+		)
+		.stereo(ipd);
+
+		let rng = &mut thread_rng();
+		let (left, right) = (
+			camera.get_ray(0.25, 0.5, rng),
+			camera.get_ray(0.75, 0.5, rng),
+		);
+		let separation = right.origin - left.origin;
+		assert!((separation.mag() - ipd).abs() < 0.00001);
+		assert!((separation.normalised() - camera.u).mag() < 0.00001);
+
+		// the centre of each eye looks at the same point on the focus plane
+		let focus = camera.lower_left + 0.5 * (camera.horizontal + camera.vertical);
+		for ray in [left, right] {
+			let to_focus = (focus - ray.origin).normalised();
+			assert!((ray.direction - to_focus).mag() < 0.00001);
+		}
+	}
+
+	#[test]
+	fn shutter_window() {
+		let camera = || SimpleCamera::new(Vec3::zero(), Vec3::z(), Vec3::y(), 40.0, 1.0, 0.0, 1.0);
+		let times = |camera: &SimpleCamera| {
+			(0..1000)
+				.map(|_| camera.get_ray(0.5, 0.5, &mut thread_rng()).time)
+				.fold((Float::MAX, Float::MIN), |(min, max), t| {
+					(min.min(t), max.max(t))
+				})
+		};
+
+		let (min, max) = times(&camera().with_shutter(0.5, 0.51));
+		assert!(min >= 0.5 && max <= 0.51);
+
+		let (min, max) = times(&camera().with_shutter(0.0, 2.0));
+		assert!(min >= 0.0 && max <= 2.0);
+		assert!(max - min > 1.5);
+	}
+
+	#[test]
+	fn barrel_distortion() {
+		let pinhole = SimpleCamera::new(Vec3::zero(), Vec3::z(), Vec3::y(), 60.0, 1.0, 0.0, 1.0);
+		// positions on the image are pulled towards the centre more the further out they are
+		let barrel = CustomCamera::new(|u: Float, v: Float, _| {
+			let (x, y) = (u - 0.5, v - 0.5);
+			let scale = 1.0 - 0.5 * (x * x + y * y);
+			pinhole.get_ray(0.5 + scale * x, 0.5 + scale * y, &mut thread_rng())
+		});
+
+		let rng = &mut thread_rng();
+		let centre = pinhole.get_ray(0.5, 0.5, rng).direction;
+		assert!((barrel.get_ray(0.5, 0.5, rng).direction - centre).mag() < 0.00001);
+
+		let angle = |ray: Ray| ray.direction.dot(centre).clamp(-1.0, 1.0).acos();
+		let mut previous = 0.0;
+		for (u, v) in [(0.6, 0.5), (0.5, 0.2), (0.8, 0.8), (0.0, 1.0)] {
+			let (bent, straight) = (
+				angle(barrel.get_ray(u, v, rng)),
+				angle(pinhole.get_ray(u, v, rng)),
+			);
+			assert!(bent < straight, "{bent} {straight}");
+			// the same side of the image
+			let direction = barrel.get_ray(u, v, rng).direction - centre;
+			assert!(direction.dot(pinhole.get_ray(u, v, rng).direction - centre) > 0.0);
+			// rays further out are bent more
+			assert!(straight - bent > previous);
+			previous = straight - bent;
+		}
+	}
+
+	#[test]
+	fn project_points() {
+		let camera = SimpleCamera::new(
+			Vec3::new(1.0, 2.0, -3.0),
+			Vec3::zero(),
+			Vec3::y(),
+			50.0,
+			1.5,
+			0.0,
+			2.0,
+		);
+		for (u, v) in [(0.5, 0.5), (0.1, 0.9), (0.75, 0.3)] {
+			// three times as far as the point on the focus plane
+			let on_plane = camera.lower_left + u * camera.horizontal + v * camera.vertical;
+			let point = camera.origin + 3.0 * (on_plane - camera.origin);
+			let (uv, depth) = camera.project(point).unwrap();
+			assert!((uv - Vec2::new(u, v)).mag() < 0.00001);
+			assert!((depth - 3.0).abs() < 0.00001);
+		}
+		assert!(camera.project(Vec3::new(2.0, 4.0, -6.0)).is_none());
+	}
+
+	#[test]
+	fn equirectangular_wraps() {
+		let camera = EquirectangularCamera::new(Vec3::zero(), Vec3::z(), Vec3::y());
+		let direction = |u, v| camera.get_ray(u, v, &mut thread_rng()).direction;
+		assert!((direction(0.5, 0.5) - Vec3::z()).mag() < 0.00001);
+		assert!((direction(0.75, 0.5) - camera.right).mag() < 0.00001);
+		assert!((direction(0.5, 1.0) - Vec3::y()).mag() < 0.00001);
+		assert!((direction(0.0, 0.3) - direction(1.0, 0.3)).mag() < 0.00001);
+
+		// a ring of coloured lights around the camera, one straddling the seam behind it
+		let primitives = (0..8)
+			.map(|i| {
+				let angle = i as Float * PI / 4.0;
+				let colour = Vec3::new((i & 1) as Float, ((i >> 1) & 1) as Float, 0.2 * i as Float);
+				AllPrimitives::Sphere(Sphere::new(
+					3.0 * Vec3::new(angle.sin(), 0.0, angle.cos()),
+					1.0,
+					emissive(colour, 1.0),
+				))
+			})
+			.collect();
+		let bvh = bvh(primitives, sky(Vec3::zero()));
+		let (width, height) = (64, 33);
+		let options = RenderOptions {
+			width,
+			height,
+			samples_per_pixel: 64,
+			seed: Some(1),
+			..Default::default()
+		};
+		let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+		let pixel = |x: u64, y: u64| {
+			let c = (3 * (y * width + x)) as usize;
+			Vec3::new(image[c], image[c + 1], image[c + 2])
+		};
+
+		// the sphere behind the camera fills both edges on the horizon
+		let behind = Vec3::new(0.0, 0.0, 0.8);
+		let (first, last) = (0..height)
+			.map(|y| (pixel(0, y), pixel(width - 1, y)))
+			.fold((Vec3::zero(), Vec3::zero()), |(a, b), (first, last)| {
+				(a + first, b + last)
+			});
+		assert!((pixel(0, height / 2) - behind).mag() < 0.0001);
+		assert!((pixel(width - 1, height / 2) - behind).mag() < 0.0001);
+		assert!(
+			(first - last).abs().component_max() < 0.1 * height as Float,
+			"{first:?} {last:?}"
+		);
+		for y in 0..height {
+			assert!((pixel(0, y) - pixel(width - 1, y)).mag() < 0.25, "{y}");
+		}
+	}
+}
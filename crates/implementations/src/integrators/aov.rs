@@ -0,0 +1,400 @@
+use crate::{
+	integrators::*,
+	spherical_sampling::cosine_hemisphere_sampling,
+	utility::{coord::Coordinate, offset_ray},
+	RenderOptions,
+};
+
+// false colours each object by a hash of its id, black for the sky, with every channel a
+// whole number of 255ths so the ids survive being saved as an 8 bit image
+pub fn id_colour(id: u32) -> Vec3 {
+	if id == 0 {
+		return Vec3::zero();
+	}
+	let hash = match ((id as u64).wrapping_mul(0x9e3779b97f4a7c15) >> 40) as u32 {
+		0 => 1,
+		hash => hash,
+	};
+	let channel = |shift: u32| ((hash >> shift) & 0xff) as Float / 255.0;
+	Vec3::new(channel(16), channel(8), channel(0))
+}
+
+// colours the first hit by its object id
+pub struct ObjectIdIntegrator;
+
+impl Integrator for ObjectIdIntegrator {
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
+		_ray: &mut Ray,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		_render_options: &RenderOptions,
+		_rng: &mut R,
+	) -> (Vec3, u64) {
+		let (_, index) = primary;
+
+		let id = match bvh.get_object(index) {
+			Some(object) => object.object_id(),
+			None => 0,
+		};
+
+		(id_colour(id), 1)
+	}
+}
+
+// 1 where a shadow catcher is shadowed from the key light (the first samplable light), 0 elsewhere
+pub struct ShadowMatteIntegrator;
+
+impl Integrator for ShadowMatteIntegrator {
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
+		ray: &mut Ray,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		_render_options: &RenderOptions,
+		rng: &mut R,
+	) -> (Vec3, u64) {
+		let (surface_intersection, _) = primary;
+		let hit = &surface_intersection.hit;
+
+		let light_index = match bvh.get_samplable().first() {
+			Some(&index) if surface_intersection.material.is_shadow_catcher() => index,
+			_ => return (Vec3::zero(), 1),
+		};
+		let light = bvh.get_object(light_index).unwrap();
+
+		let l_wi = light.sample_visible_from_point(hit.point, ray.time, rng);
+		if l_wi.dot(hit.normal) <= 0.0 {
+			return (Vec3::zero(), 1);
+		}
+
+		let origin = offset_ray(hit.point, hit.geometric_normal, hit.error, true);
+		let shadow_ray = Ray::new(origin, l_wi, ray.time);
+		let matte = match bvh.check_hit_index(&shadow_ray, light_index) {
+			Some(_) => 0.0,
+			None => 1.0,
+		};
+
+		(Vec3::one() * matte, 2)
+	}
+}
+
+// sky light reaching the first hit through cosine weighted rays that travel ao_distance
+// without hitting anything, further bounces and the surface's material are ignored
+pub struct AmbientOcclusionIntegrator;
+
+impl Integrator for AmbientOcclusionIntegrator {
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
+		ray: &mut Ray,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		render_options: &RenderOptions,
+		rng: &mut R,
+	) -> (Vec3, u64) {
+		let (surface_intersection, index) = primary;
+		let (hit, mat) = (&surface_intersection.hit, surface_intersection.material);
+
+		if index == usize::MAX {
+			let sky = mat.get_emission(hit, ray.direction);
+			return (render_options.sky_radiance(sky, true), 1);
+		}
+		if mat.is_light() || render_options.ao_samples == 0 {
+			return (mat.get_emission(hit, ray.direction), 1);
+		}
+
+		let coord = Coordinate::new_from_z(hit.normal);
+		let origin = offset_ray(hit.point, hit.geometric_normal, hit.error, true);
+		let mut total = Vec3::zero();
+		for _ in 0..render_options.ao_samples {
+			let ao_ray = ray.spawn(origin, coord.to_coord(cosine_hemisphere_sampling(rng)));
+			if !bvh.occluded(&ao_ray, render_options.ao_distance) {
+				let sky = bvh.sky().get_si(&ao_ray);
+				total += sky.material.get_emission(&sky.hit, ao_ray.direction);
+			}
+		}
+		let ao_samples = render_options.ao_samples;
+		(total / ao_samples as Float, 1 + ao_samples)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		aabox::AABox, moving_sphere::MovingSphere, random_sampler::RandomSampler, sphere::Sphere,
+		utility::test_scene::*, *,
+	};
+	use rand::thread_rng;
+
+	const WIDTH: u64 = 32;
+
+	#[test]
+	fn object_ids() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let mut left = Sphere::new(Vec3::new(-1.0, 0.0, 0.0), 0.5, material);
+		left.object_id = 1;
+		let mut right = Sphere::new(Vec3::new(1.0, 0.0, 0.0), 0.5, material);
+		right.object_id = 2;
+
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(left), AllPrimitives::Sphere(right)],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -5.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: WIDTH,
+			height: WIDTH,
+			render_method: RenderMethod::ObjectId,
+			..Default::default()
+		};
+		let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+
+		for id in 0..3 {
+			let colour = id_colour(id);
+			assert!(image
+				.chunks(3)
+				.any(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]) == colour));
+		}
+
+		// background
+		assert_eq!(&image[0..3], &[0.0; 3]);
+	}
+
+	#[test]
+	fn object_ids_at_edges() {
+		// two touching spheres filling the view so many pixels straddle an edge
+		let material = lambertian(Vec3::one(), 0.5);
+		let mut left = Sphere::new(Vec3::new(-1.0, 0.0, 0.0), 1.0, material);
+		left.object_id = 1;
+		let mut right = Sphere::new(Vec3::new(1.0, 0.0, 0.0), 1.0, material);
+		right.object_id = 2;
+
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(left), AllPrimitives::Sphere(right)],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -5.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 8,
+			width: WIDTH,
+			height: WIDTH,
+			render_method: RenderMethod::ObjectId,
+			..Default::default()
+		};
+		let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+
+		let colours = [id_colour(0), id_colour(1), id_colour(2)];
+		let ids: Vec<usize> = image
+			.chunks(3)
+			.map(|pixel| {
+				let pixel = Vec3::new(pixel[0], pixel[1], pixel[2]);
+				colours.iter().position(|&colour| colour == pixel).unwrap()
+			})
+			.collect();
+		assert!(ids.contains(&1) && ids.contains(&2));
+
+		// the ids survive being quantised to 8 bits
+		for colour in &colours[1..] {
+			for channel in [colour.x, colour.y, colour.z] {
+				let byte = (channel * 255.0).round();
+				assert_eq!(byte / 255.0, channel);
+			}
+		}
+		assert_ne!(colours[1], colours[2]);
+	}
+
+	#[test]
+	fn shadow_matte() {
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, -1000.0, 0.0),
+					1000.0,
+					Box::leak(Box::new(AllMaterials::ShadowCatcher(ShadowCatcher::new()))),
+				)),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 0.5, 0.0),
+					0.5,
+					lambertian(Vec3::one(), 0.5),
+				)),
+				// small distant light standing in for a directional light
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 100.0, 0.0),
+					1.0,
+					emissive(Vec3::one(), 1000.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+
+		let matte = |target: Vec3| {
+			let origin = Vec3::new(0.0, 1.0, -3.0);
+			(0..100)
+				.map(|_| {
+					let mut ray = Ray::new(origin, target - origin, 0.0);
+					ShadowMatteIntegrator::get_colour(
+						&mut ray,
+						&bvh,
+						&RenderOptions::default(),
+						&mut thread_rng(),
+					)
+					.0
+					.x
+				})
+				.sum::<Float>()
+				/ 100.0
+		};
+
+		let shadowed = matte(Vec3::new(0.0, 0.0, -0.3));
+		let unshadowed = matte(Vec3::new(0.0, 0.0, -2.0));
+		assert!(shadowed > unshadowed);
+		assert_eq!(shadowed, 1.0);
+		assert_eq!(unshadowed, 0.0);
+	}
+
+	#[test]
+	fn shadow_matte_at_ray_time() {
+		// a blocker that only moves between the catcher and the light by the end of the shutter
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, -1000.0, 0.0),
+					1000.0,
+					Box::leak(Box::new(AllMaterials::ShadowCatcher(ShadowCatcher::new()))),
+				)),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 100.0, 0.0),
+					1.0,
+					emissive(Vec3::one(), 1000.0),
+				)),
+				AllPrimitives::MovingSphere(MovingSphere::new(
+					Vec3::new(20.0, 2.0, 0.0),
+					Vec3::new(0.0, 2.0, 0.0),
+					0.0,
+					1.0,
+					1.0,
+					lambertian(Vec3::one(), 0.5),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+
+		let matte = |time: Float| {
+			let mut ray = Ray::new(Vec3::new(0.0, 1.0, -3.0), Vec3::new(0.0, -1.0, 3.0), time);
+			ShadowMatteIntegrator::get_colour(
+				&mut ray,
+				&bvh,
+				&RenderOptions::default(),
+				&mut thread_rng(),
+			)
+			.0
+			.x
+		};
+		assert_eq!(matte(0.0), 0.0);
+		assert_eq!(matte(1.0), 1.0);
+	}
+
+	#[test]
+	fn ambient_occlusion() {
+		// a narrow crevice between two walls standing on the ground
+		let material = lambertian(Vec3::one(), 0.5);
+		let boxes = [
+			(Vec3::new(-5.0, -1.0, -5.0), Vec3::new(5.0, 0.0, 5.0)),
+			(Vec3::new(-1.1, 0.0, -5.0), Vec3::new(-0.1, 2.0, 5.0)),
+			(Vec3::new(0.1, 0.0, -5.0), Vec3::new(1.1, 2.0, 5.0)),
+		];
+		let bvh = bvh(
+			boxes
+				.into_iter()
+				.map(|(min, max)| AllPrimitives::AABox(AABox::new(min, max, material)))
+				.collect(),
+			sky(Vec3::one()),
+		);
+
+		let ao = |x: Float, ao_distance: Float| {
+			let options = RenderOptions {
+				render_method: RenderMethod::AO,
+				ao_samples: 64,
+				ao_distance,
+				..Default::default()
+			};
+			let mut total = 0.0;
+			for _ in 0..16 {
+				let mut ray = Ray::new(Vec3::new(x, 5.0, 0.0), -Vec3::y(), 0.0);
+				total += AmbientOcclusionIntegrator::get_colour(
+					&mut ray,
+					&bvh,
+					&options,
+					&mut thread_rng(),
+				)
+				.0
+				.x;
+			}
+			total / 16.0
+		};
+
+		let exposed = ao(4.5, Float::INFINITY);
+		let crevice = ao(0.0, Float::INFINITY);
+		assert!(exposed > 0.8, "{exposed}");
+		assert!(crevice < 0.3, "{crevice}");
+		// walls further away than the ao distance don't count
+		assert_eq!(ao(0.0, 0.05), 1.0);
+	}
+
+	#[test]
+	fn ambient_occlusion_at_ray_time() {
+		// a sphere that only moves over the point by the end of the shutter
+		let bvh = bvh(
+			vec![
+				AllPrimitives::AABox(AABox::new(
+					Vec3::new(-5.0, -1.0, -5.0),
+					Vec3::new(5.0, 0.0, 5.0),
+					lambertian(Vec3::one(), 0.5),
+				)),
+				AllPrimitives::MovingSphere(MovingSphere::new(
+					Vec3::new(20.0, 2.0, 0.0),
+					Vec3::new(0.0, 2.0, 0.0),
+					0.0,
+					1.0,
+					1.5,
+					lambertian(Vec3::one(), 0.5),
+				)),
+			],
+			sky(Vec3::one()),
+		);
+		let options = RenderOptions {
+			render_method: RenderMethod::AO,
+			ao_samples: 256,
+			ao_distance: 5.0,
+			..Default::default()
+		};
+
+		let ao = |time| {
+			let mut ray = Ray::new(Vec3::new(0.0, 1.0, -3.0), Vec3::new(0.0, -1.0, 3.0), time);
+			AmbientOcclusionIntegrator::get_colour(&mut ray, &bvh, &options, &mut thread_rng())
+				.0
+				.x
+		};
+		assert_eq!(ao(0.0), 1.0);
+		// the sphere covers a little over half of the cosine weighted hemisphere
+		let covered = ao(1.0);
+		assert!((0.3..0.6).contains(&covered), "{covered}");
+	}
+}
@@ -0,0 +1,323 @@
+use crate::{
+	primitives::Axis, spherical_sampling::cosine_hemisphere_sampling, utility::coord::Coordinate,
+};
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use rayon::prelude::*;
+use rt_core::*;
+
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
+
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+
+// photons following a specular chain further than this are dropped
+const MAX_PHOTON_DEPTH: u32 = 16;
+
+#[derive(Debug)]
+pub struct Photon {
+	pub point: Vec3,
+	pub power: Vec3,
+	// points back along the path towards the light
+	pub wi: Vec3,
+}
+
+// photons that reached a non delta surface from a light through at least one delta
+// bounce, stored as a balanced kd-tree, the photon in the middle of each range splits
+// it along the axis stored at the same index
+#[derive(Debug)]
+pub struct CausticMap {
+	photons: Vec<Photon>,
+	axes: Vec<Axis>,
+	pub radius: Float,
+}
+
+impl CausticMap {
+	// emitters are treated as two sided like Emit, so photons sent into closed lights
+	// are lost, the sky isn't a photon source. A seeded map is the same on every run.
+	pub fn new<A, P, M>(bvh: &A, photon_count: u64, radius: Float, seed: Option<u64>) -> Self
+	where
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive<Material = M>,
+		M: Scatter,
+	{
+		let lights = bvh.get_samplable();
+		let mut photons: Vec<Photon> = if lights.is_empty() {
+			Vec::new()
+		} else {
+			(0..photon_count)
+				.into_par_iter()
+				.filter_map(|i| {
+					let mut rng = photon_rng(seed, i);
+					let index = lights[rng.gen_range(0..lights.len())];
+					let light = bvh.get_object(index).unwrap();
+					let scale =
+						2.0 * PI * light.area() * lights.len() as Float / photon_count as Float;
+					trace_photon(bvh, light, scale, &mut rng)
+				})
+				.collect()
+		};
+
+		let mut axes = vec![Axis::X; photons.len()];
+		build(&mut photons, &mut axes);
+		CausticMap {
+			photons,
+			axes,
+			radius,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.photons.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.photons.is_empty()
+	}
+
+	// density estimate of the radiance leaving the hit towards -wo from nearby photons
+	pub fn radiance<M: Scatter>(&self, hit: &Hit, wo: Vec3, mat: &M) -> Vec3 {
+		let mut total = Vec3::zero();
+		self.gather(0, self.photons.len(), hit.point, &mut |photon| {
+			let cos = hit.normal.dot(photon.wi);
+			if cos > 0.0 {
+				total += mat.eval(hit, wo, photon.wi) / cos * photon.power;
+			}
+		});
+		total / (PI * self.radius * self.radius)
+	}
+
+	fn gather(&self, start: usize, end: usize, point: Vec3, f: &mut impl FnMut(&Photon)) {
+		if start >= end {
+			return;
+		}
+		let mid = (start + end) / 2;
+		let photon = &self.photons[mid];
+		if (photon.point - point).mag_sq() <= self.radius * self.radius {
+			f(photon);
+		}
+
+		let axis = &self.axes[mid];
+		let offset = axis.get_axis_value(point) - axis.get_axis_value(photon.point);
+		let (near, far) = if offset < 0.0 {
+			((start, mid), (mid + 1, end))
+		} else {
+			((mid + 1, end), (start, mid))
+		};
+		self.gather(near.0, near.1, point, f);
+		if offset.abs() <= self.radius {
+			self.gather(far.0, far.1, point, f);
+		}
+	}
+}
+
+// each photon gets its own stream so a seeded map doesn't depend on how the photons
+// are split between threads
+fn photon_rng(seed: Option<u64>, photon: u64) -> SmallRng {
+	match seed {
+		Some(seed) => SmallRng::seed_from_u64(seed ^ photon.wrapping_mul(0x9e3779b97f4a7c15)),
+		None => SmallRng::from_rng(thread_rng()).unwrap(),
+	}
+}
+
+fn build(photons: &mut [Photon], axes: &mut [Axis]) {
+	if photons.len() <= 1 {
+		return;
+	}
+	let (min, max) = photons.iter().fold(
+		(Vec3::one() * Float::MAX, Vec3::one() * Float::MIN),
+		|(min, max), photon| {
+			(
+				min.min_by_component(photon.point),
+				max.max_by_component(photon.point),
+			)
+		},
+	);
+	let axis = Axis::get_max_axis(&(max - min));
+
+	let mid = photons.len() / 2;
+	photons.select_nth_unstable_by(mid, |a, b| {
+		axis.get_axis_value(a.point)
+			.total_cmp(&axis.get_axis_value(b.point))
+	});
+	axes[mid] = axis;
+
+	let (photons_left, photons_right) = photons.split_at_mut(mid);
+	let (axes_left, axes_right) = axes.split_at_mut(mid);
+	build(photons_left, axes_left);
+	build(&mut photons_right[1..], &mut axes_right[1..]);
+}
+
+fn trace_photon<A, P, M, R>(bvh: &A, light: &P, scale: Float, rng: &mut R) -> Option<Photon>
+where
+	A: AccelerationStructure<Object = P, Material = M>,
+	P: Primitive<Material = M>,
+	M: Scatter,
+	R: Rng,
+{
+	let (point, mut normal) = light.sample_surface(rng);
+	if rng.gen::<Float>() < 0.5 {
+		normal = -normal;
+	}
+	let direction = Coordinate::new_from_z(normal).to_coord(cosine_hemisphere_sampling(rng));
+
+	let emitted = Hit {
+		t: 0.0,
+		point,
+		error: Vec3::zero(),
+		normal,
+		geometric_normal: normal,
+		uv: light.get_uv(point),
+		out: true,
+	};
+	let mut power = scale * light.material()?.get_emission(&emitted, -direction);
+	let mut ray = Ray::new(point + 0.0001 * normal, direction, 0.0);
+
+	for depth in 0..MAX_PHOTON_DEPTH {
+		let (intersection, index) = bvh.check_hit(&ray);
+		if index == usize::MAX || intersection.material.is_light() {
+			return None;
+		}
+		let (hit, mat) = (&intersection.hit, intersection.material);
+
+		let wo = ray.direction;
+		if !mat.is_delta() {
+			return (depth > 0).then_some(Photon {
+				point: hit.point,
+				power,
+				wi: -wo,
+			});
+		}
+		if mat.scatter_ray(&mut ray, hit, rng) {
+			return None;
+		}
+		power *= mat.eval(hit, wo, ray.direction);
+	}
+	None
+}
+
+// tracks where a camera path gathers from the caustic map and which of its light hits
+// the map already accounts for
+#[derive(Default)]
+pub struct CausticPath {
+	gathered: bool,
+	// delta vertices since the gathering vertex, None once a non delta vertex follows it
+	specular_chain: Option<u32>,
+}
+
+impl CausticPath {
+	// called for each vertex of the path in order, returns whether to gather at it
+	pub fn vertex<M: Scatter>(&mut self, mat: &M) -> bool {
+		if !self.gathered {
+			if !mat.is_delta() {
+				self.gathered = true;
+				self.specular_chain = Some(0);
+				return true;
+			}
+			return false;
+		}
+		self.specular_chain = match self.specular_chain {
+			Some(n) if mat.is_delta() => Some(n + 1),
+			_ => None,
+		};
+		false
+	}
+
+	// whether light reached by scattering from the current vertex is in the caustic map
+	pub fn counted(&self) -> bool {
+		self.specular_chain.is_some_and(|n| n > 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{integrators::*, sphere::Sphere, utility::test_scene::*, *};
+
+	#[test]
+	fn kd_tree_gathers_nearby_photons() {
+		let mut photons: Vec<Photon> = (0..1000)
+			.map(|_| Photon {
+				point: Vec3::new(random_float(), random_float(), random_float()),
+				power: Vec3::one(),
+				wi: Vec3::y(),
+			})
+			.collect();
+		let points: Vec<Vec3> = photons.iter().map(|photon| photon.point).collect();
+		let mut axes = vec![Axis::X; photons.len()];
+		build(&mut photons, &mut axes);
+		let map = CausticMap {
+			photons,
+			axes,
+			radius: 0.1,
+		};
+
+		for _ in 0..20 {
+			let point = Vec3::new(random_float(), random_float(), random_float());
+			let mut count = 0;
+			map.gather(0, map.len(), point, &mut |_| count += 1);
+			let expected = points
+				.iter()
+				.filter(|p| (**p - point).mag_sq() <= 0.01)
+				.count();
+			assert_eq!(count, expected);
+		}
+	}
+
+	#[test]
+	fn glass_sphere_caustic() {
+		let glass = Box::leak(Box::new(AllMaterials::Refract(Refract::new(
+			texture(Vec3::one()),
+			1.5,
+		))));
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, -1000.0, 0.0),
+					1000.0,
+					lambertian(Vec3::one(), 0.8),
+				)),
+				AllPrimitives::Sphere(Sphere::new(Vec3::new(0.0, 0.75, 0.0), 0.5, glass)),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 4.0, 0.0),
+					0.001,
+					emissive(Vec3::one(), 1000000.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let map: &'static CausticMap =
+			Box::leak(Box::new(CausticMap::new(&bvh, 100000, 0.05, None)));
+		assert!(!map.is_empty());
+
+		// medians stop the rare paths that do reach the light from dominating
+		let floor = |x: Float, caustics| {
+			let options = RenderOptions {
+				caustics,
+				..Default::default()
+			};
+			let origin = Vec3::new(3.0, 0.2, 0.0);
+			let direction = (Vec3::new(x, 0.0, 0.0) - origin).normalised();
+			let mut samples: Vec<Float> = (0..1001)
+				.map(|_| {
+					let mut ray = Ray::new(origin, direction, 0.0);
+					MisIntegrator::get_colour(&mut ray, &bvh, &options, &mut thread_rng())
+						.0
+						.x
+				})
+				.collect();
+			samples.sort_by(Float::total_cmp);
+			samples[500]
+		};
+
+		// directly lit floor away from the sphere's shadow
+		let lit = floor(1.5, None);
+		assert!(lit > 0.0);
+		assert!((floor(1.5, Some(map)) - lit).abs() / lit < 0.1);
+
+		// the sphere focuses the light to a spot under it
+		let caustic = floor(0.0, Some(map));
+		assert!(caustic > 2.0 * lit);
+		assert!(floor(0.0, None) < 0.1 * caustic);
+	}
+}
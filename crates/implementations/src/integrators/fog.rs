@@ -0,0 +1,129 @@
+use crate::{aabb::AABB, integrators::mis::sample_lights};
+use rand::Rng;
+use rt_core::*;
+
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
+
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+
+// homogeneous fog filling a box, light is absorbed or scattered with the same chance
+// everywhere and colour is the fraction of each channel that scatters instead of being
+// absorbed, scattering is isotropic
+#[derive(Copy, Clone, Debug)]
+pub struct FogVolume {
+	pub bounds: AABB,
+	pub density: Float,
+	pub colour: Vec3,
+}
+
+impl FogVolume {
+	pub fn new(bounds: AABB, density: Float, colour: Vec3) -> Self {
+		FogVolume {
+			bounds,
+			density,
+			colour,
+		}
+	}
+
+	// length of the ray inside the fog before t_max
+	fn distance_inside(&self, ray: &Ray, t_max: Float) -> Float {
+		match self.bounds.overlap(ray) {
+			Some((t0, t1)) => (t1.min(t_max) - t0).max(0.0),
+			None => 0.0,
+		}
+	}
+
+	// fraction of light travelling along the ray before t_max that makes it through
+	pub fn transmittance(&self, ray: &Ray, t_max: Float) -> Float {
+		(-self.density * self.distance_inside(ray, t_max)).exp()
+	}
+
+	// single scattered light from a light sample at a point in the fog, the point is
+	// distributed by transmittance so only the chance of scattering at all remains
+	pub fn in_scattering<A, P, M, R>(&self, ray: &Ray, t_max: Float, bvh: &A, rng: &mut R) -> Vec3
+	where
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	{
+		let (t0, t1) = match self.bounds.overlap(ray) {
+			Some((t0, t1)) if t0 < t_max => (t0, t1.min(t_max)),
+			_ => return Vec3::zero(),
+		};
+		let scatter_chance = 1.0 - (-self.density * (t1 - t0)).exp();
+		if scatter_chance <= 0.0 {
+			return Vec3::zero();
+		}
+		let t = t0 - (1.0 - rng.gen::<Float>() * scatter_chance).ln() / self.density;
+
+		let point = Hit {
+			t,
+			point: ray.at(t),
+			error: Vec3::zero(),
+			normal: Vec3::zero(),
+			geometric_normal: Vec3::zero(),
+			uv: None,
+			out: true,
+		};
+		match sample_lights(bvh, &point, ray.layers, ray.time, rng) {
+			Some((l_wi, le, l_pdf, distance)) => {
+				let shadow = self.transmittance(&Ray::new(point.point, l_wi, ray.time), distance);
+				scatter_chance * shadow * self.colour * le / (4.0 * PI * l_pdf)
+			}
+			None => Vec3::zero(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{integrators::*, sphere::Sphere, utility::test_scene::*, *};
+	use rand::thread_rng;
+
+	#[test]
+	fn fog_box_attenuates_and_tints() {
+		// a white emitter fills the background and a light above the fog lights it
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 100.0, emissive(Vec3::one(), 1.0))),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 3.0, 0.0),
+					0.5,
+					emissive(Vec3::one(), 10.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let fog = FogVolume::new(
+			AABB::new(-Vec3::one(), Vec3::one()),
+			1.0,
+			Vec3::new(1.0, 0.2, 0.2),
+		);
+		let options = RenderOptions {
+			fog: Some(fog),
+			..Default::default()
+		};
+
+		let colour = |origin: Vec3| {
+			let mut total = Vec3::zero();
+			for _ in 0..256 {
+				let mut ray = Ray::new(origin, Vec3::z(), 0.0);
+				total += MisIntegrator::get_colour(&mut ray, &bvh, &options, &mut thread_rng()).0;
+			}
+			total / 256.0
+		};
+
+		assert_eq!(colour(Vec3::new(2.0, 0.0, -5.0)), Vec3::one());
+
+		// two units of fog let through e^-2 of the background
+		let through = colour(Vec3::new(0.0, 0.0, -5.0));
+		assert!(through.x > through.z);
+		assert!(through.y < 1.0 && through.z < 1.0);
+		assert!((through.y - through.z).abs() < 0.01);
+		assert!(through.z > (-2.0 as Float).exp());
+	}
+}
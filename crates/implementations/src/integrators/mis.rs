@@ -1,132 +1,265 @@
-use crate::integrators::*;
+use crate::{integrators::*, LightWeighting};
 use rt_core::*;
 
 pub struct MisIntegrator;
 
 impl Integrator for MisIntegrator {
-	fn get_colour<A: AccelerationStructure<Object = P, Material = M>, P: Primitive, M: Scatter>(
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
 		ray: &mut Ray,
-		bvh: &A,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		render_options: &RenderOptions,
+		rng: &mut R,
 	) -> (Vec3, u64) {
-		let (mut throughput, mut output) = (Vec3::one(), Vec3::zero());
-		let mut ray_count = 0;
+		let (direct, indirect, ray_count) = mis_lighting(ray, primary, bvh, render_options, rng);
+		(direct + indirect, ray_count)
+	}
+}
+
+// traces a MIS path returning (direct, indirect, rays shot). Direct holds emission seen
+// from the camera and light reaching the first hit straight from an emitter, everything
+// arriving after further bounces is indirect. They always sum to the MIS colour.
+pub fn mis_lighting<
+	'a,
+	A: AccelerationStructure<Object = P, Material = M>,
+	P: Primitive,
+	M: Scatter,
+	R: Rng,
+>(
+	ray: &mut Ray,
+	primary: (SurfaceIntersection<'a, M>, usize),
+	bvh: &'a A,
+	render_options: &RenderOptions,
+	rng: &mut R,
+) -> (Vec3, Vec3, u64) {
+	let mut throughput = Vec3::one();
+	let (mut direct, mut indirect) = (Vec3::zero(), Vec3::zero());
+	let mut add = |contribution: Vec3, depth: u32| {
+		let contribution = render_options.clamp_contribution(contribution, depth);
+		if depth <= 1 {
+			direct += contribution;
+		} else {
+			indirect += contribution;
+		}
+	};
+	let mut ray_count = 0;
 
-		let mut wo;
-		let mut hit;
-		let mut mat;
-		let (surface_intersection, _index) = bvh.check_hit(ray);
+	let mut wo;
+	let mut hit;
+	let mut mat;
+	let (surface_intersection, index) = primary;
 
-		(hit, mat) = (surface_intersection.hit, surface_intersection.material);
+	(hit, mat) = (surface_intersection.hit, surface_intersection.material);
 
-		wo = ray.direction;
+	wo = ray.direction;
 
-		let emission = mat.get_emission(&hit, wo);
+	if let Some(fog) = render_options.fog {
+		let t_max = segment_length(&hit, index);
+		ray_count += 1;
+		add(fog.in_scattering(ray, t_max, bvh, rng), 1);
+		throughput *= fog.transmittance(ray, t_max);
+	}
 
-		let exit = mat.scatter_ray(&mut ray.clone(), &hit);
+	let mut emission = mat.get_emission(&hit, wo);
+	if index == usize::MAX {
+		emission = render_options.sky_radiance(emission, true);
+	}
 
-		output += emission;
+	let exit = mat.scatter_ray(&mut ray.clone(), &hit, rng);
 
-		if exit {
-			return (output, ray_count);
+	add(throughput * emission, 0);
+
+	if exit {
+		return (direct, indirect, ray_count);
+	}
+
+	let mut depth = 1;
+	let mut bounces = BounceCounter::default();
+	let mut caustic_path = CausticPath::default();
+
+	while depth < MAX_DEPTH {
+		// covers both the light and material sample taken at this vertex
+		let bounce = bounces.bounce(mat, render_options);
+		if bounce == Bounce::Stop {
+			break;
 		}
 
-		let mut depth = 1;
+		if let Some(map) = render_options.caustics {
+			if caustic_path.vertex(mat) {
+				add(throughput * map.radiance(&hit, wo, mat), depth + 1);
+			}
+		}
 
-		while depth < MAX_DEPTH {
-			// light sampling
-			let sample_lights = sample_lights(bvh, &hit);
+		// light sampling, there is no chance of a light sample matching a delta lobe
+		if !mat.is_delta() {
+			let sample_lights = sample_lights(bvh, &hit, ray.layers, ray.time, rng);
 			ray_count += 1;
-			if let Some((l_wi, le, l_pdf)) = sample_lights {
-				let m_pdf = mat.scattering_pdf(&hit, wo, l_wi);
-				let mis_weight = power_heuristic(l_pdf, m_pdf);
-				output += throughput * mat.eval(&hit, wo, l_wi) * mis_weight * le / l_pdf;
+			if let Some((l_wi, mut le, l_pdf, distance)) = sample_lights {
+				// only the sky is infinitely far away
+				if distance == Float::INFINITY {
+					le = render_options.sky_radiance(le, false);
+				}
+				let mis_weight = match render_options.light_weighting {
+					LightWeighting::Mis => {
+						power_heuristic(l_pdf, mat.scattering_pdf(&hit, wo, l_wi))
+					}
+					LightWeighting::LightSamples => 1.0,
+				};
+				let shadow = match render_options.fog {
+					Some(fog) => fog.transmittance(&Ray::new(hit.point, l_wi, ray.time), distance),
+					None => 1.0,
+				};
+				add(
+					throughput * mat.eval(&hit, wo, l_wi) * mis_weight * shadow * le / l_pdf,
+					depth,
+				);
 			}
+		}
 
-			// material sampling and bounce
-			let exit = mat.scatter_ray(ray, &hit);
-			if exit {
-				break;
-			}
-			let m_wi = ray.direction;
+		// material sampling and bounce
+		let exit = mat.scatter_ray(ray, &hit, rng);
+		if exit {
+			break;
+		}
+		let m_wi = ray.direction;
 
-			let (intersection, index) = bvh.check_hit(ray);
+		let (intersection, index) = bvh.check_hit(ray);
 
-			let m_pdf = mat.scattering_pdf(&hit, wo, m_wi);
-			let le = intersection.material.get_emission(&hit, m_wi);
+		let m_pdf = mat.scattering_pdf(&hit, wo, m_wi);
+		let mut le = intersection.material.get_emission(&hit, m_wi);
+		if index == usize::MAX {
+			le = render_options.sky_radiance(le, false);
+		}
+		if !mat.is_delta() {
 			throughput *= mat.eval_over_scattering_pdf(&hit, wo, m_wi);
-			if le != Vec3::zero() {
-				if (bvh.get_samplable().contains(&index) && !mat.is_delta())
-					|| (index == usize::MAX && bvh.sky().can_sample())
-				{
-					let l_pdf = bvh.get_pdf_from_index(&hit, &intersection.hit, m_wi, index);
+		} else {
+			throughput *= mat.eval(&hit, wo, m_wi);
+		}
+		if let Some(fog) = render_options.fog {
+			let t_max = segment_length(&intersection.hit, index);
+			ray_count += 1;
+			add(
+				throughput * fog.in_scattering(ray, t_max, bvh, rng),
+				depth + 1,
+			);
+			throughput *= fog.transmittance(ray, t_max);
+		}
+		if le != Vec3::zero() && !caustic_path.counted() {
+			if (bvh.get_samplable().contains(&index) && !mat.is_delta())
+				|| (index == usize::MAX && bvh.sky().can_sample())
+			{
+				// already counted by the light sample when it has all of the weight
+				if render_options.light_weighting == LightWeighting::Mis {
+					let l_pdf =
+						bvh.get_pdf_from_index(&hit, &intersection.hit, m_wi, index, ray.layers);
 					let mis_weight = power_heuristic(m_pdf, l_pdf);
-					output += throughput * le * mis_weight;
-				} else {
-					output += throughput * le;
+					add(throughput * le * mis_weight, depth);
 				}
+			} else {
+				add(throughput * le, depth);
 			}
+		}
+
+		if bounce == Bounce::Last || intersection.material.is_light() {
+			break;
+		}
 
-			if intersection.material.is_light() {
+		if render_options.russian_roulette && depth > RUSSIAN_ROULETTE_THRESHOLD {
+			let p = throughput.component_max();
+			if rng.gen::<Float>() > p {
 				break;
 			}
+			throughput /= p;
+		}
 
-			if depth > RUSSIAN_ROULETTE_THRESHOLD {
-				let p = throughput.component_max();
-				let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
-				if rng.gen::<Float>() > p {
-					break;
-				}
-				throughput /= p;
-			}
+		wo = m_wi;
+		hit = intersection.hit;
+		mat = intersection.material;
 
-			wo = m_wi;
-			hit = intersection.hit;
-			mat = intersection.material;
+		depth += 1;
+	}
+	let output = direct + indirect;
+	if output.contains_nan() || !output.is_finite() {
+		return (Vec3::zero(), Vec3::zero(), ray_count);
+	}
+	(direct, indirect, ray_count)
+}
 
-			depth += 1;
-		}
-		if output.contains_nan() || !output.is_finite() {
-			return (Vec3::zero(), ray_count);
-		}
-		(output, ray_count)
+// distance a ray travels to the hit, rays escaping to the sky never end
+fn segment_length(hit: &Hit, index: usize) -> Float {
+	if index == usize::MAX {
+		Float::INFINITY
+	} else {
+		hit.t
 	}
 }
 
-fn sample_lights<A: AccelerationStructure<Object = P, Material = M>, P: Primitive, M: Scatter>(
+pub(crate) fn sample_lights<
+	A: AccelerationStructure<Object = P, Material = M>,
+	P: Primitive,
+	M: Scatter,
+	R: Rng,
+>(
 	bvh: &A,
 	hit: &Hit,
-) -> Option<(Vec3, Vec3, Float)> {
-	//l_wi, le, l_pdf
+	layers: u32,
+	time: Float,
+	rng: &mut R,
+) -> Option<(Vec3, Vec3, Float, Float)> {
+	//l_wi, le, l_pdf, distance to the light, only lights in one of the layers are sampled
+	//and moving ones are sampled where they are at the time
 	let sky = bvh.sky();
-	let samplable_len = bvh.get_samplable().len();
+	let in_layers = || {
+		bvh.get_samplable()
+			.iter()
+			.copied()
+			.filter(move |&index| bvh.get_object(index).unwrap().layers() & layers != 0)
+	};
+	// rays in every layer can sample every light without going through them
+	let samplable = bvh.get_samplable();
+	let samplable_len = if layers == u32::MAX {
+		samplable.len()
+	} else {
+		in_layers().count()
+	};
 	let sky_can_sample = sky.can_sample();
 
-	let sample_sky = |pdf_multiplier: Float| {
-		let l_wi = sky.sample();
-		let ray = Ray::new(hit.point + 0.0001 * hit.normal, l_wi, 0.0);
+	let sample_sky = |pdf_multiplier: Float, rng: &mut R| {
+		let l_wi = sky.sample(hit.point, rng);
+		let ray = Ray::new(hit.point + 0.0001 * hit.normal, l_wi, time);
 
-		let (sa, index) = bvh.check_hit(&ray);
-		if index == usize::MAX {
-			let le = sa.material.get_emission(hit, l_wi);
-			let l_pdf = sky.pdf(l_wi);
-			return Some((l_wi, le, l_pdf * pdf_multiplier));
+		if !bvh.occluded(&ray, Float::INFINITY) {
+			let le = sky.get_si(&ray).material.get_emission(hit, l_wi);
+			let l_pdf = sky.pdf(hit.point, l_wi);
+			return Some((l_wi, le, l_pdf * pdf_multiplier, Float::INFINITY));
 		}
 		None
 	};
 
-	let sample_light = |pdf_multiplier: Float, index: usize| {
-		let index = bvh.get_samplable()[index];
+	let sample_light = |pdf_multiplier: Float, index: usize, rng: &mut R| {
+		let index = if layers == u32::MAX {
+			samplable[index]
+		} else {
+			in_layers().nth(index).unwrap()
+		};
 		let light = bvh.get_object(index).unwrap();
 
-		let l_wi = light.sample_visible_from_point(hit.point);
+		let l_wi = light.sample_visible_from_point(hit.point, time, rng);
 
-		if let Some(si) =
-			bvh.check_hit_index(&Ray::new(hit.point + 0.0001 * hit.normal, l_wi, 0.0), index)
-		{
+		if let Some(si) = bvh.check_hit_index(
+			&Ray::new(hit.point + 0.0001 * hit.normal, l_wi, time),
+			index,
+		) {
 			let l_pdf = light.scattering_pdf(hit.point, l_wi, &si.hit);
 			if l_pdf > 0.0 {
 				let le = si.material.get_emission(&si.hit, l_wi);
-				return Some((l_wi, le, l_pdf * pdf_multiplier));
+				return Some((l_wi, le, l_pdf * pdf_multiplier, si.hit.t));
 			}
 		}
 		None
@@ -134,24 +267,287 @@ fn sample_lights<A: AccelerationStructure<Object = P, Material = M>, P: Primitiv
 
 	match (samplable_len, sky_can_sample) {
 		(0, false) => None,
-		(0, true) => sample_sky(1.0),
+		(0, true) => sample_sky(1.0, rng),
 		(_, false) => {
 			let multipler = 1.0 / samplable_len as Float;
-			let light_index = SmallRng::from_rng(thread_rng())
-				.unwrap()
-				.gen_range(0..samplable_len);
-			sample_light(multipler, light_index)
+			let light_index = rng.gen_range(0..samplable_len);
+			sample_light(multipler, light_index, rng)
 		}
 		(_, true) => {
 			let multipler = 1.0 / (samplable_len + 1) as Float;
-			let light_index = SmallRng::from_rng(thread_rng())
-				.unwrap()
-				.gen_range(0..=samplable_len);
+			let light_index = rng.gen_range(0..=samplable_len);
 			if light_index == samplable_len {
-				sample_sky(multipler)
+				sample_sky(multipler, rng)
 			} else {
-				sample_light(multipler, light_index)
+				sample_light(multipler, light_index, rng)
 			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		aabox::AABox, moving_sphere::MovingSphere, random_sampler::RandomSampler, sphere::Sphere,
+		utility::test_scene::*, *,
+	};
+	use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+
+	#[test]
+	fn small_light_converges_faster() {
+		// box open towards the camera lit by a small panel under its ceiling
+		let wall = lambertian(Vec3::new(0.8, 0.6, 0.5), 0.7);
+		let rect =
+			|min: Vec3, max: Vec3, material| AllPrimitives::AABox(AABox::new(min, max, material));
+		let bvh = bvh(
+			vec![
+				rect(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, -1.0, 1.0), wall),
+				rect(Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), wall),
+				rect(Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, 1.0, 1.0), wall),
+				rect(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(-1.0, 1.0, 1.0), wall),
+				rect(Vec3::new(1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), wall),
+				rect(
+					Vec3::new(-0.15, 0.9, -0.15),
+					Vec3::new(0.15, 0.9, 0.15),
+					emissive(Vec3::one(), 40.0),
+				),
+			],
+			sky(Vec3::zero()),
+		);
+		// the light is out of view so its edges don't add noise to both
+		let camera = camera(Vec3::new(0.0, -0.5, -3.5), Vec3::new(0.0, -0.5, 0.0), 1.0);
+		let options = |render_method, samples_per_pixel| RenderOptions {
+			samples_per_pixel,
+			width: 8,
+			height: 8,
+			render_method,
+			..Default::default()
+		};
+
+		let (reference, _) = render(
+			&RandomSampler,
+			options(RenderMethod::MIS, 1024),
+			&camera,
+			&bvh,
+		);
+		let error = |render_method| {
+			let (image, _) = render(&RandomSampler, options(render_method, 16), &camera, &bvh);
+			image
+				.iter()
+				.zip(reference.iter())
+				.map(|(a, b)| (a - b).powi(2))
+				.sum::<Float>()
+		};
+		// light sampling finds the panel from every bounce instead of waiting to hit it
+		let (mis, naive) = (error(RenderMethod::MIS), error(RenderMethod::Naive));
+		assert!(mis < 0.5 * naive, "{mis} {naive}");
+
+		// both converge to the same image
+		let mean = |image: &[Float]| image.iter().sum::<Float>() / image.len() as Float;
+		let (naive, _) = render(
+			&RandomSampler,
+			options(RenderMethod::Naive, 1024),
+			&camera,
+			&bvh,
+		);
+		let (naive, reference) = (mean(&naive), mean(&reference));
+		assert!(
+			(naive - reference).abs() < 0.15 * reference,
+			"{naive} {reference}"
+		);
+	}
+
+	#[test]
+	fn light_counted_once() {
+		// a floor under a single panel light
+		let floor = lambertian(Vec3::one(), 0.8);
+		let bvh = bvh(
+			vec![
+				AllPrimitives::AABox(AABox::new(
+					Vec3::new(-5.0, 0.0, -5.0),
+					Vec3::new(5.0, 0.0, 5.0),
+					floor,
+				)),
+				AllPrimitives::AABox(AABox::new(
+					Vec3::new(-0.5, 1.0, -0.5),
+					Vec3::new(0.5, 1.0, 0.5),
+					emissive(Vec3::one(), 4.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.5, -3.0), Vec3::zero(), 1.0);
+		let mean = |render_method, light_weighting| {
+			let options = RenderOptions {
+				samples_per_pixel: 256,
+				width: 8,
+				height: 8,
+				render_method,
+				light_weighting,
+				..Default::default()
+			};
+			let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+			image.iter().sum::<Float>() / image.len() as Float
+		};
+
+		// material sampling alone can't count a light twice
+		let reference = mean(RenderMethod::Naive, LightWeighting::Mis);
+		for light_weighting in [LightWeighting::Mis, LightWeighting::LightSamples] {
+			let brightness = mean(RenderMethod::MIS, light_weighting);
+			assert!(
+				(brightness - reference).abs() < 0.1 * reference,
+				"{light_weighting:?} {brightness} {reference}"
+			);
+		}
+	}
+
+	#[test]
+	fn sphere_light_penumbra() {
+		// a sphere light 4 above the ground with a plane covering x < 0 half way between,
+		// the shadow's edge is at x = 0 and the penumbra reaches the light's radius either side
+		let visibility = |radius: Float, xs: &[Float]| -> Vec<Float> {
+			let bvh = bvh(
+				vec![
+					AllPrimitives::Sphere(Sphere::new(
+						Vec3::new(0.0, 4.0, 0.0),
+						radius,
+						emissive(Vec3::one(), 1.0),
+					)),
+					AllPrimitives::AABox(AABox::new(
+						Vec3::new(-10.0, 2.0, -10.0),
+						Vec3::new(0.0, 2.0, 10.0),
+						lambertian(Vec3::one(), 0.5),
+					)),
+				],
+				sky(Vec3::zero()),
+			);
+			xs.iter()
+				.map(|&x| {
+					let hit = Hit {
+						t: 1.0,
+						point: Vec3::new(x, 0.0, 0.0),
+						error: Vec3::zero(),
+						normal: Vec3::y(),
+						geometric_normal: Vec3::y(),
+						uv: None,
+						out: true,
+					};
+					// seeded as a point just inside the penumbra sees little of the light
+					let mut rng = SmallRng::seed_from_u64(x.to_bits() as u64);
+					let unoccluded = (0..1000)
+						.filter(|_| sample_lights(&bvh, &hit, u32::MAX, 0.0, &mut rng).is_some())
+						.count();
+					unoccluded as Float / 1000.0
+				})
+				.collect()
+		};
+
+		// a point sized light casts a hard edge
+		let hard = visibility(0.001, &[-0.05, 0.05]);
+		assert!(hard[0] == 0.0 && hard[1] > 0.99, "{hard:?}");
+
+		// a larger one fades in across the penumbra
+		let soft = visibility(1.0, &[-1.5, -0.5, 0.0, 0.5, 1.5]);
+		assert_eq!((soft[0], soft[4]), (0.0, 1.0), "{soft:?}");
+		assert!(
+			soft[1] > 0.0 && soft[3] < 1.0 && soft.windows(2).all(|w| w[0] < w[1]),
+			"{soft:?}"
+		);
+		assert!((soft[2] - 0.5).abs() < 0.1, "{soft:?}");
+	}
+
+	#[test]
+	fn light_layers() {
+		// a light above the point in the first layer and one to its side in the second
+		let mut above = Sphere::new(Vec3::new(0.0, 2.0, 0.0), 0.1, emissive(Vec3::one(), 1.0));
+		above.layers = 0b01;
+		let mut side = Sphere::new(Vec3::new(2.0, 2.0, 0.0), 0.1, emissive(Vec3::one(), 1.0));
+		side.layers = 0b10;
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(above), AllPrimitives::Sphere(side)],
+			sky(Vec3::zero()),
+		);
+		let hit = Hit {
+			t: 1.0,
+			point: Vec3::zero(),
+			error: Vec3::zero(),
+			normal: Vec3::y(),
+			geometric_normal: Vec3::y(),
+			uv: None,
+			out: true,
+		};
+
+		// the only light in the layer gets every sample and all of the pdf
+		for _ in 0..100 {
+			let (l_wi, _, l_pdf, distance) =
+				sample_lights(&bvh, &hit, 0b01, 0.0, &mut thread_rng()).unwrap();
+			assert!(l_wi.x.abs() < 0.1, "{l_wi:?}");
+			let light_hit = Hit {
+				point: distance * l_wi,
+				..hit
+			};
+			let pdf = bvh.get_pdf_from_index(&hit, &light_hit, l_wi, 0, 0b01);
+			assert!((pdf - l_pdf).abs() < 0.001 * l_pdf, "{pdf} != {l_pdf}");
+		}
+		let sideways = (0..1000)
+			.filter_map(|_| sample_lights(&bvh, &hit, u32::MAX, 0.0, &mut thread_rng()))
+			.filter(|(l_wi, ..)| l_wi.x > 0.5)
+			.count();
+		assert!((400..600).contains(&sideways), "{sideways}");
+
+		// every layer takes the shortcut past the mask and has to agree with masking
+		let (l_wi, _, l_pdf, distance) =
+			sample_lights(&bvh, &hit, u32::MAX, 0.0, &mut thread_rng()).unwrap();
+		let light_hit = Hit {
+			point: distance * l_wi,
+			..hit
+		};
+		let sampled = |&index: &usize| match bvh.get_object(index) {
+			Some(AllPrimitives::Sphere(sphere)) => (sphere.center.x > 1.0) == (l_wi.x > 0.5),
+			_ => false,
+		};
+		let index = *bvh
+			.get_samplable()
+			.iter()
+			.find(|index| sampled(index))
+			.unwrap();
+		let all = bvh.get_pdf_from_index(&hit, &light_hit, l_wi, index, u32::MAX);
+		let both = bvh.get_pdf_from_index(&hit, &light_hit, l_wi, index, 0b11);
+		assert_eq!(all, both);
+		assert!((all - l_pdf).abs() < 0.001 * l_pdf, "{all} != {l_pdf}");
+	}
+
+	#[test]
+	fn moving_light() {
+		// a light that only moves over the point by the end of the shutter
+		let bvh = bvh(
+			vec![AllPrimitives::MovingSphere(MovingSphere::new(
+				Vec3::new(20.0, 2.0, 0.0),
+				Vec3::new(0.0, 2.0, 0.0),
+				0.0,
+				1.0,
+				0.5,
+				emissive(Vec3::one(), 1.0),
+			))],
+			sky(Vec3::zero()),
+		);
+		let hit = Hit {
+			t: 1.0,
+			point: Vec3::zero(),
+			error: Vec3::zero(),
+			normal: Vec3::y(),
+			geometric_normal: Vec3::y(),
+			uv: None,
+			out: true,
+		};
+
+		for _ in 0..100 {
+			let (l_wi, le, _, distance) =
+				sample_lights(&bvh, &hit, u32::MAX, 1.0, &mut thread_rng()).unwrap();
+			assert!(l_wi.y > 0.9, "{l_wi:?}");
+			assert!((1.5..2.0).contains(&distance), "{distance}");
+			assert_eq!(le, Vec3::one());
+		}
+	}
+}
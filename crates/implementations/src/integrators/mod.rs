@@ -1,68 +1,230 @@
-use crate::rt_core::*;
-use rand::rngs::SmallRng;
-use rand::thread_rng;
+use crate::{rt_core::*, RenderOptions};
 use rand::Rng;
-use rand::SeedableRng;
 
 const MAX_DEPTH: u32 = 50;
 const RUSSIAN_ROULETTE_THRESHOLD: u32 = 3;
 
+pub mod aov;
+pub mod caustics;
+pub mod fog;
 pub mod mis;
+#[cfg(feature = "spectral")]
+pub mod spectral;
+pub use aov::*;
+pub use caustics::*;
+pub use fog::*;
 pub use mis::*;
+#[cfg(feature = "spectral")]
+pub use spectral::*;
+
+// counts bounces of each lobe type along a path
+#[derive(Default)]
+struct BounceCounter {
+	diffuse: u32,
+	specular: u32,
+}
+
+// how a path goes on after a bounce
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Bounce {
+	Continue,
+	// past the diffuse limit, light reaching the vertex is still added but the path ends
+	// at the next hit
+	Last,
+	Stop,
+}
+
+impl BounceCounter {
+	// records a bounce off the material
+	fn bounce<M: Scatter>(&mut self, mat: &M, render_options: &RenderOptions) -> Bounce {
+		if mat.is_delta() {
+			self.specular += 1;
+		} else {
+			self.diffuse += 1;
+		}
+		if self.specular > render_options.max_specular_depth {
+			Bounce::Stop
+		} else if self.diffuse > render_options.max_diffuse_depth {
+			Bounce::Last
+		} else {
+			Bounce::Continue
+		}
+	}
+}
 
 pub trait Integrator {
-	fn get_colour<A: AccelerationStructure<Object = P, Material = M>, P: Primitive, M: Scatter>(
+	// traces the path from the primary ray, when an AOV pass needs the primary hit the
+	// samplers find it with primary_hit and continue from it instead. Every random choice
+	// along the path comes from rng so a seeded render is repeatable.
+	fn get_colour<
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
 		ray: &mut Ray,
 		bvh: &A,
+		render_options: &RenderOptions,
+		rng: &mut R,
+	) -> (Vec3, u64) {
+		let primary = primary_hit(ray, bvh, render_options.clip_plane);
+		Self::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+	}
+
+	// continues a path from an already found first hit of the primary ray
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
+		ray: &mut Ray,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		render_options: &RenderOptions,
+		rng: &mut R,
 	) -> (Vec3, u64);
 }
 
+// first hits of a bundle of primary rays, traced as a packet unless a clip plane is set
+pub fn primary_hits<
+	'a,
+	A: AccelerationStructure<Object = P, Material = M>,
+	P: Primitive,
+	M: Scatter,
+>(
+	rays: &mut [Ray],
+	bvh: &'a A,
+	clip_plane: Option<(Vec3, Vec3)>,
+) -> Vec<(SurfaceIntersection<'a, M>, usize)> {
+	match clip_plane {
+		Some(_) => rays
+			.iter_mut()
+			.map(|ray| primary_hit(ray, bvh, clip_plane))
+			.collect(),
+		None => bvh.check_hit_packet(rays),
+	}
+}
+
+// first hit of a primary ray ignoring anything on the negative side of the clip plane,
+// if the ray starts behind the plane it is moved forward onto the plane
+pub fn primary_hit<
+	'a,
+	A: AccelerationStructure<Object = P, Material = M>,
+	P: Primitive,
+	M: Scatter,
+>(
+	ray: &mut Ray,
+	bvh: &'a A,
+	clip_plane: Option<(Vec3, Vec3)>,
+) -> (SurfaceIntersection<'a, M>, usize) {
+	let (point, normal) = match clip_plane {
+		Some(plane) => plane,
+		None => return bvh.check_hit(ray),
+	};
+
+	let distance = (ray.origin - point).dot(normal);
+	let cos = ray.direction.dot(normal);
+	let t_plane = -distance / cos;
+
+	if distance < 0.0 {
+		if cos <= 0.0 {
+			return (bvh.sky().get_si(ray), usize::MAX);
+		}
+		*ray = ray.spawn(ray.at(t_plane), ray.direction);
+		return bvh.check_hit(ray);
+	}
+
+	let hit = bvh.check_hit(ray);
+	if hit.1 != usize::MAX && cos < 0.0 && hit.0.hit.t > t_plane {
+		return (bvh.sky().get_si(ray), usize::MAX);
+	}
+	hit
+}
+
 pub struct NaiveIntegrator;
 
 impl Integrator for NaiveIntegrator {
-	fn get_colour<A: AccelerationStructure<Object = P, Material = M>, P: Primitive, M: Scatter>(
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
 		ray: &mut Ray,
-		bvh: &A,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		render_options: &RenderOptions,
+		rng: &mut R,
 	) -> (Vec3, u64) {
 		let (mut throughput, mut output) = (Vec3::one(), Vec3::zero());
 		let mut depth = 0;
 		let mut ray_count = 0;
+		let mut bounces = BounceCounter::default();
+		let mut last = false;
+		let mut caustic_path = CausticPath::default();
+		let mut primary = Some(primary);
 
 		while depth < MAX_DEPTH {
-			let hit_info = bvh.check_hit(ray);
+			let hit_info = match primary.take() {
+				Some(hit) => hit,
+				None => bvh.check_hit(ray),
+			};
 
 			ray_count += 1;
 
-			let (surface_intersection, _index) = hit_info;
+			let (surface_intersection, index) = hit_info;
 			let (hit, mat) = (&surface_intersection.hit, &surface_intersection.material);
 
 			let wo = ray.direction;
 
-			let emission = mat.get_emission(hit, wo);
+			let mut emission = mat.get_emission(hit, wo);
+			if index == usize::MAX {
+				emission = render_options.sky_radiance(emission, depth == 0);
+			}
 
-			let exit = mat.scatter_ray(ray, hit);
+			let exit = mat.scatter_ray(ray, hit, rng);
 
 			if depth == 0 {
-				output += emission;
+				output += render_options.clamp_contribution(emission, 0);
 				if exit {
 					break;
 				}
 			}
 
 			if exit {
-				output += throughput * emission;
+				if !caustic_path.counted() {
+					output += render_options.clamp_contribution(throughput * emission, depth);
+				}
 				break;
 			}
 
+			if last {
+				break;
+			}
+			match bounces.bounce(*mat, render_options) {
+				Bounce::Continue => (),
+				Bounce::Last => last = true,
+				Bounce::Stop => break,
+			}
+
+			if let Some(map) = render_options.caustics {
+				if caustic_path.vertex(*mat) {
+					let caustic = throughput * map.radiance(hit, wo, *mat);
+					output += render_options.clamp_contribution(caustic, depth + 1);
+				}
+			}
+
 			if !mat.is_delta() {
 				throughput *= mat.eval_over_scattering_pdf(hit, wo, ray.direction);
 			} else {
 				throughput *= mat.eval(hit, wo, ray.direction);
 			}
 
-			if depth > RUSSIAN_ROULETTE_THRESHOLD {
+			if render_options.russian_roulette && depth > RUSSIAN_ROULETTE_THRESHOLD {
 				let p = throughput.component_max();
-				let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
 				if rng.gen::<Float>() > p {
 					break;
 				}
@@ -77,3 +239,250 @@ impl Integrator for NaiveIntegrator {
 		(output, ray_count)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*, *};
+	use rand::thread_rng;
+
+	const WIDTH: u64 = 16;
+
+	fn render_clipped(clip_plane: Option<(Vec3, Vec3)>) -> Vec<Float> {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				emissive(Vec3::one(), 1.0),
+			))],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: WIDTH,
+			height: WIDTH,
+			clip_plane,
+			..Default::default()
+		};
+		render(&RandomSampler, options, &camera, &bvh).0
+	}
+
+	fn pixel(image: &[Float], x: u64, y: u64) -> Float {
+		image[(3 * (y * WIDTH + x)) as usize]
+	}
+
+	#[test]
+	fn clip_plane_bisects_sphere() {
+		let image = render_clipped(Some((Vec3::zero(), Vec3::x())));
+
+		let y = WIDTH / 2;
+		// camera right is +x so only the right half of the sphere remains
+		assert_eq!(pixel(&image, WIDTH / 2 - 2, y), 0.0);
+		assert_eq!(pixel(&image, WIDTH / 2 + 2, y), 1.0);
+	}
+
+	fn render_mirrors(max_diffuse_depth: u32, max_specular_depth: u32) -> Vec<Float> {
+		let mirror = Box::leak(Box::new(AllMaterials::Reflect(Reflect::new(
+			texture(Vec3::one()),
+			0.0,
+		))));
+		// light is behind the camera so it can only be seen in the mirror
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 5.0, mirror)),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 0.0, 3.0),
+					1.0,
+					emissive(Vec3::one(), 1.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::zero(), -Vec3::z(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: WIDTH,
+			height: WIDTH,
+			render_method: RenderMethod::Naive,
+			max_diffuse_depth,
+			max_specular_depth,
+			..Default::default()
+		};
+		render(&RandomSampler, options, &camera, &bvh).0
+	}
+
+	#[test]
+	fn specular_depth() {
+		assert!(render_mirrors(50, 0).iter().all(|&v| v == 0.0));
+		assert!(render_mirrors(0, 1).iter().any(|&v| v > 0.0));
+	}
+
+	#[test]
+	fn diffuse_depth() {
+		// a diffuse sphere lit by a light behind the camera, the sphere can't light itself
+		// so all of its light is direct
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 1.0, lambertian(Vec3::one(), 0.8))),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 0.0, -6.0),
+					2.0,
+					emissive(Vec3::one(), 1.0),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let mean = |render_method, max_diffuse_depth| {
+			let options = RenderOptions {
+				samples_per_pixel: 256,
+				width: WIDTH,
+				height: WIDTH,
+				render_method,
+				max_diffuse_depth,
+				..Default::default()
+			};
+			let image = render(&RandomSampler, options, &camera, &bvh).0;
+			image.iter().sum::<Float>() / image.len() as Float
+		};
+		for render_method in [RenderMethod::Naive, RenderMethod::MIS] {
+			let (direct, full) = (mean(render_method, 0), mean(render_method, 50));
+			assert!(direct > 0.0);
+			assert!((direct - full).abs() / full < 0.05, "{direct} {full}");
+		}
+	}
+
+	#[test]
+	fn reference_matches_russian_roulette() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			gradient_sky(Vec3::zero(), Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 256,
+			width: WIDTH,
+			height: WIDTH,
+			..Default::default()
+		};
+		let reference = RenderOptions {
+			sampler: SamplerType::Adaptive,
+			..options
+		}
+		.reference();
+		assert!(matches!(reference.sampler, SamplerType::Random));
+		assert!(!reference.russian_roulette);
+		assert!(reference.epsilon_converge.is_none() && reference.sample_mask.is_none());
+
+		let mean = |options| {
+			let image = render(&RandomSampler, options, &camera, &bvh).0;
+			image.iter().sum::<Float>() / image.len() as Float
+		};
+		let (rr, reference) = (mean(options), mean(reference));
+		assert!((rr - reference).abs() / reference < 0.02);
+	}
+
+	#[test]
+	fn camera_behind_clip_plane() {
+		let image = render_clipped(Some((Vec3::new(0.0, 0.0, -2.0), Vec3::z())));
+		assert_eq!(pixel(&image, WIDTH / 2, WIDTH / 2), 1.0);
+
+		let image = render_clipped(Some((Vec3::new(0.0, 0.0, -2.0), -Vec3::z())));
+		assert!(image.iter().all(|&v| v == 0.0));
+	}
+
+	#[test]
+	fn clamp_schedule() {
+		let schedule = ClampSchedule::new(1.0, 4.0);
+		let bright = Vec3::new(10.0, 5.0, 0.0);
+		assert_eq!(schedule.clamp(bright, 0), Vec3::new(1.0, 0.5, 0.0));
+		assert_eq!(schedule.clamp(bright, 1), Vec3::new(4.0, 2.0, 0.0));
+		assert_eq!(schedule.clamp(bright, 2), bright);
+
+		// the sky is seen directly in the background and after one bounce on the sphere
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(10.0 * Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		for render_method in [RenderMethod::Naive, RenderMethod::MIS] {
+			let options = RenderOptions {
+				samples_per_pixel: 16,
+				width: WIDTH,
+				height: WIDTH,
+				render_method,
+				clamp: Some(schedule),
+				..Default::default()
+			};
+			let image = render(&RandomSampler, options, &camera, &bvh).0;
+			assert_eq!(pixel(&image, 0, 0), 1.0);
+			let centre = pixel(&image, WIDTH / 2, WIDTH / 2);
+			assert!(centre > 1.0 && centre <= 4.0, "{centre}");
+		}
+		assert!(RenderOptions::default().reference().clamp.is_none());
+	}
+
+	#[test]
+	fn sky_clamp() {
+		// rough floor facing +z under a sky that brightens towards +z
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(0.0, 0.0, -1000.0),
+				1000.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			gradient_sky(20.0 * Vec3::one(), Vec3::zero()),
+		);
+		let colour = |direction: Vec3, sky_clamp, render_method| {
+			let options = RenderOptions {
+				render_method,
+				sky_clamp,
+				..Default::default()
+			};
+			let mut ray = Ray::new(Vec3::z(), direction, 0.0);
+			match render_method {
+				RenderMethod::Naive => {
+					NaiveIntegrator::get_colour(&mut ray, &bvh, &options, &mut thread_rng())
+						.0
+						.x
+				}
+				_ => {
+					MisIntegrator::get_colour(&mut ray, &bvh, &options, &mut thread_rng())
+						.0
+						.x
+				}
+			}
+		};
+
+		for render_method in [RenderMethod::Naive, RenderMethod::MIS] {
+			let background = Vec3::new(0.0, 0.6, 0.8);
+			assert_eq!(
+				colour(background, Some(1.0), render_method),
+				colour(background, None, render_method)
+			);
+
+			let variance = |sky_clamp| {
+				let samples: Vec<Float> = (0..4096)
+					.map(|_| colour(-Vec3::z(), sky_clamp, render_method))
+					.collect();
+				let mean = samples.iter().sum::<Float>() / samples.len() as Float;
+				samples
+					.iter()
+					.map(|s| (s - mean) * (s - mean))
+					.sum::<Float>() / samples.len() as Float
+			};
+			let (full, clamped) = (variance(None), variance(Some(1.0)));
+			assert!(clamped < 0.1 * full, "{clamped} {full}");
+		}
+		assert!(RenderOptions::default().reference().sky_clamp.is_none());
+	}
+}
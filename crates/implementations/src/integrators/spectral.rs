@@ -0,0 +1,119 @@
+use crate::{
+	integrators::{Bounce, BounceCounter, Integrator, MAX_DEPTH, RUSSIAN_ROULETTE_THRESHOLD},
+	spectral::{sample_wavelength, upsample},
+	RenderOptions,
+};
+use rand::Rng;
+use rt_core::*;
+
+// naive path tracing of a single random wavelength, every rgb along the path is
+// upsampled to a spectrum and the radiance at the wavelength is converted back to rgb
+pub struct SpectralIntegrator;
+
+impl Integrator for SpectralIntegrator {
+	fn get_colour_from_hit<
+		'a,
+		A: AccelerationStructure<Object = P, Material = M>,
+		P: Primitive,
+		M: Scatter,
+		R: Rng,
+	>(
+		ray: &mut Ray,
+		primary: (SurfaceIntersection<'a, M>, usize),
+		bvh: &'a A,
+		render_options: &RenderOptions,
+		rng: &mut R,
+	) -> (Vec3, u64) {
+		let (wavelength, weight) = sample_wavelength(rng);
+		let (mut throughput, mut output) = (1.0, Vec3::zero());
+		let mut ray_count = 0;
+		let mut bounces = BounceCounter::default();
+		let mut last = false;
+		let mut primary = Some(primary);
+
+		for depth in 0..MAX_DEPTH {
+			let (surface_intersection, index) = match primary.take() {
+				Some(hit) => hit,
+				None => bvh.check_hit(ray),
+			};
+			ray_count += 1;
+			let (hit, mat) = (&surface_intersection.hit, &surface_intersection.material);
+			let wo = ray.direction;
+
+			let mut emission = mat.get_emission(hit, wo);
+			if index == usize::MAX {
+				emission = render_options.sky_radiance(emission, depth == 0);
+			}
+			let radiance = throughput * upsample(emission, wavelength) * weight;
+			output += render_options.clamp_contribution(radiance, depth);
+
+			if last || mat.scatter_ray(ray, hit, rng) {
+				break;
+			}
+			match bounces.bounce(*mat, render_options) {
+				Bounce::Continue => (),
+				Bounce::Last => last = true,
+				Bounce::Stop => break,
+			}
+
+			let factor = if mat.is_delta() {
+				mat.eval(hit, wo, ray.direction)
+			} else {
+				mat.eval_over_scattering_pdf(hit, wo, ray.direction)
+			};
+			throughput *= upsample(factor, wavelength);
+
+			if render_options.russian_roulette && depth > RUSSIAN_ROULETTE_THRESHOLD {
+				let p = throughput.min(1.0);
+				if rng.gen::<Float>() > p {
+					break;
+				}
+				throughput /= p;
+			}
+		}
+		if output.contains_nan() || !output.is_finite() {
+			return (Vec3::zero(), ray_count);
+		}
+		(output, ray_count)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*, *};
+
+	#[test]
+	fn matches_rgb() {
+		// a grey sphere under a coloured sky
+		let sky_colour = Vec3::new(0.2, 0.4, 0.8);
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.5),
+			))],
+			sky(sky_colour),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -4.0), Vec3::zero(), 1.0);
+		let mean = |render_method| {
+			let options = RenderOptions {
+				width: 8,
+				height: 8,
+				samples_per_pixel: 512,
+				render_method,
+				..Default::default()
+			};
+			let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+			let total = image
+				.chunks(3)
+				.fold(Vec3::zero(), |a, p| a + Vec3::new(p[0], p[1], p[2]));
+			total / (image.len() / 3) as Float
+		};
+		let (spectral, naive) = (mean(RenderMethod::Spectral), mean(RenderMethod::Naive));
+		assert!(
+			(spectral - naive).abs().component_max() < 0.05,
+			"{spectral:?} {naive:?}"
+		);
+	}
+}
@@ -5,6 +5,8 @@ mod materials;
 mod primitives;
 mod samplers;
 mod sky;
+#[cfg(feature = "spectral")]
+pub mod spectral;
 mod statistics;
 mod textures;
 mod utility;
@@ -20,5 +22,6 @@ pub use statistics::*;
 pub use textures::*;
 pub use utility::*;
 
+pub use integrators::{id_colour, CausticMap, FogVolume};
 pub use primitives::triangle::Triangle;
 pub use rt_core;
@@ -1,4 +1,5 @@
-use crate::{textures::Texture, utility::offset_ray};
+use crate::textures::Texture;
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug, Clone)]
@@ -21,8 +22,10 @@ where
 	T: Texture,
 {
 	fn get_emission(&self, hit: &Hit, wo: Vec3) -> Vec3 {
-		let point = offset_ray(hit.point, hit.normal, hit.error, true);
-		self.strength * self.texture.colour_value(wo, point)
+		self.strength * self.texture.hit_colour(wo, hit)
+	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
 	}
 	fn scattering_pdf(&self, _hit: &Hit, _wo: Vec3, _wi: Vec3) -> Float {
 		unreachable!()
@@ -30,10 +33,50 @@ where
 	fn is_light(&self) -> bool {
 		true
 	}
+	// the colour of the light without its strength
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		self.texture.hit_colour(wo, hit)
+	}
 	fn eval(&self, _hit: &Hit, _: Vec3, _: Vec3) -> Vec3 {
 		unreachable!()
 	}
-	fn scatter_ray(&self, _: &mut Ray, _: &Hit) -> bool {
+	fn scatter_ray<R: Rng>(&self, _: &mut Ray, _: &Hit, _: &mut R) -> bool {
 		true
 	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid() && self.strength >= 0.0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ImageTexture;
+
+	#[test]
+	fn uv_emission() {
+		// a red and a blue texel side by side
+		let texture = ImageTexture {
+			data: vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)],
+			dim: (1, 0),
+			rotation: 0.0,
+		};
+		let emit = Emit::new(&texture, 2.0);
+		assert!(emit.requires_uv());
+
+		let emission = |u: Float| {
+			let hit = Hit {
+				t: 1.0,
+				point: Vec3::zero(),
+				error: Vec3::zero(),
+				normal: Vec3::z(),
+				geometric_normal: Vec3::z(),
+				uv: Some(Vec2::new(u, 0.5)),
+				out: true,
+			};
+			emit.get_emission(&hit, Vec3::z())
+		};
+		assert_eq!(emission(0.25), Vec3::new(2.0, 0.0, 0.0));
+		assert_eq!(emission(0.75), Vec3::new(0.0, 0.0, 2.0));
+	}
 }
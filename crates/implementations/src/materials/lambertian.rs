@@ -1,5 +1,5 @@
 use crate::{textures::Texture, utility::offset_ray};
-use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug, Clone)]
@@ -27,15 +27,15 @@ impl<'a, T> Scatter for Lambertian<'a, T>
 where
 	T: Texture,
 {
-	fn scatter_ray(&self, ray: &mut Ray, hit: &Hit) -> bool {
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, rng: &mut R) -> bool {
 		let direction = crate::statistics::bxdfs::lambertian::sample(
 			ray.direction, // no negation since lambertian::sample doesn't use ray.direction
 			hit.normal,
-			&mut SmallRng::from_rng(thread_rng()).unwrap(),
+			rng,
 		);
 
-		let point = offset_ray(hit.point, hit.normal, hit.error, true);
-		*ray = Ray::new(point, direction, ray.time);
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, true);
+		*ray = ray.spawn(point, direction);
 
 		false
 	}
@@ -43,9 +43,18 @@ where
 		crate::statistics::bxdfs::lambertian::pdf(wo, wi, hit.normal)
 	}
 	fn eval(&self, hit: &Hit, wo: Vec3, wi: Vec3) -> Vec3 {
-		self.texture.colour_value(wo, hit.point) * self.albedo * hit.normal.dot(wi).max(0.0) / PI
+		self.texture.hit_colour(wo, hit) * self.albedo * hit.normal.dot(wi).max(0.0) / PI
 	}
 	fn eval_over_scattering_pdf(&self, hit: &Hit, wo: Vec3, _: Vec3) -> Vec3 {
-		self.texture.colour_value(wo, hit.point) * self.albedo
+		self.texture.hit_colour(wo, hit) * self.albedo
+	}
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		self.texture.hit_colour(wo, hit) * self.albedo
+	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
+	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid() && self.albedo >= 0.0
 	}
 }
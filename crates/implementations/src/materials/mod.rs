@@ -5,11 +5,14 @@ pub mod emissive;
 pub mod lambertian;
 pub mod reflect;
 pub mod refract;
+pub mod rough_dielectric;
+pub mod shadow_catcher;
 pub mod trowbridge_reitz;
 
 pub use crate::{
 	materials::{
 		emissive::Emit, lambertian::Lambertian, reflect::Reflect, refract::Refract,
+		rough_dielectric::RoughDielectric, shadow_catcher::ShadowCatcher,
 		trowbridge_reitz::TrowbridgeReitz,
 	},
 	textures::Texture,
@@ -22,4 +25,6 @@ pub enum AllMaterials<'a, T: Texture> {
 	TrowbridgeReitz(TrowbridgeReitz<'a, T>),
 	Reflect(Reflect<'a, T>),
 	Refract(Refract<'a, T>),
+	RoughDielectric(RoughDielectric<'a, T>),
+	ShadowCatcher(ShadowCatcher),
 }
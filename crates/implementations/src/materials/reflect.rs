@@ -1,7 +1,7 @@
 use crate::{
-	textures::Texture,
-	utility::{offset_ray, random_unit_vector},
+	statistics::spherical_sampling::random_unit_vector, textures::Texture, utility::offset_ray,
 };
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug, Clone)]
@@ -17,27 +17,81 @@ where
 	pub fn new(texture: &'a T, fuzz: Float) -> Self {
 		Reflect { texture, fuzz }
 	}
+
+	// perturbs the mirror direction by a point in a sphere of radius fuzz, rejecting
+	// directions that end up below the surface so they aren't absorbed, if every
+	// attempt fails (only likely at grazing angles) the mirror direction is used
+	fn fuzzed<R: Rng>(&self, reflected: Vec3, normal: Vec3, rng: &mut R) -> Vec3 {
+		const ATTEMPTS: usize = 16;
+		if self.fuzz == 0.0 {
+			return reflected;
+		}
+		(0..ATTEMPTS)
+			.map(|_| (reflected + self.fuzz * random_unit_vector(rng)).normalised())
+			.find(|direction| direction.dot(normal) > 0.0)
+			.unwrap_or(reflected)
+	}
 }
 
 impl<'a, T> Scatter for Reflect<'a, T>
 where
 	T: Texture,
 {
-	fn scatter_ray(&self, ray: &mut Ray, hit: &Hit) -> bool {
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, rng: &mut R) -> bool {
 		let mut direction = -ray.direction;
 		direction.reflect(hit.normal);
-		let point = offset_ray(hit.point, hit.normal, hit.error, true);
-		*ray = Ray::new(
-			point,
-			direction + self.fuzz * random_unit_vector(),
-			ray.time,
-		);
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, true);
+		*ray = ray.spawn(point, self.fuzzed(direction, hit.normal, rng));
 		false
 	}
 	fn eval(&self, hit: &Hit, wo: Vec3, _: Vec3) -> Vec3 {
-		self.texture.colour_value(wo, hit.point)
+		self.texture.hit_colour(wo, hit)
+	}
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		self.texture.hit_colour(wo, hit)
 	}
 	fn is_delta(&self) -> bool {
 		true
 	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
+	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::test_scene::texture;
+	use rand::thread_rng;
+
+	#[test]
+	fn fuzz_stays_above_surface() {
+		let reflect = Reflect::new(texture(Vec3::one()), 1.0);
+		let hit = Hit {
+			t: 1.0,
+			point: Vec3::zero(),
+			error: Vec3::zero(),
+			normal: Vec3::y(),
+			geometric_normal: Vec3::y(),
+			uv: None,
+			out: true,
+		};
+
+		for i in 0..10000 {
+			// every other ray grazes the surface where most fuzzed directions would go below
+			let direction = if i % 2 == 0 {
+				let d = random_unit_vector(&mut thread_rng());
+				Vec3::new(d.x, -d.y.abs() - 0.01, d.z)
+			} else {
+				Vec3::new(1.0, -0.001, 0.0)
+			};
+			let mut ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), direction, 0.0);
+			assert!(!reflect.scatter_ray(&mut ray, &hit, &mut thread_rng()));
+			assert!(ray.direction.dot(hit.normal) > 0.0);
+			assert!((ray.direction.mag() - 1.0).abs() < 0.0001);
+		}
+	}
 }
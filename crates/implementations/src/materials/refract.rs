@@ -1,8 +1,5 @@
-use crate::{
-	materials::reflect::Reflect,
-	textures::Texture,
-	utility::{offset_ray, random_float},
-};
+use crate::{materials::reflect::Reflect, textures::Texture, utility::offset_ray};
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug, Clone)]
@@ -24,7 +21,7 @@ impl<'a, T> Scatter for Refract<'a, T>
 where
 	T: Texture,
 {
-	fn scatter_ray(&self, ray: &mut Ray, hit: &Hit) -> bool {
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, rng: &mut R) -> bool {
 		let mut eta_fraction = 1.0 / self.eta;
 		if !hit.out {
 			eta_fraction = self.eta;
@@ -36,24 +33,33 @@ where
 		let cannot_refract = eta_fraction * sin_theta > 1.0;
 		let f0 = (1.0 - eta_fraction) / (1.0 + eta_fraction);
 		let f0 = f0 * f0 * Vec3::one();
-		if cannot_refract || fresnel(cos_theta, f0).x > random_float() {
+		if cannot_refract || fresnel(cos_theta, f0).x > rng.gen() {
 			let ref_mat = Reflect::new(self.texture, 0.0);
-			return ref_mat.scatter_ray(ray, hit);
+			return ref_mat.scatter_ray(ray, hit, rng);
 		}
 
 		let perp = eta_fraction * (ray.direction + cos_theta * hit.normal);
 		let para = -1.0 * (1.0 - perp.mag_sq()).abs().sqrt() * hit.normal;
 		let direction = perp + para;
-		let point = offset_ray(hit.point, hit.normal, hit.error, false);
-		*ray = Ray::new(point, direction, ray.time);
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, false);
+		*ray = ray.spawn(point, direction);
 		false
 	}
 	fn eval(&self, hit: &Hit, wo: Vec3, _: Vec3) -> Vec3 {
-		self.texture.colour_value(wo, hit.point)
+		self.texture.hit_colour(wo, hit)
+	}
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		self.texture.hit_colour(wo, hit)
 	}
 	fn is_delta(&self) -> bool {
 		true
 	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
+	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid()
+	}
 }
 
 pub fn fresnel(cos: Float, f0: Vec3) -> Vec3 {
@@ -0,0 +1,265 @@
+use crate::{
+	materials::refract::{fresnel, Refract},
+	statistics::bxdfs::{trowbridge_reitz, trowbridge_reitz_vndf},
+	textures::Texture,
+	utility::{coord::Coordinate, offset_ray},
+};
+use rand::Rng;
+use rt_core::*;
+
+// glass with a rough surface, microfacet normals are sampled from the visible normals
+// of a Trowbridge-Reitz distribution then the ray reflects or refracts through them
+// chosen by Fresnel. A roughness of 0 is the same as Refract. Like Refract the
+// radiance isn't scaled by the change in eta on transmission.
+#[derive(Debug, Clone)]
+pub struct RoughDielectric<'a, T: Texture> {
+	pub texture: &'a T,
+	pub alpha: Float,
+	pub eta: Float,
+}
+
+impl<'a, T> RoughDielectric<'a, T>
+where
+	T: Texture,
+{
+	pub fn new(texture: &'a T, roughness: Float, eta: Float) -> Self {
+		Self {
+			texture,
+			alpha: roughness * roughness,
+			eta,
+		}
+	}
+
+	// eta on the side of the normal over eta on the other side
+	fn eta_fraction(&self, hit: &Hit) -> Float {
+		if hit.out {
+			1.0 / self.eta
+		} else {
+			self.eta
+		}
+	}
+
+	// chance of reflecting off a microfacet with the given cosine to the viewer
+	fn reflectance(&self, hit: &Hit, cos: Float) -> Float {
+		let eta_fraction = self.eta_fraction(hit);
+		if eta_fraction * eta_fraction * (1.0 - cos * cos) > 1.0 {
+			return 1.0;
+		}
+		let f0 = (1.0 - eta_fraction) / (1.0 + eta_fraction);
+		fresnel(cos, f0 * f0 * Vec3::one()).x
+	}
+
+	fn lambda(&self, cos: Float) -> Float {
+		let tan_sq = (1.0 - cos * cos).max(0.0) / (cos * cos);
+		0.5 * ((1.0 + self.alpha * self.alpha * tan_sq).sqrt() - 1.0)
+	}
+
+	// microfacet normal on the viewer's side and whether wi is transmitted
+	fn half_vector(&self, hit: &Hit, v: Vec3, wi: Vec3) -> (Vec3, bool) {
+		let transmitted = wi.dot(hit.normal) < 0.0;
+		let h = if transmitted {
+			wi / self.eta_fraction(hit) + v
+		} else {
+			wi + v
+		}
+		.normalised();
+		if h.dot(hit.normal) < 0.0 {
+			(-h, transmitted)
+		} else {
+			(h, transmitted)
+		}
+	}
+
+	// (bsdf times cosine, pdf) for the viewer at v
+	fn evaluate(&self, hit: &Hit, v: Vec3, wi: Vec3) -> (Float, Float) {
+		let (cos_o, cos_i) = (v.dot(hit.normal), wi.dot(hit.normal));
+		if cos_o <= 0.0 || cos_i == 0.0 {
+			return (0.0, 0.0);
+		}
+		let (h, transmitted) = self.half_vector(hit, v, wi);
+		let (v_h, wi_h) = (v.dot(h), wi.dot(h));
+		if v_h <= 0.0 || (wi_h < 0.0) != transmitted {
+			return (0.0, 0.0);
+		}
+
+		let d = trowbridge_reitz::d(self.alpha, h.dot(hit.normal));
+		let g1 = 1.0 / (1.0 + self.lambda(cos_o));
+		let g2 = 1.0 / (1.0 + self.lambda(cos_o) + self.lambda(cos_i));
+		let f = self.reflectance(hit, v_h);
+		// density of visible normals
+		let vndf = g1 * v_h * d / cos_o;
+
+		if transmitted {
+			let denom = wi_h + v_h * self.eta_fraction(hit);
+			let denom = denom * denom;
+			(
+				(1.0 - f) * d * g2 * wi_h.abs() * v_h / (cos_o * denom),
+				(1.0 - f) * vndf * wi_h.abs() / denom,
+			)
+		} else {
+			(f * d * g2 / (4.0 * cos_o), f * vndf / (4.0 * v_h))
+		}
+	}
+}
+
+impl<'a, T> Scatter for RoughDielectric<'a, T>
+where
+	T: Texture,
+{
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, rng: &mut R) -> bool {
+		if self.is_delta() {
+			return Refract::new(self.texture, self.eta).scatter_ray(ray, hit, rng);
+		}
+
+		let v = -ray.direction;
+		let coord = Coordinate::new_from_z(hit.normal);
+		let h = coord.to_coord(trowbridge_reitz_vndf::isotropic::sample_vndf(
+			self.alpha,
+			coord.create_inverse().to_coord(v),
+			rng,
+		));
+		let cos = v.dot(h);
+		if cos <= 0.0 {
+			return true;
+		}
+
+		let (direction, is_brdf) = if rng.gen::<Float>() < self.reflectance(hit, cos) {
+			(v.reflected(h), true)
+		} else {
+			let eta_fraction = self.eta_fraction(hit);
+			let cos_t = (1.0 - eta_fraction * eta_fraction * (1.0 - cos * cos)).sqrt();
+			(-eta_fraction * v + (eta_fraction * cos - cos_t) * h, false)
+		};
+		// microfacets can send rays to the wrong side of the surface
+		if (direction.dot(hit.normal) > 0.0) != is_brdf {
+			return true;
+		}
+
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, is_brdf);
+		*ray = ray.spawn(point, direction);
+		false
+	}
+	fn scattering_pdf(&self, hit: &Hit, wo: Vec3, wi: Vec3) -> Float {
+		self.evaluate(hit, -wo, wi).1
+	}
+	fn eval(&self, hit: &Hit, wo: Vec3, wi: Vec3) -> Vec3 {
+		let colour = self.texture.hit_colour(wo, hit);
+		if self.is_delta() {
+			return colour;
+		}
+		self.evaluate(hit, -wo, wi).0 * colour
+	}
+	fn eval_over_scattering_pdf(&self, hit: &Hit, wo: Vec3, wi: Vec3) -> Vec3 {
+		let (f, pdf) = self.evaluate(hit, -wo, wi);
+		if pdf == 0.0 {
+			return Vec3::zero();
+		}
+		f / pdf * self.texture.hit_colour(wo, hit)
+	}
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		self.texture.hit_colour(wo, hit)
+	}
+	fn is_delta(&self) -> bool {
+		self.alpha == 0.0
+	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
+	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid() && self.alpha >= 0.0 && self.eta > 0.0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::test_scene::texture;
+	use rand::thread_rng;
+
+	fn hit(out: bool) -> Hit {
+		Hit {
+			t: 1.0,
+			point: Vec3::zero(),
+			error: Vec3::zero(),
+			normal: Vec3::y(),
+			geometric_normal: Vec3::y(),
+			uv: None,
+			out,
+		}
+	}
+
+	fn scatter<M: Scatter>(mat: &M, direction: Vec3, hit: &Hit) -> Option<Vec3> {
+		let mut ray = Ray::new(Vec3::y(), direction, 0.0);
+		(!mat.scatter_ray(&mut ray, hit, &mut thread_rng())).then_some(ray.direction)
+	}
+
+	#[test]
+	fn smooth_matches_refract() {
+		let tex = texture(Vec3::one());
+		let (rough, smooth) = (RoughDielectric::new(tex, 0.0, 1.5), Refract::new(tex, 1.5));
+		assert!(rough.is_delta());
+
+		let direction = Vec3::new(1.0, -1.0, 0.0).normalised();
+		let mirror = Vec3::new(1.0, 1.0, 0.0).normalised();
+		// from inside the glass this angle is totally internally reflected
+		for out in [true, false] {
+			let hit = hit(out);
+			let refracted = (0..100)
+				.filter_map(|_| scatter(&smooth, direction, &hit))
+				.find(|d| d.y < 0.0);
+			assert_eq!(refracted.is_some(), out);
+
+			let (mut rough_reflected, mut smooth_reflected): (i32, i32) = (0, 0);
+			for _ in 0..10000 {
+				let a = scatter(&rough, direction, &hit).unwrap();
+				if a.y > 0.0 {
+					rough_reflected += 1;
+					assert!((a - mirror).mag() < 0.0001);
+				} else {
+					assert!((a - refracted.unwrap()).mag() < 0.0001);
+				}
+				if scatter(&smooth, direction, &hit).unwrap().y > 0.0 {
+					smooth_reflected += 1;
+				}
+			}
+			assert!((rough_reflected - smooth_reflected).abs() < 200);
+		}
+	}
+
+	#[test]
+	fn rough_transmission_spreads() {
+		let tex = texture(Vec3::one());
+		let rough = RoughDielectric::new(tex, 0.5, 1.5);
+		let smooth = RoughDielectric::new(tex, 0.0, 1.5);
+		let hit = hit(true);
+
+		// refracted direction through the smooth surface
+		let direction = Vec3::new(1.0, -2.0, 0.0).normalised();
+		let refracted = (0..100)
+			.filter_map(|_| scatter(&smooth, direction, &hit))
+			.find(|d| d.y < 0.0)
+			.unwrap();
+
+		let angles: Vec<Float> = (0..10000)
+			.filter_map(|_| scatter(&rough, direction, &hit))
+			.filter(|d| d.y < 0.0)
+			.map(|d| d.dot(refracted).clamp(-1.0, 1.0).acos().to_degrees())
+			.collect();
+		let mean = angles.iter().sum::<Float>() / angles.len() as Float;
+		let spread = angles.iter().filter(|&&a| a > 5.0).count();
+		// most rays stay around the smooth direction but many leave it
+		assert!(mean > 2.0 && mean < 30.0, "{mean}");
+		assert!(spread > angles.len() / 4);
+
+		// sampled directions are weighted by the bsdf over their pdf
+		for _ in 0..1000 {
+			if let Some(wi) = scatter(&rough, direction, &hit) {
+				let weight = rough.eval_over_scattering_pdf(&hit, direction, wi);
+				let expected =
+					rough.eval(&hit, direction, wi) / rough.scattering_pdf(&hit, direction, wi);
+				assert!((weight - expected).mag() < 0.0001);
+				assert!(weight.x <= 1.0 + 0.0001);
+			}
+		}
+	}
+}
@@ -0,0 +1,38 @@
+use crate::utility::offset_ray;
+use rand::Rng;
+use rt_core::*;
+
+// invisible surface that lets rays pass straight through, only shows up in the shadow matte
+#[derive(Debug, Clone, Default)]
+pub struct ShadowCatcher;
+
+impl ShadowCatcher {
+	pub fn new() -> Self {
+		ShadowCatcher
+	}
+}
+
+impl Scatter for ShadowCatcher {
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, _: &mut R) -> bool {
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, false);
+		*ray = ray.spawn(point, ray.direction);
+		false
+	}
+	fn eval(&self, _hit: &Hit, wo: Vec3, wi: Vec3) -> Vec3 {
+		// only the continuing direction carries any light
+		if wo.dot(wi) > 1.0 - 100.0 * Float::EPSILON {
+			Vec3::one()
+		} else {
+			Vec3::zero()
+		}
+	}
+	fn eval_over_scattering_pdf(&self, _hit: &Hit, _wo: Vec3, _wi: Vec3) -> Vec3 {
+		Vec3::one()
+	}
+	fn is_delta(&self) -> bool {
+		true
+	}
+	fn is_shadow_catcher(&self) -> bool {
+		true
+	}
+}
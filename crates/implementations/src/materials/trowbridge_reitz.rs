@@ -1,7 +1,10 @@
 use crate::{materials::refract, statistics::bxdfs::*, textures::Texture, utility::offset_ray};
-use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rand::Rng;
 use rt_core::*;
 
+// Cook-Torrance microfacet reflection with the Trowbridge-Reitz (GGX) distribution,
+// height correlated Smith shadowing and Schlick's Fresnel, directions are sampled from
+// the distribution of visible normals. A roughness of 0 is a perfect mirror.
 #[derive(Debug, Clone)]
 pub struct TrowbridgeReitz<'a, T: Texture> {
 	pub texture: &'a T,
@@ -26,7 +29,7 @@ where
 	fn fresnel(&self, hit: &Hit, wo: Vec3, wi: Vec3, h: Vec3) -> Vec3 {
 		let f0 = ((1.0 - self.ior) / (1.0 + self.ior)).abs();
 		let f0 = f0 * f0;
-		let f0 = lerp(f0, self.texture.colour_value(wi, hit.point), self.metallic);
+		let f0 = f0.lerp(self.texture.hit_colour(wi, hit), self.metallic);
 		refract::fresnel(wo.dot(h), f0)
 	}
 }
@@ -35,16 +38,15 @@ impl<'a, T> Scatter for TrowbridgeReitz<'a, T>
 where
 	T: Texture,
 {
-	fn scatter_ray(&self, ray: &mut Ray, hit: &Hit) -> bool {
-		let direction = trowbridge_reitz_vndf::isotropic::sample(
-			self.alpha,
-			-ray.direction,
-			hit.normal,
-			&mut SmallRng::from_rng(thread_rng()).unwrap(),
-		);
+	fn scatter_ray<R: Rng>(&self, ray: &mut Ray, hit: &Hit, rng: &mut R) -> bool {
+		let direction = if self.is_delta() {
+			(-ray.direction).reflected(hit.normal)
+		} else {
+			trowbridge_reitz_vndf::isotropic::sample(self.alpha, -ray.direction, hit.normal, rng)
+		};
 
-		let point = offset_ray(hit.point, hit.normal, hit.error, true);
-		*ray = Ray::new(point, direction, ray.time);
+		let point = offset_ray(hit.point, hit.geometric_normal, hit.error, true);
+		*ray = ray.spawn(point, direction);
 
 		false
 	}
@@ -59,6 +61,9 @@ where
 	}
 	fn eval(&self, hit: &Hit, wo: Vec3, wi: Vec3) -> Vec3 {
 		let wo = -wo;
+		if self.is_delta() {
+			return self.fresnel(hit, wo, wi, hit.normal);
+		}
 		let h = (wi + wo).normalised();
 
 		if wi.dot(hit.normal) < 0.0 || h.dot(wo) < 0.0 {
@@ -85,8 +90,62 @@ where
 
 		f * g / trowbridge_reitz_vndf::isotropic::g1(self.alpha, hit.normal, h, wo)
 	}
+	// white tinted by the fresnel term of the mirror direction
+	fn base_colour(&self, hit: &Hit, wo: Vec3) -> Vec3 {
+		let wi = (-wo).reflected(hit.normal);
+		self.fresnel(hit, -wo, wi, hit.normal)
+	}
+	fn is_delta(&self) -> bool {
+		self.alpha == 0.0
+	}
+	fn requires_uv(&self) -> bool {
+		self.texture.requires_uv()
+	}
+	fn is_valid(&self) -> bool {
+		self.texture.is_valid() && self.alpha >= 0.0
+	}
 }
 
-fn lerp(a: Vec3, b: Vec3, t: Float) -> Vec3 {
-	(1.0 - t) * a + t * b
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*, AllMaterials,
+		AllPrimitives, RenderMethod, RenderOptions,
+	};
+
+	// mean pixel of a white metal sphere filling the view in a white environment
+	fn furnace(roughness: Float) -> Float {
+		let metal = Box::leak(Box::new(AllMaterials::TrowbridgeReitz(
+			TrowbridgeReitz::new(texture(Vec3::one()), roughness, Vec3::one(), 1.0),
+		)));
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 1.0, metal))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -2.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 16,
+			width: 16,
+			height: 16,
+			render_method: RenderMethod::Naive,
+			..Default::default()
+		};
+		let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+		image.iter().sum::<Float>() / image.len() as Float
+	}
+
+	#[test]
+	fn white_furnace() {
+		let mirror = TrowbridgeReitz::new(texture(Vec3::one()), 0.0, Vec3::one(), 1.0);
+		assert!(mirror.is_delta());
+		assert!((furnace(0.0) - 1.0).abs() < 0.0001);
+
+		// single scattering loses energy to masking, mostly at grazing angles on rougher
+		// surfaces, but never adds any
+		for (roughness, lowest) in [(0.2, 0.98), (0.5, 0.85)] {
+			let mean = furnace(roughness);
+			assert!(mean > lowest && mean < 1.0 + 0.001, "{roughness} {mean}");
+		}
+	}
 }
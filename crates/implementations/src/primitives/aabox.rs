@@ -0,0 +1,350 @@
+use crate::{
+	aabb::{AABound, AABB},
+	primitives::{Axis, FLAT_PADDING},
+	utility::{check_side, gamma},
+};
+use rand::Rng;
+use rt_core::*;
+
+const AXES: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+// axis aligned box intersected with the slab method, the normal comes from the slab
+// the ray enters (or leaves, from inside) through
+#[derive(Debug, Clone)]
+pub struct AABox<'a, M: Scatter> {
+	pub min: Vec3,
+	pub max: Vec3,
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> AABox<'a, M>
+where
+	M: Scatter,
+{
+	pub fn new(min: Vec3, max: Vec3, material: &'a M) -> Self {
+		AABox {
+			min: min.min_by_component(max),
+			max: min.max_by_component(max),
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	// area of one of the two faces perpendicular to the axis
+	fn face_area(&self, axis: &Axis) -> Float {
+		let extent = axis.point_without_axis(self.max - self.min);
+		extent.x * extent.y
+	}
+
+	// area points are sampled uniformly over, the two faces of a box with no thickness
+	// are the same points so are only counted once
+	fn sampled_area(&self) -> Float {
+		let flat = AXES
+			.iter()
+			.any(|axis| axis.get_axis_value(self.min) == axis.get_axis_value(self.max));
+		if flat {
+			0.5 * self.area()
+		} else {
+			self.area()
+		}
+	}
+}
+
+impl<'a, M> Primitive for AABox<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let (mut entry, mut exit) = ((Float::MIN, Axis::X), (Float::MAX, Axis::X));
+		for axis in AXES {
+			let inverse = axis.get_axis_value(ray.d_inverse);
+			let origin = axis.get_axis_value(ray.origin);
+			let t1 = (axis.get_axis_value(self.min) - origin) * inverse;
+			let t2 = (axis.get_axis_value(self.max) - origin) * inverse;
+			let (near, far) = (t1.min(t2), t1.max(t2));
+			if near > entry.0 {
+				entry = (near, axis);
+			}
+			if far < exit.0 {
+				exit = (far, axis);
+			}
+		}
+		if entry.0 > exit.0 || exit.0 <= 0.0 {
+			return None;
+		}
+		let (t, axis) = if entry.0 > 0.0 { entry } else { exit };
+
+		// the point is moved exactly onto the face it hit
+		let point = ray.at(t);
+		let value = axis.get_axis_value(point);
+		let (face, side) = if (value - axis.get_axis_value(self.min)).abs()
+			< (value - axis.get_axis_value(self.max)).abs()
+		{
+			(axis.get_axis_value(self.min), -1.0)
+		} else {
+			(axis.get_axis_value(self.max), 1.0)
+		};
+		let point = Axis::point_from_2d(&axis.point_without_axis(point), &axis, face);
+		let point_error = gamma(3) * point.abs();
+
+		let mut normal = side * axis.return_point_with_axis(Vec3::one());
+		let out = check_side(&mut normal, &ray.direction);
+
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			point_error,
+			normal,
+			None,
+			out,
+			self.material,
+		))
+	}
+	fn does_int(&self, ray: &Ray) -> bool {
+		AABB::new(self.min, self.max).does_int(ray)
+	}
+	fn area(&self) -> Float {
+		2.0 * AXES.iter().map(|axis| self.face_area(axis)).sum::<Float>()
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.sample_surface(rng).0
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		// faces are picked in proportion to their area
+		let mut pick = 0.5 * self.area() * rng.gen::<Float>();
+		let mut axis = Axis::Z;
+		for a in AXES {
+			if pick < self.face_area(&a) {
+				axis = a;
+				break;
+			}
+			pick -= self.face_area(&a);
+		}
+
+		let (min, max) = (
+			axis.point_without_axis(self.min),
+			axis.point_without_axis(self.max),
+		);
+		let on_face = Vec2::new(
+			min.x + (max.x - min.x) * rng.gen::<Float>(),
+			min.y + (max.y - min.y) * rng.gen::<Float>(),
+		);
+		let (face, side) = if rng.gen::<Float>() < 0.5 {
+			(axis.get_axis_value(self.min), -1.0)
+		} else {
+			(axis.get_axis_value(self.max), 1.0)
+		};
+		(
+			Axis::point_from_2d(&on_face, &axis, face),
+			side * axis.return_point_with_axis(Vec3::one()),
+		)
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
+		(self.get_sample(rng) - in_point).normalised()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		(sampled_hit.point - hit_point).mag_sq()
+			/ (wi.dot(sampled_hit.normal).abs() * self.sampled_area())
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+impl<'a, M: Scatter> AABound for AABox<'a, M> {
+	// a box with no thickness along an axis is an axis aligned rectangle, its bounds are
+	// padded along that axis so the BVH doesn't get a degenerate node
+	fn get_aabb(&self) -> AABB {
+		let (mut min, mut max) = (self.min, self.max);
+		for axis in AXES {
+			let k = axis.get_axis_value(self.min);
+			if k == axis.get_axis_value(self.max) {
+				let free = (axis.point_without_axis(min), axis.point_without_axis(max));
+				min = Axis::point_from_2d(&free.0, &axis, k - FLAT_PADDING);
+				max = Axis::point_from_2d(&free.1, &axis, k + FLAT_PADDING);
+			}
+		}
+		AABB::new(min, max)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		bilinear_patch::BilinearPatch,
+		utility::{random_float, test_scene::*},
+	};
+	use rand::thread_rng;
+
+	#[test]
+	fn slabs_match_faces() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let (min, max) = (Vec3::new(-1.0, -0.5, 0.0), Vec3::new(2.0, 1.0, 0.5));
+		let aabox = AABox::new(min, max, material);
+		assert!((aabox.area() - 2.0 * (4.5 + 1.5 + 0.75)).abs() < 0.0001);
+
+		// each face as a planar patch
+		let corner = |x: bool, y: bool, z: bool| {
+			Vec3::new(
+				if x { max.x } else { min.x },
+				if y { max.y } else { min.y },
+				if z { max.z } else { min.z },
+			)
+		};
+		let mut faces = Vec::new();
+		for side in [false, true] {
+			faces.push([
+				corner(side, false, false),
+				corner(side, true, false),
+				corner(side, false, true),
+				corner(side, true, true),
+			]);
+			faces.push([
+				corner(false, side, false),
+				corner(true, side, false),
+				corner(false, side, true),
+				corner(true, side, true),
+			]);
+			faces.push([
+				corner(false, false, side),
+				corner(true, false, side),
+				corner(false, true, side),
+				corner(true, true, side),
+			]);
+		}
+		let faces: Vec<_> = faces
+			.into_iter()
+			.map(|points| BilinearPatch::new(points, material))
+			.collect();
+
+		let mut hits = 0;
+		for _ in 0..1000 {
+			let origin =
+				6.0 * Vec3::new(random_float(), random_float(), random_float()) - 3.0 * Vec3::one();
+			let target =
+				3.0 * Vec3::new(random_float(), random_float(), random_float()) - 1.5 * Vec3::one();
+			let ray = Ray::new(origin, target - origin, 0.0);
+
+			let closest = faces
+				.iter()
+				.filter_map(|face| face.get_int(&ray))
+				.min_by(|a, b| a.hit.t.total_cmp(&b.hit.t));
+			assert_eq!(aabox.does_int(&ray), aabox.get_int(&ray).is_some());
+			match (aabox.get_int(&ray), closest) {
+				(Some(a), Some(b)) => {
+					hits += 1;
+					assert!((a.hit.t - b.hit.t).abs() < 0.0001);
+					assert!((a.hit.point - b.hit.point).mag() < 0.0001);
+					assert!((a.hit.normal - b.hit.normal).mag() < 0.0001);
+
+					let inside =
+						origin.min_by_component(min) == min && origin.max_by_component(max) == max;
+					assert_eq!(a.hit.out, !inside);
+				}
+				(None, None) => (),
+				// rays grazing an edge may be decided differently
+				(a, b) => {
+					let point = a.or(b).unwrap().hit.point;
+					let on_edge = [point - min, max - point]
+						.iter()
+						.flat_map(|d| [d.x, d.y, d.z])
+						.filter(|d| d.abs() < 0.001)
+						.count();
+					assert!(on_edge >= 2);
+				}
+			}
+		}
+		assert!(hits > 0);
+	}
+
+	#[test]
+	fn flat_bounds() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let rect = AABox::new(
+			Vec3::new(-1.0, 2.0, -3.0),
+			Vec3::new(1.0, 2.0, 4.0),
+			material,
+		);
+		let aabb = rect.get_aabb();
+		assert!(aabb.get_extent().y > 0.0);
+		assert_eq!((aabb.min.x, aabb.max.x), (-1.0, 1.0));
+		assert_eq!((aabb.min.z, aabb.max.z), (-3.0, 4.0));
+
+		let contains = |p: Vec3| {
+			p.min_by_component(aabb.min) == aabb.min && p.max_by_component(aabb.max) == aabb.max
+		};
+		for _ in 0..100 {
+			let (point, _) = rect.sample_surface(&mut thread_rng());
+			assert!(contains(point), "{point:?}");
+		}
+		for corner in [
+			rect.min,
+			rect.max,
+			Vec3::new(-1.0, 2.0, 4.0),
+			Vec3::new(1.0, 2.0, -3.0),
+		] {
+			assert!(contains(corner));
+		}
+
+		// rays hitting the rectangle are inside its bounds
+		let ray = Ray::new(Vec3::new(0.5, 5.0, 0.5), -Vec3::y(), 0.0);
+		let t = rect.get_int(&ray).unwrap().hit.t;
+		assert!((t - 3.0).abs() < 0.0001);
+		assert!(aabb.does_int(&ray));
+	}
+
+	#[test]
+	fn rect_sides() {
+		// a rectangle faces along the positive axis it's flat on
+		let material = lambertian(Vec3::one(), 0.5);
+		let rect = AABox::new(
+			Vec3::new(-1.0, -1.0, 2.0),
+			Vec3::new(1.0, 1.0, 2.0),
+			material,
+		);
+		let front = rect
+			.get_int(&Ray::new(Vec3::new(0.2, 0.3, 5.0), -Vec3::z(), 0.0))
+			.unwrap();
+		assert!(front.hit.out);
+		assert_eq!(front.hit.normal, Vec3::z());
+
+		let back = rect
+			.get_int(&Ray::new(
+				Vec3::new(0.2, 0.3, -1.0),
+				Vec3::new(0.1, 0.0, 1.0),
+				0.0,
+			))
+			.unwrap();
+		assert!(!back.hit.out);
+		// the normal is still against the ray
+		assert_eq!(back.hit.normal, -Vec3::z());
+		assert!((back.hit.point.z - 2.0).abs() < 0.00001);
+	}
+
+	#[test]
+	fn box_bounds() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let aabb = AABox::new(0.5 * Vec3::one(), -0.5 * Vec3::one(), material).get_aabb();
+		assert_eq!(aabb.min, -0.5 * Vec3::one());
+		assert_eq!(aabb.max, 0.5 * Vec3::one());
+
+		// a box collapsed to a point still has valid bounds around it
+		let aabb = AABox::new(Vec3::one(), Vec3::one(), material).get_aabb();
+		assert!(aabb.min.x < 1.0 && aabb.min.y < 1.0 && aabb.min.z < 1.0);
+		assert!(aabb.max.x > 1.0 && aabb.max.y > 1.0 && aabb.max.z > 1.0);
+	}
+}
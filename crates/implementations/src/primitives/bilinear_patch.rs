@@ -0,0 +1,318 @@
+use crate::{
+	aabb::{AABound, AABB},
+	utility::{check_side, gamma},
+};
+use rand::Rng;
+use rt_core::*;
+
+// the surface P(u, v) = (1 - u)(1 - v) p00 + u(1 - v) p10 + (1 - u)v p01 + uv p11
+// with points ordered [p00, p10, p01, p11], normals are interpolated the same way
+#[derive(Debug, Clone)]
+pub struct BilinearPatch<'a, M: Scatter> {
+	pub points: [Vec3; 4],
+	pub normals: [Vec3; 4],
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> BilinearPatch<'a, M>
+where
+	M: Scatter,
+{
+	// uses the surface's own normal at each corner
+	pub fn new(points: [Vec3; 4], material: &'a M) -> Self {
+		let mut patch = BilinearPatch {
+			points,
+			normals: [Vec3::zero(); 4],
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		};
+		patch.normals = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(u, v)| {
+			let (dpdu, dpdv) = patch.partials(u, v);
+			dpdu.cross(dpdv).normalised()
+		});
+		patch
+	}
+
+	pub fn with_normals(points: [Vec3; 4], normals: [Vec3; 4], material: &'a M) -> Self {
+		BilinearPatch {
+			points,
+			normals,
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	fn bilinear(values: &[Vec3; 4], u: Float, v: Float) -> Vec3 {
+		(1.0 - u) * (1.0 - v) * values[0]
+			+ u * (1.0 - v) * values[1]
+			+ (1.0 - u) * v * values[2]
+			+ u * v * values[3]
+	}
+
+	pub fn point(&self, u: Float, v: Float) -> Vec3 {
+		Self::bilinear(&self.points, u, v)
+	}
+
+	// (dP/du, dP/dv)
+	fn partials(&self, u: Float, v: Float) -> (Vec3, Vec3) {
+		let [p00, p10, p01, p11] = self.points;
+		(
+			(1.0 - v) * (p10 - p00) + v * (p11 - p01),
+			(1.0 - u) * (p01 - p00) + u * (p11 - p10),
+		)
+	}
+
+	// the surface area covered by a unit of uv space around (u, v)
+	fn jacobian(&self, u: Float, v: Float) -> Float {
+		let (dpdu, dpdv) = self.partials(u, v);
+		dpdu.cross(dpdv).mag()
+	}
+}
+
+impl<'a, M> Primitive for BilinearPatch<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	// Reshetov's intersection from Ray Tracing Gems II, chapter 8, u is found from a
+	// quadratic then v and t from the closest approach of the ray to the line at u
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let [p00, p10, p01, p11] = self.points;
+		let dir = ray.direction;
+
+		let e10 = p10 - p00;
+		let e11 = p11 - p10;
+		let e00 = p01 - p00;
+		let qn = e10.cross(p01 - p11);
+		let q00 = p00 - ray.origin;
+		let q10 = p10 - ray.origin;
+
+		let a = q00.cross(dir).dot(e00);
+		let c = qn.dot(dir);
+		let b = q10.cross(dir).dot(e11) - (a + c);
+
+		let discriminant = b * b - 4.0 * a * c;
+		if discriminant < 0.0 {
+			return None;
+		}
+		let sqrt_val = discriminant.sqrt();
+
+		let (u1, u2) = if c == 0.0 {
+			// the quadratic degenerates to a linear equation, for planar patches
+			(-a / b, -1.0)
+		} else {
+			let q = (-b - sqrt_val.copysign(b)) / 2.0;
+			(q / c, a / q)
+		};
+
+		let mut closest: Option<(Float, Float, Float)> = None;
+		for u in [u1, u2] {
+			if !(0.0..=1.0).contains(&u) {
+				continue;
+			}
+			let pa = q00 + u * (q10 - q00);
+			let pb = e00 + u * (e11 - e00);
+			let n = dir.cross(pb);
+			let det = n.dot(n);
+			if det == 0.0 {
+				continue;
+			}
+			let n = n.cross(pa);
+			let (t, v) = (n.dot(pb) / det, n.dot(dir) / det);
+			if !(0.0..=1.0).contains(&v) || t <= 0.0 {
+				continue;
+			}
+			if closest.is_none_or(|(closest_t, _, _)| t < closest_t) {
+				closest = Some((t, u, v));
+			}
+		}
+		let (t, u, v) = closest?;
+
+		let point = self.point(u, v);
+
+		// rough bound from the magnitudes summed in the interpolation
+		let weights = [(1.0 - u) * (1.0 - v), u * (1.0 - v), (1.0 - u) * v, u * v];
+		let abs_sum = self
+			.points
+			.iter()
+			.zip(weights)
+			.fold(Vec3::zero(), |acc, (p, w)| acc + (w * *p).abs());
+		let point_error = gamma(7) * abs_sum;
+
+		if t < point_error.component_max() / dir.mag() {
+			return None;
+		}
+
+		let mut normal = Self::bilinear(&self.normals, u, v).normalised();
+		let out = check_side(&mut normal, &dir);
+
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			point_error,
+			normal,
+			Some(Vec2::new(u, v)),
+			out,
+			self.material,
+		))
+	}
+	fn area(&self) -> Float {
+		// exact for planar patches, curved ones are approximated by a grid of quads
+		const STEPS: usize = 8;
+		let mut area = 0.0;
+		for i in 0..STEPS {
+			for j in 0..STEPS {
+				let (u0, u1) = (
+					i as Float / STEPS as Float,
+					(i + 1) as Float / STEPS as Float,
+				);
+				let (v0, v1) = (
+					j as Float / STEPS as Float,
+					(j + 1) as Float / STEPS as Float,
+				);
+				let [a, b, c, d] = [
+					self.point(u0, v0),
+					self.point(u1, v0),
+					self.point(u0, v1),
+					self.point(u1, v1),
+				];
+				area += 0.5 * ((b - a).cross(d - a).mag() + (d - a).cross(c - a).mag());
+			}
+		}
+		area
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.point(rng.gen::<Float>(), rng.gen::<Float>())
+	}
+	// uv is rejection sampled by the jacobian which is largest at one of the corners
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let max = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+			.map(|(u, v)| self.jacobian(u, v))
+			.into_iter()
+			.fold(0.0, Float::max);
+		loop {
+			let (u, v) = (rng.gen::<Float>(), rng.gen::<Float>());
+			if rng.gen::<Float>() * max <= self.jacobian(u, v) {
+				let (dpdu, dpdv) = self.partials(u, v);
+				return (self.point(u, v), dpdu.cross(dpdv).normalised());
+			}
+		}
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
+		(self.get_sample(rng) - in_point).normalised()
+	}
+	// samples are uniform in uv so the area density depends on where the patch is hit
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		let jacobian = match sampled_hit.uv {
+			Some(uv) => self.jacobian(uv.x, uv.y),
+			None => self.area(),
+		};
+		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * jacobian)
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+// the patch lies within the convex hull of its corners
+impl<'a, M: Scatter> AABound for BilinearPatch<'a, M> {
+	fn get_aabb(&self) -> AABB {
+		let [p00, p10, p01, p11] = self.points;
+		AABB::new(
+			p00.min_by_component(p10.min_by_component(p01.min_by_component(p11))),
+			p00.max_by_component(p10.max_by_component(p01.max_by_component(p11))),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		triangle::Triangle,
+		utility::{random_float, test_scene::*},
+	};
+
+	#[test]
+	fn planar_patch_matches_triangles() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let [p00, p10, p01, p11] = [
+			Vec3::new(-1.0, -1.0, 0.0),
+			Vec3::new(1.0, -1.0, 0.0),
+			Vec3::new(-1.0, 1.0, 0.0),
+			Vec3::new(1.0, 1.0, 0.0),
+		];
+		let patch = BilinearPatch::new([p00, p10, p01, p11], material);
+		let triangles = [
+			Triangle::new([p00, p10, p11], [Vec3::z(); 3], material),
+			Triangle::new([p00, p11, p01], [Vec3::z(); 3], material),
+		];
+		assert!((patch.area() - 4.0).abs() < 0.0001);
+
+		let mut hits = 0;
+		for _ in 0..1000 {
+			// rays stay z dominant, triangles pick the wrong shear axis for y dominant rays
+			let origin = Vec3::new(
+				3.0 * random_float() - 1.5,
+				3.0 * random_float() - 1.5,
+				if random_float() < 0.5 { 4.0 } else { -4.0 },
+			);
+			let target = Vec3::new(3.0 * random_float() - 1.5, 3.0 * random_float() - 1.5, 0.0);
+			let ray = Ray::new(origin, (target - origin).normalised(), 0.0);
+			let triangle_hit = triangles.iter().find_map(|t| t.get_int(&ray));
+			match (patch.get_int(&ray), triangle_hit) {
+				(Some(a), Some(b)) => {
+					hits += 1;
+					assert!((a.hit.t - b.hit.t).abs() < 0.0001);
+					assert!((a.hit.point - b.hit.point).mag() < 0.0001);
+					assert!((a.hit.normal - b.hit.normal).mag() < 0.0001);
+					assert_eq!(a.hit.out, b.hit.out);
+				}
+				(None, None) => (),
+				// rays grazing an edge may be decided differently
+				(a, b) => {
+					let hit = a.or(b).unwrap();
+					assert!(hit.hit.point.x.abs().max(hit.hit.point.y.abs()) > 0.999);
+				}
+			}
+		}
+		assert!(hits > 0);
+	}
+
+	#[test]
+	fn warped_patch_is_curved() {
+		let material = lambertian(Vec3::one(), 0.5);
+		// one raised corner gives the saddle z = u * v over the unit square
+		let patch = BilinearPatch::new(
+			[Vec3::zero(), Vec3::x(), Vec3::y(), Vec3::new(1.0, 1.0, 1.0)],
+			material,
+		);
+
+		for (u, v) in [(0.5, 0.5), (0.75, 0.25), (0.9, 0.8)] {
+			let ray = Ray::new(Vec3::new(u, v, 5.0), -Vec3::z(), 0.0);
+			let hit = patch.get_int(&ray).unwrap().hit;
+			assert!((hit.point - Vec3::new(u, v, u * v)).mag() < 0.0001);
+			assert!((hit.t - (5.0 - u * v)).abs() < 0.0001);
+
+			// interpolated normals only approximate the surface normal
+			let normal = Vec3::new(-v, -u, 1.0).normalised();
+			assert!(hit.normal.dot(normal) > 0.95);
+		}
+		assert!(patch
+			.get_int(&Ray::new(Vec3::new(1.5, 0.5, 5.0), -Vec3::z(), 0.0))
+			.is_none());
+	}
+}
@@ -0,0 +1,290 @@
+use crate::{
+	aabb::{AABound, AABB},
+	utility::coord::onb_from_normal,
+};
+use rand::Rng;
+use rt_core::*;
+
+// closed cylinder standing on the disk around base, its axis points from the base to
+// the top cap height away
+#[derive(Debug, Clone)]
+pub struct Cylinder<'a, M: Scatter> {
+	pub base: Vec3,
+	pub axis: Vec3,
+	pub radius: Float,
+	pub height: Float,
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> Cylinder<'a, M>
+where
+	M: Scatter,
+{
+	pub fn new(base: Vec3, axis: Vec3, radius: Float, height: Float, material: &'a M) -> Self {
+		Cylinder {
+			base,
+			axis: axis.normalised(),
+			radius,
+			height,
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	// part of a vector at right angles to the axis
+	fn perpendicular(&self, v: Vec3) -> Vec3 {
+		v - v.dot(self.axis) * self.axis
+	}
+
+	// closest distance along the ray to the side or a cap and the outward normal there
+	fn intersect(&self, ray: &Ray) -> Option<(Float, Vec3)> {
+		let offset = ray.origin - self.base;
+		let (d_perp, o_perp) = (
+			self.perpendicular(ray.direction),
+			self.perpendicular(offset),
+		);
+		let (d_axis, o_axis) = (ray.direction.dot(self.axis), offset.dot(self.axis));
+		let mut closest: Option<(Float, Vec3)> = None;
+		let mut consider = |t: Float, normal: Vec3| {
+			if t > 0.0 && closest.is_none_or(|(closest, _)| t < closest) {
+				closest = Some((t, normal));
+			}
+		};
+
+		// the side, rays parallel to the axis never cross it
+		let a = d_perp.mag_sq();
+		if a > 0.0 {
+			let b = d_perp.dot(o_perp);
+			let c = o_perp.mag_sq() - self.radius * self.radius;
+			let discriminant = b * b - a * c;
+			if discriminant > 0.0 {
+				// q avoids the cancellation of subtracting close values as in the sphere
+				let q = -(b + b.signum() * discriminant.sqrt());
+				for t in [q / a, c / q] {
+					let h = o_axis + t * d_axis;
+					if (0.0..=self.height).contains(&h) {
+						consider(t, (o_perp + t * d_perp) / self.radius);
+					}
+				}
+			}
+		}
+
+		// the caps, rays at right angles to the axis never cross them
+		if d_axis != 0.0 {
+			for (h, normal) in [(0.0, -self.axis), (self.height, self.axis)] {
+				let t = (h - o_axis) / d_axis;
+				if (o_perp + t * d_perp).mag_sq() <= self.radius * self.radius {
+					consider(t, normal);
+				}
+			}
+		}
+		closest
+	}
+
+	fn side_area(&self) -> Float {
+		2.0 * PI * self.radius * self.height
+	}
+}
+
+impl<'a, M> Primitive for Cylinder<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let (t, mut normal) = self.intersect(ray)?;
+		let point = ray.at(t);
+
+		// normal faces against the ray like the sphere's
+		let mut out = true;
+		if normal.dot(ray.direction) > 0.0 {
+			out = false;
+			normal = -normal;
+		}
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			EPSILON * Vec3::one(),
+			normal,
+			self.get_uv(point),
+			out,
+			self.material,
+		))
+	}
+	fn does_int(&self, ray: &Ray) -> bool {
+		self.intersect(ray).is_some()
+	}
+	// u goes around the axis and v up it, the caps are at v = 0 and v = 1
+	fn get_uv(&self, point: Vec3) -> Option<Vec2> {
+		if self.material.requires_uv() {
+			let (x, y, _) = onb_from_normal(self.axis);
+			let offset = point - self.base;
+			let phi = offset.dot(y).atan2(offset.dot(x)) + PI;
+			let v = (offset.dot(self.axis) / self.height).clamp(0.0, 1.0);
+			return Some(Vec2::new(phi / (2.0 * PI), v));
+		}
+		None
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.sample_surface(rng).0
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let (x, y, _) = onb_from_normal(self.axis);
+		let phi = 2.0 * PI * rng.gen::<Float>();
+		let around = phi.cos() * x + phi.sin() * y;
+		// the side and caps are picked in proportion to their area
+		let pick = self.area() * rng.gen::<Float>();
+		if pick < self.side_area() {
+			let point =
+				self.base + self.radius * around + self.height * rng.gen::<Float>() * self.axis;
+			(point, around)
+		} else {
+			let r = self.radius * rng.gen::<Float>().sqrt();
+			let (h, normal) = if rng.gen::<Float>() < 0.5 {
+				(0.0, -self.axis)
+			} else {
+				(self.height, self.axis)
+			};
+			(self.base + r * around + h * self.axis, normal)
+		}
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
+		(self.get_sample(rng) - in_point).normalised()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * self.area())
+	}
+	fn area(&self) -> Float {
+		self.side_area() + 2.0 * PI * self.radius * self.radius
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+// bounds of the two caps, a disk reaches radius * sin of the angle between the axis
+// and each world axis either side of its centre
+impl<'a, M: Scatter> AABound for Cylinder<'a, M> {
+	fn get_aabb(&self) -> AABB {
+		let a = self.axis;
+		let extent = self.radius
+			* Vec3::new(
+				(1.0 - a.x * a.x).max(0.0).sqrt(),
+				(1.0 - a.y * a.y).max(0.0).sqrt(),
+				(1.0 - a.z * a.z).max(0.0).sqrt(),
+			);
+		let top = self.base + self.height * self.axis;
+		AABB::new(
+			self.base.min_by_component(top) - extent,
+			self.base.max_by_component(top) + extent,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		utility::{random_unit_vector, test_scene::*},
+		AllMaterials, AllTextures, Lambertian, Lerp,
+	};
+	use rand::thread_rng;
+
+	#[test]
+	fn side_and_caps() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let cylinder = Cylinder::new(Vec3::zero(), Vec3::y(), 1.0, 2.0, material);
+
+		// a ray at the side
+		let hit = cylinder
+			.get_int(&Ray::new(Vec3::new(5.0, 1.0, 0.0), -Vec3::x(), 0.0))
+			.unwrap()
+			.hit;
+		assert!((hit.t - 4.0).abs() < 0.0001);
+		assert!((hit.normal - Vec3::x()).mag() < 0.0001);
+		assert!(hit.out);
+
+		// a ray down the axis through the top cap
+		let down = Ray::new(Vec3::new(0.3, 5.0, 0.2), -Vec3::y(), 0.0);
+		let hit = cylinder.get_int(&down).unwrap().hit;
+		assert!((hit.t - 3.0).abs() < 0.0001);
+		assert!((hit.normal - Vec3::y()).mag() < 0.0001);
+		assert!(hit.out);
+
+		// parallel to the axis outside the radius misses, above the top it misses the side
+		assert!(!cylinder.does_int(&Ray::new(Vec3::new(1.5, 5.0, 0.0), -Vec3::y(), 0.0)));
+		assert!(!cylinder.does_int(&Ray::new(Vec3::new(5.0, 2.5, 0.0), -Vec3::x(), 0.0)));
+
+		// from inside the normal still faces the ray
+		let hit = cylinder
+			.get_int(&Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::x(), 0.0))
+			.unwrap()
+			.hit;
+		assert!((hit.t - 1.0).abs() < 0.0001);
+		assert!((hit.normal + Vec3::x()).mag() < 0.0001);
+		assert!(!hit.out);
+		let hit = cylinder
+			.get_int(&Ray::new(Vec3::new(0.5, 1.0, 0.0), -Vec3::y(), 0.0))
+			.unwrap()
+			.hit;
+		assert!((hit.t - 1.0).abs() < 0.0001);
+		assert_eq!(hit.normal, Vec3::y());
+		assert!(!hit.out);
+
+		// the lerp texture asks for uvs
+		let uv_material: &TestMaterial =
+			Box::leak(Box::new(AllMaterials::Lambertian(Lambertian::new(
+				Box::leak(Box::new(AllTextures::Lerp(Lerp::new(
+					Vec3::one(),
+					Vec3::zero(),
+				)))),
+				0.5,
+			))));
+		let textured = Cylinder::new(Vec3::zero(), Vec3::y(), 1.0, 2.0, uv_material);
+		let uv = textured.get_uv(Vec3::new(1.0, 1.5, 0.0)).unwrap();
+		assert!((uv.y - 0.75).abs() < 0.0001);
+		let opposite = textured.get_uv(Vec3::new(-1.0, 1.5, 0.0)).unwrap();
+		assert!(((uv.x - opposite.x).abs() - 0.5).abs() < 0.0001);
+
+		// a tilted cylinder's bounds hold its surface
+		let tilted = Cylinder::new(
+			Vec3::new(1.0, -2.0, 0.5),
+			Vec3::new(1.0, 2.0, -0.5),
+			0.7,
+			3.0,
+			material,
+		);
+		let aabb = tilted.get_aabb();
+		for _ in 0..1000 {
+			let (point, normal) = tilted.sample_surface(&mut thread_rng());
+			let inside = (point - aabb.min)
+				.component_min()
+				.min((aabb.max - point).component_min());
+			assert!(inside > -0.0001, "{point:?}");
+			assert!((normal.mag() - 1.0).abs() < 0.0001);
+
+			// rays from in front of a sampled point hit it or the cylinder before it,
+			// grazing ones are left out as rounding can make them miss
+			let direction = random_unit_vector();
+			if direction.dot(normal) < 0.2 {
+				continue;
+			}
+			let origin = point + 10.0 * direction;
+			let ray = Ray::new(origin, point - origin, 0.0);
+			let hit = tilted.get_int(&ray).unwrap().hit;
+			assert!(hit.t <= (point - origin).mag() + 0.001);
+		}
+	}
+}
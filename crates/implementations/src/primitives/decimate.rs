@@ -0,0 +1,415 @@
+use crate::primitives::AllPrimitives;
+use rt_core::*;
+use std::{collections::HashMap, sync::Arc};
+
+// how much more moving a vertex off the mesh's boundary costs than off its surface
+const BOUNDARY_WEIGHT: Float = 1000.0;
+
+// sum of squared distances to a set of planes as the symmetric 4x4 matrix of
+// Garland and Heckbert, stored as its upper triangle
+#[derive(Clone, Copy, Default)]
+struct Quadric([Float; 10]);
+
+impl Quadric {
+	// plane through the point with the unit normal, scaled by the weight
+	fn plane(normal: Vec3, point: Vec3, weight: Float) -> Self {
+		let (a, b, c) = (normal.x, normal.y, normal.z);
+		let d = -normal.dot(point);
+		Quadric(
+			[
+				a * a,
+				a * b,
+				a * c,
+				a * d,
+				b * b,
+				b * c,
+				b * d,
+				c * c,
+				c * d,
+				d * d,
+			]
+			.map(|v| weight * v),
+		)
+	}
+
+	fn add(&mut self, other: &Quadric) {
+		for (a, b) in self.0.iter_mut().zip(other.0) {
+			*a += b;
+		}
+	}
+
+	fn error(&self, p: Vec3) -> Float {
+		let q = &self.0;
+		let (x, y, z) = (p.x, p.y, p.z);
+		q[0] * x * x
+			+ 2.0 * q[1] * x * y
+			+ 2.0 * q[2] * x * z
+			+ 2.0 * q[3] * x
+			+ q[4] * y * y
+			+ 2.0 * q[5] * y * z
+			+ 2.0 * q[6] * y
+			+ q[7] * z * z
+			+ 2.0 * q[8] * z
+			+ q[9]
+	}
+}
+
+// normal and uv index of a triangle's corner
+type Attributes = (usize, Option<usize>);
+
+fn face_normal(points: [Vec3; 3]) -> Vec3 {
+	(points[1] - points[0]).cross(points[2] - points[0])
+}
+
+// collapses edges of each mesh, cheapest first by quadric error, until it has about
+// ratio of its triangles left. An edge collapses onto whichever end costs less so no
+// vertices are added and the mesh stays inside its bounds, and collapses that would
+// fold a triangle over are skipped. Vertices on a normal or uv seam never move or take
+// another's place so each corner keeps the normal and uv of the vertex it points at.
+// Triangles that aren't part of a mesh are kept and the number of triangles removed is
+// returned.
+pub fn decimate<M: Scatter>(primitives: &mut Vec<AllPrimitives<M>>, ratio: Float) -> usize {
+	if ratio >= 1.0 {
+		return 0;
+	}
+	let mut meshes: HashMap<usize, Vec<usize>> = HashMap::new();
+	for (i, primitive) in primitives.iter().enumerate() {
+		if let AllPrimitives::MeshTriangle(triangle) = primitive {
+			meshes
+				.entry(Arc::as_ptr(&triangle.mesh) as usize)
+				.or_default()
+				.push(i);
+		}
+	}
+
+	let mut removed = vec![false; primitives.len()];
+	for indices in meshes.into_values() {
+		let vertices = match &primitives[indices[0]] {
+			AllPrimitives::MeshTriangle(triangle) => triangle.mesh.vertices.clone(),
+			_ => unreachable!(),
+		};
+		let (triangles, attributes) = indices
+			.iter()
+			.map(|&i| match &primitives[i] {
+				AllPrimitives::MeshTriangle(t) => (
+					t.point_indices,
+					[0, 1, 2].map(|k| (t.normal_indices[k], t.uv_indices.map(|uvs| uvs[k]))),
+				),
+				_ => unreachable!(),
+			})
+			.unzip();
+		let target = (indices.len() as Float * ratio.max(0.0)).ceil() as usize;
+		let decimated = decimate_mesh(&vertices, triangles, attributes, target);
+
+		for (&i, triangle) in indices.iter().zip(decimated) {
+			match (&mut primitives[i], triangle) {
+				(AllPrimitives::MeshTriangle(t), Some((points, attributes))) => {
+					t.point_indices = points;
+					t.normal_indices = attributes.map(|(normal, _)| normal);
+					if let Some(uv_indices) = &mut t.uv_indices {
+						*uv_indices = attributes.map(|(_, uv)| uv.unwrap());
+					}
+				}
+				(_, None) => removed[i] = true,
+				_ => unreachable!(),
+			}
+		}
+	}
+
+	let mut i = 0;
+	primitives.retain(|_| {
+		i += 1;
+		!removed[i - 1]
+	});
+	removed.iter().filter(|&&r| r).count()
+}
+
+// the mesh's triangles and the attributes of their corners after collapsing edges until
+// no more than target are left or no edge can collapse, removed triangles are None
+fn decimate_mesh(
+	vertices: &[Vec3],
+	mut triangles: Vec<[usize; 3]>,
+	mut attributes: Vec<[Attributes; 3]>,
+	target: usize,
+) -> Vec<Option<([usize; 3], [Attributes; 3])>> {
+	// the attributes every corner at a vertex shares, vertices whose corners differ are on
+	// a seam
+	let mut vertex_attributes: Vec<Option<Attributes>> = vec![None; vertices.len()];
+	let mut seam = vec![false; vertices.len()];
+	for (points, attributes) in triangles.iter().zip(&attributes) {
+		for (&point, &corner) in points.iter().zip(attributes) {
+			match vertex_attributes[point] {
+				None => vertex_attributes[point] = Some(corner),
+				Some(shared) if shared != corner => seam[point] = true,
+				_ => (),
+			}
+		}
+	}
+
+	let mut quadrics = vec![Quadric::default(); vertices.len()];
+	let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+	for points in &triangles {
+		let normal = face_normal(points.map(|i| vertices[i]));
+		if normal.mag_sq() == 0.0 {
+			continue;
+		}
+		let plane = Quadric::plane(normal.normalised(), vertices[points[0]], 0.5 * normal.mag());
+		for (j, &point) in points.iter().enumerate() {
+			quadrics[point].add(&plane);
+			let other = points[(j + 1) % 3];
+			*edge_count
+				.entry((point.min(other), point.max(other)))
+				.or_default() += 1;
+		}
+	}
+	// edges with a triangle on only one side get a plane at right angles to the surface so
+	// the outline doesn't shrink
+	for points in &triangles {
+		let normal = face_normal(points.map(|i| vertices[i]));
+		for j in 0..3 {
+			let (a, b) = (points[j], points[(j + 1) % 3]);
+			if edge_count.get(&(a.min(b), a.max(b))) != Some(&1) {
+				continue;
+			}
+			let edge = vertices[b] - vertices[a];
+			let side = edge.cross(normal);
+			if side.mag_sq() == 0.0 {
+				continue;
+			}
+			let plane = Quadric::plane(
+				side.normalised(),
+				vertices[a],
+				BOUNDARY_WEIGHT * edge.mag_sq(),
+			);
+			quadrics[a].add(&plane);
+			quadrics[b].add(&plane);
+		}
+	}
+
+	let mut alive = vec![true; triangles.len()];
+	let mut count = triangles.len();
+	while count > target {
+		let mut around: HashMap<usize, Vec<usize>> = HashMap::new();
+		let mut edges = Vec::new();
+		for (t, points) in triangles.iter().enumerate().filter(|(t, _)| alive[*t]) {
+			for (j, &a) in points.iter().enumerate() {
+				around.entry(a).or_default().push(t);
+				let b = points[(j + 1) % 3];
+				edges.push((a.min(b), a.max(b)));
+			}
+		}
+		edges.sort_unstable();
+		edges.dedup();
+
+		// (cost, vertex removed, vertex kept)
+		let mut collapses: Vec<(Float, usize, usize)> = edges
+			.into_iter()
+			.map(|(a, b)| {
+				let mut quadric = quadrics[a];
+				quadric.add(&quadrics[b]);
+				let (onto_a, onto_b) = (quadric.error(vertices[a]), quadric.error(vertices[b]));
+				if onto_a < onto_b {
+					(onto_a, b, a)
+				} else {
+					(onto_b, a, b)
+				}
+			})
+			.collect();
+		collapses.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+		// the triangles around a vertex that has moved, or a neighbour of one, have
+		// changed so its collapses wait for the next pass
+		let mut touched = vec![false; vertices.len()];
+		let mut collapsed = false;
+		for (_, from, to) in collapses {
+			if count <= target {
+				break;
+			}
+			if touched[from] || touched[to] || seam[from] || seam[to] {
+				continue;
+			}
+			let folds = around[&from].iter().any(|&t| {
+				let points = triangles[t];
+				if points.contains(&to) {
+					return false;
+				}
+				let before = face_normal(points.map(|i| vertices[i]));
+				let after = face_normal(points.map(|i| vertices[if i == from { to } else { i }]));
+				after.dot(before) <= 0.0
+			});
+			if folds {
+				continue;
+			}
+
+			for &t in &around[&from] {
+				if triangles[t].contains(&to) {
+					alive[t] = false;
+					count -= 1;
+				} else {
+					let k = triangles[t].iter().position(|&i| i == from).unwrap();
+					triangles[t][k] = to;
+					attributes[t][k] = vertex_attributes[to].unwrap();
+				}
+			}
+			let removed = quadrics[from];
+			quadrics[to].add(&removed);
+			for &t in around[&from].iter().chain(&around[&to]) {
+				for i in triangles[t] {
+					touched[i] = true;
+				}
+			}
+			collapsed = true;
+		}
+		if !collapsed {
+			break;
+		}
+	}
+
+	triangles
+		.into_iter()
+		.zip(attributes)
+		.zip(alive)
+		.map(|(triangle, alive)| alive.then_some(triangle))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		aabb::AABB,
+		triangle::{MeshData, MeshTriangle},
+		utility::test_scene::lambertian,
+	};
+
+	#[test]
+	fn halves_sphere() {
+		// a uv sphere of radius 1 with a normal and uv for each vertex, the last segment
+		// wraps round to uvs with u = 1 making a seam
+		const RINGS: usize = 24;
+		const SEGMENTS: usize = 48;
+		let mut vertices = vec![Vec3::y(), -Vec3::y()];
+		for ring in 1..RINGS {
+			let theta = ring as Float / RINGS as Float * PI;
+			for segment in 0..SEGMENTS {
+				let phi = segment as Float / SEGMENTS as Float * 2.0 * PI;
+				vertices.push(Vec3::new(
+					theta.sin() * phi.cos(),
+					theta.cos(),
+					theta.sin() * phi.sin(),
+				));
+			}
+		}
+		let vertex = |ring: usize, segment: usize| match ring {
+			0 => 0,
+			r if r == RINGS => 1,
+			r => 2 + (r - 1) * SEGMENTS + segment % SEGMENTS,
+		};
+		let uv = |point: Vec3| {
+			let u = point.z.atan2(point.x).rem_euclid(2.0 * PI) / (2.0 * PI);
+			Vec2::new(u, 1.0 - point.y.clamp(-1.0, 1.0).acos() / PI)
+		};
+		let mut uvs: Vec<Vec2> = vertices.iter().map(|&point| uv(point)).collect();
+		let seam_start = uvs.len();
+		for ring in 1..RINGS {
+			uvs.push(Vec2::new(1.0, uvs[vertex(ring, 0)].y));
+		}
+		let uv_index = |ring: usize, segment: usize| match (ring, segment) {
+			(r, SEGMENTS) if r != 0 && r != RINGS => seam_start + r - 1,
+			(r, s) => vertex(r, s),
+		};
+		let mut mesh = MeshData::new(vertices.clone(), vertices);
+		mesh.uvs = uvs;
+		let mesh = Arc::new(mesh);
+		let material = lambertian(Vec3::one(), 0.5);
+		let mut primitives = Vec::new();
+		for ring in 0..RINGS {
+			for segment in 0..SEGMENTS {
+				let corners = [
+					(ring, segment),
+					(ring + 1, segment),
+					(ring + 1, segment + 1),
+					(ring, segment + 1),
+				];
+				for corners in [[0, 1, 2], [0, 2, 3]].map(|c| c.map(|i| corners[i])) {
+					let points = corners.map(|(r, s)| vertex(r, s));
+					if points[0] != points[1] && points[1] != points[2] && points[2] != points[0] {
+						let mut triangle =
+							MeshTriangle::new(points, points, material, mesh.clone());
+						triangle.uv_indices = Some(corners.map(|(r, s)| uv_index(r, s)));
+						primitives.push(AllPrimitives::MeshTriangle(triangle));
+					}
+				}
+			}
+		}
+		let bounds = |primitives: &[AllPrimitives<_>]| {
+			let mut aabb = None;
+			for primitive in primitives {
+				let AllPrimitives::MeshTriangle(t) = primitive else {
+					unreachable!()
+				};
+				for i in t.point_indices {
+					AABB::extend_contains(&mut aabb, t.mesh.vertices[i]);
+				}
+			}
+			aabb.unwrap()
+		};
+		let original = bounds(&primitives);
+
+		let mut decimated = primitives.clone();
+		let removed = decimate(&mut decimated, 0.5);
+		assert_eq!(removed + decimated.len(), primitives.len());
+		let half = primitives.len() / 2;
+		assert!(
+			decimated.len().abs_diff(half) <= half / 20,
+			"{} {}",
+			decimated.len(),
+			primitives.len()
+		);
+
+		// every vertex left is one of the sphere's so inside its bounds, and the outline
+		// barely moves
+		let simplified = bounds(&decimated);
+		for (inner, outer) in [
+			(original.min, simplified.min),
+			(simplified.max, original.max),
+		] {
+			assert_eq!(inner.max_by_component(outer), outer);
+		}
+		for (a, b) in [
+			(simplified.min, original.min),
+			(simplified.max, original.max),
+		] {
+			assert!((a - b).abs().component_max() < 0.05, "{a:?} {b:?}");
+		}
+		for primitive in &decimated {
+			let AllPrimitives::MeshTriangle(t) = primitive else {
+				unreachable!()
+			};
+			for i in t.point_indices {
+				assert!((t.mesh.vertices[i].mag() - 1.0).abs() < 0.0001);
+			}
+			let [a, b, c] = t.point_indices;
+			assert!(a != b && b != c && c != a);
+
+			// each corner has the normal and uv of its vertex, with u = 1 on the seam only
+			// next to the segment before it
+			assert_eq!(t.normal_indices, t.point_indices);
+			let mut us = Vec::new();
+			for (point, uv_index) in t.point_indices.into_iter().zip(t.uv_indices.unwrap()) {
+				let (point, corner_uv) = (t.mesh.vertices[point], t.mesh.uvs[uv_index]);
+				let expected = uv(point);
+				assert!((corner_uv.y - expected.y).abs() < 0.0001);
+				if point.y.abs() < 1.0 {
+					assert!(corner_uv.x == expected.x || (expected.x == 0.0 && corner_uv.x == 1.0));
+					us.push(corner_uv.x);
+				}
+			}
+			let spread =
+				us.iter().copied().fold(0.0, Float::max) - us.iter().copied().fold(1.0, Float::min);
+			assert!(spread < 0.5, "{us:?}");
+		}
+
+		assert_eq!(decimate(&mut primitives.clone(), 1.0), 0);
+	}
+}
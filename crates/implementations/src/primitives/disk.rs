@@ -0,0 +1,212 @@
+use crate::{
+	aabb::{AABound, AABB},
+	primitives::FLAT_PADDING,
+	utility::{check_side, coord::onb_from_normal, gamma},
+};
+use rand::Rng;
+use rt_core::*;
+
+// flat disk facing along its normal, points closer to the centre than the inner radius
+// are cut out to make an annulus
+#[derive(Debug, Clone)]
+pub struct Disk<'a, M: Scatter> {
+	pub center: Vec3,
+	pub normal: Vec3,
+	pub radius: Float,
+	pub inner_radius: Float,
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> Disk<'a, M>
+where
+	M: Scatter,
+{
+	pub fn new(center: Vec3, normal: Vec3, radius: Float, material: &'a M) -> Self {
+		Disk {
+			center,
+			normal: normal.normalised(),
+			radius,
+			inner_radius: 0.0,
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	pub fn with_inner_radius(mut self, inner_radius: Float) -> Self {
+		self.inner_radius = inner_radius;
+		self
+	}
+
+	// distance along the ray to the plane where it lands between the radii
+	fn intersect(&self, ray: &Ray) -> Option<Float> {
+		// rays parallel to the plane never cross it
+		let denominator = ray.direction.dot(self.normal);
+		if denominator == 0.0 {
+			return None;
+		}
+		let t = (self.center - ray.origin).dot(self.normal) / denominator;
+		if t <= 0.0 || !t.is_finite() {
+			return None;
+		}
+		let distance_sq = (ray.at(t) - self.center).mag_sq();
+		let inner_sq = self.inner_radius * self.inner_radius;
+		(distance_sq <= self.radius * self.radius && distance_sq >= inner_sq).then_some(t)
+	}
+}
+
+impl<'a, M> Primitive for Disk<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let t = self.intersect(ray)?;
+
+		// the point is moved exactly onto the plane
+		let point = ray.at(t);
+		let point = point - (point - self.center).dot(self.normal) * self.normal;
+		let point_error = gamma(5) * (point.abs() + self.center.abs());
+
+		let mut normal = self.normal;
+		let out = check_side(&mut normal, &ray.direction);
+
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			point_error,
+			normal,
+			self.get_uv(point),
+			out,
+			self.material,
+		))
+	}
+	fn does_int(&self, ray: &Ray) -> bool {
+		self.intersect(ray).is_some()
+	}
+	// u is the angle around the centre and v goes from the inner radius to the outer
+	fn get_uv(&self, point: Vec3) -> Option<Vec2> {
+		if self.material.requires_uv() {
+			let (x, y, _) = onb_from_normal(self.normal);
+			let offset = point - self.center;
+			let phi = offset.dot(y).atan2(offset.dot(x)) + PI;
+			let band = (self.radius - self.inner_radius).max(EPSILON);
+			let v = ((offset.mag() - self.inner_radius) / band).clamp(0.0, 1.0);
+			return Some(Vec2::new(phi / (2.0 * PI), v));
+		}
+		None
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.sample_surface(rng).0
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let (x, y, _) = onb_from_normal(self.normal);
+		let inner_sq = self.inner_radius * self.inner_radius;
+		let r = (inner_sq + rng.gen::<Float>() * (self.radius * self.radius - inner_sq)).sqrt();
+		let phi = 2.0 * PI * rng.gen::<Float>();
+		(
+			self.center + r * (phi.cos() * x + phi.sin() * y),
+			self.normal,
+		)
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
+		(self.get_sample(rng) - in_point).normalised()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * self.area())
+	}
+	fn area(&self) -> Float {
+		PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+// a circle reaches radius * sin of the angle between its normal and each world axis
+// either side of its centre, padded along axes it's flat on
+impl<'a, M: Scatter> AABound for Disk<'a, M> {
+	fn get_aabb(&self) -> AABB {
+		let n = self.normal;
+		let extent = Vec3::new(
+			(1.0 - n.x * n.x).max(0.0).sqrt(),
+			(1.0 - n.y * n.y).max(0.0).sqrt(),
+			(1.0 - n.z * n.z).max(0.0).sqrt(),
+		) * self.radius;
+		let extent = extent.max_by_component(FLAT_PADDING * Vec3::one());
+		AABB::new(self.center - extent, self.center + extent)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::test_scene::*;
+	use rand::thread_rng;
+
+	#[test]
+	fn disk_and_annulus() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let center = Vec3::new(0.0, 1.0, 0.0);
+		let disk = Disk::new(center, Vec3::y(), 1.0, material);
+		let annulus = disk.clone().with_inner_radius(0.5);
+		let down = |x: Float| Ray::new(Vec3::new(x, 5.0, 0.0), -Vec3::y(), 0.0);
+
+		// through the centre the disk is hit and the annulus' hole missed
+		let hit = disk.get_int(&down(0.0)).unwrap().hit;
+		assert!((hit.t - 4.0).abs() < 0.0001);
+		assert!((hit.point - center).mag() < 0.0001);
+		assert_eq!(hit.normal, Vec3::y());
+		assert!(hit.out);
+		assert!(annulus.get_int(&down(0.0)).is_none());
+		assert!(!annulus.does_int(&down(0.2)));
+
+		// both are hit in the band and missed outside it
+		for primitive in [&disk, &annulus] {
+			assert!(primitive.does_int(&down(0.75)));
+			assert!(primitive.get_int(&down(1.2)).is_none());
+		}
+		let below = Ray::new(Vec3::new(0.75, -1.0, 0.0), Vec3::y(), 0.0);
+		let hit = annulus.get_int(&below).unwrap().hit;
+		assert_eq!(hit.normal, -Vec3::y());
+		assert!(!hit.out);
+
+		// rays in the plane or parallel to it miss without NaNs
+		for origin in [center - 2.0 * Vec3::x(), center + Vec3::y()] {
+			let ray = Ray::new(origin, Vec3::x(), 0.0);
+			assert!(disk.get_int(&ray).is_none());
+			assert!(!disk.does_int(&ray));
+		}
+
+		assert!((annulus.area() - 0.75 * PI).abs() < 0.0001);
+		let tilted = Disk::new(center, Vec3::new(1.0, 1.0, 0.0), 2.0, material);
+		let max = tilted.get_aabb().max;
+		let half_diagonal = (2.0 as Float).sqrt();
+		assert!((max - Vec3::new(half_diagonal, 1.0 + half_diagonal, 2.0)).mag() < 0.0001);
+		for disk in [&annulus, &tilted] {
+			let aabb = disk.get_aabb();
+			for _ in 0..100 {
+				let (point, normal) = disk.sample_surface(&mut thread_rng());
+				let from_center = (point - disk.center).mag();
+				assert!(from_center >= disk.inner_radius - 0.0001);
+				assert!(from_center <= disk.radius + 0.0001);
+				assert!((point - disk.center).dot(normal).abs() < 0.0001);
+				let inside = (point - aabb.min)
+					.component_min()
+					.min((aabb.max - point).component_min());
+				assert!(inside > -0.0001, "{point:?}");
+			}
+		}
+	}
+}
@@ -0,0 +1,207 @@
+use crate::{
+	aabb::{AABound, AABB},
+	utility::gamma,
+};
+use rand::Rng;
+use rt_core::*;
+
+// another primitive moved to a new place, rotated about y then translated, so the same
+// geometry can be reused at several positions. Rays are moved into the primitive's
+// space to be intersected and its hits are moved back out.
+#[derive(Debug, Clone)]
+pub struct Instance<P> {
+	pub primitive: Box<P>,
+	pub translation: Vec3,
+	rotation: Rotation,
+}
+
+impl<P> Instance<P> {
+	pub fn new(primitive: P) -> Self {
+		Instance {
+			primitive: Box::new(primitive),
+			translation: Vec3::zero(),
+			rotation: Rotation::identity(),
+		}
+	}
+
+	pub fn with_translation(mut self, translation: Vec3) -> Self {
+		self.translation = translation;
+		self
+	}
+
+	// anticlockwise looking down y
+	pub fn with_rotation_y(mut self, degrees: Float) -> Self {
+		self.rotation = Rotation::from_axis_angle(Vec3::y(), degrees.to_radians());
+		self
+	}
+
+	fn rotate(&self, v: Vec3) -> Vec3 {
+		self.rotation.rotate(v)
+	}
+
+	fn unrotate(&self, v: Vec3) -> Vec3 {
+		self.rotation.inverse().rotate(v)
+	}
+
+	fn to_world(&self, point: Vec3) -> Vec3 {
+		self.rotate(point) + self.translation
+	}
+
+	fn to_local(&self, point: Vec3) -> Vec3 {
+		self.unrotate(point - self.translation)
+	}
+
+	fn local_ray(&self, ray: &Ray) -> Ray {
+		ray.spawn(self.to_local(ray.origin), self.unrotate(ray.direction))
+	}
+
+	fn world_hit(&self, hit: &mut Hit) {
+		hit.point = self.to_world(hit.point);
+		hit.normal = self.rotate(hit.normal);
+		hit.geometric_normal = self.rotate(hit.geometric_normal);
+		// each local axis's error spreads over the world axes it's rotated onto, and the
+		// translation rounds again
+		let error = hit.error;
+		hit.error = error.x * self.rotate(Vec3::x()).abs()
+			+ error.y * self.rotate(Vec3::y()).abs()
+			+ error.z * self.rotate(Vec3::z()).abs()
+			+ gamma(3) * hit.point.abs();
+	}
+}
+
+impl<P> Primitive for Instance<P>
+where
+	P: Primitive + AABound,
+{
+	type Material = P::Material;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<P::Material>> {
+		// directions keep their length so t is the same in both spaces
+		let mut intersection = self.primitive.get_int(&self.local_ray(ray))?;
+		self.world_hit(&mut intersection.hit);
+		Some(intersection)
+	}
+	fn does_int(&self, ray: &Ray) -> bool {
+		self.primitive.does_int(&self.local_ray(ray))
+	}
+	fn get_uv(&self, point: Vec3) -> Option<Vec2> {
+		self.primitive.get_uv(self.to_local(point))
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.to_world(self.primitive.get_sample(rng))
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let (point, normal) = self.primitive.sample_surface(rng);
+		(self.to_world(point), self.rotate(normal))
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, time: Float, rng: &mut R) -> Vec3 {
+		self.rotate(
+			self.primitive
+				.sample_visible_from_point(self.to_local(in_point), time, rng),
+		)
+	}
+	fn area(&self) -> Float {
+		self.primitive.area()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		let local_hit = Hit {
+			point: self.to_local(sampled_hit.point),
+			normal: self.unrotate(sampled_hit.normal),
+			geometric_normal: self.unrotate(sampled_hit.geometric_normal),
+			..*sampled_hit
+		};
+		self.primitive
+			.scattering_pdf(self.to_local(hit_point), self.unrotate(wi), &local_hit)
+	}
+	fn material_is_light(&self) -> bool {
+		self.primitive.material_is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.primitive.object_id()
+	}
+	fn layers(&self) -> u32 {
+		self.primitive.layers()
+	}
+	fn material(&self) -> Option<&P::Material> {
+		self.primitive.material()
+	}
+}
+
+// bounds of the corners of the primitive's bounds moved into place
+impl<P: AABound> AABound for Instance<P> {
+	fn get_aabb(&self) -> AABB {
+		let aabb = self.primitive.get_aabb();
+		let mut bounds = None;
+		for i in 0..8 {
+			let corner = Vec3::new(
+				if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+				if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+				if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+			);
+			AABB::extend_contains(&mut bounds, self.to_world(corner));
+		}
+		bounds.unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{aabox::AABox, utility::test_scene::lambertian};
+	use rand::thread_rng;
+
+	#[test]
+	fn rotated_box() {
+		// a unit cube beside the y axis that a quarter turn moves behind it
+		let material = lambertian(Vec3::one(), 0.5);
+		let aabox = AABox::new(
+			Vec3::new(0.0, -0.5, -0.5),
+			Vec3::new(1.0, 0.5, 0.5),
+			material,
+		);
+		let instance = Instance::new(aabox.clone()).with_rotation_y(90.0);
+
+		let down = |x: Float, z: Float| Ray::new(Vec3::new(x, 5.0, z), -Vec3::y(), 0.0);
+		let (behind, beside) = (down(0.0, -0.8), down(0.8, 0.0));
+		assert!(aabox.get_int(&behind).is_none());
+		assert!(aabox.get_int(&beside).is_some());
+		assert!(!instance.does_int(&beside));
+		assert!(instance.get_int(&beside).is_none());
+
+		assert!(instance.does_int(&behind));
+		let hit = instance.get_int(&behind).unwrap().hit;
+		assert!((hit.t - 4.5).abs() < 0.0001);
+		assert!((hit.point - Vec3::new(0.0, 0.5, -0.8)).mag() < 0.0001);
+		assert!((hit.normal - Vec3::y()).mag() < 0.0001);
+
+		// a side that faced along z now faces along x
+		let side = Ray::new(Vec3::new(5.0, 0.0, -0.5), -Vec3::x(), 0.0);
+		let hit = instance.get_int(&side).unwrap().hit;
+		assert!((hit.point - Vec3::new(0.5, 0.0, -0.5)).mag() < 0.0001);
+		assert!((hit.normal - Vec3::x()).mag() < 0.0001);
+		assert!(hit.out);
+
+		let aabb = instance.get_aabb();
+		assert!(
+			(aabb.min - Vec3::new(-0.5, -0.5, -1.0))
+				.abs()
+				.component_max()
+				< 0.0001
+		);
+		assert!((aabb.max - Vec3::new(0.5, 0.5, 0.0)).abs().component_max() < 0.0001);
+
+		// moved along z the ray behind it misses and one following it hits
+		let moved = instance.with_translation(Vec3::new(0.0, 0.0, 3.0));
+		assert!(moved.get_int(&behind).is_none());
+		let hit = moved.get_int(&down(0.0, 2.2)).unwrap().hit;
+		assert!((hit.point - Vec3::new(0.0, 0.5, 2.2)).mag() < 0.0001);
+		let aabb = moved.get_aabb();
+		for _ in 0..100 {
+			let (point, normal) = moved.sample_surface(&mut thread_rng());
+			let inside = (point - aabb.min)
+				.component_min()
+				.min((aabb.max - point).component_min());
+			assert!(inside > -0.0001, "{point:?}");
+			assert!((normal.mag() - 1.0).abs() < 0.0001);
+		}
+	}
+}
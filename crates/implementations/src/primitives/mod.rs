@@ -1,24 +1,122 @@
 use crate::{
 	aabb::{AABound, AABB},
+	camera::SimpleCamera,
 	primitives::{
+		aabox::AABox,
+		bilinear_patch::BilinearPatch,
+		cylinder::Cylinder,
+		disk::Disk,
+		instance::Instance,
+		moving_sphere::MovingSphere,
+		quad::Quad,
 		sphere::Sphere,
 		triangle::{MeshTriangle, Triangle},
 	},
 };
 use proc::Primitive;
 use rt_core::*;
+use std::{collections::HashMap, sync::Arc};
 
+pub mod aabox;
+pub mod bilinear_patch;
+pub mod cylinder;
+mod decimate;
+pub mod disk;
+pub mod instance;
+pub mod moving_sphere;
+pub mod quad;
 pub mod sphere;
 pub mod triangle;
 
+pub use decimate::decimate;
+
+// half thickness given to the bounds of a primitive along an axis it's flat on
+pub(crate) const FLAT_PADDING: Float = 0.0001;
+
 #[derive(Primitive, Debug, Clone)]
 pub enum AllPrimitives<'a, M: Scatter> {
 	Sphere(Sphere<'a, M>),
 	Triangle(Triangle<'a, M>),
 	MeshTriangle(MeshTriangle<'a, M>),
+	BilinearPatch(BilinearPatch<'a, M>),
+	AABox(AABox<'a, M>),
+	MovingSphere(MovingSphere<'a, M>),
+	Instance(Instance<AllPrimitives<'a, M>>),
+	Cylinder(Cylinder<'a, M>),
+	Disk(Disk<'a, M>),
+	Quad(Quad<'a, M>),
+}
+
+impl<'a, M: Scatter> AllPrimitives<'a, M> {
+	pub fn set_material(&mut self, material: &'a M) {
+		match self {
+			AllPrimitives::Sphere(p) => p.material = material,
+			AllPrimitives::Triangle(p) => p.material = material,
+			AllPrimitives::MeshTriangle(p) => p.material = material,
+			AllPrimitives::BilinearPatch(p) => p.material = material,
+			AllPrimitives::AABox(p) => p.material = material,
+			AllPrimitives::MovingSphere(p) => p.material = material,
+			AllPrimitives::Instance(p) => p.primitive.set_material(material),
+			AllPrimitives::Cylinder(p) => p.material = material,
+			AllPrimitives::Disk(p) => p.material = material,
+			AllPrimitives::Quad(p) => p.material = material,
+		}
+	}
 }
 
-#[derive(Clone, Debug)]
+// joins the vertices of mesh triangles that land in the same cell of a grid over the
+// image, cells are sqrt(min_area) pixels wide and get deeper with distance so they're
+// about as deep as they are wide. Triangles left without any area are removed and the
+// number removed is returned
+pub fn simplify_subpixel<M: Scatter>(
+	primitives: &mut Vec<AllPrimitives<M>>,
+	camera: &SimpleCamera,
+	resolution: (u64, u64),
+	min_area: Float,
+) -> usize {
+	if min_area <= 0.0 {
+		return 0;
+	}
+	let cell = min_area.sqrt();
+	let depth_step = (1.0 + cell * camera.viewport_width / resolution.0 as Float).ln();
+
+	// each vertex is moved onto the first vertex found in its cell
+	let mut clusters: HashMap<(usize, i64, i64, i64), usize> = HashMap::new();
+	let mut moved_to: HashMap<(usize, usize), usize> = HashMap::new();
+	for primitive in primitives.iter_mut() {
+		if let AllPrimitives::MeshTriangle(triangle) = primitive {
+			let mesh = Arc::as_ptr(&triangle.mesh) as usize;
+			for index in triangle.point_indices.iter_mut() {
+				let vertex = *index;
+				*index = *moved_to.entry((mesh, vertex)).or_insert_with(|| {
+					match camera.project(triangle.mesh.vertices[vertex]) {
+						Some((uv, depth)) => *clusters
+							.entry((
+								mesh,
+								(uv.x * resolution.0 as Float / cell).floor() as i64,
+								(uv.y * resolution.1 as Float / cell).floor() as i64,
+								(depth.ln() / depth_step).floor() as i64,
+							))
+							.or_insert(vertex),
+						None => vertex,
+					}
+				});
+			}
+		}
+	}
+
+	let count = primitives.len();
+	primitives.retain(|primitive| match primitive {
+		AllPrimitives::MeshTriangle(triangle) => {
+			let [a, b, c] = triangle.point_indices;
+			a != b && b != c && c != a
+		}
+		_ => true,
+	});
+	count - primitives.len()
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum Axis {
 	X,
 	Y,
@@ -89,3 +187,82 @@ impl Axis {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{triangle::MeshData, utility::test_scene::lambertian};
+
+	#[test]
+	fn subpixel_lod() {
+		// a finely tessellated square far in front of the camera
+		const N: usize = 40;
+		let vertices = (0..=N)
+			.flat_map(|y| {
+				(0..=N).map(move |x| {
+					Vec3::new(x as Float / N as Float, y as Float / N as Float, 20.0)
+						- Vec3::new(0.5, 0.5, 0.0)
+				})
+			})
+			.collect();
+		let mesh = Arc::new(MeshData::new(vertices, vec![-Vec3::z()]));
+		let material = lambertian(Vec3::one(), 0.5);
+		let corner = |x: usize, y: usize| y * (N + 1) + x;
+		let mut primitives = Vec::new();
+		for (x, y) in (0..N).flat_map(|y| (0..N).map(move |x| (x, y))) {
+			for points in [
+				[corner(x, y), corner(x + 1, y), corner(x + 1, y + 1)],
+				[corner(x, y), corner(x + 1, y + 1), corner(x, y + 1)],
+			] {
+				primitives.push(AllPrimitives::MeshTriangle(MeshTriangle::new(
+					points,
+					[0; 3],
+					material,
+					mesh.clone(),
+				)));
+			}
+		}
+
+		let camera = SimpleCamera::new(Vec3::zero(), Vec3::z(), Vec3::y(), 40.0, 1.0, 0.0, 1.0);
+		let pixel = 2.0 * 20.0 * (20.0 as Float).to_radians().tan() / 128.0;
+		let bounds = |primitives: &[AllPrimitives<_>]| {
+			primitives
+				.iter()
+				.flat_map(|p| match p {
+					AllPrimitives::MeshTriangle(t) => t.point_indices.map(|i| t.mesh.vertices[i]),
+					_ => unreachable!(),
+				})
+				.fold(
+					(Vec3::one() * Float::MAX, Vec3::one() * Float::MIN),
+					|(min, max), p| (min.min_by_component(p), max.max_by_component(p)),
+				)
+		};
+		let (min, max) = bounds(&primitives);
+
+		let mut previous = primitives.len();
+		for min_area in [0.0, 0.05, 0.5, 2.0, 4.0] {
+			let mut simplified = primitives.clone();
+			let removed = simplify_subpixel(&mut simplified, &camera, (128, 128), min_area);
+			assert_eq!(removed + simplified.len(), primitives.len());
+			if min_area == 0.0 {
+				assert_eq!(removed, 0);
+			} else {
+				assert!(simplified.len() < previous, "{min_area}");
+			}
+			previous = simplified.len();
+
+			// the outline moves by less than a cell
+			let tolerance = min_area.sqrt() * pixel + 0.00001;
+			let (s_min, s_max) = bounds(&simplified);
+			assert!(
+				(s_min - min).abs().component_max() <= tolerance,
+				"{min_area}"
+			);
+			assert!(
+				(s_max - max).abs().component_max() <= tolerance,
+				"{min_area}"
+			);
+		}
+		assert!(previous < primitives.len() / 10);
+	}
+}
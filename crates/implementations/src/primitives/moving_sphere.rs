@@ -0,0 +1,181 @@
+use crate::{
+	aabb::{AABound, AABB},
+	sphere::{intersect, uv},
+};
+use rand::Rng;
+use rt_core::*;
+
+// sphere moving in a straight line from center0 at time0 to center1 at time1, rays
+// see it where it is at their time so it's blurred over the camera's shutter
+#[derive(Debug, Clone)]
+pub struct MovingSphere<'a, M: Scatter> {
+	pub center0: Vec3,
+	pub center1: Vec3,
+	pub time0: Float,
+	pub time1: Float,
+	pub radius: Float,
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> MovingSphere<'a, M>
+where
+	M: Scatter,
+{
+	pub fn new(
+		center0: Vec3,
+		center1: Vec3,
+		time0: Float,
+		time1: Float,
+		radius: Float,
+		material: &'a M,
+	) -> Self {
+		MovingSphere {
+			center0,
+			center1,
+			time0,
+			time1,
+			radius,
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	// the sphere stays at its ends before time0 and after time1
+	pub fn center(&self, time: Float) -> Vec3 {
+		let duration = self.time1 - self.time0;
+		if duration <= 0.0 {
+			return self.center0;
+		}
+		let t = ((time - self.time0) / duration).clamp(0.0, 1.0);
+		self.center0.lerp(self.center1, t)
+	}
+
+	// point distributed uniformly over the sphere where it is at the time and the normal
+	fn sample_at<R: Rng>(&self, time: Float, rng: &mut R) -> (Vec3, Vec3) {
+		let z = 1.0 - 2.0 * rng.gen::<Float>();
+		let a = (1.0 - z * z).max(0.0).sqrt();
+		let b = 2.0 * PI * rng.gen::<Float>();
+		let normal = Vec3::new(a * b.cos(), a * b.sin(), z);
+		(self.center(time) + self.radius * normal, normal)
+	}
+}
+
+impl<'a, M> Primitive for MovingSphere<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let center = self.center(ray.time);
+		let (t, point, normal, out) = intersect(center, self.radius, ray)?;
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			EPSILON * Vec3::one(),
+			normal,
+			self.material
+				.requires_uv()
+				.then(|| uv(center, self.radius, point)),
+			out,
+			self.material,
+		))
+	}
+	// samples without a time, like photons leaving a light, are taken where the sphere is
+	// at a random one, its pdf by area doesn't depend on where it is
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.sample_surface(rng).0
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let time = self.time0 + (self.time1 - self.time0) * rng.gen::<Float>();
+		self.sample_at(time, rng)
+	}
+	// light samples are taken where the sphere is at the time of the ray being lit
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, time: Float, rng: &mut R) -> Vec3 {
+		(self.sample_at(time, rng).0 - in_point).normalised()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * self.area())
+	}
+	fn area(&self) -> Float {
+		4.0 * PI * self.radius * self.radius
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+// bounds of the sphere at both ends of its path
+impl<'a, M: Scatter> AABound for MovingSphere<'a, M> {
+	fn get_aabb(&self) -> AABB {
+		let extent = self.radius * Vec3::one();
+		AABB::new(
+			(self.center0 - extent).min_by_component(self.center1 - extent),
+			(self.center0 + extent).max_by_component(self.center1 + extent),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::test_scene::lambertian;
+	use rand::thread_rng;
+
+	#[test]
+	fn tracks_center() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let sphere = MovingSphere::new(
+			Vec3::zero(),
+			Vec3::new(2.0, 0.0, 0.0),
+			0.0,
+			1.0,
+			0.5,
+			material,
+		);
+
+		// rays straight down at each end of the path, at both times
+		for (x, time, hit) in [
+			(0.0, 0.0, true),
+			(0.0, 1.0, false),
+			(2.0, 0.0, false),
+			(2.0, 1.0, true),
+			(1.0, 0.5, true),
+		] {
+			let ray = Ray::new(Vec3::new(x, 5.0, 0.0), -Vec3::y(), time);
+			let int = sphere.get_int(&ray);
+			assert_eq!(int.is_some(), hit, "{x} {time}");
+			assert_eq!(sphere.does_int(&ray), hit);
+			if let Some(int) = int {
+				let center = sphere.center(time);
+				assert!((int.hit.point - (center + 0.5 * Vec3::y())).mag() < 0.0001);
+				assert!((int.hit.t - 4.5).abs() < 0.0001);
+				assert_eq!(int.hit.normal, Vec3::y());
+			}
+		}
+		// times outside the path stay at its ends
+		assert_eq!(sphere.center(-1.0), Vec3::zero());
+		assert_eq!(sphere.center(2.0), Vec3::new(2.0, 0.0, 0.0));
+
+		let aabb = sphere.get_aabb();
+		assert_eq!(aabb.min, Vec3::new(-0.5, -0.5, -0.5));
+		assert_eq!(aabb.max, Vec3::new(2.5, 0.5, 0.5));
+		for _ in 0..100 {
+			let (point, normal) = sphere.sample_surface(&mut thread_rng());
+			assert!(point.min_by_component(aabb.min) == aabb.min);
+			assert!(point.max_by_component(aabb.max) == aabb.max);
+			assert!((normal.mag() - 1.0).abs() < 0.0001);
+		}
+	}
+}
@@ -0,0 +1,225 @@
+use crate::{
+	aabb::{AABound, AABB},
+	primitives::{Axis, FLAT_PADDING},
+	utility::{check_side, gamma},
+};
+use rand::Rng;
+use rt_core::*;
+
+// parallelogram with a corner at q and sides along the edges u and v, its points are
+// q + alpha * u + beta * v with alpha and beta in [0, 1]
+#[derive(Debug, Clone)]
+pub struct Quad<'a, M: Scatter> {
+	pub q: Vec3,
+	pub u: Vec3,
+	pub v: Vec3,
+	// unit normal of the plane along u x v
+	pub normal: Vec3,
+	// u x v over its squared length, turns a point's offsets from q into alpha and beta
+	w: Vec3,
+	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
+}
+
+impl<'a, M> Quad<'a, M>
+where
+	M: Scatter,
+{
+	pub fn new(q: Vec3, u: Vec3, v: Vec3, material: &'a M) -> Self {
+		let n = u.cross(v);
+		Quad {
+			q,
+			u,
+			v,
+			normal: n.normalised(),
+			w: n / n.mag_sq(),
+			material,
+			object_id: 0,
+			layers: u32::MAX,
+		}
+	}
+
+	// alpha and beta of a point on the plane
+	fn coordinates(&self, point: Vec3) -> Vec2 {
+		let offset = point - self.q;
+		Vec2::new(
+			self.w.dot(offset.cross(self.v)),
+			self.w.dot(self.u.cross(offset)),
+		)
+	}
+
+	// distance along the ray to the plane and the alpha and beta of where it lands
+	fn intersect(&self, ray: &Ray) -> Option<(Float, Vec2)> {
+		// rays parallel to the plane never cross it
+		let denominator = ray.direction.dot(self.normal);
+		if denominator.abs() < 1e-8 {
+			return None;
+		}
+		let t = (self.q - ray.origin).dot(self.normal) / denominator;
+		if t <= 0.0 {
+			return None;
+		}
+		let coordinates = self.coordinates(ray.at(t));
+		let inside = (0.0..=1.0).contains(&coordinates.x) && (0.0..=1.0).contains(&coordinates.y);
+		inside.then_some((t, coordinates))
+	}
+
+	fn corners(&self) -> [Vec3; 4] {
+		[
+			self.q,
+			self.q + self.u,
+			self.q + self.v,
+			self.q + self.u + self.v,
+		]
+	}
+}
+
+impl<'a, M> Primitive for Quad<'a, M>
+where
+	M: Scatter,
+{
+	type Material = M;
+	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
+		let (t, uv) = self.intersect(ray)?;
+
+		// the point is rebuilt from the quad so it lies on it
+		let point = self.q + uv.x * self.u + uv.y * self.v;
+		let point_error = gamma(5) * (self.q.abs() + (uv.x * self.u).abs() + (uv.y * self.v).abs());
+
+		let mut normal = self.normal;
+		let out = check_side(&mut normal, &ray.direction);
+
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			point_error,
+			normal,
+			Some(uv),
+			out,
+			self.material,
+		))
+	}
+	fn does_int(&self, ray: &Ray) -> bool {
+		self.intersect(ray).is_some()
+	}
+	fn get_uv(&self, point: Vec3) -> Option<Vec2> {
+		Some(self.coordinates(point))
+	}
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		self.sample_surface(rng).0
+	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		(
+			self.q + rng.gen::<Float>() * self.u + rng.gen::<Float>() * self.v,
+			self.normal,
+		)
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
+		(self.get_sample(rng) - in_point).normalised()
+	}
+	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
+		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * self.area())
+	}
+	fn area(&self) -> Float {
+		self.u.cross(self.v).mag()
+	}
+	fn material_is_light(&self) -> bool {
+		self.material.is_light()
+	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
+}
+
+// bounds of the corners, padded along axes the quad is flat on
+impl<'a, M: Scatter> AABound for Quad<'a, M> {
+	fn get_aabb(&self) -> AABB {
+		let mut bounds = None;
+		for corner in self.corners() {
+			AABB::extend_contains(&mut bounds, corner);
+		}
+		let mut aabb = bounds.unwrap();
+		for axis in [Axis::X, Axis::Y, Axis::Z] {
+			if axis.get_axis_value(aabb.max) - axis.get_axis_value(aabb.min) < FLAT_PADDING {
+				let padding = axis.return_point_with_axis(FLAT_PADDING * Vec3::one());
+				aabb.min -= padding;
+				aabb.max += padding;
+			}
+		}
+		aabb
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::test_scene::*;
+	use rand::thread_rng;
+
+	#[test]
+	fn tilted_quad() {
+		// a 2 x 2 square tilted 45 degrees about x through the origin, on the plane y = z
+		let material = lambertian(Vec3::one(), 0.5);
+		let quad = Quad::new(
+			Vec3::new(-1.0, -1.0, -1.0),
+			Vec3::new(2.0, 0.0, 0.0),
+			Vec3::new(0.0, 2.0, 2.0),
+			material,
+		);
+		assert!((quad.area() - 4.0 * (2.0 as Float).sqrt()).abs() < 0.0001);
+
+		let down = |x: Float, z: Float| Ray::new(Vec3::new(x, 5.0, z), -Vec3::y(), 0.0);
+		let hit = quad.get_int(&down(0.0, 0.0)).unwrap().hit;
+		assert!((hit.t - 5.0).abs() < 0.0001);
+		assert!(hit.point.mag() < 0.0001);
+		assert!((hit.uv.unwrap().x - 0.5).abs() < 0.0001);
+		assert!((hit.uv.unwrap().y - 0.5).abs() < 0.0001);
+		// u x v points down and along z so the ray sees the back face
+		let facing = Vec3::new(0.0, 1.0, -1.0).normalised();
+		assert!((hit.normal - facing).mag() < 0.0001);
+		assert!(!hit.out);
+
+		// just inside each edge hits and just outside misses
+		for (x, z) in [(0.99, 0.0), (-0.99, 0.0), (0.0, 0.99), (0.0, -0.99)] {
+			assert!(quad.does_int(&down(x, z)), "{x} {z}");
+			assert!(!quad.does_int(&down(x * 1.02, z * 1.02)), "{x} {z}");
+			assert!(quad.get_int(&down(x * 1.02, z * 1.02)).is_none());
+		}
+
+		// rays parallel to the plane miss
+		let parallel = Ray::new(Vec3::new(0.0, -0.5, -1.0), Vec3::new(0.0, 1.0, 1.0), 0.0);
+		assert!(quad.get_int(&parallel).is_none());
+
+		// rays at sampled points hit them with the matching uv
+		let aabb = quad.get_aabb();
+		for _ in 0..100 {
+			let (point, normal) = quad.sample_surface(&mut thread_rng());
+			assert!((normal - quad.normal).mag() < 0.0001);
+			let inside = (point - aabb.min)
+				.component_min()
+				.min((aabb.max - point).component_min());
+			assert!(inside > -0.0001, "{point:?}");
+
+			let origin = point + 3.0 * Vec3::new(0.2, 1.0, 0.1);
+			let hit = quad
+				.get_int(&Ray::new(origin, point - origin, 0.0))
+				.unwrap()
+				.hit;
+			assert!((hit.point - point).mag() < 0.001);
+			let uv = hit.uv.unwrap();
+			let expected = quad.get_uv(point).unwrap();
+			assert!((uv.x - expected.x).abs() < 0.001 && (uv.y - expected.y).abs() < 0.001);
+		}
+
+		// a quad flat along y still has bounds with some thickness
+		let flat = Quad::new(Vec3::zero(), Vec3::x(), Vec3::z(), material).get_aabb();
+		assert!(flat.max.y > flat.min.y);
+	}
+}
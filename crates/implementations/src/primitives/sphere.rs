@@ -1,8 +1,8 @@
 use crate::{
 	aabb::{AABound, AABB},
-	utility::{coord::Coordinate, random_float},
+	utility::coord::Coordinate,
 };
-
+use rand::Rng;
 use rt_core::*;
 
 #[derive(Debug, Clone)]
@@ -10,6 +10,8 @@ pub struct Sphere<'a, M: Scatter> {
 	pub center: Vec3,
 	pub radius: Float,
 	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
 }
 
 impl<'a, M> Sphere<'a, M>
@@ -21,126 +23,148 @@ where
 			center,
 			radius,
 			material,
+			object_id: 0,
+			layers: u32::MAX,
 		}
 	}
 }
 
+// 1 - cos of the half angle of the cone a sphere covers, without the cancellation of
+// subtracting cos from 1 when the sphere is small or far away
+fn one_minus_cos_theta_max(sin_theta_max_sq: Float) -> Float {
+	let cos_theta_max = (1.0 - sin_theta_max_sq).max(0.0).sqrt();
+	sin_theta_max_sq / (1.0 + cos_theta_max)
+}
+
+// distance along the ray to the sphere, the point hit, the normal facing against the
+// ray and whether the ray hit the outside
 #[allow(clippy::suspicious_operation_groupings)]
+pub(super) fn intersect(
+	center: Vec3,
+	radius: Float,
+	ray: &Ray,
+) -> Option<(Float, Vec3, Vec3, bool)> {
+	let dir = ray.direction;
+	let orig = ray.origin;
+
+	// simplified terms for algorithm below
+	let deltap = center - orig;
+	let ddp = dir.dot(deltap);
+	let deltapdot = deltap.dot(deltap);
+
+	let remedy_term = deltap - ddp * dir;
+	let discriminant = radius * radius - remedy_term.dot(remedy_term);
+
+	// check if any solutions exist
+	if discriminant > 0.0 {
+		// the square root of the discriminant
+		let sqrt_val = discriminant.sqrt();
+
+		// Get intermediate q value based on ddp sign
+		let q = if ddp > 0.0 {
+			ddp + sqrt_val
+		} else {
+			ddp - sqrt_val
+		};
+
+		// Get two solutions of quadratic formula
+		let mut t0 = q;
+		let mut t1 = (deltapdot - radius * radius) / q;
+
+		// Make sure t1 > t0 (for sorting purposes)
+		if t1 < t0 {
+			std::mem::swap(&mut t0, &mut t1);
+		};
+
+		// Get smallest t value that is above 0
+		let t = if t0 > 0.0 {
+			t0
+		} else {
+			if t1 <= 0.0 {
+				return None;
+			}
+			t1
+		};
+
+		// Get point at "t"
+		let point = ray.at(t);
+
+		// Get normal from intersection point
+		let mut normal = (point - center) / radius;
+
+		// Make sure normal faces outward and make note of what side of the object the ray is on
+		let mut out = true;
+		if normal.dot(dir) > 0.0 {
+			out = false;
+			normal = -normal;
+		}
+
+		Some((t, point, normal, out))
+	} else {
+		None
+	}
+}
+
+pub(super) fn uv(center: Vec3, radius: Float, point: Vec3) -> Vec2 {
+	let x = (center.x - point.x) / radius;
+	let y = (center.y - point.y) / radius;
+	let z = (center.z - point.z) / radius;
+	let phi = (-1.0 * z).atan2(x) + PI;
+	let theta = (-1.0 * y).acos();
+
+	Vec2::new(phi / (2.0 * PI), theta / PI)
+}
+
 impl<'a, M> Primitive for Sphere<'a, M>
 where
 	M: Scatter,
 {
 	type Material = M;
 	fn get_int(&self, ray: &Ray) -> Option<SurfaceIntersection<M>> {
-		let dir = ray.direction;
-		let center = self.center;
-		let radius = self.radius;
-		let orig = ray.origin;
-
-		// simplified terms for algorithm below
-		let deltap = center - orig;
-		let ddp = dir.dot(deltap);
-		let deltapdot = deltap.dot(deltap);
-
-		let remedy_term = deltap - ddp * dir;
-		let discriminant = radius * radius - remedy_term.dot(remedy_term);
-
-		// check if any solutions exist
-		if discriminant > 0.0 {
-			// the square root of the discriminant
-			let sqrt_val = discriminant.sqrt();
-
-			// Get intermediate q value based on ddp sign
-			let q = if ddp > 0.0 {
-				ddp + sqrt_val
-			} else {
-				ddp - sqrt_val
-			};
-
-			// Get two solutions of quadratic formula
-			let mut t0 = q;
-			let mut t1 = (deltapdot - radius * radius) / q;
-
-			// Make sure t1 > t0 (for sorting purposes)
-			if t1 < t0 {
-				std::mem::swap(&mut t0, &mut t1);
-			};
-
-			// Get smallest t value that is above 0
-			let t = if t0 > 0.0 {
-				t0
-			} else {
-				if t1 <= 0.0 {
-					return None;
-				}
-				t1
-			};
-
-			// Get point at "t"
-			let point = ray.at(t);
-
-			// Get normal from intersection point
-			let mut normal = (point - center) / radius;
-
-			// Make sure normal faces outward and make note of what side of the object the ray is on
-			let mut out = true;
-			if normal.dot(dir) > 0.0 {
-				out = false;
-				normal = -normal;
-			}
-
-			// fill in details about intersection point
-			Some(SurfaceIntersection::new(
-				t,
-				point,
-				EPSILON * Vec3::one(),
-				normal,
-				self.get_uv(point),
-				out,
-				self.material,
-			))
-		} else {
-			None
-		}
+		let (t, point, normal, out) = intersect(self.center, self.radius, ray)?;
+		Some(SurfaceIntersection::new(
+			t,
+			point,
+			EPSILON * Vec3::one(),
+			normal,
+			self.get_uv(point),
+			out,
+			self.material,
+		))
 	}
 	fn get_uv(&self, point: Vec3) -> Option<Vec2> {
 		if self.material.requires_uv() {
-			let x = (self.center.x - point.x) / self.radius;
-			let y = (self.center.y - point.y) / self.radius;
-			let z = (self.center.z - point.z) / self.radius;
-			let phi = (-1.0 * z).atan2(x) + PI;
-			let theta = (-1.0 * y).acos();
-
-			return Some(Vec2::new(phi / (2.0 * PI), theta / PI));
+			return Some(uv(self.center, self.radius, point));
 		}
 		None
 	}
-	fn get_sample(&self) -> Vec3 {
-		let z = 1.0 - 2.0 * random_float();
+	fn get_sample<R: Rng>(&self, rng: &mut R) -> Vec3 {
+		let z = 1.0 - 2.0 * rng.gen::<Float>();
 		let a = (1.0 - z * z).max(0.0).sqrt();
-		let b = 2.0 * PI * random_float();
+		let b = 2.0 * PI * rng.gen::<Float>();
 		self.center + self.radius * Vec3::new(a * b.cos(), a * b.sin(), z)
 	}
-	fn sample_visible_from_point(&self, in_point: Vec3) -> Vec3 {
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		let point = self.get_sample(rng);
+		(point, (point - self.center) / self.radius.abs())
+	}
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
 		let distance_sq = (in_point - self.center).mag_sq();
 		let point = if distance_sq <= self.radius * self.radius {
-			self.get_sample()
+			self.get_sample(rng)
 		} else {
-			let distance = distance_sq.sqrt();
 			let sin_theta_max_sq = self.radius * self.radius / distance_sq;
-			let cost_theta_max = (1.0 - sin_theta_max_sq).max(0.0).sqrt();
-			let r1 = random_float();
-			let cos_theta = (1.0 - r1) + r1 * cost_theta_max;
-			let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
-			let phi = 2.0 * random_float() * PI;
-
-			// calculate alpha
-			let ds = distance * cos_theta
-				- (self.radius * self.radius - distance_sq * sin_theta * sin_theta)
-					.max(0.0)
-					.sqrt();
-			let cos_alpha = (distance_sq + self.radius * self.radius - ds * ds)
-				/ (2.0 * distance * self.radius);
+			let sin_theta_max = sin_theta_max_sq.sqrt();
+			// 1 - cos theta is kept apart from cos theta, which rounds to 1 for distant lights
+			let one_minus_cos_theta =
+				rng.gen::<Float>() * one_minus_cos_theta_max(sin_theta_max_sq);
+			let cos_theta = 1.0 - one_minus_cos_theta;
+			let sin_theta_sq = one_minus_cos_theta * (2.0 - one_minus_cos_theta);
+			let phi = 2.0 * rng.gen::<Float>() * PI;
+
+			// angle at the centre between the point and the direction to the hit point
+			let cos_alpha = sin_theta_sq / sin_theta_max
+				+ cos_theta * (1.0 - sin_theta_sq / sin_theta_max_sq).max(0.0).sqrt();
 			let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
 
 			// get sphere point
@@ -159,10 +183,7 @@ where
 			return (sampled_hit.point - hit_point).mag_sq()
 				/ (wi.dot(sampled_hit.normal).abs() * self.area());
 		}
-		let sin_theta_max_sq = rsq / dsq;
-		let cos_theta_max = (1.0 - sin_theta_max_sq).max(0.0).sqrt();
-
-		1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+		1.0 / (2.0 * PI * one_minus_cos_theta_max(rsq / dsq))
 	}
 	fn area(&self) -> Float {
 		4.0 * PI * self.radius * self.radius
@@ -170,6 +191,15 @@ where
 	fn material_is_light(&self) -> bool {
 		self.material.is_light()
 	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
 }
 
 impl<'a, M: Scatter> AABound for Sphere<'a, M> {
@@ -1,17 +1,30 @@
 use crate::{
 	aabb::{AABound, AABB},
 	primitives::Axis,
-	utility::{check_side, gamma},
+	utility::{check_side, coord::onb_from_normal, gamma},
 };
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use rt_core::*;
-use std::sync::Arc;
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::Arc,
+};
+
+// texture coordinates used when a triangle isn't given any
+const DEFAULT_UVS: [Vec2; 3] = [
+	Vec2 { x: 0.0, y: 0.0 },
+	Vec2 { x: 1.0, y: 0.0 },
+	Vec2 { x: 1.0, y: 1.0 },
+];
 
 #[derive(Clone, Debug)]
 pub struct Triangle<'a, M: Scatter> {
 	pub points: [Vec3; 3],
 	pub normals: [Vec3; 3],
+	pub uvs: [Vec2; 3],
 	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
 }
 
 impl<'a, M> Triangle<'a, M>
@@ -21,8 +34,11 @@ where
 	pub fn new(points: [Vec3; 3], normals: [Vec3; 3], material: &'a M) -> Self {
 		Triangle {
 			points,
-			normals,
+			normals: normals.map(unit_normal),
+			uvs: DEFAULT_UVS,
 			material,
+			object_id: 0,
+			layers: u32::MAX,
 		}
 	}
 }
@@ -31,7 +47,10 @@ where
 pub struct MeshTriangle<'a, M: Scatter> {
 	pub point_indices: [usize; 3],
 	pub normal_indices: [usize; 3],
+	pub uv_indices: Option<[usize; 3]>,
 	pub material: &'a M,
+	pub object_id: u32,
+	pub layers: u32,
 	pub mesh: Arc<MeshData>,
 }
 
@@ -48,28 +67,133 @@ where
 		MeshTriangle {
 			point_indices,
 			normal_indices,
+			uv_indices: None,
 			material,
+			object_id: 0,
+			layers: u32::MAX,
 			mesh,
 		}
 	}
 }
 
+impl<'a, M> MeshTriangle<'a, M>
+where
+	M: Scatter,
+{
+	// reverses the winding, the corners keep their normals and uvs
+	pub fn flip(&mut self) {
+		self.point_indices.swap(1, 2);
+		self.normal_indices.swap(1, 2);
+		if let Some(uv_indices) = &mut self.uv_indices {
+			uv_indices.swap(1, 2);
+		}
+	}
+
+	// whether the triangle goes from point a to b along one of its edges
+	fn has_edge(&self, a: usize, b: usize) -> bool {
+		(0..3).any(|i| self.point_indices[i] == a && self.point_indices[(i + 1) % 3] == b)
+	}
+}
+
+// makes the winding of triangles sharing an edge consistent, each connected set of
+// triangles follows the first triangle in it and neighbours that go along a shared edge
+// in the same direction are flipped, returns the number of triangles flipped
+pub fn fix_winding<M: Scatter>(triangles: &mut [MeshTriangle<M>]) -> usize {
+	let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+	for (index, triangle) in triangles.iter().enumerate() {
+		let p = triangle.point_indices;
+		for (a, b) in [(p[0], p[1]), (p[1], p[2]), (p[2], p[0])] {
+			edges.entry((a.min(b), a.max(b))).or_default().push(index);
+		}
+	}
+
+	let mut visited = vec![false; triangles.len()];
+	let mut flipped = 0;
+	for seed in 0..triangles.len() {
+		if visited[seed] {
+			continue;
+		}
+		visited[seed] = true;
+		let mut queue = VecDeque::from([seed]);
+		while let Some(index) = queue.pop_front() {
+			let p = triangles[index].point_indices;
+			for (a, b) in [(p[0], p[1]), (p[1], p[2]), (p[2], p[0])] {
+				for &neighbour in &edges[&(a.min(b), a.max(b))] {
+					if visited[neighbour] {
+						continue;
+					}
+					visited[neighbour] = true;
+					if triangles[neighbour].has_edge(a, b) {
+						triangles[neighbour].flip();
+						flipped += 1;
+					}
+					queue.push_back(neighbour);
+				}
+			}
+		}
+	}
+	flipped
+}
+
 #[derive(Debug)]
 pub struct MeshData {
 	pub vertices: Vec<Vec3>,
 	pub normals: Vec<Vec3>,
+	pub uvs: Vec<Vec2>,
 }
 
 impl MeshData {
 	pub fn new(vertices: Vec<Vec3>, normals: Vec<Vec3>) -> Self {
-		MeshData { vertices, normals }
+		MeshData {
+			vertices,
+			normals: normals.into_iter().map(unit_normal).collect(),
+			uvs: Vec::new(),
+		}
+	}
+}
+
+// zero normals are left for the intersection to replace with the face's normal
+fn unit_normal(normal: Vec3) -> Vec3 {
+	if normal.mag_sq() > 0.0 {
+		normal.normalised()
+	} else {
+		normal
+	}
+}
+
+// (dp/du, dp/dv) of the triangle, the solve is degenerate when the texture
+// coordinates don't span an area and a tangent frame around the face's normal is used
+pub fn uv_tangents(points: [Vec3; 3], uvs: [Vec2; 3]) -> (Vec3, Vec3) {
+	let (dp02, dp12) = (points[0] - points[2], points[1] - points[2]);
+	let (duv02, duv12) = (uvs[0] - uvs[2], uvs[1] - uvs[2]);
+	let det = duv02.x * duv12.y - duv02.y * duv12.x;
+	if det.abs() > 1e-9 {
+		let inv_det = 1.0 / det;
+		let dpdu = inv_det * (duv12.y * dp02 - duv02.y * dp12);
+		let dpdv = inv_det * (duv02.x * dp12 - duv12.x * dp02);
+		if dpdu.cross(dpdv).mag_sq() > 0.0 {
+			return (dpdu, dpdv);
+		}
 	}
+	let (tangent, bitangent, _) = onb_from_normal(dp02.cross(dp12).normalised());
+	(tangent, bitangent)
 }
 
 pub trait TriangleTrait<'a, M: Scatter> {
 	fn get_point(&self, index: usize) -> Vec3;
 	fn get_normal(&self, index: usize) -> Vec3;
+	fn get_uv(&self, index: usize) -> Vec2;
 	fn get_material(&self) -> &'a M;
+
+	// unit tangent pointing along increasing u and the bitangent along v, for
+	// orienting normal maps
+	fn tangents(&self) -> (Vec3, Vec3) {
+		let (dpdu, dpdv) = uv_tangents(
+			[0, 1, 2].map(|i| self.get_point(i)),
+			[0, 1, 2].map(|i| self.get_uv(i)),
+		);
+		(dpdu.normalised(), dpdv.normalised())
+	}
 }
 
 impl<'a, M> TriangleTrait<'a, M> for Triangle<'a, M>
@@ -82,6 +206,9 @@ where
 	fn get_normal(&self, index: usize) -> Vec3 {
 		self.normals[index]
 	}
+	fn get_uv(&self, index: usize) -> Vec2 {
+		self.uvs[index]
+	}
 	fn get_material(&self) -> &'a M {
 		self.material
 	}
@@ -97,18 +224,34 @@ where
 	fn get_normal(&self, index: usize) -> Vec3 {
 		self.mesh.normals[self.normal_indices[index]]
 	}
+	fn get_uv(&self, index: usize) -> Vec2 {
+		match self.uv_indices {
+			Some(indices) => self.mesh.uvs[indices[index]],
+			None => DEFAULT_UVS[index],
+		}
+	}
 	fn get_material(&self) -> &'a M {
 		self.material
 	}
 }
 
+// watertight test, the points are translated, permuted and sheared into a space that
+// depends on the ray so unlike Moller-Trumbore there are no edges to precompute per
+// triangle, caching them would also add 24 bytes to every mesh triangle
 pub fn triangle_intersection<'a, T: TriangleTrait<'a, M>, M: Scatter>(
 	triangle: &'a T,
 	ray: &Ray,
 ) -> Option<SurfaceIntersection<'a, M>> {
-	let mut p0t = triangle.get_point(0) - ray.origin;
-	let mut p1t = triangle.get_point(1) - ray.origin;
-	let mut p2t = triangle.get_point(2) - ray.origin;
+	// fetch the points once, for mesh triangles each lookup goes through the mesh data
+	let points = [
+		triangle.get_point(0),
+		triangle.get_point(1),
+		triangle.get_point(2),
+	];
+
+	let mut p0t = points[0] - ray.origin;
+	let mut p1t = points[1] - ray.origin;
+	let mut p2t = points[2] - ray.origin;
 
 	let max_axis = Axis::get_max_abs_axis(&ray.direction);
 	Axis::swap_z(&mut p0t, &max_axis);
@@ -176,43 +319,61 @@ pub fn triangle_intersection<'a, T: TriangleTrait<'a, M>, M: Scatter>(
 		return None;
 	}
 
-	let uv = b0 * Vec2::new(0.0, 0.0) + b1 * Vec2::new(1.0, 0.0) + b2 * Vec2::new(1.0, 1.0);
+	let uv = b0 * triangle.get_uv(0) + b1 * triangle.get_uv(1) + b2 * triangle.get_uv(2);
 
-	let mut normal =
+	// the vertex normals are interpolated for shading, interpolating unit normals
+	// shortens them
+	let face_normal = (points[1] - points[0])
+		.cross(points[2] - points[0])
+		.normalised();
+	let normal =
 		b0 * triangle.get_normal(0) + b1 * triangle.get_normal(1) + b2 * triangle.get_normal(2);
+	let mut normal = if normal.mag_sq() > 0.0 {
+		normal.normalised()
+	} else {
+		face_normal
+	};
 
 	let out = check_side(&mut normal, &ray.direction);
+	let geometric_normal = if face_normal.dot(ray.direction) > 0.0 {
+		-face_normal
+	} else {
+		face_normal
+	};
 
-	let x_abs_sum = (b0 * triangle.get_point(0).x).abs()
-		+ (b1 * triangle.get_point(1).x).abs()
-		+ (b2 * triangle.get_point(2).x).abs();
-	let y_abs_sum = (b0 * triangle.get_point(0).y).abs()
-		+ (b1 * triangle.get_point(1).y).abs()
-		+ (b2 * triangle.get_point(2).y).abs();
-	let z_abs_sum = (b0 * triangle.get_point(0).z).abs()
-		+ (b1 * triangle.get_point(1).z).abs()
-		+ (b2 * triangle.get_point(2).z).abs();
+	let x_abs_sum = (b0 * points[0].x).abs() + (b1 * points[1].x).abs() + (b2 * points[2].x).abs();
+	let y_abs_sum = (b0 * points[0].y).abs() + (b1 * points[1].y).abs() + (b2 * points[2].y).abs();
+	let z_abs_sum = (b0 * points[0].z).abs() + (b1 * points[1].z).abs() + (b2 * points[2].z).abs();
 
 	let point_error = gamma(7) * Vec3::new(x_abs_sum, y_abs_sum, z_abs_sum)
-		+ gamma(6)
-			* Vec3::new(
-				b2 * triangle.get_point(2).x,
-				b2 * triangle.get_point(2).y,
-				b2 * triangle.get_point(2).z,
-			);
+		+ gamma(6) * Vec3::new(b2 * points[2].x, b2 * points[2].y, b2 * points[2].z);
+
+	let point = b0 * points[0] + b1 * points[1] + b2 * points[2];
+
+	Some(
+		SurfaceIntersection::new(
+			t,
+			point,
+			point_error,
+			normal,
+			Some(uv),
+			out,
+			triangle.get_material(),
+		)
+		.with_geometric_normal(geometric_normal),
+	)
+}
 
-	let point =
-		b0 * triangle.get_point(0) + b1 * triangle.get_point(1) + b2 * triangle.get_point(2);
-
-	Some(SurfaceIntersection::new(
-		t,
-		point,
-		point_error,
-		normal,
-		Some(uv),
-		out,
-		triangle.get_material(),
-	))
+// uniform point on the triangle and its geometric normal
+fn sample_triangle<R: Rng>(points: [Vec3; 3], rng: &mut R) -> (Vec3, Vec3) {
+	let a = rng.gen::<Float>().sqrt();
+	let (b0, b1) = (1.0 - a, a * rng.gen::<Float>());
+
+	let point = b0 * points[0] + b1 * points[1] + (1.0 - b0 - b1) * points[2];
+	let normal = (points[1] - points[0])
+		.cross(points[2] - points[0])
+		.normalised();
+	(point, normal)
 }
 
 impl<'a, M> Primitive for Triangle<'a, M>
@@ -229,8 +390,7 @@ where
 			.cross(self.points[2] - self.points[0])
 			.mag()
 	}
-	fn sample_visible_from_point(&self, in_point: Vec3) -> Vec3 {
-		let mut rng = thread_rng();
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
 		let uv = rng.gen::<Float>().sqrt();
 		let uv = (1.0 - uv, uv * rng.gen::<Float>());
 
@@ -239,12 +399,24 @@ where
 
 		(point - in_point).normalised()
 	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		sample_triangle(self.points, rng)
+	}
 	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
 		(sampled_hit.point - hit_point).mag_sq() / (sampled_hit.normal.dot(wi).abs() * self.area())
 	}
 	fn material_is_light(&self) -> bool {
 		self.material.is_light()
 	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
 }
 
 impl<'a, M> Primitive for MeshTriangle<'a, M>
@@ -264,8 +436,7 @@ where
 			)
 			.mag()
 	}
-	fn sample_visible_from_point(&self, in_point: Vec3) -> Vec3 {
-		let mut rng = thread_rng();
+	fn sample_visible_from_point<R: Rng>(&self, in_point: Vec3, _time: Float, rng: &mut R) -> Vec3 {
 		let uv = rng.gen::<Float>().sqrt();
 		let uv = (1.0 - uv, uv * rng.gen::<Float>().sqrt());
 
@@ -275,12 +446,24 @@ where
 
 		(point - in_point).normalised()
 	}
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+		sample_triangle(self.point_indices.map(|i| self.mesh.vertices[i]), rng)
+	}
 	fn scattering_pdf(&self, hit_point: Vec3, wi: Vec3, sampled_hit: &Hit) -> Float {
 		(sampled_hit.point - hit_point).mag_sq() / (wi.dot(sampled_hit.normal).abs() * self.area())
 	}
 	fn material_is_light(&self) -> bool {
 		self.material.is_light()
 	}
+	fn object_id(&self) -> u32 {
+		self.object_id
+	}
+	fn layers(&self) -> u32 {
+		self.layers
+	}
+	fn material(&self) -> Option<&M> {
+		Some(self.material)
+	}
 }
 impl<'a, M: Scatter> AABound for Triangle<'a, M> {
 	fn get_aabb(&self) -> AABB {
@@ -305,3 +488,210 @@ impl<'a, M: Scatter> AABound for MeshTriangle<'a, M> {
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::{random_unit_vector, test_scene::*};
+
+	#[test]
+	fn mesh_and_flat_triangles_agree() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let points = [
+			Vec3::new(-1.0, -0.5, 0.2),
+			Vec3::new(1.0, -0.3, -0.1),
+			Vec3::new(0.1, 1.0, 0.0),
+		];
+		let normals = [Vec3::z(), Vec3::new(0.1, 0.0, 1.0).normalised(), Vec3::z()];
+
+		let flat = Triangle::new(points, normals, material);
+		let mesh = MeshTriangle::new(
+			[2, 0, 1],
+			[0, 2, 1],
+			material,
+			Arc::new(MeshData::new(
+				vec![points[1], points[2], points[0]],
+				vec![normals[0], normals[2], normals[1]],
+			)),
+		);
+
+		let mut hits = 0;
+		for _ in 0..1000 {
+			let origin = 3.0 * random_unit_vector();
+			let ray = Ray::new(origin, 0.5 * random_unit_vector() - origin, 0.0);
+			match (flat.get_int(&ray), mesh.get_int(&ray)) {
+				(Some(a), Some(b)) => {
+					hits += 1;
+					assert_eq!(a.hit.t, b.hit.t);
+					assert_eq!(a.hit.point, b.hit.point);
+					assert_eq!(a.hit.normal, b.hit.normal);
+				}
+				(None, None) => (),
+				_ => panic!("only one of the triangles was hit"),
+			}
+		}
+		assert!(hits > 0);
+	}
+
+	#[test]
+	fn smooth_shading() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let points = [
+			Vec3::new(-1.0, -1.0, 0.0),
+			Vec3::new(1.0, -1.0, 0.0),
+			Vec3::new(0.0, 1.0, 0.0),
+		];
+		let normals = [
+			Vec3::new(-0.3, -0.2, 1.0),
+			Vec3::new(0.4, -0.1, 1.0),
+			Vec3::new(0.0, 0.5, 1.0),
+		]
+		.map(|n| n.normalised());
+		let triangle = MeshTriangle::new(
+			[0, 1, 2],
+			[0, 1, 2],
+			material,
+			Arc::new(MeshData::new(points.to_vec(), normals.to_vec())),
+		);
+
+		// at the centroid the shading normal is the vertices' average, the geometric
+		// normal stays the face's and both face the ray
+		let centroid = (points[0] + points[1] + points[2]) / 3.0;
+		let average = ((normals[0] + normals[1] + normals[2]) / 3.0).normalised();
+		for side in [1.0, -1.0] {
+			let origin = centroid + side * 2.0 * Vec3::z();
+			let hit = triangle
+				.get_int(&Ray::new(origin, centroid - origin, 0.0))
+				.unwrap()
+				.hit;
+			assert!((hit.point - centroid).mag() < 0.0001);
+			assert!(
+				(hit.normal - side * average).mag() < 0.0001,
+				"{:?}",
+				hit.normal
+			);
+			assert_eq!(hit.geometric_normal, side * Vec3::z());
+			assert_eq!(hit.out, side > 0.0);
+		}
+	}
+
+	#[test]
+	fn unit_normals() {
+		let material = lambertian(Vec3::one(), 0.5);
+		let points = [
+			Vec3::new(-1000.0, -500.0, 0.0),
+			Vec3::new(1000.0, -500.0, 0.0),
+			Vec3::new(0.0, 1000.0, 10.0),
+		];
+		let triangle = |normals| Triangle::new(points, normals, material);
+		let shading = triangle([
+			5.0 * Vec3::z(),
+			Vec3::new(1.0, 0.0, 1.0),
+			Vec3::new(0.0, -3.0, 1.0),
+		]);
+		let zero = triangle([Vec3::zero(); 3]);
+
+		// rays stay z dominant
+		let origin = Vec3::new(0.0, 0.0, -1000.0);
+		for _ in 0..100 {
+			let target = Vec3::new(
+				400.0 * random_unit_vector().x,
+				200.0 * random_unit_vector().y,
+				0.0,
+			);
+			let ray = Ray::new(origin, target - origin, 0.0);
+			for triangle in [&shading, &zero] {
+				let hit = triangle.get_int(&ray).unwrap().hit;
+				assert!((hit.normal.mag() - 1.0).abs() < 0.0001, "{:?}", hit.normal);
+			}
+		}
+	}
+
+	#[test]
+	fn uv_tangents() {
+		let material = lambertian(Vec3::one(), 0.5);
+		// u runs along -y and v along x on a triangle in the xy plane
+		let points = [
+			Vec3::new(0.0, 0.0, 1.0),
+			Vec3::new(0.0, -2.0, 1.0),
+			Vec3::new(3.0, 0.0, 1.0),
+		];
+		let mut flat = Triangle::new(points, [Vec3::zero(); 3], material);
+		flat.uvs = [
+			Vec2::new(0.5, 0.0),
+			Vec2::new(1.5, 0.0),
+			Vec2::new(0.5, 1.0),
+		];
+		let (tangent, bitangent) = flat.tangents();
+		assert!((tangent + Vec3::y()).mag() < 0.0001, "{tangent:?}");
+		assert!((bitangent - Vec3::x()).mag() < 0.0001, "{bitangent:?}");
+
+		// the same uvs looked up through mesh data
+		let mut mesh_data = MeshData::new(points.to_vec(), vec![Vec3::zero()]);
+		mesh_data.uvs = vec![
+			Vec2::new(0.5, 1.0),
+			Vec2::new(0.5, 0.0),
+			Vec2::new(1.5, 0.0),
+		];
+		let mut mesh = MeshTriangle::new([0, 1, 2], [0; 3], material, Arc::new(mesh_data));
+		mesh.uv_indices = Some([1, 2, 0]);
+		assert_eq!(mesh.tangents(), (tangent, bitangent));
+
+		// the hit's uv is interpolated from the vertices
+		let ray = Ray::new(Vec3::new(1.0, -0.5, 0.0), Vec3::z(), 0.0);
+		let uv = flat.get_int(&ray).unwrap().hit.uv.unwrap();
+		assert!((uv - Vec2::new(0.75, 1.0 / 3.0)).mag() < 0.0001, "{uv:?}");
+
+		// uvs without area fall back to a frame around the face
+		flat.uvs = [Vec2::zero(); 3];
+		let (tangent, bitangent) = flat.tangents();
+		assert!(tangent.dot(Vec3::z()).abs() < 0.0001);
+		assert!(bitangent.dot(Vec3::z()).abs() < 0.0001);
+		assert!((tangent.cross(bitangent).mag() - 1.0).abs() < 0.0001);
+	}
+
+	#[test]
+	fn winding() {
+		let material = lambertian(Vec3::one(), 0.5);
+		// a fan of four triangles around the centre of a square, and a separate triangle
+		let mesh = Arc::new(MeshData::new(
+			vec![
+				Vec3::new(0.0, 0.0, 0.0),
+				Vec3::new(-1.0, -1.0, 0.0),
+				Vec3::new(1.0, -1.0, 0.0),
+				Vec3::new(1.0, 1.0, 0.0),
+				Vec3::new(-1.0, 1.0, 0.0),
+				Vec3::new(5.0, 0.0, 0.0),
+				Vec3::new(6.0, 0.0, 0.0),
+				Vec3::new(5.0, 1.0, 0.0),
+			],
+			vec![Vec3::z()],
+		));
+		let triangle = |points| MeshTriangle::new(points, [0; 3], material, mesh.clone());
+		let mut triangles = vec![
+			triangle([0, 1, 2]),
+			triangle([0, 2, 3]),
+			// reversed
+			triangle([0, 4, 3]),
+			triangle([0, 4, 1]),
+			triangle([5, 7, 6]),
+		];
+		triangles[2].uv_indices = Some([0, 1, 2]);
+
+		let face_normal = |t: &MeshTriangle<_>| {
+			let p = t.point_indices.map(|i| mesh.vertices[i]);
+			(p[1] - p[0]).cross(p[2] - p[0]).normalised()
+		};
+		assert_eq!(face_normal(&triangles[2]), -Vec3::z());
+
+		assert_eq!(fix_winding(&mut triangles), 1);
+		assert_eq!(triangles[2].point_indices, [0, 3, 4]);
+		assert_eq!(triangles[2].uv_indices, Some([0, 2, 1]));
+		for triangle in &triangles[..4] {
+			assert_eq!(face_normal(triangle), Vec3::z());
+		}
+		// unconnected triangles keep their own winding
+		assert_eq!(triangles[4].point_indices, [5, 7, 6]);
+		assert_eq!(fix_winding(&mut triangles), 0);
+	}
+}
@@ -0,0 +1,141 @@
+use crate::*;
+use rt_core::*;
+
+// stops sampling each pixel once the 95% confidence interval of its luminance is within
+// adaptive_threshold times the mean, pixels that don't converge run to samples_per_pixel.
+// Passes are accumulated like the RandomSampler and the samples each pixel got are in
+// the progress' moments.
+pub struct AdaptiveSampler;
+
+// samples taken before a pixel's variance is trusted enough to stop sampling it
+pub const MIN_SAMPLES: u64 = 16;
+
+impl Sampler for AdaptiveSampler {
+	fn sample_image<C, P, M, T, F, A>(
+		&self,
+		render_options: RenderOptions,
+		camera: &C,
+		acceleration_structure: &A,
+		presentation_update: Option<(&mut T, F)>,
+	) where
+		C: Camera,
+		P: Primitive,
+		M: Scatter,
+		F: Fn(&mut T, &SamplerProgress, u64) -> bool,
+		A: AccelerationStructure<Object = P, Material = M>,
+	{
+		let render_options = RenderOptions {
+			sampler: SamplerType::Adaptive,
+			..render_options
+		};
+		random_sampler::RandomSampler.sample_image(
+			render_options,
+			camera,
+			acceleration_structure,
+			presentation_update,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*};
+
+	#[test]
+	fn converged_pixels_stop() {
+		// a diffuse sphere lit by a small light is noisy, the wall behind it isn't
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 1.0, lambertian(Vec3::one(), 0.8))),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 4.0, -3.0),
+					2.0,
+					emissive(Vec3::one(), 4.0),
+				)),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(0.0, 0.0, 1003.0),
+					1000.0,
+					emissive(Vec3::one(), 0.5),
+				)),
+			],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -4.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 64,
+			width: 12,
+			height: 12,
+			render_method: RenderMethod::Naive,
+			seed: Some(2),
+			..Default::default()
+		};
+
+		let run = |sampler: &dyn Fn(&mut SamplerProgress)| {
+			let mut progress = SamplerProgress::new(12 * 12, 3);
+			sampler(&mut progress);
+			progress
+		};
+		let update = |progress: &mut SamplerProgress, previous: &SamplerProgress, i: u64| {
+			progress.rays_shot += previous.rays_shot;
+			accumulate_mean(&mut progress.current_image, &previous.current_image, i);
+			progress.moments.clone_from(&previous.moments);
+			false
+		};
+		let uniform = run(&|progress| {
+			RandomSampler.sample_image(options, &camera, &bvh, Some((progress, update)))
+		});
+		let adaptive = run(&|progress| {
+			AdaptiveSampler.sample_image(options, &camera, &bvh, Some((progress, update)))
+		});
+
+		// the wall converges straight away while the sphere's pixels run to the cap
+		let counts = adaptive.sample_counts();
+		assert_eq!(counts[0], MIN_SAMPLES);
+		assert_eq!(*counts.iter().max().unwrap(), 64);
+		assert!(counts.iter().filter(|&&count| count == 64).count() > 10);
+		assert!(4 * adaptive.rays_shot < 3 * uniform.rays_shot);
+
+		// pixels that stopped early keep the value they converged to
+		for pixel in [0, 11, 12 * 11] {
+			let c = 3 * pixel;
+			assert!((adaptive.current_image[c] - 0.5).abs() < 0.0001);
+		}
+
+		// the error is close to sampling every pixel uniformly and well below uniform sampling
+		// with the same number of rays, it's a little higher as pixels whose first samples
+		// happened not to vary stop early
+		let uniform_run = |samples_per_pixel, seed| {
+			let options = RenderOptions {
+				samples_per_pixel,
+				seed: Some(seed),
+				..options
+			};
+			run(&|progress| {
+				RandomSampler.sample_image(options, &camera, &bvh, Some((progress, update)))
+			})
+		};
+		let reference = uniform_run(1024, 3).current_image;
+		let error = |image: &[Float]| {
+			image
+				.iter()
+				.zip(reference.iter())
+				.map(|(a, b)| (a - b).powi(2))
+				.sum::<Float>()
+		};
+		let budget = (64 * adaptive.rays_shot).div_ceil(uniform.rays_shot);
+		let same_rays = error(&uniform_run(budget, 2).current_image);
+		let (uniform_error, adaptive_error) = (
+			error(&uniform.current_image),
+			error(&adaptive.current_image),
+		);
+		assert!(
+			adaptive_error < 1.25 * uniform_error,
+			"{adaptive_error} {uniform_error}"
+		);
+		assert!(
+			adaptive_error < 0.8 * same_rays,
+			"{adaptive_error} {same_rays}"
+		);
+	}
+}
@@ -0,0 +1,111 @@
+use rt_core::*;
+use std::path::Path;
+
+// the fractional parts of multiples of these spread consecutive samples evenly
+const SHIFT_U: Float = 0.618034;
+const SHIFT_V: Float = 0.754878;
+
+// tile of values in [0, 1) repeated over the image, neighbouring values are far apart
+// so jitter taken from it leaves error that is high frequency and less visible
+#[derive(Copy, Clone, Debug)]
+pub struct BlueNoise {
+	values: &'static [Float],
+	size: u64,
+}
+
+impl BlueNoise {
+	// square greyscale image
+	pub fn load<P: AsRef<Path>>(filepath: &P) -> Self {
+		let img = match image::open(filepath) {
+			Ok(img) => img.to_luma32f(),
+			Err(e) => panic!("{e}"),
+		};
+		if img.width() != img.height() {
+			panic!("blue noise tile must be square");
+		}
+		let size = img.width() as u64;
+		let values: Vec<Float> = img
+			.into_raw()
+			.into_iter()
+			.map(|v| (v as Float).clamp(0.0, 0.999999))
+			.collect();
+		BlueNoise {
+			values: Box::leak(values.into_boxed_slice()),
+			size,
+		}
+	}
+
+	// ranks pixels by repeatedly filling the emptiest one, measured by a gaussian
+	// falloff from those already filled that wraps around the tile's edges
+	pub fn generate(size: u64) -> Self {
+		const SIGMA: Float = 1.5;
+		let n = (size * size) as usize;
+		let mut energy: Vec<Float> = vec![0.0; n];
+		let mut values = vec![Float::NAN; n];
+
+		for rank in 0..n {
+			let next = (0..n)
+				.filter(|&i| values[i].is_nan())
+				.min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+				.unwrap();
+			values[next] = (rank as Float + 0.5) / n as Float;
+
+			let (nx, ny) = (next as u64 % size, next as u64 / size);
+			for (i, e) in energy.iter_mut().enumerate() {
+				let wrapped = |a: u64, b: u64| {
+					let d = a.abs_diff(b);
+					d.min(size - d) as Float
+				};
+				let (dx, dy) = (wrapped(i as u64 % size, nx), wrapped(i as u64 / size, ny));
+				*e += (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp();
+			}
+		}
+		BlueNoise {
+			values: Box::leak(values.into_boxed_slice()),
+			size,
+		}
+	}
+
+	fn value(&self, x: u64, y: u64) -> Float {
+		self.values[((y % self.size) * self.size + x % self.size) as usize]
+	}
+
+	// jitter within the pixel for the given sample, each sample shifts the whole tile
+	// by the same amount so it stays blue noise, v reads the tile offset by half of it
+	pub fn pixel_offset(&self, x: u64, y: u64, sample: u64) -> (Float, Float) {
+		let half = self.size / 2;
+		(
+			(self.value(x, y) + sample as Float * SHIFT_U).fract(),
+			(self.value(x + half, y + half) + sample as Float * SHIFT_V).fract(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn neighbouring_offsets() {
+		let noise = BlueNoise::generate(16);
+		assert_eq!(noise.values, BlueNoise::generate(16).values);
+
+		// every rank is used once
+		let mut sorted = noise.values.to_vec();
+		sorted.sort_by(Float::total_cmp);
+		for (i, v) in sorted.iter().enumerate() {
+			assert!((v - (i as Float + 0.5) / 256.0).abs() < 0.00001);
+		}
+
+		for (x, y) in (0..16).flat_map(|x| (0..16).map(move |y| (x, y))) {
+			let offset = noise.pixel_offset(x, y, 0);
+			assert_eq!(offset, noise.pixel_offset(x, y, 0));
+			assert_eq!(offset, noise.pixel_offset(x + 16, y + 32, 0));
+			// filled pixels push their neighbours to much later ranks
+			for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+				assert!((offset.0 - noise.pixel_offset(nx, ny, 0).0).abs() > 0.03);
+			}
+			assert_ne!(offset, noise.pixel_offset(x, y, 1));
+		}
+	}
+}
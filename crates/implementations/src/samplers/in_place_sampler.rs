@@ -0,0 +1,154 @@
+use crate::{
+	random_sampler::{sample_pixels, PIXEL_CHUNK_SIZE},
+	*,
+};
+use rayon::prelude::*;
+use rt_core::*;
+
+// keeps a single image holding the running mean of each pixel, updated in place as
+// samples come in, rather than double buffering each pass like the RandomSampler.
+// The image passed to the presentation update is already the mean so it is passed
+// with a sample count of 1, replacing whatever was accumulated before.
+pub struct InPlaceSampler;
+
+// folds a sample into the running mean of n samples, returning the relative change
+pub fn update_mean(mean: &mut [Float], sample: Vec3, n: u64) -> Float {
+	let mut difference = 0.0;
+	for (mean, sample) in mean.iter_mut().zip([sample.x, sample.y, sample.z]) {
+		let previous = *mean;
+		*mean += (sample - previous) / n as Float;
+		difference += (*mean - previous).abs() / mean.abs().max(EPSILON);
+	}
+	difference
+}
+
+impl Sampler for InPlaceSampler {
+	fn sample_image<C, P, M, T, F, A>(
+		&self,
+		render_options: RenderOptions,
+		camera: &C,
+		acceleration_structure: &A,
+		mut presentation_update: Option<(&mut T, F)>,
+	) where
+		C: Camera,
+		P: Primitive,
+		M: Scatter,
+		F: Fn(&mut T, &SamplerProgress, u64) -> bool,
+		A: AccelerationStructure<Object = P, Material = M>,
+	{
+		let channels = 3;
+		let pixel_num = render_options.width * render_options.height;
+
+		let mut progress = SamplerProgress::new(pixel_num, channels);
+
+		let pixel_chunk_size = PIXEL_CHUNK_SIZE;
+		let chunk_size = pixel_chunk_size * channels;
+		let mut total_rays = 0;
+
+		for i in 0..render_options.samples_per_pixel {
+			let (rays_shot, difference) = progress
+				.current_image
+				.par_chunks_mut(chunk_size as usize)
+				.zip(progress.moments.par_chunks_mut(pixel_chunk_size as usize))
+				.enumerate()
+				.map(|(chunk_i, (chunk, chunk_moments))| {
+					let (mut rays_shot, mut difference) = (0, 0.0);
+					let first_pixel = pixel_chunk_size * chunk_i as u64;
+					let pixels: Vec<u64> = (first_pixel..(first_pixel + chunk.len() as u64 / 3))
+						.filter(|&pixel_i| i < render_options.pixel_samples(pixel_i))
+						.collect();
+
+					let results =
+						sample_pixels(&render_options, camera, acceleration_structure, &pixels, i);
+
+					for (pixel_i, (colour, rays, _)) in pixels.into_iter().zip(results) {
+						let c = ((pixel_i - first_pixel) * channels) as usize;
+						difference += update_mean(&mut chunk[c..(c + 3)], colour, i + 1);
+						chunk_moments[(pixel_i - first_pixel) as usize].add(colour);
+						rays_shot += rays;
+					}
+					(rays_shot, difference)
+				})
+				.reduce(|| (0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+			progress.rays_shot = rays_shot;
+			progress.samples_completed = i + 1;
+			progress.update_noise();
+
+			if let Some((ref mut data, f)) = presentation_update.as_mut() {
+				if f(data, &progress, 1) {
+					return;
+				}
+			}
+
+			// stop once successive accumulations stop changing
+			if let Some(epsilon) = render_options.epsilon_converge {
+				if i != 0 && difference / progress.current_image.len() as Float <= epsilon {
+					return;
+				}
+			}
+
+			total_rays += rays_shot;
+			if render_options
+				.max_rays
+				.is_some_and(|max_rays| total_rays >= max_rays)
+			{
+				return;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		random_sampler::RandomSampler, sphere::Sphere, utility::random_float,
+		utility::test_scene::*,
+	};
+
+	#[test]
+	fn matches_batch_average() {
+		let samples: Vec<Vec3> = (0..100000)
+			.map(|_| 1000.0 * Vec3::new(random_float(), random_float(), random_float()))
+			.collect();
+
+		let mut mean = [0.0; 3];
+		for (i, sample) in samples.iter().enumerate() {
+			update_mean(&mut mean, *sample, i as u64 + 1);
+		}
+
+		let sum = samples.iter().fold(Vec3::zero(), |acc, s| acc + *s);
+		let batch = sum / samples.len() as Float;
+		for (mean, batch) in mean.iter().zip([batch.x, batch.y, batch.z]) {
+			assert!((mean - batch).abs() / batch < 0.0001);
+		}
+	}
+
+	#[test]
+	fn renders_like_random_sampler() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.5),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 64,
+			width: 16,
+			height: 16,
+			..Default::default()
+		};
+
+		let (in_place, _) = render(&InPlaceSampler, options, &camera, &bvh);
+		let (random, _) = render(&RandomSampler, options, &camera, &bvh);
+
+		let mean = |image: &[Float]| image.iter().sum::<Float>() / image.len() as Float;
+		assert!((mean(&in_place) - mean(&random)).abs() < 0.01);
+		// the sky is visible in the corners
+		assert_eq!(in_place[0], 1.0);
+	}
+}
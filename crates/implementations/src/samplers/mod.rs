@@ -1,6 +1,16 @@
+use crate::{blue_noise::BlueNoise, CausticMap, FogVolume};
+use rand::Rng;
+use rayon::prelude::*;
 use rt_core::*;
+use std::io::{self, Write};
+use std::path::Path;
 
+pub mod adaptive_sampler;
+pub mod blue_noise;
+pub mod in_place_sampler;
 pub mod random_sampler;
+pub mod stratified_sampler;
+pub mod tiled_output;
 
 use clap::ValueEnum;
 
@@ -21,35 +31,303 @@ pub trait Sampler: Sync {
 
 #[derive(Copy, Clone, Debug)]
 pub struct RenderOptions {
+	pub sampler: SamplerType,
 	pub samples_per_pixel: u64,
 	pub render_method: RenderMethod,
 	pub width: u64,
 	pub height: u64,
 	pub gamma: Float,
+	// stops the saved image is brightened by, brackets are taken around it
+	pub exposure: Float,
+	// out of gamut colours in the saved image are desaturated instead of clipped
+	pub gamut_compression: bool,
+	pub epsilon_converge: Option<Float>,
+	// rendering stops after the pass in which this many rays have been shot in total
+	pub max_rays: Option<u64>,
+	// (point, normal), geometry on the negative side is hidden from primary rays
+	pub clip_plane: Option<(Vec3, Vec3)>,
+	pub seed: Option<u64>,
+	// per pixel weights in [0, 1] scaling samples between min_samples and samples_per_pixel
+	pub sample_mask: Option<&'static [Float]>,
+	pub min_samples: u64,
+	pub max_diffuse_depth: u32,
+	pub max_specular_depth: u32,
+	pub russian_roulette: bool,
+	// primary rays of packet_size x packet_size pixel blocks are traced together, 1 disables
+	pub packet_size: u64,
+	pub clamp: Option<ClampSchedule>,
+	// caustic light at the first non delta vertex of paths comes from the map instead
+	pub caustics: Option<&'static CausticMap>,
+	// only followed by the MIS integrator
+	pub fog: Option<FogVolume>,
+	// pixel jitter read from a tiled blue noise texture instead of the random stream
+	pub blue_noise: Option<BlueNoise>,
+	// largest channel of sky radiance reaching anything but primary rays
+	pub sky_clamp: Option<Float>,
+	// camera rays and the paths following them only hit primitives in these layers
+	pub layers: u32,
+	// rays cast from each first hit by the ambient occlusion render method
+	pub ao_samples: u64,
+	// occluders further away than this don't block the sky
+	pub ao_distance: Float,
+	// also accumulates the even and odd sample passes into separate images, only
+	// supported by the random sampler
+	pub split_halves: bool,
+	// the adaptive sampler stops sampling a pixel once the 95% confidence interval of its
+	// luminance is narrower than this fraction of the mean either side of it
+	pub adaptive_threshold: Float,
+	// also keeps the normal of each pixel's primary hits, only supported by the random
+	// sampler
+	pub normal_pass: bool,
+	// also keeps the distance to each pixel's nearest primary hit, only supported by the
+	// random sampler
+	pub depth_pass: bool,
+	// depth given to pixels that see the sky
+	pub depth_far: Float,
+	// also keeps the mean base colour of each pixel's primary hits, only supported by the
+	// random sampler
+	pub albedo_pass: bool,
+	// also keeps the mean direct and indirect light of each pixel from the same paths as
+	// the image, only supported by the random sampler with MIS
+	pub lighting_pass: bool,
+	// how the MIS integrator shares light between light and material samples
+	pub light_weighting: LightWeighting,
 }
 
 impl Default for RenderOptions {
 	fn default() -> Self {
 		Self {
+			sampler: SamplerType::Random,
 			samples_per_pixel: 128,
 			render_method: RenderMethod::MIS,
 			width: 1920,
 			height: 1080,
 			gamma: 2.2,
+			exposure: 0.0,
+			gamut_compression: false,
+			epsilon_converge: None,
+			max_rays: None,
+			clip_plane: None,
+			seed: None,
+			sample_mask: None,
+			min_samples: 1,
+			max_diffuse_depth: 50,
+			max_specular_depth: 50,
+			russian_roulette: true,
+			packet_size: 1,
+			clamp: None,
+			caustics: None,
+			fog: None,
+			blue_noise: None,
+			sky_clamp: None,
+			layers: u32::MAX,
+			ao_samples: 16,
+			ao_distance: Float::INFINITY,
+			split_halves: false,
+			adaptive_threshold: 0.01,
+			normal_pass: false,
+			depth_pass: false,
+			depth_far: Float::INFINITY,
+			albedo_pass: false,
+			lighting_pass: false,
+			light_weighting: LightWeighting::Mis,
 		}
 	}
 }
 
+impl RenderOptions {
+	// settings for a ground truth render, every path runs until it terminates or hits the
+	// depth limit and every pixel gets the full sample count
+	pub fn reference(self) -> Self {
+		Self {
+			sampler: SamplerType::Random,
+			russian_roulette: false,
+			epsilon_converge: None,
+			max_rays: None,
+			sample_mask: None,
+			clamp: None,
+			caustics: None,
+			sky_clamp: None,
+			..self
+		}
+	}
+
+	// renders at a fraction of the resolution for a quick look, the result is meant to be
+	// upscaled back to the original size
+	pub fn preview(self, scale: Float) -> Self {
+		let scale = |size: u64| ((size as Float * scale).round() as u64).clamp(2, size.max(2));
+		Self {
+			width: scale(self.width),
+			height: scale(self.height),
+			sample_mask: None,
+			..self
+		}
+	}
+
+	// renders factor x factor pixels for each one in the image, the result is meant to be
+	// box filtered back down to the original size
+	pub fn supersample(self, factor: u64) -> Self {
+		Self {
+			width: self.width * factor,
+			height: self.height * factor,
+			sample_mask: None,
+			..self
+		}
+	}
+
+	// clamps a contribution reaching the camera after the given number of bounces
+	pub fn clamp_contribution(&self, contribution: Vec3, depth: u32) -> Vec3 {
+		match self.clamp {
+			Some(schedule) => schedule.clamp(contribution, depth),
+			None => contribution,
+		}
+	}
+
+	// the sky seen directly by the camera always keeps its full radiance, it's scaled down
+	// like ClampSchedule for rays after a bounce
+	pub fn sky_radiance(&self, radiance: Vec3, primary: bool) -> Vec3 {
+		match self.sky_clamp {
+			Some(max) if !primary => ClampSchedule::new(max, 1.0).clamp(radiance, 0),
+			_ => radiance,
+		}
+	}
+
+	// whether any pass needs what the primary rays hit
+	pub fn keeps_aovs(&self) -> bool {
+		self.normal_pass || self.depth_pass || self.albedo_pass || self.lighting_pass
+	}
+
+	pub fn pixel_samples(&self, pixel_i: u64) -> u64 {
+		// object ids can't be averaged so each pixel keeps its first sample
+		if matches!(self.render_method, RenderMethod::ObjectId) {
+			return self.samples_per_pixel.min(1);
+		}
+		match self.sample_mask {
+			Some(mask) => {
+				let min = self.min_samples.min(self.samples_per_pixel);
+				let weight = mask[pixel_i as usize].clamp(0.0, 1.0);
+				min + (weight * (self.samples_per_pixel - min) as Float).round() as u64
+			}
+			None => self.samples_per_pixel,
+		}
+	}
+
+	// whether the pixel gets the given sample pass, the adaptive sampler also leaves out
+	// pixels whose samples so far have converged
+	pub fn samples_pixel(&self, pixel_i: u64, sample: u64, moments: &PixelMoments) -> bool {
+		if sample >= self.pixel_samples(pixel_i) {
+			return false;
+		}
+		if !matches!(self.sampler, SamplerType::Adaptive)
+			|| moments.count() < self.min_samples.max(adaptive_sampler::MIN_SAMPLES)
+		{
+			return true;
+		}
+		moments
+			.confidence_interval()
+			.is_none_or(|half_width| half_width > self.adaptive_threshold * moments.mean())
+	}
+}
+
+// firefly clamp whose threshold is base * growth^depth for light arriving after depth
+// bounces, so with growth above one directly visible light is clamped the most and later
+// bounces keep more of their energy
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClampSchedule {
+	pub base: Float,
+	pub growth: Float,
+}
+
+impl ClampSchedule {
+	pub fn new(base: Float, growth: Float) -> Self {
+		Self { base, growth }
+	}
+
+	pub fn threshold(&self, depth: u32) -> Float {
+		self.base * self.growth.powi(depth as i32)
+	}
+
+	// scales the contribution down so no channel exceeds the threshold, keeping its hue
+	pub fn clamp(&self, contribution: Vec3, depth: u32) -> Vec3 {
+		let max = contribution.component_max();
+		let threshold = self.threshold(depth);
+		if max > threshold {
+			contribution * (threshold / max)
+		} else {
+			contribution
+		}
+	}
+}
+
+// loads an image as greyscale weights resized to the output resolution
+pub fn load_sample_mask<P: AsRef<Path>>(filepath: &P, width: u64, height: u64) -> Vec<Float> {
+	let img = match image::open(filepath) {
+		Ok(img) => img,
+		Err(e) => panic!("{e}"),
+	};
+	let img = image::imageops::resize(
+		&img.to_luma32f(),
+		width as u32,
+		height as u32,
+		image::imageops::FilterType::Triangle,
+	);
+	img.into_raw().into_iter().map(|v| v as Float).collect()
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum SamplerType {
+	Random,
+	InPlace,
+	Stratified,
+	Adaptive,
+}
+
+// light that can be reached both by sampling a light and by sampling the material is
+// only counted once, either weighted between the two or from the light sample alone
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LightWeighting {
+	// power heuristic weights on both samples
+	#[default]
+	Mis,
+	// material sampled rays skip the emission of lights that could have been sampled
+	LightSamples,
+}
+
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum RenderMethod {
 	Naive,
 	MIS,
+	ObjectId,
+	ShadowMatte,
+	AO,
+	// traces a wavelength per sample, rgb colours are upsampled to spectra
+	#[cfg(feature = "spectral")]
+	Spectral,
 }
 
 pub struct SamplerProgress {
 	pub samples_completed: u64,
 	pub rays_shot: u64,
 	pub current_image: Vec<Float>,
+	// mean standard error of the pixels' accumulated luminance
+	pub noise: Float,
+	// luminance statistics of each pixel's samples so far
+	pub moments: Vec<PixelMoments>,
+	pub halves: Option<SplitAccumulation>,
+	// mean primary hit normal of each pixel remapped to [0, 1], when there's a normal pass
+	pub normals: Option<Vec<Float>>,
+	// distance from the camera to each pixel's nearest primary hit, one value per pixel,
+	// when there's a depth pass
+	pub depth: Option<Vec<Float>>,
+	// mean base colour of the material at each pixel's primary hits, when there's an
+	// albedo pass
+	pub albedo: Option<Vec<Float>>,
+	// mean light reaching each pixel straight from an emitter or after a single bounce,
+	// when there's a lighting pass
+	pub direct: Option<Vec<Float>>,
+	// mean light reaching each pixel after further bounces, adding the direct light gives
+	// the image
+	pub indirect: Option<Vec<Float>>,
 }
 
 impl SamplerProgress {
@@ -58,10 +336,158 @@ impl SamplerProgress {
 			samples_completed: 0,
 			rays_shot: 0,
 			current_image: vec![0.0; (pixel_num * channels) as usize],
+			noise: 0.0,
+			moments: vec![PixelMoments::default(); pixel_num as usize],
+			halves: None,
+			normals: None,
+			depth: None,
+			albedo: None,
+			direct: None,
+			indirect: None,
+		}
+	}
+
+	// samples each pixel has had, showing where an adaptive render spent its time
+	pub fn sample_counts(&self) -> Vec<u64> {
+		self.moments.iter().map(PixelMoments::count).collect()
+	}
+
+	// pixels with fewer than two samples have no estimate and are left out
+	pub fn update_noise(&mut self) {
+		let (total, count) = self
+			.moments
+			.par_iter()
+			.filter_map(|moments| moments.standard_error())
+			.fold(
+				|| (0.0, 0),
+				|(total, count), error| (total + error, count + 1),
+			)
+			.reduce(|| (0.0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+		self.noise = total / count.max(1) as Float;
+	}
+}
+
+// means of the even and odd numbered sample passes kept apart, for denoiser training
+// the difference between the two estimates the noise left in the combined image
+#[derive(Clone, Debug, Default)]
+pub struct SplitAccumulation {
+	pub images: [Vec<Float>; 2],
+	pub samples: [u64; 2],
+}
+
+impl SplitAccumulation {
+	// adds the image of a sample pass, numbered from 0
+	pub fn add(&mut self, image: &[Float], pass: u64) {
+		let half = (pass % 2) as usize;
+		if self.images[half].is_empty() {
+			self.images[half] = vec![0.0; image.len()];
+		}
+		self.samples[half] += 1;
+		accumulate_mean(&mut self.images[half], image, self.samples[half]);
+	}
+
+	pub fn even(&self) -> &[Float] {
+		&self.images[0]
+	}
+
+	pub fn odd(&self) -> &[Float] {
+		&self.images[1]
+	}
+}
+
+// count, sum and sum of squares of the luminance of a pixel's samples
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PixelMoments {
+	count: u64,
+	sum: Float,
+	sum_sq: Float,
+}
+
+impl PixelMoments {
+	pub fn add(&mut self, colour: Vec3) {
+		let luminance = 0.2126 * colour.x + 0.7152 * colour.y + 0.0722 * colour.z;
+		self.count += 1;
+		self.sum += luminance;
+		self.sum_sq += luminance * luminance;
+	}
+
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	pub fn mean(&self) -> Float {
+		self.sum / self.count.max(1) as Float
+	}
+
+	// unbiased sample variance
+	pub fn variance(&self) -> Option<Float> {
+		if self.count < 2 {
+			return None;
 		}
+		let n = self.count as Float;
+		Some(((self.sum_sq - self.sum * self.sum / n) / (n - 1.0)).max(0.0))
+	}
+
+	// standard error of the mean of the samples
+	pub fn standard_error(&self) -> Option<Float> {
+		Some((self.variance()? / self.count as Float).sqrt())
+	}
+
+	// half width of the 95% confidence interval of the mean
+	pub fn confidence_interval(&self) -> Option<Float> {
+		Some(1.96 * self.standard_error()?)
+	}
+}
+
+// one row per pixel in image order of its position, sample count and luminance variance,
+// as csv or a json array, pixels without a variance estimate leave it empty (null)
+pub fn write_pixel_stats<W: Write>(
+	writer: &mut W,
+	width: u64,
+	moments: &[PixelMoments],
+	json: bool,
+) -> io::Result<()> {
+	if json {
+		writeln!(writer, "[")?;
+	} else {
+		writeln!(writer, "x,y,samples,variance")?;
+	}
+	for (i, pixel) in moments.iter().enumerate() {
+		let (x, y) = (i as u64 % width, i as u64 / width);
+		let samples = pixel.count();
+		if json {
+			let variance = match pixel.variance() {
+				Some(variance) => variance.to_string(),
+				None => "null".to_string(),
+			};
+			let separator = if i + 1 == moments.len() { "" } else { "," };
+			writeln!(
+				writer,
+				"\t{{\"x\": {x}, \"y\": {y}, \"samples\": {samples}, \"variance\": {variance}}}{separator}"
+			)?;
+		} else {
+			let variance = pixel.variance().map(|v| v.to_string()).unwrap_or_default();
+			writeln!(writer, "{x},{y},{samples},{variance}")?;
+		}
+	}
+	if json {
+		writeln!(writer, "]")?;
+	}
+	Ok(())
+}
+
+// updates the running mean with the nth sample, returning the mean absolute
+// relative change across all channels
+pub fn accumulate_mean(mean: &mut [Float], sample: &[Float], n: u64) -> Float {
+	let mut difference = 0.0;
+	for (mean, sample) in mean.iter_mut().zip(sample.iter()) {
+		let previous = *mean;
+		*mean += (sample - previous) / n as Float;
+		difference += (*mean - previous).abs() / mean.abs().max(EPSILON);
 	}
+	difference / mean.len().max(1) as Float
 }
 
 pub trait Camera: Sync {
-	fn get_ray(&self, u: Float, v: Float) -> Ray;
+	fn get_ray<R: Rng>(&self, u: Float, v: Float, rng: &mut R) -> Ray;
 }
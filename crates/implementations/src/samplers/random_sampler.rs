@@ -1,11 +1,310 @@
 use crate::integrators::*;
 use crate::*;
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use rayon::prelude::*;
 use rt_core::*;
+use std::collections::HashMap;
 
 pub struct RandomSampler;
 
+// pixels in each of the chunks a pass is split into between threads
+pub(super) const PIXEL_CHUNK_SIZE: u64 = 10000;
+
+// splitmix64 finaliser
+pub(super) fn mix(mut x: u64) -> u64 {
+	x = x.wrapping_add(0x9e3779b97f4a7c15);
+	x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+	x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+	x ^ (x >> 31)
+}
+
+// jittered primary ray through a pixel for the given sample pass
+fn pixel_ray<C: Camera, R: Rng>(
+	render_options: &RenderOptions,
+	camera: &C,
+	rng: &mut R,
+	pixel_i: u64,
+	sample: u64,
+) -> Ray {
+	let x = pixel_i % render_options.width;
+	let y = (pixel_i - x) / render_options.width;
+	let (jitter_u, jitter_v) = match (render_options.blue_noise, render_options.sampler) {
+		(Some(noise), _) => noise.pixel_offset(x, y, sample),
+		(None, SamplerType::Stratified) => stratified_sampler::pixel_offset(
+			rng,
+			pixel_i,
+			sample,
+			render_options.pixel_samples(pixel_i),
+		),
+		(None, _) => (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)),
+	};
+	let u = (jitter_u + x as Float) / (render_options.width - 1) as Float;
+	let v = 1.0 - (jitter_v + y as Float) / (render_options.height - 1) as Float;
+
+	camera.get_ray(u, v, rng).with_layers(render_options.layers) // remember to add le DOF
+}
+
+// traces the path returning its (direct, indirect) light and the rays shot, methods other
+// than MIS don't split their paths so all their light counts as direct
+fn integrate<'a, P, M, A, R>(
+	render_options: &RenderOptions,
+	acceleration_structure: &'a A,
+	ray: &mut Ray,
+	primary: (SurfaceIntersection<'a, M>, usize),
+	rng: &mut R,
+) -> ((Vec3, Vec3), u64)
+where
+	P: Primitive,
+	M: Scatter,
+	A: AccelerationStructure<Object = P, Material = M>,
+	R: Rng,
+{
+	let bvh = acceleration_structure;
+	let (colour, rays) = match render_options.render_method {
+		RenderMethod::MIS if render_options.lighting_pass => {
+			let (direct, indirect, rays) = mis_lighting(ray, primary, bvh, render_options, rng);
+			return ((direct, indirect), rays);
+		}
+		RenderMethod::Naive => {
+			NaiveIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+		RenderMethod::MIS => {
+			MisIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+		RenderMethod::ObjectId => {
+			ObjectIdIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+		RenderMethod::ShadowMatte => {
+			ShadowMatteIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+		RenderMethod::AO => {
+			AmbientOcclusionIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+		#[cfg(feature = "spectral")]
+		RenderMethod::Spectral => {
+			SpectralIntegrator::get_colour_from_hit(ray, primary, bvh, render_options, rng)
+		}
+	};
+	((colour, Vec3::zero()), rays)
+}
+
+// traces the whole path of a camera ray, for when no pass needs the primary hit
+fn get_colour<P, M, A, R>(
+	render_options: &RenderOptions,
+	acceleration_structure: &A,
+	ray: &mut Ray,
+	rng: &mut R,
+) -> (Vec3, u64)
+where
+	P: Primitive,
+	M: Scatter,
+	A: AccelerationStructure<Object = P, Material = M>,
+	R: Rng,
+{
+	let bvh = acceleration_structure;
+	match render_options.render_method {
+		RenderMethod::Naive => NaiveIntegrator::get_colour(ray, bvh, render_options, rng),
+		RenderMethod::MIS => MisIntegrator::get_colour(ray, bvh, render_options, rng),
+		RenderMethod::ObjectId => ObjectIdIntegrator::get_colour(ray, bvh, render_options, rng),
+		RenderMethod::ShadowMatte => {
+			ShadowMatteIntegrator::get_colour(ray, bvh, render_options, rng)
+		}
+		RenderMethod::AO => AmbientOcclusionIntegrator::get_colour(ray, bvh, render_options, rng),
+		#[cfg(feature = "spectral")]
+		RenderMethod::Spectral => SpectralIntegrator::get_colour(ray, bvh, render_options, rng),
+	}
+}
+
+// what a sample's primary ray hit and how its light splits, kept for the AOV passes
+#[derive(Clone, Copy, Default)]
+pub(super) struct PrimaryAov {
+	// remapped from [-1, 1] to [0, 1], the sky is black
+	pub normal: Vec3,
+	// distance from the camera, the sky is at the far depth
+	pub depth: Float,
+	// base colour of the material hit, the sky's colour for misses
+	pub albedo: Vec3,
+	// the path's direct and indirect light, set once it's been traced
+	pub direct: Vec3,
+	pub indirect: Vec3,
+}
+
+impl PrimaryAov {
+	// the ray is as it left the camera, before any clip plane moved it
+	fn new(
+		render_options: &RenderOptions,
+		ray: &Ray,
+		primary: &(SurfaceIntersection<impl Scatter>, usize),
+	) -> Self {
+		let (intersection, index) = primary;
+		let albedo = intersection
+			.material
+			.base_colour(&intersection.hit, ray.direction);
+		match *index {
+			usize::MAX => PrimaryAov {
+				normal: Vec3::zero(),
+				depth: render_options.depth_far,
+				albedo,
+				direct: Vec3::zero(),
+				indirect: Vec3::zero(),
+			},
+			_ => PrimaryAov {
+				normal: 0.5 * (intersection.hit.normal + Vec3::one()),
+				depth: (intersection.hit.point - ray.origin).mag(),
+				albedo,
+				direct: Vec3::zero(),
+				indirect: Vec3::zero(),
+			},
+		}
+	}
+}
+
+// running mean of each pixel's rgb value over the passes that sampled it
+fn add_to_means(
+	means: &mut [Float],
+	values: impl Iterator<Item = (u64, Vec3)>,
+	moments: &[PixelMoments],
+) {
+	for (pixel_i, value) in values {
+		let count = moments[pixel_i as usize].count() as Float;
+		let c = (pixel_i * 3) as usize;
+		for (mean, value) in means[c..(c + 3)]
+			.iter_mut()
+			.zip([value.x, value.y, value.z])
+		{
+			*mean += (value - *mean) / count;
+		}
+	}
+}
+
+// jittered sample of a pixel returning the colour, rays shot and what the primary ray
+// hit when a pass keeps it
+pub(super) fn sample_pixel<C, P, M, A, R>(
+	render_options: &RenderOptions,
+	camera: &C,
+	acceleration_structure: &A,
+	rng: &mut R,
+	pixel_i: u64,
+	sample: u64,
+) -> (Vec3, u64, PrimaryAov)
+where
+	C: Camera,
+	P: Primitive,
+	M: Scatter,
+	A: AccelerationStructure<Object = P, Material = M>,
+	R: Rng,
+{
+	let mut ray = pixel_ray(render_options, camera, rng, pixel_i, sample);
+	if !render_options.keeps_aovs() {
+		let (colour, rays) = get_colour(render_options, acceleration_structure, &mut ray, rng);
+		return (colour, rays, PrimaryAov::default());
+	}
+	let camera_ray = ray;
+	let primary = primary_hit(&mut ray, acceleration_structure, render_options.clip_plane);
+	let mut aov = PrimaryAov::new(render_options, &camera_ray, &primary);
+	let ((direct, indirect), rays) = integrate(
+		render_options,
+		acceleration_structure,
+		&mut ray,
+		primary,
+		rng,
+	);
+	(aov.direct, aov.indirect) = (direct, indirect);
+	(direct + indirect, rays, aov)
+}
+
+// samples each of the pixels once, with a packet size above one the primary rays of
+// each packet_size x packet_size block of pixels are traced together. Each pixel has
+// its own rng stream so both modes give the same result.
+pub(super) fn sample_pixels<C, P, M, A>(
+	render_options: &RenderOptions,
+	camera: &C,
+	acceleration_structure: &A,
+	pixels: &[u64],
+	sample: u64,
+) -> Vec<(Vec3, u64, PrimaryAov)>
+where
+	C: Camera,
+	P: Primitive,
+	M: Scatter,
+	A: AccelerationStructure<Object = P, Material = M>,
+{
+	let size = render_options.packet_size;
+	if size <= 1 {
+		return pixels
+			.iter()
+			.map(|&pixel_i| {
+				sample_pixel(
+					render_options,
+					camera,
+					acceleration_structure,
+					&mut pixel_rng(render_options.seed, sample, pixel_i),
+					pixel_i,
+					sample,
+				)
+			})
+			.collect();
+	}
+
+	let mut rngs: Vec<SmallRng> = pixels
+		.iter()
+		.map(|&pixel_i| pixel_rng(render_options.seed, sample, pixel_i))
+		.collect();
+	let rays: Vec<Ray> = pixels
+		.iter()
+		.zip(rngs.iter_mut())
+		.map(|(&pixel_i, rng)| pixel_ray(render_options, camera, rng, pixel_i, sample))
+		.collect();
+
+	// indices into pixels grouped by block in order of first appearance
+	let mut blocks: Vec<Vec<usize>> = Vec::new();
+	let mut block_lookup = HashMap::new();
+	for (i, &pixel_i) in pixels.iter().enumerate() {
+		let (x, y) = (
+			pixel_i % render_options.width,
+			pixel_i / render_options.width,
+		);
+		let block = *block_lookup.entry((y / size, x / size)).or_insert_with(|| {
+			blocks.push(Vec::new());
+			blocks.len() - 1
+		});
+		blocks[block].push(i);
+	}
+
+	let mut results = vec![(Vec3::zero(), 0, PrimaryAov::default()); pixels.len()];
+	for block in blocks {
+		let mut packet: Vec<Ray> = block.iter().map(|&i| rays[i]).collect();
+		let hits = primary_hits(
+			&mut packet,
+			acceleration_structure,
+			render_options.clip_plane,
+		);
+		for ((&i, mut ray), primary) in block.iter().zip(packet).zip(hits) {
+			let mut aov = PrimaryAov::new(render_options, &rays[i], &primary);
+			let ((direct, indirect), rays) = integrate(
+				render_options,
+				acceleration_structure,
+				&mut ray,
+				primary,
+				&mut rngs[i],
+			);
+			(aov.direct, aov.indirect) = (direct, indirect);
+			results[i] = (direct + indirect, rays, aov);
+		}
+	}
+	results
+}
+
+// each pass of each pixel gets its own stream for the camera jitter and every choice
+// along the path, so a seeded render is the same however the pixels are split between
+// threads, chunks or tiles
+pub(super) fn pixel_rng(seed: Option<u64>, sample: u64, pixel: u64) -> SmallRng {
+	match seed {
+		Some(seed) => SmallRng::seed_from_u64(mix(mix(seed ^ mix(sample)) ^ pixel)),
+		None => SmallRng::from_rng(rand::thread_rng()).unwrap(),
+	}
+}
+
 impl Sampler for RandomSampler {
 	fn sample_image<C, P, M, T, F, A>(
 		&self,
@@ -28,8 +327,34 @@ impl Sampler for RandomSampler {
 			SamplerProgress::new(pixel_num, channels),
 		);
 
-		let pixel_chunk_size = 10000;
+		let mut mean = (render_options.epsilon_converge.is_some()
+			|| render_options.sample_mask.is_some()
+			|| matches!(render_options.sampler, SamplerType::Adaptive)
+			|| matches!(render_options.render_method, RenderMethod::ObjectId))
+		.then(|| vec![0.0; (pixel_num * channels) as usize]);
+
+		let mut moments = vec![PixelMoments::default(); pixel_num as usize];
+		let mut halves = render_options.split_halves.then(SplitAccumulation::default);
+		let mut normals = render_options
+			.normal_pass
+			.then(|| vec![0.0; (pixel_num * channels) as usize]);
+		let mut depth = render_options
+			.depth_pass
+			.then(|| vec![Float::INFINITY; pixel_num as usize]);
+		let mut albedo = render_options
+			.albedo_pass
+			.then(|| vec![0.0; (pixel_num * channels) as usize]);
+		let mut lighting = render_options.lighting_pass.then(|| {
+			(
+				vec![0.0; (pixel_num * channels) as usize],
+				vec![0.0; (pixel_num * channels) as usize],
+			)
+		});
+		let keep_aovs = render_options.keeps_aovs();
+
+		let pixel_chunk_size = PIXEL_CHUNK_SIZE;
 		let chunk_size = pixel_chunk_size * channels;
+		let mut total_rays = 0;
 
 		for i in 0..render_options.samples_per_pixel {
 			let (previous, current) = if i % 2 == 0 {
@@ -38,47 +363,115 @@ impl Sampler for RandomSampler {
 				(&accumulator_buffers.1, &mut accumulator_buffers.0)
 			};
 
+			let mean_image = mean.as_deref();
+			let active: Vec<bool> = moments
+				.par_iter()
+				.enumerate()
+				.map(|(pixel_i, moments)| render_options.samples_pixel(pixel_i as u64, i, moments))
+				.collect();
+
+			// what the pass's primary rays hit when there's an AOV pass
+			let mut pass_aovs = Vec::new();
 			rayon::scope(|s| {
 				s.spawn(|_| {
-					current.rays_shot = current
+					let chunks: Vec<(u64, Vec<(u64, PrimaryAov)>)> = current
 						.current_image
 						.par_chunks_mut(chunk_size as usize)
 						.enumerate()
 						.map(|(chunk_i, chunk)| {
-							let mut rng = rand::thread_rng();
 							let mut rays_shot = 0;
-							for chunk_pixel_i in 0..(chunk.len() / 3) {
-								let pixel_i =
-									chunk_pixel_i as u64 + pixel_chunk_size * chunk_i as u64;
-								let x = pixel_i % render_options.width;
-								let y = (pixel_i - x) / render_options.width;
-								let u = (rng.gen_range(0.0..1.0) + x as Float)
-									/ (render_options.width - 1) as Float;
-								let v = 1.0
-									- (rng.gen_range(0.0..1.0) + y as Float)
-										/ (render_options.height - 1) as Float;
-
-								let mut ray = camera.get_ray(u, v); // remember to add le DOF
-								let result = match render_options.render_method {
-									RenderMethod::Naive => NaiveIntegrator::get_colour(
-										&mut ray,
-										acceleration_structure,
-									),
-									RenderMethod::MIS => {
-										MisIntegrator::get_colour(&mut ray, acceleration_structure)
-									}
-								};
-
-								chunk[chunk_pixel_i * channels as usize] = result.0.x;
-								chunk[chunk_pixel_i * channels as usize + 1] = result.0.y;
-								chunk[chunk_pixel_i * channels as usize + 2] = result.0.z;
-								rays_shot += result.1;
+
+							// once a pixel's budget is used up or it has converged repeating
+							// its mean leaves the accumulated value unchanged
+							let first_pixel = pixel_chunk_size * chunk_i as u64;
+							let mut pixels = Vec::with_capacity(chunk.len() / 3);
+							for pixel_i in first_pixel..(first_pixel + chunk.len() as u64 / 3) {
+								if !active[pixel_i as usize] {
+									let c = ((pixel_i - first_pixel) * channels) as usize;
+									let p = (pixel_i * channels) as usize;
+									chunk[c..(c + 3)]
+										.copy_from_slice(&mean_image.unwrap()[p..(p + 3)]);
+								} else {
+									pixels.push(pixel_i);
+								}
+							}
+
+							let results = sample_pixels(
+								&render_options,
+								camera,
+								acceleration_structure,
+								&pixels,
+								i,
+							);
+
+							let mut chunk_aovs = Vec::new();
+							for (pixel_i, (colour, rays, aov)) in pixels.into_iter().zip(results) {
+								let c = ((pixel_i - first_pixel) * channels) as usize;
+								chunk[c] = colour.x;
+								chunk[c + 1] = colour.y;
+								chunk[c + 2] = colour.z;
+								rays_shot += rays;
+								if keep_aovs {
+									chunk_aovs.push((pixel_i, aov));
+								}
 							}
-							rays_shot
+							(rays_shot, chunk_aovs)
 						})
-						.sum();
+						.collect();
+					current.rays_shot = chunks.iter().map(|(rays, _)| rays).sum();
+					pass_aovs = chunks.into_iter().flat_map(|(_, aovs)| aovs).collect();
 				});
 			});
+			moments
+				.par_iter_mut()
+				.enumerate()
+				.filter(|(pixel_i, _)| active[*pixel_i])
+				.for_each(|(pixel_i, moments)| {
+					let c = pixel_i * channels as usize;
+					let colour = &current.current_image[c..(c + 3)];
+					moments.add(Vec3::new(colour[0], colour[1], colour[2]));
+				});
+			if let Some(normals) = normals.as_mut() {
+				let values = pass_aovs
+					.iter()
+					.map(|(pixel_i, aov)| (*pixel_i, aov.normal));
+				add_to_means(normals, values, &moments);
+			}
+			if let Some(albedo) = albedo.as_mut() {
+				let values = pass_aovs
+					.iter()
+					.map(|(pixel_i, aov)| (*pixel_i, aov.albedo));
+				add_to_means(albedo, values, &moments);
+			}
+			if let Some((direct, indirect)) = lighting.as_mut() {
+				let values = pass_aovs
+					.iter()
+					.map(|(pixel_i, aov)| (*pixel_i, aov.direct));
+				add_to_means(direct, values, &moments);
+				let values = pass_aovs
+					.iter()
+					.map(|(pixel_i, aov)| (*pixel_i, aov.indirect));
+				add_to_means(indirect, values, &moments);
+			}
+			if let Some(depth) = depth.as_mut() {
+				// the nearest hit is kept so edges aren't blended between the surfaces
+				for (pixel_i, aov) in &pass_aovs {
+					let nearest = &mut depth[*pixel_i as usize];
+					*nearest = nearest.min(aov.depth);
+				}
+			}
+			current.normals.clone_from(&normals);
+			current.depth.clone_from(&depth);
+			current.albedo.clone_from(&albedo);
+			current.direct = lighting.as_ref().map(|(direct, _)| direct.clone());
+			current.indirect = lighting.as_ref().map(|(_, indirect)| indirect.clone());
+			current.moments.clone_from(&moments);
+			current.update_noise();
+			if let Some(halves) = halves.as_mut() {
+				halves.add(&current.current_image, i);
+			}
+			current.halves.clone_from(&halves);
+
 			if i != 0 {
 				if let Some((ref mut data, f)) = presentation_update.as_mut() {
 					if f(data, previous, i) {
@@ -86,6 +479,32 @@ impl Sampler for RandomSampler {
 					}
 				};
 			}
+
+			if let Some(mean) = mean.as_mut() {
+				let difference = accumulate_mean(mean, &current.current_image, i + 1);
+
+				// stop once successive accumulations stop changing
+				if let Some(epsilon) = render_options.epsilon_converge {
+					if i != 0 && difference < epsilon {
+						if let Some((ref mut data, f)) = presentation_update.as_mut() {
+							f(data, current, i + 1);
+						}
+						return;
+					}
+				}
+			}
+
+			// the pass that uses up the ray budget is the last
+			total_rays += current.rays_shot;
+			if render_options
+				.max_rays
+				.is_some_and(|max_rays| total_rays >= max_rays)
+			{
+				if let Some((ref mut data, f)) = presentation_update.as_mut() {
+					f(data, current, i + 1);
+				}
+				return;
+			}
 		}
 
 		let (previous, _) = if render_options.samples_per_pixel % 2 == 0 {
@@ -98,3 +517,652 @@ impl Sampler for RandomSampler {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		adaptive_sampler::AdaptiveSampler, in_place_sampler::InPlaceSampler, sphere::Sphere,
+		stratified_sampler::StratifiedSampler, utility::test_scene::*,
+	};
+
+	const SAMPLES: u64 = 16;
+
+	fn options() -> RenderOptions {
+		RenderOptions {
+			samples_per_pixel: SAMPLES,
+			width: 16,
+			height: 16,
+			epsilon_converge: Some(0.001),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn flat_scene_converges_early() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				100.0,
+				emissive(Vec3::one(), 1.0),
+			))],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+
+		let (image, samples) = render(&RandomSampler, options(), &camera, &bvh);
+
+		assert!(samples < SAMPLES);
+		assert!(image.iter().all(|v| (v - 1.0).abs() < 0.0001));
+	}
+
+	#[test]
+	fn noise_decreases() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let options = RenderOptions {
+			samples_per_pixel: 64,
+			width: 16,
+			height: 16,
+			render_method: RenderMethod::Naive,
+			..Default::default()
+		};
+
+		// both samplers report once per pass
+		let noise = |sampler: SamplerType| {
+			let mut noise: Vec<Float> = Vec::new();
+			let update = |noise: &mut Vec<Float>, progress: &SamplerProgress, _| {
+				noise.push(progress.noise);
+				false
+			};
+			let options = RenderOptions { sampler, ..options };
+			match sampler {
+				SamplerType::Random => {
+					RandomSampler.sample_image(options, &camera, &bvh, Some((&mut noise, update)))
+				}
+				SamplerType::InPlace => {
+					InPlaceSampler.sample_image(options, &camera, &bvh, Some((&mut noise, update)))
+				}
+				SamplerType::Adaptive => {
+					AdaptiveSampler.sample_image(options, &camera, &bvh, Some((&mut noise, update)))
+				}
+				SamplerType::Stratified => StratifiedSampler.sample_image(
+					options,
+					&camera,
+					&bvh,
+					Some((&mut noise, update)),
+				),
+			}
+			noise
+		};
+		for sampler in [SamplerType::Random, SamplerType::InPlace] {
+			let noise = noise(sampler);
+			assert_eq!(noise.len(), 64);
+			// the standard error halves with four times the samples
+			let (a, b, c) = (noise[3], noise[15], noise[63]);
+			assert!(a > 0.0 && b < a && c < b, "{a} {b} {c}");
+			assert!((c / b - 0.5).abs() < 0.1, "{b} {c}");
+		}
+	}
+
+	#[test]
+	fn seeded_pixels() {
+		let offsets = |seed, pixel| {
+			let mut rng = pixel_rng(seed, 0, pixel);
+			(0..10000)
+				.map(|_| rng.gen_range(0.0..1.0))
+				.collect::<Vec<Float>>()
+		};
+
+		assert_eq!(offsets(Some(7), 3), offsets(Some(7), 3));
+		assert_ne!(offsets(Some(7), 3), offsets(Some(8), 3));
+
+		let (a, b) = (offsets(Some(7), 3), offsets(Some(7), 4));
+		let mean = |v: &[Float]| v.iter().sum::<Float>() / v.len() as Float;
+		let (mean_a, mean_b) = (mean(&a), mean(&b));
+		let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+		for (a, b) in a.iter().zip(b.iter()) {
+			covariance += (a - mean_a) * (b - mean_b);
+			var_a += (a - mean_a).powi(2);
+			var_b += (b - mean_b).powi(2);
+		}
+		let correlation = covariance / (var_a * var_b).sqrt();
+		assert!(correlation.abs() < 0.05);
+	}
+
+	#[test]
+	fn seeded_renders_repeat() {
+		let bvh = bvh(
+			vec![
+				AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 1.0, lambertian(Vec3::one(), 0.5))),
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(1.5, 1.5, -1.0),
+					0.5,
+					emissive(Vec3::one(), 4.0),
+				)),
+			],
+			sky(Vec3::new(0.2, 0.3, 0.4)),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -4.0), Vec3::zero(), 1.0);
+		let options = |render_method, seed, packet_size| RenderOptions {
+			samples_per_pixel: 4,
+			width: 12,
+			height: 12,
+			render_method,
+			seed: Some(seed),
+			packet_size,
+			..Default::default()
+		};
+
+		for method in [RenderMethod::Naive, RenderMethod::MIS] {
+			let (a, _) = render(&RandomSampler, options(method, 11, 1), &camera, &bvh);
+			let (b, _) = render(&RandomSampler, options(method, 11, 1), &camera, &bvh);
+			let (packet, _) = render(&RandomSampler, options(method, 11, 4), &camera, &bvh);
+			let (other, _) = render(&RandomSampler, options(method, 12, 1), &camera, &bvh);
+			assert!(a.iter().any(|&v| v != 0.0));
+			assert_eq!(a, b);
+			assert_eq!(a, packet);
+			assert_ne!(a, other);
+		}
+	}
+
+	#[test]
+	fn sample_mask() {
+		let width = 16;
+		// bright square in the middle of the image
+		let mask: Vec<Float> = (0..(width * width))
+			.map(|i| {
+				let (x, y) = (i % width, i / width);
+				if (4..12).contains(&x) && (4..12).contains(&y) {
+					1.0
+				} else {
+					0.0
+				}
+			})
+			.collect();
+		let options = RenderOptions {
+			samples_per_pixel: SAMPLES,
+			width,
+			height: width,
+			sample_mask: Some(Box::leak(mask.into_boxed_slice())),
+			min_samples: 2,
+			..Default::default()
+		};
+
+		let inside = options.pixel_samples(8 * width + 8);
+		let outside = options.pixel_samples(0);
+		assert_eq!(inside, SAMPLES);
+		assert_eq!(outside, 2);
+
+		// pixels that run out of samples keep their value
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				100.0,
+				emissive(Vec3::one(), 1.0),
+			))],
+			sky(Vec3::zero()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+		assert!(image.iter().all(|v| (v - 1.0).abs() < 0.0001));
+	}
+
+	#[test]
+	fn pixel_stats() {
+		let width = 4;
+		// columns get 2, 4, 6 and 8 samples
+		let mask: Vec<Float> = (0..(width * width))
+			.map(|i| (i % width) as Float / 3.0)
+			.collect();
+		let options = RenderOptions {
+			samples_per_pixel: 8,
+			width,
+			height: width,
+			sample_mask: Some(Box::leak(mask.into_boxed_slice())),
+			min_samples: 2,
+			render_method: RenderMethod::Naive,
+			..Default::default()
+		};
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+
+		let mut moments = Vec::new();
+		let update = |moments: &mut Vec<PixelMoments>, progress: &SamplerProgress, _| {
+			moments.clone_from(&progress.moments);
+			false
+		};
+		RandomSampler.sample_image(options, &camera, &bvh, Some((&mut moments, update)));
+		for (i, pixel) in moments.iter().enumerate() {
+			assert_eq!(pixel.count(), options.pixel_samples(i as u64));
+		}
+
+		let mut csv = Vec::new();
+		write_pixel_stats(&mut csv, width, &moments, false).unwrap();
+		let csv = String::from_utf8(csv).unwrap();
+		let rows: Vec<&str> = csv.lines().skip(1).collect();
+		assert_eq!(rows.len(), 16);
+		for (i, row) in rows.iter().enumerate() {
+			let fields: Vec<&str> = row.split(',').collect();
+			assert_eq!(fields[..2], [(i % 4).to_string(), (i / 4).to_string()]);
+			assert_eq!(fields[2], options.pixel_samples(i as u64).to_string());
+			assert!(fields[3].parse::<Float>().unwrap() >= 0.0);
+		}
+
+		let mut json = Vec::new();
+		write_pixel_stats(&mut json, width, &moments, true).unwrap();
+		let json = String::from_utf8(json).unwrap();
+		assert_eq!(json.lines().count(), 16 + 2);
+		assert!(json.contains("{\"x\": 3, \"y\": 3, \"samples\": 8, \"variance\": "));
+	}
+
+	#[test]
+	fn noisy_scene_runs_to_cap() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.5),
+			))],
+			gradient_sky(Vec3::zero(), Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+
+		let (_, samples) = render(&RandomSampler, options(), &camera, &bvh);
+
+		assert_eq!(samples, SAMPLES);
+	}
+
+	#[test]
+	fn packets_match_single_rays() {
+		let primitives = (0..5)
+			.map(|i| {
+				let mut sphere = Sphere::new(
+					Vec3::new(i as Float - 2.0, 0.0, i as Float * 0.5),
+					0.6,
+					lambertian(Vec3::one(), 0.5),
+				);
+				sphere.object_id = i + 1;
+				AllPrimitives::Sphere(sphere)
+			})
+			.collect();
+		let bvh = bvh(primitives, sky(Vec3::zero()));
+		let camera = camera(Vec3::new(0.0, 0.0, -6.0), Vec3::zero(), 1.0);
+
+		// odd dimensions so some blocks are cut off at the image edges
+		let options = |packet_size| RenderOptions {
+			samples_per_pixel: 2,
+			width: 23,
+			height: 17,
+			render_method: RenderMethod::ObjectId,
+			seed: Some(3),
+			packet_size,
+			..Default::default()
+		};
+
+		let (single, _) = render(&RandomSampler, options(1), &camera, &bvh);
+		assert!(single.iter().any(|&v| v != 0.0));
+		for packet_size in [2, 3, 8] {
+			let (packet, _) = render(&RandomSampler, options(packet_size), &camera, &bvh);
+			assert_eq!(single, packet);
+		}
+	}
+
+	#[test]
+	fn split_halves() {
+		let options = RenderOptions {
+			samples_per_pixel: 16,
+			width: 8,
+			height: 8,
+			render_method: RenderMethod::Naive,
+			split_halves: true,
+			..Default::default()
+		};
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+
+		let mut output = (vec![0.0; 8 * 8 * 3], None);
+		type Output = (Vec<Float>, Option<SplitAccumulation>);
+		let update = |output: &mut Output, progress: &SamplerProgress, i: u64| {
+			accumulate_mean(&mut output.0, &progress.current_image, i);
+			output.1.clone_from(&progress.halves);
+			false
+		};
+		RandomSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)));
+		let (full, halves) = output;
+		let halves = halves.unwrap();
+
+		assert_eq!(halves.samples, [8, 8]);
+		assert_ne!(halves.even(), halves.odd());
+		for ((even, odd), full) in halves.even().iter().zip(halves.odd()).zip(full) {
+			assert!((0.5 * (even + odd) - full).abs() < 0.0001);
+		}
+	}
+	#[test]
+	fn ray_budget() {
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let max_rays = 5000;
+		let options = RenderOptions {
+			samples_per_pixel: 1000,
+			width: 8,
+			height: 8,
+			render_method: RenderMethod::Naive,
+			max_rays: Some(max_rays),
+			..Default::default()
+		};
+
+		// image, passes, total rays and the rays of the last pass
+		let mut output = (vec![0.0; 8 * 8 * 3], 0, 0, 0);
+		type Output = (Vec<Float>, u64, u64, u64);
+		let update = |output: &mut Output, progress: &SamplerProgress, i: u64| {
+			accumulate_mean(&mut output.0, &progress.current_image, i);
+			output.1 = i;
+			output.2 += progress.rays_shot;
+			output.3 = progress.rays_shot;
+			false
+		};
+		for sampler in [SamplerType::Random, SamplerType::InPlace] {
+			output.2 = 0;
+			let options = RenderOptions { sampler, ..options };
+			match sampler {
+				SamplerType::InPlace => {
+					let update = |output: &mut Output, progress: &SamplerProgress, _| {
+						output.0.clone_from(&progress.current_image);
+						output.1 = progress.samples_completed;
+						output.2 += progress.rays_shot;
+						output.3 = progress.rays_shot;
+						false
+					};
+					InPlaceSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)))
+				}
+				_ => {
+					RandomSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)))
+				}
+			}
+			let (image, passes, rays, last_pass) = &output;
+			assert!(*passes > 1 && *passes < 1000, "{sampler:?} {passes}");
+			assert!(
+				*rays >= max_rays && rays - last_pass < max_rays,
+				"{sampler:?} {rays}"
+			);
+			assert!(image.iter().all(|v| v.is_finite() && *v > 0.0));
+		}
+	}
+
+	#[test]
+	fn normal_pass() {
+		let options = RenderOptions {
+			samples_per_pixel: 8,
+			width: 9,
+			height: 9,
+			render_method: RenderMethod::ObjectId,
+			seed: Some(3),
+			normal_pass: true,
+			..Default::default()
+		};
+		let bvh = bvh(
+			vec![AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::one(), 0.8),
+			))],
+			sky(Vec3::one()),
+		);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+
+		let mut output = (vec![0.0; 9 * 9 * 3], None);
+		type Output = (Vec<Float>, Option<Vec<Float>>);
+		let update = |output: &mut Output, progress: &SamplerProgress, i: u64| {
+			accumulate_mean(&mut output.0, &progress.current_image, i);
+			output.1.clone_from(&progress.normals);
+			false
+		};
+		RandomSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)));
+		let (beauty, normals) = output;
+		let normals = normals.unwrap();
+
+		// the centre of the sphere faces the camera and the corners see the sky
+		let normal = |x: usize, y: usize| {
+			let c = 3 * (9 * y + x);
+			Vec3::new(normals[c], normals[c + 1], normals[c + 2])
+		};
+		let centre = normal(4, 4);
+		assert!(centre.z < 0.05, "{centre:?}");
+		assert!(
+			(centre - Vec3::new(0.5, 0.5, 0.0)).mag() < 0.1,
+			"{centre:?}"
+		);
+		assert_eq!(normal(0, 0), Vec3::zero());
+		assert!(normal(2, 4).x < normal(4, 4).x && normal(6, 4).x > normal(4, 4).x);
+		assert!(normals.iter().all(|v| (0.0..=1.0).contains(v)));
+
+		// the beauty is the same without the normal pass
+		let (without, _) = render(
+			&RandomSampler,
+			RenderOptions {
+				normal_pass: false,
+				..options
+			},
+			&camera,
+			&bvh,
+		);
+		assert_eq!(beauty, without);
+	}
+
+	#[test]
+	fn depth_pass() {
+		let (width, height) = (16, 8);
+		let options = RenderOptions {
+			samples_per_pixel: 4,
+			width,
+			height,
+			render_method: RenderMethod::ObjectId,
+			seed: Some(5),
+			depth_pass: true,
+			depth_far: 100.0,
+			..Default::default()
+		};
+		// one sphere on the left about 5 away and another on the right about 8 away
+		let (near, far) = (Vec3::new(-1.2, 0.0, 0.0), Vec3::new(1.2, 0.0, 3.0));
+		let bvh = bvh(
+			[near, far]
+				.into_iter()
+				.map(|centre| {
+					AllPrimitives::Sphere(Sphere::new(centre, 0.5, lambertian(Vec3::one(), 0.8)))
+				})
+				.collect(),
+			sky(Vec3::one()),
+		);
+		let origin = Vec3::new(0.0, 0.0, -5.0);
+		let camera = camera(origin, Vec3::zero(), 2.0);
+
+		let mut output = None;
+		let update = |output: &mut Option<Vec<Float>>, progress: &SamplerProgress, _: u64| {
+			output.clone_from(&progress.depth);
+			false
+		};
+		RandomSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)));
+		let depth = output.unwrap();
+		assert_eq!(depth.len(), (width * height) as usize);
+
+		let hits = |left: bool| -> Vec<Float> {
+			depth
+				.iter()
+				.enumerate()
+				.filter(|(i, d)| (*i as u64 % width < width / 2) == left && **d != 100.0)
+				.map(|(_, d)| *d)
+				.collect()
+		};
+		let (left, right) = (hits(true), hits(false));
+		assert!(!left.is_empty() && !right.is_empty());
+		// between the front of each sphere and its silhouette
+		let nearest = |centre: Vec3| (centre - origin).mag() - 0.5;
+		for d in &left {
+			assert!(
+				*d > nearest(near) - 0.001 && *d < nearest(near) + 0.5,
+				"{d}"
+			);
+		}
+		for d in &right {
+			assert!(*d > nearest(far) - 0.001 && *d < nearest(far) + 0.5, "{d}");
+		}
+		// everything else sees the sky
+		assert!(depth.iter().filter(|d| **d == 100.0).count() > left.len() + right.len());
+	}
+
+	#[test]
+	fn albedo_pass() {
+		let options = RenderOptions {
+			samples_per_pixel: 4,
+			width: 9,
+			height: 9,
+			albedo_pass: true,
+			..Default::default()
+		};
+		let red = Vec3::new(1.0, 0.0, 0.0);
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let albedo = |sky_colour: Vec3| {
+			let bvh = bvh(
+				vec![
+					AllPrimitives::Sphere(Sphere::new(Vec3::zero(), 1.0, lambertian(red, 0.8))),
+					AllPrimitives::Sphere(Sphere::new(
+						Vec3::new(0.0, 3.0, -1.0),
+						0.5,
+						emissive(Vec3::new(0.2, 1.0, 0.2), 10.0),
+					)),
+				],
+				sky(sky_colour),
+			);
+			let mut output = None;
+			let update = |output: &mut Option<Vec<Float>>, progress: &SamplerProgress, _: u64| {
+				output.clone_from(&progress.albedo);
+				false
+			};
+			RandomSampler.sample_image(options, &camera, &bvh, Some((&mut output, update)));
+			output.unwrap()
+		};
+
+		// the sphere is the same red lit by a dark or a bright blue sky
+		for sky_colour in [Vec3::zero(), Vec3::new(0.2, 0.4, 4.0)] {
+			let image = albedo(sky_colour);
+			let pixel = |x: usize, y: usize| {
+				let c = 3 * (9 * y + x);
+				Vec3::new(image[c], image[c + 1], image[c + 2])
+			};
+			assert!(
+				(pixel(4, 4) - 0.8 * red).mag() < 0.00001,
+				"{:?}",
+				pixel(4, 4)
+			);
+			assert!((pixel(0, 0) - sky_colour).mag() < 0.00001);
+		}
+	}
+
+	// renders with a lighting pass returning the image and its direct and indirect light
+	fn lighting(
+		primitives: Vec<TestPrimitive>,
+		camera: &SimpleCamera,
+		samples_per_pixel: u64,
+	) -> [Vec<Float>; 3] {
+		let options = RenderOptions {
+			samples_per_pixel,
+			width: 16,
+			height: 16,
+			seed: Some(5),
+			lighting_pass: true,
+			..Default::default()
+		};
+		let bvh = bvh(primitives, gradient_sky(Vec3::zero(), Vec3::one()));
+		let mut output = [vec![0.0; 16 * 16 * 3], Vec::new(), Vec::new()];
+		let update = |output: &mut [Vec<Float>; 3], progress: &SamplerProgress, i: u64| {
+			// the image is accumulated the same way the renderer does
+			for (pixel, sample) in output[0].iter_mut().zip(&progress.current_image) {
+				*pixel += (sample - *pixel) / i as Float;
+			}
+			output[1].clone_from(progress.direct.as_ref().unwrap());
+			output[2].clone_from(progress.indirect.as_ref().unwrap());
+			false
+		};
+		RandomSampler.sample_image(options, camera, &bvh, Some((&mut output, update)));
+		output
+	}
+
+	#[test]
+	fn lighting_pass() {
+		// the ground bounces light from the sphere back onto it
+		let primitives = vec![
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(0.0, -1000.0, 0.0),
+				1000.0,
+				lambertian(Vec3::one(), 0.8),
+			)),
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(0.0, 0.5, 0.0),
+				0.5,
+				lambertian(Vec3::one(), 0.8),
+			)),
+		];
+		let camera = camera(Vec3::new(0.0, 1.0, -3.0), Vec3::new(0.0, 0.3, 0.0), 1.0);
+		// each sample's light is split between the two
+		let [image, direct, indirect] = lighting(primitives.clone(), &camera, 1);
+		for ((pixel, direct), indirect) in image.iter().zip(&direct).zip(&indirect) {
+			assert_eq!(direct + indirect, *pixel);
+		}
+
+		// their means come from the image's own paths so only differ from it by rounding
+		let [image, direct, indirect] = lighting(primitives, &camera, 16);
+		for ((pixel, direct), indirect) in image.iter().zip(&direct).zip(&indirect) {
+			assert!(
+				(direct + indirect - pixel).abs() <= 0.00001 * pixel.max(1.0),
+				"{direct} + {indirect} != {pixel}"
+			);
+		}
+		let sum = |image: &[Float]| image.iter().sum::<Float>();
+		assert!(sum(&indirect) > 0.0);
+		assert!(sum(&direct) > sum(&indirect));
+	}
+
+	#[test]
+	fn no_indirect_light() {
+		// a convex object alone under the sky never sees itself so every path has one bounce
+		let primitives = vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::zero(),
+			1.0,
+			lambertian(Vec3::one(), 0.8),
+		))];
+		let camera = camera(Vec3::new(0.0, 0.0, -3.0), Vec3::zero(), 1.0);
+		let [_, direct, indirect] = lighting(primitives, &camera, 16);
+
+		assert!(indirect.iter().all(|&v| v == 0.0));
+		assert!(direct.iter().any(|&v| v > 0.0));
+	}
+}
@@ -0,0 +1,109 @@
+use crate::{random_sampler::mix, *};
+use rand::Rng;
+use rt_core::*;
+
+// splits each pixel into an n x n grid, n being the square root of the pixel's sample
+// count rounded down, and jitters one sample inside each cell, any samples left over
+// are jittered over the whole pixel. Passes are accumulated like the RandomSampler.
+pub struct StratifiedSampler;
+
+// jitter within the pixel for the given sample pass. Consecutive passes step
+// diagonally through the grid so partial renders still cover the pixel, and each
+// pixel starts from a different cell.
+pub(super) fn pixel_offset<R: Rng>(
+	rng: &mut R,
+	pixel_i: u64,
+	sample: u64,
+	samples: u64,
+) -> (Float, Float) {
+	let n = (samples as f64).sqrt() as u64;
+	let (u, v) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+	if sample >= n * n {
+		return (u, v);
+	}
+	let cell = (sample * (n + 1) + mix(pixel_i)) % (n * n);
+	let (x, y) = (cell % n, cell / n);
+	((x as Float + u) / n as Float, (y as Float + v) / n as Float)
+}
+
+impl Sampler for StratifiedSampler {
+	fn sample_image<C, P, M, T, F, A>(
+		&self,
+		render_options: RenderOptions,
+		camera: &C,
+		acceleration_structure: &A,
+		presentation_update: Option<(&mut T, F)>,
+	) where
+		C: Camera,
+		P: Primitive,
+		M: Scatter,
+		F: Fn(&mut T, &SamplerProgress, u64) -> bool,
+		A: AccelerationStructure<Object = P, Material = M>,
+	{
+		let render_options = RenderOptions {
+			sampler: SamplerType::Stratified,
+			..render_options
+		};
+		random_sampler::RandomSampler.sample_image(
+			render_options,
+			camera,
+			acceleration_structure,
+			presentation_update,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, utility::test_scene::*};
+	use rand::{rngs::SmallRng, SeedableRng};
+
+	#[test]
+	fn one_sample_per_cell() {
+		let mut rng = SmallRng::seed_from_u64(1);
+		for samples in [1, 9, 16, 20] {
+			let n = (samples as f64).sqrt() as usize;
+			let mut cells = vec![0; n * n];
+			for sample in 0..samples {
+				let (u, v) = pixel_offset(&mut rng, 17, sample, samples);
+				assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+				if sample < (n * n) as u64 {
+					let (x, y) = ((u * n as Float) as usize, (v * n as Float) as usize);
+					cells[y * n + x] += 1;
+				}
+			}
+			assert!(cells.iter().all(|&count| count == 1), "{samples} {cells:?}");
+		}
+	}
+
+	#[test]
+	fn lower_error_than_random() {
+		// the sky brightens across the image so each pixel covers a range of values
+		let bvh = bvh(Vec::new(), gradient_sky(Vec3::one(), Vec3::zero()));
+		let camera = camera(Vec3::zero(), Vec3::x(), 1.0);
+		let options = |samples_per_pixel, seed| RenderOptions {
+			samples_per_pixel,
+			width: 8,
+			height: 8,
+			render_method: RenderMethod::Naive,
+			seed: Some(seed),
+			..Default::default()
+		};
+
+		let (reference, _) = render(&StratifiedSampler, options(4096, 0), &camera, &bvh);
+		let error = |image: Vec<Float>| {
+			image
+				.iter()
+				.zip(reference.iter())
+				.map(|(a, b)| (a - b).powi(2))
+				.sum::<Float>()
+		};
+		let (mut stratified, mut random) = (0.0, 0.0);
+		for seed in 1..5 {
+			stratified += error(render(&StratifiedSampler, options(16, seed), &camera, &bvh).0);
+			random += error(render(&RandomSampler, options(16, seed), &camera, &bvh).0);
+		}
+		assert!(stratified < 0.25 * random, "{stratified} {random}");
+	}
+}
@@ -0,0 +1,237 @@
+use crate::{in_place_sampler::update_mean, random_sampler::sample_pixels, *};
+use rayon::prelude::*;
+use rt_core::*;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// little endian portable float map written a run of pixels at a time, so an image
+// doesn't need to be held in memory to be saved. Rows are stored bottom to top.
+pub struct PfmWriter {
+	file: File,
+	width: u64,
+	height: u64,
+	header_len: u64,
+}
+
+impl PfmWriter {
+	pub fn create<P: AsRef<Path>>(path: P, width: u64, height: u64) -> io::Result<Self> {
+		let mut file = File::create(path)?;
+		let header = format!("PF\n{width} {height}\n-1.0\n");
+		file.write_all(header.as_bytes())?;
+		let header_len = header.len() as u64;
+		file.set_len(header_len + 12 * width * height)?;
+		Ok(PfmWriter {
+			file,
+			width,
+			height,
+			header_len,
+		})
+	}
+
+	// rgb values of consecutive pixels in image order starting at first_pixel, the
+	// first row being the top of the image
+	#[allow(clippy::unnecessary_cast)]
+	pub fn write_pixels(&mut self, first_pixel: u64, data: &[Float]) -> io::Result<()> {
+		let mut pixel = first_pixel;
+		let mut data = data;
+		while !data.is_empty() {
+			let (x, y) = (pixel % self.width, pixel / self.width);
+			let run = ((self.width - x) as usize).min(data.len() / 3);
+			let offset = self.header_len + 12 * ((self.height - 1 - y) * self.width + x);
+			let bytes: Vec<u8> = data[..(3 * run)]
+				.iter()
+				.flat_map(|&v| (v as f32).to_le_bytes())
+				.collect();
+			self.file.seek(SeekFrom::Start(offset))?;
+			self.file.write_all(&bytes)?;
+			data = &data[(3 * run)..];
+			pixel += run as u64;
+		}
+		Ok(())
+	}
+}
+
+// side of the square tiles an image is rendered in
+const TILE_SIZE: u64 = 64;
+
+// renders the image a tile at a time, every sample of a tile is taken before it's
+// written to the file and dropped, so only the tiles being rendered are in memory. Pixels
+// draw from the same rng streams as in memory so a seeded render matches it. The adaptive
+// sampler, epsilon_converge and max_rays need the whole image so they're rejected.
+// Returns the rays shot.
+pub fn render_to_pfm<C, P, M, A, Q>(
+	render_options: RenderOptions,
+	camera: &C,
+	acceleration_structure: &A,
+	path: Q,
+) -> io::Result<u64>
+where
+	C: Camera,
+	P: Primitive,
+	M: Scatter,
+	A: AccelerationStructure<Object = P, Material = M>,
+	Q: AsRef<Path>,
+{
+	let unsupported = if matches!(render_options.sampler, SamplerType::Adaptive) {
+		Some("the adaptive sampler")
+	} else if render_options.epsilon_converge.is_some() {
+		Some("epsilon_converge")
+	} else if render_options.max_rays.is_some() {
+		Some("max_rays")
+	} else {
+		None
+	};
+	if let Some(option) = unsupported {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			format!("{option} needs the whole image so can't be streamed to a file"),
+		));
+	}
+
+	let (width, height) = (render_options.width, render_options.height);
+	let writer = PfmWriter::create(path, width, height)?;
+	let writer = Mutex::new(writer);
+
+	let tiles_across = width.div_ceil(TILE_SIZE);
+	(0..(tiles_across * height.div_ceil(TILE_SIZE)))
+		.into_par_iter()
+		.map(|tile| {
+			let (x0, y0) = (
+				(tile % tiles_across) * TILE_SIZE,
+				(tile / tiles_across) * TILE_SIZE,
+			);
+			let (x1, y1) = ((x0 + TILE_SIZE).min(width), (y0 + TILE_SIZE).min(height));
+			let tile_pixels: Vec<u64> = (y0..y1)
+				.flat_map(|y| (x0..x1).map(move |x| y * width + x))
+				.collect();
+			let mut mean = vec![0.0; 3 * tile_pixels.len()];
+			let mut rays_shot = 0;
+
+			for i in 0..render_options.samples_per_pixel {
+				// positions in the tile of the pixels that get this pass
+				let (positions, pixels): (Vec<usize>, Vec<u64>) = tile_pixels
+					.iter()
+					.copied()
+					.enumerate()
+					.filter(|&(_, pixel_i)| i < render_options.pixel_samples(pixel_i))
+					.unzip();
+				let results =
+					sample_pixels(&render_options, camera, acceleration_structure, &pixels, i);
+				for (position, (colour, rays, _)) in positions.into_iter().zip(results) {
+					update_mean(&mut mean[(3 * position)..(3 * position + 3)], colour, i + 1);
+					rays_shot += rays;
+				}
+			}
+
+			let mut writer = writer.lock().unwrap();
+			for (y, row) in (y0..y1).zip(mean.chunks(3 * (x1 - x0) as usize)) {
+				writer.write_pixels(y * width + x0, row)?;
+			}
+			Ok(rays_shot)
+		})
+		.try_reduce(|| 0, |a, b| Ok(a + b))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{random_sampler::RandomSampler, sphere::Sphere, utility::test_scene::*};
+
+	#[test]
+	fn matches_in_memory_render() {
+		let mut primitives: Vec<_> = (0..5)
+			.map(|i| {
+				AllPrimitives::Sphere(Sphere::new(
+					Vec3::new(i as Float - 2.0, 0.0, i as Float * 0.5),
+					0.6,
+					lambertian(Vec3::one(), 0.5),
+				))
+			})
+			.collect();
+		primitives.push(AllPrimitives::Sphere(Sphere::new(
+			Vec3::new(0.0, 3.0, -1.0),
+			1.0,
+			emissive(Vec3::one(), 4.0),
+		)));
+		let bvh = bvh(primitives, sky(Vec3::new(0.1, 0.2, 0.3)));
+		let camera = camera(Vec3::new(0.0, 0.0, -6.0), Vec3::zero(), 1.5);
+
+		for render_method in [RenderMethod::Naive, RenderMethod::MIS] {
+			// a few tiles with the last row and column cut short
+			let options = RenderOptions {
+				samples_per_pixel: 3,
+				width: 150,
+				height: 100,
+				render_method,
+				seed: Some(4),
+				..Default::default()
+			};
+
+			let directory = std::env::temp_dir();
+			let (tiled, memory) = (
+				directory.join(format!("rt_tiled_{}.pfm", std::process::id())),
+				directory.join(format!("rt_tiled_memory_{}.pfm", std::process::id())),
+			);
+			let rays = render_to_pfm(options, &camera, &bvh, &tiled).unwrap();
+			assert!(rays > 0);
+
+			let (image, _) = render(&RandomSampler, options, &camera, &bvh);
+			PfmWriter::create(&memory, 150, 100)
+				.unwrap()
+				.write_pixels(0, &image)
+				.unwrap();
+
+			let files = (
+				std::fs::read(&tiled).unwrap(),
+				std::fs::read(&memory).unwrap(),
+			);
+			std::fs::remove_file(tiled).unwrap();
+			std::fs::remove_file(memory).unwrap();
+			let (tiled, memory) = files;
+			assert!(tiled.starts_with(b"PF\n150 100\n-1.0\n"));
+			assert_eq!(tiled.len(), 16 + 12 * 150 * 100);
+			assert!(tiled == memory);
+
+			assert!(image.iter().any(|&v| v != 0.0));
+			// the top left pixel is the first of the last row in the file
+			let bottom_row = tiled.len() - 12 * 150;
+			let red = f32::from_le_bytes(tiled[bottom_row..(bottom_row + 4)].try_into().unwrap());
+			assert_eq!(red as Float, image[0]);
+		}
+	}
+
+	#[test]
+	fn rejects_whole_image_options() {
+		let bvh = bvh(Vec::new(), sky(Vec3::one()));
+		let camera = camera(Vec3::new(0.0, 0.0, -6.0), Vec3::zero(), 1.0);
+		let path =
+			std::env::temp_dir().join(format!("rt_tiled_rejected_{}.pfm", std::process::id()));
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: 4,
+			height: 4,
+			..Default::default()
+		};
+		let unsupported = [
+			RenderOptions {
+				sampler: SamplerType::Adaptive,
+				..options
+			},
+			RenderOptions {
+				epsilon_converge: Some(0.01),
+				..options
+			},
+			RenderOptions {
+				max_rays: Some(100),
+				..options
+			},
+		];
+		for options in unsupported {
+			let error = render_to_pfm(options, &camera, &bvh, &path).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+			assert!(!path.exists());
+		}
+	}
+}
@@ -1,10 +1,9 @@
 use crate::distributions::Distribution2D;
 use crate::generate_values;
 use crate::next_float;
-use crate::random_float;
-use rand::rngs::SmallRng;
-use rand::thread_rng;
-use rand::SeedableRng;
+use crate::spherical_sampling::random_unit_vector;
+use crate::utility::coord::Coordinate;
+use rand::Rng;
 use rt_core::*;
 
 use crate::Texture;
@@ -15,6 +14,58 @@ pub struct Sky<'a, T: Texture, M: Scatter> {
 	mat: &'a M,
 	pub distribution: Option<Distribution2D>,
 	sampler_res: (usize, usize),
+	pub portal: Option<Portal>,
+}
+
+// rectangular opening the sky is seen through from inside a scene, the sky is sampled
+// by aiming at points on it instead of over the whole sphere
+#[derive(Debug, Clone)]
+pub struct Portal {
+	pub corner: Vec3,
+	// perpendicular edges from the corner
+	pub u: Vec3,
+	pub v: Vec3,
+}
+
+impl Portal {
+	pub fn new(corner: Vec3, u: Vec3, v: Vec3) -> Self {
+		Portal { corner, u, v }
+	}
+
+	fn normal(&self) -> Vec3 {
+		self.u.cross(self.v).normalised()
+	}
+
+	fn area(&self) -> Float {
+		self.u.cross(self.v).mag()
+	}
+
+	pub fn sample<R: Rng>(&self, point: Vec3, rng: &mut R) -> Vec3 {
+		let target = self.corner + rng.gen::<Float>() * self.u + rng.gen::<Float>() * self.v;
+		(target - point).normalised()
+	}
+
+	// solid angle pdf, directions missing the portal can't be sampled
+	pub fn pdf(&self, point: Vec3, wi: Vec3) -> Float {
+		let normal = self.normal();
+		let cos = wi.dot(normal);
+		if cos == 0.0 {
+			return 0.0;
+		}
+		let t = (self.corner - point).dot(normal) / cos;
+		if t <= 0.0 {
+			return 0.0;
+		}
+		let offset = point + t * wi - self.corner;
+		let (a, b) = (
+			offset.dot(self.u) / self.u.mag_sq(),
+			offset.dot(self.v) / self.v.mag_sq(),
+		);
+		if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+			return 0.0;
+		}
+		t * t / (cos.abs() * self.area())
+	}
 }
 
 impl<'a, T: Texture, M: Scatter> Sky<'a, T, M> {
@@ -32,15 +83,24 @@ impl<'a, T: Texture, M: Scatter> Sky<'a, T, M> {
 			mat,
 			distribution,
 			sampler_res,
+			portal: None,
 		}
 	}
+
+	pub fn with_portal(mut self, portal: Portal) -> Self {
+		self.portal = Some(portal);
+		self
+	}
 }
 
 impl<'a, T: Texture, M: Scatter> NoHit<M> for Sky<'a, T, M> {
 	fn get_colour(&self, ray: &Ray) -> Vec3 {
 		self.texture.colour_value(ray.direction, ray.origin)
 	}
-	fn pdf(&self, wi: Vec3) -> Float {
+	fn pdf(&self, point: Vec3, wi: Vec3) -> Float {
+		if let Some(portal) = &self.portal {
+			return portal.pdf(point, wi);
+		}
 		let sin_theta = (1.0 - wi.z * wi.z).sqrt();
 		if sin_theta <= 0.0 {
 			return 0.0;
@@ -59,17 +119,16 @@ impl<'a, T: Texture, M: Scatter> NoHit<M> for Sky<'a, T, M> {
 			/ (sin_theta * TAU * PI)
 	}
 	fn can_sample(&self) -> bool {
-		self.sampler_res.0 | self.sampler_res.1 != 0
+		self.portal.is_some() || self.sampler_res.0 | self.sampler_res.1 != 0
 	}
-	fn sample(&self) -> Vec3 {
-		let uv = self
-			.distribution
-			.as_ref()
-			.unwrap()
-			.sample(&mut SmallRng::from_rng(thread_rng()).unwrap());
+	fn sample<R: Rng>(&self, point: Vec3, rng: &mut R) -> Vec3 {
+		if let Some(portal) = &self.portal {
+			return portal.sample(point, rng);
+		}
+		let uv = self.distribution.as_ref().unwrap().sample(rng);
 
-		let u = next_float(uv.0 as Float + random_float()) / self.sampler_res.0 as Float;
-		let v = next_float(uv.1 as Float + random_float()) / self.sampler_res.1 as Float;
+		let u = next_float(uv.0 as Float + rng.gen::<Float>()) / self.sampler_res.0 as Float;
+		let v = next_float(uv.1 as Float + rng.gen::<Float>()) / self.sampler_res.1 as Float;
 
 		let phi = u * 2.0 * PI;
 		let theta = v * PI;
@@ -83,6 +142,7 @@ impl<'a, T: Texture, M: Scatter> NoHit<M> for Sky<'a, T, M> {
 				point: Vec3::zero(),
 				error: Vec3::zero(),
 				normal: Vec3::zero(),
+				geometric_normal: Vec3::zero(),
 				uv: None,
 				out: false,
 			},
@@ -91,6 +151,93 @@ impl<'a, T: Texture, M: Scatter> NoHit<M> for Sky<'a, T, M> {
 	}
 }
 
+// sky without a sky model for a cheap sun, directions within sun_angle degrees of sun_dir
+// see the sun material and every other direction the ambient one, both should be emissive
+#[derive(Debug, Clone)]
+pub struct ConeSunSky<'a, M: Scatter> {
+	pub sun_dir: Vec3,
+	pub sun_angle: Float,
+	sun: &'a M,
+	ambient: &'a M,
+}
+
+// chance a sampled direction is aimed at the sun rather than anywhere on the sphere
+const SUN_SAMPLE_CHANCE: Float = 0.5;
+
+impl<'a, M: Scatter> ConeSunSky<'a, M> {
+	pub fn new(sun_dir: Vec3, sun_angle: Float, sun: &'a M, ambient: &'a M) -> Self {
+		ConeSunSky {
+			sun_dir: sun_dir.normalised(),
+			sun_angle,
+			sun,
+			ambient,
+		}
+	}
+
+	fn in_sun(&self, direction: Vec3) -> bool {
+		direction.normalised().dot(self.sun_dir) >= self.sun_angle.to_radians().cos()
+	}
+
+	// solid angle of the sun over 2 pi, written with the half angle so small suns don't
+	// round to nothing
+	fn one_minus_cos(&self) -> Float {
+		let half = 0.5 * self.sun_angle.to_radians();
+		2.0 * half.sin() * half.sin()
+	}
+}
+
+impl<'a, M: Scatter> NoHit<M> for ConeSunSky<'a, M> {
+	fn get_colour(&self, ray: &Ray) -> Vec3 {
+		let si = self.get_si(ray);
+		si.material.get_emission(&si.hit, ray.direction)
+	}
+	fn pdf(&self, _: Vec3, wi: Vec3) -> Float {
+		let sun = if self.in_sun(wi) {
+			1.0 / (2.0 * PI * self.one_minus_cos())
+		} else {
+			0.0
+		};
+		SUN_SAMPLE_CHANCE * sun + (1.0 - SUN_SAMPLE_CHANCE) / (4.0 * PI)
+	}
+	fn can_sample(&self) -> bool {
+		true
+	}
+	fn sample<R: Rng>(&self, _: Vec3, rng: &mut R) -> Vec3 {
+		if rng.gen::<Float>() >= SUN_SAMPLE_CHANCE {
+			return random_unit_vector(rng);
+		}
+		// uniform in the cone around the sun
+		let one_minus_cos_theta = rng.gen::<Float>() * self.one_minus_cos();
+		let cos_theta = 1.0 - one_minus_cos_theta;
+		let sin_theta = (one_minus_cos_theta * (2.0 - one_minus_cos_theta)).sqrt();
+		let phi = 2.0 * PI * rng.gen::<Float>();
+		Coordinate::new_from_z(self.sun_dir).to_coord(Vec3::from_spherical(
+			sin_theta,
+			cos_theta,
+			phi.sin(),
+			phi.cos(),
+		))
+	}
+	fn get_si(&self, ray: &Ray) -> SurfaceIntersection<M> {
+		SurfaceIntersection {
+			hit: Hit {
+				t: 0.0,
+				point: Vec3::zero(),
+				error: Vec3::zero(),
+				normal: Vec3::zero(),
+				geometric_normal: Vec3::zero(),
+				uv: None,
+				out: false,
+			},
+			material: if self.in_sun(ray.direction) {
+				self.sun
+			} else {
+				self.ambient
+			},
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -98,8 +245,157 @@ mod tests {
 	use crate::AllMaterials;
 	use crate::AllTextures;
 	use crate::Emit;
+	use crate::ImageTexture;
 	use crate::Lerp;
+	use rand::thread_rng;
 	//use rand::rngs::ThreadRng;
+	use crate::{
+		bilinear_patch::BilinearPatch, integrators::*, utility::test_scene::*, AllPrimitives,
+	};
+
+	#[test]
+	fn portal_reduces_noise() {
+		// closed room between -1 and 1 with a window in the middle of the ceiling
+		let wall = lambertian(Vec3::one(), 0.5);
+		let quad = |corner: Vec3, u: Vec3, v: Vec3| {
+			AllPrimitives::BilinearPatch(BilinearPatch::new(
+				[corner, corner + u, corner + v, corner + u + v],
+				wall,
+			))
+		};
+		let (x, y, z) = (2.0 * Vec3::x(), 2.0 * Vec3::y(), 2.0 * Vec3::z());
+		let mut room = vec![
+			quad(-Vec3::one(), x, z),
+			quad(-Vec3::one(), x, y),
+			quad(-Vec3::one(), y, z),
+			quad(Vec3::one(), -x, -y),
+			quad(Vec3::one(), -y, -z),
+		];
+		let ceiling = Vec3::new(-1.0, 1.0, -1.0);
+		room.push(quad(ceiling, 0.375 * x, z));
+		room.push(quad(ceiling + 0.625 * x, 0.375 * x, z));
+		room.push(quad(ceiling + 0.375 * x, 0.25 * x, 0.375 * z));
+		room.push(quad(ceiling + 0.375 * x + 0.625 * z, 0.25 * x, 0.375 * z));
+		let window = Portal::new(
+			Vec3::new(-0.25, 1.0, -0.25),
+			0.5 * Vec3::z(),
+			0.5 * Vec3::x(),
+		);
+
+		let floor = |sky: TestSky| {
+			let bvh = bvh(room.clone(), sky);
+			let samples: Vec<Float> = (0..10000)
+				.map(|_| {
+					let mut ray = Ray::new(Vec3::new(0.5, 0.0, 0.0), -Vec3::y(), 0.0);
+					MisIntegrator::get_colour(
+						&mut ray,
+						&bvh,
+						&Default::default(),
+						&mut thread_rng(),
+					)
+					.0
+					.x
+				})
+				.collect();
+			let mean = samples.iter().sum::<Float>() / samples.len() as Float;
+			let variance = samples
+				.iter()
+				.map(|s| (s - mean) * (s - mean))
+				.sum::<Float>()
+				/ samples.len() as Float;
+			(mean, variance)
+		};
+		let unguided = || {
+			let tex = texture(Vec3::one());
+			let mat = Box::leak(Box::new(AllMaterials::Emit(Emit::new(tex, 1.0))));
+			Sky::new(tex, mat, (16, 8))
+		};
+
+		let (mean, variance) = floor(unguided());
+		let (portal_mean, portal_variance) = floor(unguided().with_portal(window));
+		assert!(mean > 0.0);
+		assert!(
+			(portal_mean - mean).abs() / mean < 0.2,
+			"{portal_mean} {mean}"
+		);
+		assert!(
+			portal_variance < 0.1 * variance,
+			"{portal_variance} {variance}"
+		);
+	}
+
+	#[test]
+	fn environment_map() {
+		// a 1x2 radiance image, the upper half of the sky is red and the lower a brighter green
+		let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n".to_vec();
+		data.extend([128, 0, 0, 129, 0, 128, 0, 131]);
+		let tex = AllTextures::ImageTexture(ImageTexture::from_hdr(&data[..]));
+		let mat = AllMaterials::Emit(Emit::new(&tex, 1.0));
+		let sky = Sky::new(&tex, &mat, (4, 2));
+
+		let colour = |direction: Vec3| sky.get_colour(&Ray::new(Vec3::zero(), direction, 0.0));
+		let (red, green) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0));
+		assert_eq!(colour(Vec3::z()), red);
+		assert_eq!(colour(Vec3::new(1.0, -2.0, 0.1)), red);
+		assert_eq!(colour(-Vec3::z()), green);
+		assert_eq!(colour(Vec3::new(-1.0, 0.5, -0.1)), green);
+		// the sky is sampled where it's brightest
+		let below = (0..100)
+			.filter(|_| sky.sample(Vec3::zero(), &mut thread_rng()).z < 0.0)
+			.count();
+		assert!(below > 85);
+
+		// a 2x1 image split into the -y and +y halves spun half a turn
+		let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+		data.extend([128, 0, 0, 129, 0, 0, 128, 129]);
+		let texture = ImageTexture::from_hdr(&data[..]);
+		let value =
+			|texture: &ImageTexture, direction| texture.colour_value(direction, Vec3::zero());
+		assert_eq!(value(&texture, -Vec3::y()), red);
+		assert_eq!(value(&texture, Vec3::y()).z, 1.0);
+		let spun = texture.with_rotation(180.0);
+		assert_eq!(value(&spun, Vec3::y()), red);
+		assert_eq!(value(&spun, -Vec3::y()).z, 1.0);
+	}
+
+	#[test]
+	fn cone_sun() {
+		let (sun_colour, ambient_colour) = (Vec3::new(1.0, 0.9, 0.8), Vec3::new(0.1, 0.2, 0.4));
+		let sky = ConeSunSky::new(
+			Vec3::new(0.0, 1.0, 1.0),
+			5.0,
+			emissive(sun_colour, 100.0),
+			emissive(ambient_colour, 1.0),
+		);
+		let colour = |direction: Vec3| sky.get_colour(&Ray::new(Vec3::zero(), direction, 0.0));
+		// tilted away from the sun by the angle in degrees
+		let tilted = |degrees: Float| {
+			let angle = (45.0 + degrees).to_radians();
+			Vec3::new(0.0, angle.sin(), angle.cos())
+		};
+		assert_eq!(colour(Vec3::new(0.0, 2.0, 2.0)), 100.0 * sun_colour);
+		assert_eq!(colour(tilted(4.9)), 100.0 * sun_colour);
+		assert_eq!(colour(tilted(-4.9)), 100.0 * sun_colour);
+		assert_eq!(colour(tilted(5.1)), ambient_colour);
+		assert_eq!(colour(-Vec3::z()), ambient_colour);
+
+		// sampled directions weighted by their pdf integrate the sky's radiance
+		let samples = 100000;
+		let mut total = Vec3::zero();
+		for _ in 0..samples {
+			let direction = sky.sample(Vec3::zero(), &mut thread_rng());
+			assert!((direction.mag() - 1.0).abs() < 0.0001);
+			total += colour(direction) / sky.pdf(Vec3::zero(), direction);
+		}
+		let sun_solid_angle = 2.0 * PI * (1.0 - (5.0 as Float).to_radians().cos());
+		let expected =
+			100.0 * sun_colour * sun_solid_angle + ambient_colour * (4.0 * PI - sun_solid_angle);
+		let estimate = total / samples as Float;
+		assert!(
+			((estimate - expected) / expected).abs().component_max() < 0.02,
+			"{estimate:?} {expected:?}"
+		);
+	}
 
 	#[test]
 	fn sky_sampling() {
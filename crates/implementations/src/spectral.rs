@@ -0,0 +1,165 @@
+use rand::Rng;
+use rt_core::*;
+use std::sync::OnceLock;
+
+// wavelengths in nm traced by the spectral integrator
+pub const MIN_WAVELENGTH: Float = 380.0;
+pub const MAX_WAVELENGTH: Float = 720.0;
+
+// Smits' spectra of the primaries and their complements, reflectances in ten equal bins
+// from MIN_WAVELENGTH to MAX_WAVELENGTH. Any rgb is the sum of white with one
+// complement and one primary so reflectances in [0, 1] stay smooth and in [0, 1].
+const WHITE: [Float; 10] = [
+	1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const CYAN: [Float; 10] = [
+	0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const MAGENTA: [Float; 10] = [
+	1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const YELLOW: [Float; 10] = [
+	0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const RED: [Float; 10] = [
+	0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const GREEN: [Float; 10] = [
+	0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const BLUE: [Float; 10] = [
+	1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+// value of the smooth spectrum for the rgb at the wavelength
+pub fn upsample(rgb: Vec3, wavelength: Float) -> Float {
+	let bin = ((wavelength - MIN_WAVELENGTH) / (MAX_WAVELENGTH - MIN_WAVELENGTH) * 10.0)
+		.clamp(0.0, 9.0) as usize;
+	let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+	if r <= g && r <= b {
+		r * WHITE[bin]
+			+ if g <= b {
+				(g - r) * CYAN[bin] + (b - g) * BLUE[bin]
+			} else {
+				(b - r) * CYAN[bin] + (g - b) * GREEN[bin]
+			}
+	} else if g <= r && g <= b {
+		g * WHITE[bin]
+			+ if r <= b {
+				(r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]
+			} else {
+				(b - g) * MAGENTA[bin] + (r - b) * RED[bin]
+			}
+	} else {
+		b * WHITE[bin]
+			+ if r <= g {
+				(r - b) * YELLOW[bin] + (g - r) * GREEN[bin]
+			} else {
+				(g - b) * YELLOW[bin] + (r - g) * RED[bin]
+			}
+	}
+}
+
+// piecewise gaussian with different widths either side of the mean
+fn lobe(wavelength: Float, mean: Float, below: Float, above: Float) -> Float {
+	let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+	(-0.5 * t * t).exp()
+}
+
+// CIE 1931 colour matching functions from the multi-lobe fit by Wyman, Sloan and Shirley
+pub fn cie_xyz(wavelength: Float) -> Vec3 {
+	let l = wavelength;
+	Vec3::new(
+		1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+			- 0.065 * lobe(l, 501.1, 20.4, 26.2),
+		0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1),
+		1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8),
+	)
+}
+
+// linear sRGB of the colour matching functions, channels can be negative outside the gamut
+fn linear_rgb(wavelength: Float) -> Vec3 {
+	let xyz = cie_xyz(wavelength);
+	Vec3::new(
+		3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+		-0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+		0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+	)
+}
+
+// each channel integrated over the traced wavelengths so a constant spectrum of 1 is white
+fn rgb_normalisation() -> Vec3 {
+	static NORMALISATION: OnceLock<Vec3> = OnceLock::new();
+	*NORMALISATION.get_or_init(|| {
+		let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+		(0..steps)
+			.map(|i| linear_rgb(MIN_WAVELENGTH + i as Float + 0.5))
+			.fold(Vec3::zero(), |a, b| a + b)
+	})
+}
+
+// rgb of unit radiance at the wavelength over the chance of tracing it
+pub fn wavelength_rgb(wavelength: Float) -> Vec3 {
+	let rgb = linear_rgb(wavelength) / rgb_normalisation();
+	rgb * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+// uniform wavelength with the rgb weight of its radiance
+pub fn sample_wavelength<R: Rng>(rng: &mut R) -> (Float, Vec3) {
+	let wavelength = MIN_WAVELENGTH + (MAX_WAVELENGTH - MIN_WAVELENGTH) * rng.gen::<Float>();
+	(wavelength, wavelength_rgb(wavelength))
+}
+
+// rgb of a spectrum integrated in 1nm steps
+pub fn spectrum_to_rgb<F: Fn(Float) -> Float>(spectrum: F) -> Vec3 {
+	let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+	(0..steps)
+		.map(|i| {
+			let wavelength = MIN_WAVELENGTH + i as Float + 0.5;
+			spectrum(wavelength) * linear_rgb(wavelength)
+		})
+		.fold(Vec3::zero(), |a, b| a + b)
+		/ rgb_normalisation()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::thread_rng;
+
+	#[test]
+	fn narrow_band_light() {
+		// upsampled colours convert back to about the same rgb
+		for rgb in [
+			Vec3::one(),
+			Vec3::new(1.0, 0.0, 0.0),
+			Vec3::new(0.2, 0.5, 0.8),
+		] {
+			let back = spectrum_to_rgb(|wavelength| upsample(rgb, wavelength));
+			assert!((back - rgb).abs().component_max() < 0.1, "{rgb:?} {back:?}");
+		}
+
+		// a yellow light with all of its power close to 580nm
+		let light = |wavelength: Float| lobe(wavelength, 580.0, 5.0, 5.0);
+		let light_rgb = spectrum_to_rgb(light);
+		assert!(light_rgb.x > 0.0 && light_rgb.y > 0.0, "{light_rgb:?}");
+
+		// multiplying in rgb keeps all of the light's red but the red surface's spectrum
+		// reflects little around 580nm
+		let red = Vec3::new(1.0, 0.0, 0.0);
+		let rgb_multiply = light_rgb * red;
+		let spectral = spectrum_to_rgb(|wavelength| light(wavelength) * upsample(red, wavelength));
+		assert!(
+			spectral.x < 0.5 * rgb_multiply.x,
+			"{spectral:?} {rgb_multiply:?}"
+		);
+
+		// sampled wavelengths average to the integral
+		let (mut total, rng) = (Vec3::zero(), &mut thread_rng());
+		for _ in 0..100000 {
+			let (wavelength, weight) = sample_wavelength(rng);
+			total += upsample(Vec3::one(), wavelength) * weight;
+		}
+		assert!((total / 100000.0 - Vec3::one()).abs().component_max() < 0.05);
+	}
+}
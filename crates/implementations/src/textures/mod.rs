@@ -1,8 +1,8 @@
-use image::{io::Reader, GenericImageView};
+use image::{codecs::hdr::HdrDecoder, io::Reader, ColorType, GenericImageView};
 use proc::Texture;
 use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
 use rt_core::*;
-use std::path::Path;
+use std::{fs::File, io::BufRead, io::BufReader, path::Path};
 
 const PERLIN_RVECS: usize = 256;
 
@@ -10,10 +10,33 @@ pub trait Texture: Sync {
 	fn colour_value(&self, _: Vec3, _: Vec3) -> Vec3 {
 		Vec3::new(1.0, 1.0, 1.0)
 	}
+	// colour at a point with texture coordinates, textures that aren't mapped by them
+	// ignore the uv
+	fn uv_colour_value(&self, _: Vec2, direction: Vec3, point: Vec3) -> Vec3 {
+		self.colour_value(direction, point)
+	}
+	// colour at a surface hit using its texture coordinates when it has them
+	fn hit_colour(&self, direction: Vec3, hit: &Hit) -> Vec3 {
+		match hit.uv {
+			Some(uv) => self.uv_colour_value(uv, direction, hit.point),
+			None => self.colour_value(direction, hit.point),
+		}
+	}
 	fn requires_uv(&self) -> bool {
 		false
 	}
+	// false for colours that are negative or not finite
+	fn is_valid(&self) -> bool {
+		true
+	}
+}
+
+fn valid_colour(colour: Vec3) -> bool {
+	[colour.x, colour.y, colour.z]
+		.iter()
+		.all(|v| v.is_finite() && *v >= 0.0)
 }
+
 #[derive(Texture, Debug, Clone)]
 pub enum AllTextures {
 	CheckeredTexture(CheckeredTexture),
@@ -70,14 +93,26 @@ impl Texture for CheckeredTexture {
 	fn requires_uv(&self) -> bool {
 		false
 	}
+	fn is_valid(&self) -> bool {
+		valid_colour(self.colour_one) && valid_colour(self.colour_two)
+	}
 }
 
+// gradient noise over a lattice of random unit vectors, the same seed always gives the
+// same noise. Turbulence sums that many octaves of the noise's magnitude and marble
+// makes stripes along z bent by it, the value is mapped from colour_two at 0 to
+// colour_one at 1
 #[derive(Debug, Clone)]
 pub struct Perlin {
 	ran_vecs: [Vec3; PERLIN_RVECS],
 	perm_x: [u32; PERLIN_RVECS],
 	perm_y: [u32; PERLIN_RVECS],
 	perm_z: [u32; PERLIN_RVECS],
+	pub scale: Float,
+	pub turbulence: u32,
+	pub marble: bool,
+	pub colour_one: Vec3,
+	pub colour_two: Vec3,
 }
 
 impl Default for Perlin {
@@ -88,25 +123,59 @@ impl Default for Perlin {
 
 impl Perlin {
 	pub fn new() -> Self {
-		let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+		Self::seeded(thread_rng().gen())
+	}
+
+	pub fn seeded(seed: u64) -> Self {
+		let mut rng = SmallRng::seed_from_u64(seed);
 
 		let mut ran_vecs: [Vec3; PERLIN_RVECS] = [Vec3::one(); PERLIN_RVECS];
 		for ran_vec in &mut ran_vecs {
-			*ran_vec = rng.gen_range(-1.0..1.0) * Vec3::one();
+			let mut v = Vec3::zero();
+			while v.mag_sq() > 1.0 || v.mag_sq() < 0.0001 {
+				v = Vec3::new(
+					rng.gen_range(-1.0..1.0),
+					rng.gen_range(-1.0..1.0),
+					rng.gen_range(-1.0..1.0),
+				);
+			}
+			*ran_vec = v.normalised();
 		}
 
-		let perm_x = Self::generate_perm();
-		let perm_y = Self::generate_perm();
-		let perm_z = Self::generate_perm();
+		let perm_x = Self::generate_perm(&mut rng);
+		let perm_y = Self::generate_perm(&mut rng);
+		let perm_z = Self::generate_perm(&mut rng);
 
 		Perlin {
 			ran_vecs,
 			perm_x,
 			perm_y,
 			perm_z,
+			scale: 1.0,
+			turbulence: 0,
+			marble: false,
+			colour_one: Vec3::one(),
+			colour_two: Vec3::zero(),
 		}
 	}
 
+	pub fn with_scale(mut self, scale: Float) -> Self {
+		self.scale = scale;
+		self
+	}
+
+	pub fn with_turbulence(mut self, octaves: u32, marble: bool) -> Self {
+		self.turbulence = octaves;
+		self.marble = marble;
+		self
+	}
+
+	pub fn with_colours(mut self, colour_one: Vec3, colour_two: Vec3) -> Self {
+		self.colour_one = colour_one;
+		self.colour_two = colour_two;
+		self
+	}
+
 	pub fn noise(&self, point: Vec3) -> Float {
 		let u = point.x - point.x.floor();
 
@@ -131,18 +200,40 @@ impl Perlin {
 		Perlin::trilinear_lerp(c, u, v, w)
 	}
 
-	fn generate_perm() -> [u32; PERLIN_RVECS] {
+	// sum of the noise's magnitude at doubling frequencies and halving weights
+	pub fn turbulence(&self, point: Vec3, octaves: u32) -> Float {
+		let (mut total, mut point, mut weight) = (0.0, point, 1.0);
+		for _ in 0..octaves {
+			total += weight * self.noise(point);
+			weight *= 0.5;
+			point = 2.0 * point;
+		}
+		total.abs()
+	}
+
+	// value in [0, 1] at the point
+	pub fn value(&self, point: Vec3) -> Float {
+		let point = self.scale * point;
+		let value = match (self.turbulence, self.marble) {
+			(0, false) => 0.5 * (1.0 + self.noise(point)),
+			(octaves, false) => self.turbulence(point, octaves),
+			(octaves, true) => {
+				0.5 * (1.0 + (point.z + 10.0 * self.turbulence(point, octaves)).sin())
+			}
+		};
+		value.clamp(0.0, 1.0)
+	}
+
+	fn generate_perm(rng: &mut SmallRng) -> [u32; PERLIN_RVECS] {
 		let mut perm: [u32; PERLIN_RVECS] = [0; PERLIN_RVECS];
 		for (i, perm) in perm.iter_mut().enumerate() {
 			*perm = i as u32;
 		}
-		Self::permute(&mut perm);
+		Self::permute(&mut perm, rng);
 		perm
 	}
 
-	fn permute(perm: &mut [u32; PERLIN_RVECS]) {
-		let mut rng = rand::rngs::SmallRng::from_rng(rand::thread_rng()).unwrap();
-
+	fn permute(perm: &mut [u32; PERLIN_RVECS], rng: &mut SmallRng) {
 		for i in (1..PERLIN_RVECS).rev() {
 			let target = rng.gen_range(0..i);
 			perm[0..PERLIN_RVECS].swap(i, target);
@@ -171,12 +262,16 @@ impl Perlin {
 
 impl Texture for Box<Perlin> {
 	fn colour_value(&self, _: Vec3, point: Vec3) -> Vec3 {
-		0.5 * Vec3::one() * (1.0 + self.noise(point))
+		let t = self.value(point);
+		t * self.colour_one + (1.0 - t) * self.colour_two
 	}
 
 	fn requires_uv(&self) -> bool {
 		false
 	}
+	fn is_valid(&self) -> bool {
+		valid_colour(self.colour_one) && valid_colour(self.colour_two)
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -197,12 +292,18 @@ impl Texture for SolidColour {
 	fn requires_uv(&self) -> bool {
 		false
 	}
+	fn is_valid(&self) -> bool {
+		valid_colour(self.colour)
+	}
 }
 
+// image read as an equirectangular map by direction, with z up and rotated about it by
+// rotation radians, or by the uv of a surface
 #[derive(Debug, Clone)]
 pub struct ImageTexture {
 	pub data: Vec<Vec3>,
 	pub dim: (usize, usize),
+	pub rotation: Float,
 }
 
 impl ImageTexture {
@@ -210,6 +311,18 @@ impl ImageTexture {
 	where
 		P: AsRef<Path>,
 	{
+		// the image crate converts .hdr files to 8 bit so they are decoded separately
+		let is_hdr = filepath
+			.as_ref()
+			.extension()
+			.is_some_and(|e| e.eq_ignore_ascii_case("hdr"));
+		if is_hdr {
+			return match File::open(filepath) {
+				Ok(file) => Self::from_hdr(BufReader::new(file)),
+				Err(e) => panic!("{e}"),
+			};
+		}
+
 		// open image and get dimensions
 
 		let img = match image::open(filepath) {
@@ -226,39 +339,86 @@ impl ImageTexture {
 			}
 		};
 
+		// integer formats hold sRGB values and float formats are already linear
+		let is_srgb = !matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+		let data = img
+			.to_rgb32f()
+			.into_raw()
+			.chunks(3)
+			.map(|col| {
+				let col = Vec3::new(col[0] as Float, col[1] as Float, col[2] as Float);
+				if is_srgb {
+					Vec3::new(
+						srgb_to_linear(col.x),
+						srgb_to_linear(col.y),
+						srgb_to_linear(col.z),
+					)
+				} else {
+					col
+				}
+			})
+			.collect();
+
+		Self::from_data(data, img.dimensions())
+	}
+
+	// decodes a Radiance RGBE (.hdr) image to linear values
+	pub fn from_hdr<R: BufRead>(reader: R) -> Self {
+		let decoder = match HdrDecoder::new(reader) {
+			Ok(decoder) => decoder,
+			Err(e) => panic!("{e}"),
+		};
+		let metadata = decoder.metadata();
+		let data = match decoder.read_image_hdr() {
+			Ok(pixels) => pixels
+				.into_iter()
+				.map(|p| Vec3::new(p[0] as Float, p[1] as Float, p[2] as Float))
+				.collect(),
+			Err(e) => panic!("{e}"),
+		};
+
+		Self::from_data(data, (metadata.width, metadata.height))
+	}
+
+	fn from_data(data: Vec<Vec3>, dim: (u32, u32)) -> Self {
 		// make sure image in non-zero
-		let dim = img.dimensions();
 		assert!(dim.0 != 0 && dim.1 != 0);
 
 		// - 1 to prevent indices out of range in colour_value
 		let dim = ((dim.0 - 1) as usize, (dim.1 - 1) as usize);
 
-		// get raw pixel data as Vec<u16> then convert to Vec<Vec3>
-		let mut data: Vec<Vec3> = Vec::new();
-		let image = img.to_rgb32f();
-		for col in image.into_raw().chunks(3) {
-			data.push(Vec3::new(
-				*col.first().unwrap() as Float,
-				*col.get(1).unwrap() as Float,
-				*col.get(2).unwrap() as Float,
-			));
+		Self {
+			data,
+			dim,
+			rotation: 0.0,
 		}
+	}
 
-		Self { data, dim }
+	// spins the map about the up axis
+	pub fn with_rotation(mut self, degrees: Float) -> Self {
+		self.rotation = degrees.to_radians();
+		self
+	}
+
+	// pixel covering the point at the fractions of the width from the left and the
+	// height from the top
+	fn texel(&self, x: Float, y: Float) -> Vec3 {
+		let (width, height) = (self.dim.0 + 1, self.dim.1 + 1);
+		let x = ((x * width as Float) as usize).min(self.dim.0);
+		let y = ((y * height as Float) as usize).min(self.dim.1);
+		self.data[y * width + x]
 	}
 }
 
 impl Texture for ImageTexture {
 	fn colour_value(&self, direction: Vec3, _: Vec3) -> Vec3 {
-		let phi = direction.y.atan2(direction.x) + PI;
-		let theta = direction.z.acos();
-		let uv = Vec2::new(phi / (2.0 * PI), theta / PI);
-		let x_pixel = (self.dim.0 as Float * uv.x) as usize;
-		let y_pixel = (self.dim.1 as Float * uv.y) as usize;
-
-		// + 1 to get width in pixels
-		let index = y_pixel * (self.dim.0 + 1) + x_pixel;
-		self.data[index]
+		let phi = direction.y.atan2(direction.x) + PI - self.rotation;
+		let theta = direction.z.clamp(-1.0, 1.0).acos();
+		self.texel((phi / (2.0 * PI)).rem_euclid(1.0), theta / PI)
+	}
+	// u wraps around and v is clamped to the image, v = 0 is the bottom row
+	fn uv_colour_value(&self, uv: Vec2, _: Vec3, _: Vec3) -> Vec3 {
+		self.texel(uv.x.rem_euclid(1.0), 1.0 - uv.y.clamp(0.0, 1.0))
 	}
 	fn requires_uv(&self) -> bool {
 		true
@@ -288,4 +448,86 @@ impl Texture for Lerp {
 	fn requires_uv(&self) -> bool {
 		true
 	}
+	fn is_valid(&self) -> bool {
+		valid_colour(self.colour_one) && valid_colour(self.colour_two)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn radiance_hdr() {
+		// a 2x1 flat (not run length encoded) image, each pixel is a shared exponent
+		// e with mantissas m giving m * 2^(e - 136)
+		let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+		data.extend([128, 64, 32, 129, 200, 100, 50, 131]);
+
+		let texture = ImageTexture::from_hdr(&data[..]);
+		assert_eq!(texture.dim, (1, 0));
+		assert_eq!(texture.data[0], Vec3::new(1.0, 0.5, 0.25));
+		assert_eq!(texture.data[1], Vec3::new(6.25, 3.125, 1.5625));
+	}
+
+	#[test]
+	fn uv_image() {
+		// a 3x2 checker of sRGB greys
+		let path = std::env::temp_dir().join("rt_uv_checker.png");
+		image::RgbImage::from_fn(3, 2, |x, y| {
+			image::Rgb([if (x + y) % 2 == 0 { 255 } else { 128 }; 3])
+		})
+		.save(&path)
+		.unwrap();
+		let texture = ImageTexture::new(&path);
+		let colour =
+			|u: Float, v: Float| texture.uv_colour_value(Vec2::new(u, v), Vec3::z(), Vec3::zero());
+
+		// opposite corners
+		let (bottom_left, top_right) = (colour(0.0, 0.0), colour(0.999, 0.999));
+		assert_ne!(bottom_left, top_right);
+		assert!((bottom_left.x - srgb_to_linear(128.0 / 255.0)).abs() < 0.0001);
+		assert_eq!(top_right, Vec3::one());
+		// u wraps around and v is clamped
+		assert_eq!(colour(1.0, 0.0), bottom_left);
+		assert_eq!(colour(-0.01, 0.0), colour(0.99, 0.0));
+		assert_eq!(colour(0.0, -0.1), bottom_left);
+		assert_eq!(colour(0.999, 1.1), top_right);
+
+		let path = std::env::temp_dir().join("rt_uv_single.png");
+		image::RgbImage::from_pixel(1, 1, image::Rgb([255, 0, 255]))
+			.save(&path)
+			.unwrap();
+		let texture = ImageTexture::new(&path);
+		for (u, v) in [(0.0, 0.0), (1.0, 1.0), (-3.5, 0.5), (0.5, 2.0)] {
+			let colour = texture.uv_colour_value(Vec2::new(u, v), Vec3::z(), Vec3::zero());
+			assert_eq!(colour, Vec3::new(1.0, 0.0, 1.0));
+		}
+	}
+
+	#[test]
+	fn seeded_noise() {
+		let marble = || Box::new(Perlin::seeded(7).with_scale(4.0).with_turbulence(7, true));
+		let (a, b) = (marble(), marble());
+		let other = Box::new(Perlin::seeded(8).with_scale(4.0).with_turbulence(7, true));
+
+		let mut differs = false;
+		for i in 0..100 {
+			let point = Vec3::new(
+				0.37 * i as Float,
+				1.3 - 0.11 * i as Float,
+				0.05 * i as Float,
+			);
+			let colour = a.colour_value(Vec3::z(), point);
+			assert_eq!(colour, b.colour_value(Vec3::z(), point));
+			assert!(colour.x >= 0.0 && colour.x <= 1.0);
+			differs |= colour != other.colour_value(Vec3::z(), point);
+		}
+		assert!(differs);
+
+		// the noise is zero on the lattice and varies between it
+		let noise = Perlin::seeded(7);
+		assert_eq!(noise.noise(Vec3::new(3.0, -2.0, 5.0)), 0.0);
+		assert_ne!(noise.noise(Vec3::new(3.5, -2.5, 5.5)), 0.0);
+	}
 }
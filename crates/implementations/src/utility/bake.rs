@@ -0,0 +1,155 @@
+use crate::{
+	triangle::MeshTriangle,
+	utility::{coord::Coordinate, cosine_hemisphere_sampling},
+};
+use rayon::prelude::*;
+use rt_core::*;
+use std::io::{self, Write};
+
+// Returns the fraction of cosine weighted hemisphere rays from each vertex of
+// the triangles' mesh that escape the scene. The hemisphere is oriented using
+// the average shading normal at the vertex, vertices that no triangle uses
+// are left fully unoccluded. All triangles have to share a mesh.
+pub fn bake_ao<A, M>(acceleration: &A, triangles: &[MeshTriangle<M>], samples: u64) -> Vec<Float>
+where
+	A: AccelerationStructure,
+	M: Scatter,
+{
+	let mesh = match triangles.first() {
+		Some(triangle) => &triangle.mesh,
+		None => return Vec::new(),
+	};
+
+	let mut normals = vec![Vec3::zero(); mesh.vertices.len()];
+	for triangle in triangles {
+		for (&point, &normal) in triangle
+			.point_indices
+			.iter()
+			.zip(triangle.normal_indices.iter())
+		{
+			normals[point] += mesh.normals[normal];
+		}
+	}
+
+	mesh.vertices
+		.par_iter()
+		.zip(normals.par_iter())
+		.map(|(&point, &normal)| {
+			if normal.mag_sq() == 0.0 || samples == 0 {
+				return 1.0;
+			}
+			let normal = normal.normalised();
+			let coord = Coordinate::new_from_z(normal);
+			let origin = point + 0.0001 * normal;
+
+			let unoccluded = (0..samples)
+				.filter(|_| {
+					let ray = Ray::new(origin, coord.to_coord(cosine_hemisphere_sampling()), 0.0);
+					!acceleration.occluded(&ray, Float::INFINITY)
+				})
+				.count();
+			unoccluded as Float / samples as Float
+		})
+		.collect()
+}
+
+// writes the triangles' mesh as an OBJ with the baked values as greyscale
+// vertex colours ("v x y z r g b")
+pub fn write_obj<W: Write, M: Scatter>(
+	writer: &mut W,
+	triangles: &[MeshTriangle<M>],
+	ao: &[Float],
+) -> io::Result<()> {
+	let mesh = match triangles.first() {
+		Some(triangle) => &triangle.mesh,
+		None => return Ok(()),
+	};
+
+	for (vertex, ao) in mesh.vertices.iter().zip(ao.iter()) {
+		writeln!(
+			writer,
+			"v {} {} {} {ao} {ao} {ao}",
+			vertex.x, vertex.y, vertex.z
+		)?;
+	}
+	for normal in &mesh.normals {
+		writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+	}
+	for triangle in triangles {
+		let [p1, p2, p3] = triangle.point_indices.map(|i| i + 1);
+		let [n1, n2, n3] = triangle.normal_indices.map(|i| i + 1);
+		writeln!(writer, "f {p1}//{n1} {p2}//{n2} {p3}//{n3}")?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		triangle::MeshData,
+		utility::test_scene::{self, TestMaterial},
+		AllPrimitives,
+	};
+	use std::sync::Arc;
+
+	#[test]
+	fn concave_vertex() {
+		let material = test_scene::lambertian(Vec3::one(), 0.5);
+
+		// a long narrow trough with the crease along z and a flat triangle
+		// well above it
+		let (height, half_width) = (4.0, 1.0);
+		let mut vertices = Vec::new();
+		for x in [0.0, -half_width, half_width] {
+			let y = if x == 0.0 { 0.0 } else { height };
+			for z in [-10.0, 0.0, 10.0] {
+				vertices.push(Vec3::new(x, y, z));
+			}
+		}
+		vertices.extend([
+			Vec3::new(100.0, 10.0, 0.0),
+			Vec3::new(101.0, 10.0, 0.0),
+			Vec3::new(100.0, 10.0, 1.0),
+		]);
+		let normals = vec![
+			Vec3::new(height, half_width, 0.0).normalised(),
+			Vec3::new(-height, half_width, 0.0).normalised(),
+			Vec3::y(),
+		];
+		let mesh = Arc::new(MeshData::new(vertices, normals));
+
+		let triangle = |points, normal| {
+			MeshTriangle::<TestMaterial>::new(points, [normal; 3], material, mesh.clone())
+		};
+		let mut triangles = Vec::new();
+		for (side, normal) in [(3, 0), (6, 1)] {
+			for z in 0..2 {
+				triangles.push(triangle([z, z + 1, side + z], normal));
+				triangles.push(triangle([z + 1, side + z + 1, side + z], normal));
+			}
+		}
+		triangles.push(triangle([9, 10, 11], 2));
+
+		let bvh = test_scene::bvh(
+			triangles
+				.iter()
+				.cloned()
+				.map(AllPrimitives::MeshTriangle)
+				.collect(),
+			test_scene::sky(Vec3::one()),
+		);
+
+		let ao = bake_ao(&bvh, &triangles, 1000);
+		assert_eq!(ao.len(), 12);
+		assert!(ao[1] < 0.5, "crease ao: {}", ao[1]);
+		assert!(ao[9] > 0.99, "exposed ao: {}", ao[9]);
+		assert!(ao[1] < ao[9]);
+
+		let mut obj = Vec::new();
+		write_obj(&mut obj, &triangles, &ao).unwrap();
+		let obj = String::from_utf8(obj).unwrap();
+		assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 12);
+		assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 9);
+	}
+}
@@ -1,4 +1,17 @@
-use rt_core::Vec3;
+use rt_core::{Float, Vec3};
+
+// right handed (tangent, bitangent, normal) from a unit normal, uses the branchless
+// construction from Duff et al. 2017 so the frame changes smoothly except at -z
+pub fn onb_from_normal(n: Vec3) -> (Vec3, Vec3, Vec3) {
+	let sign = (1.0 as Float).copysign(n.z);
+	let a = -1.0 / (sign + n.z);
+	let b = n.x * n.y * a;
+	(
+		Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+		Vec3::new(b, sign + n.y * n.y * a, -n.y),
+		n,
+	)
+}
 
 pub struct Coordinate {
 	pub x: Vec3,
@@ -8,16 +21,8 @@ pub struct Coordinate {
 
 impl Coordinate {
 	pub fn new_from_z(z: Vec3) -> Self {
-		let x = if z.x.abs() > z.y.abs() {
-			Vec3::new(-z.z, 0.0, z.x) / (z.x * z.x + z.z * z.z).sqrt()
-		} else {
-			Vec3::new(0.0, z.z, -z.y) / (z.y * z.y + z.z * z.z).sqrt()
-		};
-		Coordinate {
-			x,
-			y: x.cross(z),
-			z,
-		}
+		let (x, y, z) = onb_from_normal(z);
+		Coordinate { x, y, z }
 	}
 	pub fn create_inverse(&self) -> Self {
 		let x = Vec3::new(self.x.x, self.y.x, self.z.x);
@@ -36,6 +41,26 @@ mod tests {
 
 	use super::*;
 
+	#[test]
+	fn orthonormal_basis() {
+		let normals = (0..10000).map(|_| random_unit_vector()).chain([
+			Vec3::z(),
+			-Vec3::z(),
+			Vec3::x(),
+			-Vec3::y(),
+		]);
+		for n in normals {
+			let (t, b, normal) = onb_from_normal(n);
+			assert_eq!(normal, n);
+			for v in [t, b] {
+				assert!((v.mag() - 1.0).abs() < 0.0001);
+				assert!(v.dot(n).abs() < 0.0001);
+			}
+			assert!(t.dot(b).abs() < 0.0001);
+			assert!((t.cross(b) - n).mag() < 0.0001);
+		}
+	}
+
 	#[test]
 	fn inverse() {
 		let z = random_unit_vector();
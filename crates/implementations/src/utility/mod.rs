@@ -1,7 +1,10 @@
 use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
 use rt_core::{Float, Vec3, PI};
 
+pub mod bake;
 pub mod coord;
+#[cfg(test)]
+pub mod test_scene;
 
 pub fn check_side(normal: &mut Vec3, ray_direction: &Vec3) -> bool {
 	if normal.dot(*ray_direction) > 0.0 {
@@ -85,35 +88,28 @@ pub fn gamma(n: u32) -> Float {
 	nm / (1.0 - nm)
 }
 
+// moves the point off the surface by its error, each coordinate is then rounded away
+// from the surface so a point with no error still ends up on the right side of it while
+// coordinates along the surface are left alone
 pub fn offset_ray(origin: Vec3, normal: Vec3, error: Vec3, is_brdf: bool) -> Vec3 {
 	let offset_val = normal.abs().dot(error);
-	let mut offset = offset_val * normal;
-
-	if !is_brdf {
-		offset = -offset;
-	}
-
-	let mut new_origin = origin + offset;
-
-	if offset.x > 0.0 {
-		new_origin.x = next_float(new_origin.x);
-	} else {
-		new_origin.x = previous_float(new_origin.x);
-	}
-
-	if offset.y > 0.0 {
-		new_origin.y = next_float(new_origin.y);
-	} else {
-		new_origin.y = previous_float(new_origin.y);
-	}
-
-	if offset.z > 0.0 {
-		new_origin.z = next_float(new_origin.z);
-	} else {
-		new_origin.z = previous_float(new_origin.z);
-	}
-
-	new_origin
+	let direction = if is_brdf { normal } else { -normal };
+	let new_origin = origin + offset_val * direction;
+
+	let round = |value: Float, direction: Float| {
+		if direction > 0.0 {
+			next_float(value)
+		} else if direction < 0.0 {
+			previous_float(value)
+		} else {
+			value
+		}
+	};
+	Vec3::new(
+		round(new_origin.x, direction.x),
+		round(new_origin.y, direction.y),
+		round(new_origin.z, direction.z),
+	)
 }
 
 pub fn sort_by_indices<T>(vec: &mut [T], mut indices: Vec<usize>) {
@@ -0,0 +1,89 @@
+// Helpers for building small scenes in tests, everything is leaked so the
+// returned values can be 'static
+use crate::{
+	split::SplitType, AllMaterials, AllPrimitives, AllTextures, Bvh, Camera, Emit, Lambertian,
+	Lerp, RenderOptions, Sampler, SamplerProgress, SimpleCamera, Sky, SolidColour, TraversalOrder,
+};
+use region::Region;
+use rt_core::*;
+
+pub type TestMaterial = AllMaterials<'static, AllTextures>;
+pub type TestPrimitive = AllPrimitives<'static, TestMaterial>;
+pub type TestSky = Sky<'static, AllTextures, TestMaterial>;
+pub type TestBvh = Bvh<TestPrimitive, TestMaterial, TestSky>;
+
+pub fn texture(colour: Vec3) -> &'static AllTextures {
+	Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(colour))))
+}
+
+pub fn lambertian(colour: Vec3, albedo: Float) -> &'static TestMaterial {
+	Box::leak(Box::new(AllMaterials::Lambertian(Lambertian::new(
+		texture(colour),
+		albedo,
+	))))
+}
+
+pub fn emissive(colour: Vec3, strength: Float) -> &'static TestMaterial {
+	Box::leak(Box::new(AllMaterials::Emit(Emit::new(
+		texture(colour),
+		strength,
+	))))
+}
+
+pub fn sky(colour: Vec3) -> TestSky {
+	let tex = texture(colour);
+	let mat = Box::leak(Box::new(AllMaterials::Emit(Emit::new(tex, 1.0))));
+	Sky::new(tex, mat, (0, 0))
+}
+
+pub fn gradient_sky(colour_one: Vec3, colour_two: Vec3) -> TestSky {
+	let tex = Box::leak(Box::new(AllTextures::Lerp(Lerp::new(
+		colour_one, colour_two,
+	))));
+	let mat = Box::leak(Box::new(AllMaterials::Emit(Emit::new(tex, 1.0))));
+	Sky::new(tex, mat, (0, 0))
+}
+
+pub fn bvh(primitives: Vec<TestPrimitive>, sky: TestSky) -> TestBvh {
+	let region = Box::leak(Box::new(Region::new()));
+	Bvh::new(
+		region.alloc_slice(&primitives),
+		sky,
+		SplitType::Sah,
+		TraversalOrder::Axis,
+	)
+}
+
+pub fn camera(origin: Vec3, lookat: Vec3, aspect_ratio: Float) -> SimpleCamera {
+	SimpleCamera::new(
+		origin,
+		lookat,
+		Vec3::y(),
+		40.0,
+		aspect_ratio,
+		0.0,
+		(lookat - origin).mag(),
+	)
+}
+
+// renders returning the averaged image and the number of samples completed
+pub fn render<S: Sampler, C: Camera>(
+	sampler: &S,
+	render_options: RenderOptions,
+	camera: &C,
+	bvh: &TestBvh,
+) -> (Vec<Float>, u64) {
+	let mut progress = SamplerProgress::new(render_options.width * render_options.height, 3);
+	let update = |progress: &mut SamplerProgress, previous: &SamplerProgress, i: u64| -> bool {
+		progress.samples_completed = i;
+		progress.rays_shot += previous.rays_shot;
+		progress
+			.current_image
+			.iter_mut()
+			.zip(previous.current_image.iter())
+			.for_each(|(pres, acc)| *pres += (acc - *pres) / i as Float);
+		false
+	};
+	sampler.sample_image(render_options, camera, bvh, Some((&mut progress, update)));
+	(progress.current_image, progress.samples_completed)
+}
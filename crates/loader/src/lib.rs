@@ -3,6 +3,7 @@ pub mod meshes;
 pub mod misc;
 pub mod obj;
 pub mod parser;
+pub mod points;
 pub mod primitives;
 pub mod textures;
 
@@ -193,10 +194,17 @@ pub enum LoadErr {
 	Any(Box<dyn std::error::Error>),
 }
 
+// primitives, cameras, sky and the names of meshes that loaded no triangles
+type LoadedScene<'a, P, C, S> = (RegionUniqSlice<'a, P>, Vec<C>, S, Vec<String>);
+
+// only the object isolate names or indexes in each obj file is loaded when it is set,
+// prepare can change the primitives using the cameras before they're put in the region
 pub fn load_file_full<'a, T, M, P, C, S>(
 	region: &'a mut Region,
 	file: &str,
-) -> Result<(RegionUniqSlice<'a, P>, C, S), LoadErr>
+	isolate: Option<&str>,
+	prepare: impl FnOnce(&mut Vec<P>, &[C]),
+) -> Result<LoadedScene<'a, P, C, S>, LoadErr>
 where
 	T: Texture + Load,
 	M: Scatter + Load,
@@ -210,10 +218,13 @@ where
 		Err(e) => return Err(LoadErr::FileNotRead(file.into(), e)),
 	};
 	log::debug!("Parsing scene file {}", file);
-	let scene_conf = match parser::from_str(&scene_file) {
+	let mut scene_conf = match parser::from_str(&scene_file) {
 		Ok(c) => c,
 		Err(e) => return Err(LoadErr::ParseError(e)),
 	};
+	if let Some(name) = isolate {
+		isolate_object(&mut scene_conf, name);
+	}
 
 	let mut lookup = Lookup::new();
 
@@ -228,18 +239,20 @@ where
 	region_insert_with_lookup(region, materials, |n, s| lookup.scatter_insert(n, s));
 
 	log::info!("Loading other objects...");
-	let camera = load_scene_camera(&scene_conf, &lookup, region)?;
+	let cameras = load_scene_cameras(&scene_conf, &lookup, region)?;
 	let sky = load_scene_sky(&scene_conf, &lookup, region)?;
 
 	log::info!("Loading primitives...");
-	let primitives = {
+	let (primitives, empty_meshes) = {
 		let mut primitives = load_primitives::<P>(&scene_conf, &lookup, region)?;
 		log::info!("Loading meshes...");
-		primitives.extend(load_meshes::<P>(&scene_conf, &lookup, region)?);
-		region.alloc_slice(&primitives)
+		let (meshes, empty_meshes) = load_meshes::<P>(&scene_conf, &lookup, region)?;
+		primitives.extend(meshes);
+		prepare(&mut primitives, &cameras);
+		(region.alloc_slice(&primitives), empty_meshes)
 	};
 
-	Ok((primitives, camera, sky))
+	Ok((primitives, cameras, sky, empty_meshes))
 }
 
 pub fn load_str_full<'a, T, M, P, C, S>(
@@ -279,7 +292,7 @@ where
 	let primitives = {
 		let mut primitives = load_primitives::<PrimitiveType>(&scene_conf, &lookup, region)?;
 		log::info!("Loading meshes...");
-		primitives.extend(load_meshes::<PrimitiveType>(&scene_conf, &lookup, region)?);
+		primitives.extend(load_meshes::<PrimitiveType>(&scene_conf, &lookup, region)?.0);
 		region.alloc_slice(&primitives)
 	};
 
@@ -305,6 +318,26 @@ where
 	Ok(C::load(props, region)?.1)
 }
 
+// every camera in the order they appear in the file
+pub fn load_scene_cameras<C>(
+	objects: &[parser::Object],
+	lookup: &Lookup,
+	region: &mut Region,
+) -> Result<Vec<C>, LoadErr>
+where
+	C: Camera + Load,
+{
+	let cameras = objects
+		.iter()
+		.filter(|o| o.kind.is_camera())
+		.map(|o| Ok(C::load(Properties::new(lookup, o), region)?.1))
+		.collect::<Result<Vec<C>, LoadErr>>()?;
+	if cameras.is_empty() {
+		return Err(LoadErr::MissingCamera);
+	}
+	Ok(cameras)
+}
+
 pub fn load_scene_sky<S, M>(
 	objects: &[parser::Object],
 	lookup: &Lookup,
@@ -411,20 +444,50 @@ fn load_primitives<P: Primitive + Load>(
 	Ok(primitives)
 }
 
+// drops every primitive except the meshes read from obj files, which then only load
+// the objects with the given name, or at the given index in files without that name
+pub fn isolate_object<'a>(objects: &mut Vec<parser::Object<'a>>, name: &'a str) {
+	let obj_mesh = |o: &parser::Object| {
+		o.kind.is_mesh() && o.lookup("type") == Some(parser::ObjectValue::Text("mesh"))
+	};
+	objects.retain(|o| !(o.kind.is_primitive() || o.kind.is_mesh()) || obj_mesh(o));
+	for object in objects.iter_mut().filter(|o| o.kind.is_mesh()) {
+		object
+			.values
+			.insert("isolate", parser::ObjectValue::Text(name));
+	}
+}
+
+// the primitives of every mesh and the names of meshes without any, a mesh is named by
+// its name, its file or its position among the meshes
 fn load_meshes<P: Primitive + Load>(
 	objects: &[parser::Object],
 	lookup: &Lookup,
 	region: &mut Region,
-) -> Result<Vec<P>, LoadErr>
+) -> Result<(Vec<P>, Vec<String>), LoadErr>
 where
 	Vec<P>: Load,
 {
 	let mut primitives = Vec::new();
-	for obj in objects.iter().filter(|o| o.kind.is_mesh()) {
+	let mut empty = Vec::new();
+	for (i, obj) in objects.iter().filter(|o| o.kind.is_mesh()).enumerate() {
 		let props = Properties::new(lookup, obj);
-		primitives.extend(<Vec<P> as Load>::load(props, region)?.1);
+		let mesh = <Vec<P> as Load>::load(props, region)?.1;
+		if mesh.is_empty() {
+			let file = ["obj", "points"]
+				.into_iter()
+				.find_map(|key| match obj.lookup(key) {
+					Some(parser::ObjectValue::Text(file)) => Some(file),
+					_ => None,
+				});
+			empty.push(match obj.name.or(file) {
+				Some(name) => name.to_owned(),
+				None => format!("mesh {i}"),
+			});
+		}
+		primitives.extend(mesh);
 	}
-	Ok(primitives)
+	Ok((primitives, empty))
 }
 
 #[cfg(test)]
@@ -503,6 +566,43 @@ primitive (
 			load_str_full::<Tex, Mat, Prim, SimpleCamera, SkyType>(&mut region, DATA).unwrap();
 
 		let (p, _, s) = stuff;
-		let _: Bvh<Prim, Mat, SkyType> = Bvh::new(p, s, split::SplitType::Sah);
+		let _: Bvh<Prim, Mat, SkyType> =
+			Bvh::new(p, s, split::SplitType::Sah, TraversalOrder::Axis);
+	}
+
+	#[test]
+	fn empty_meshes() {
+		// a point cloud without any points next to a cuboid
+		let path = std::env::temp_dir().join(format!("rt_no_points_{}.xyz", std::process::id()));
+		std::fs::write(&path, "# no points\n").unwrap();
+		let data = format!(
+			"{DATA}
+mesh (
+	type points
+	points {}
+)
+
+mesh (
+	type aacuboid
+	material ground
+	point_one 0 0 0
+	point_two 1 1 1
+)",
+			path.display()
+		);
+
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let objects = parser::from_str(&data).unwrap();
+		let textures = load_textures::<AllTextures>(&objects, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials = load_materials::<MaterialType>(&objects, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, s| lookup.scatter_insert(n, s));
+		let (primitives, empty) =
+			load_meshes::<PrimitiveType>(&objects, &lookup, &mut region).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(primitives.len(), 12);
+		assert_eq!(empty, [path.display().to_string()]);
 	}
 }
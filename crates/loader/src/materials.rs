@@ -27,6 +27,14 @@ impl<T: Texture> Load for AllMaterials<'_, T> {
 				let x = Refract::load(props, region)?;
 				(x.0, Self::Refract(x.1))
 			}
+			"rough_dielectric" => {
+				let x = RoughDielectric::load(props, region)?;
+				(x.0, Self::RoughDielectric(x.1))
+			}
+			"shadow_catcher" => {
+				let x = ShadowCatcher::load(props, region)?;
+				(x.0, Self::ShadowCatcher(x.1))
+			}
 			"trowbridge_reitz" => {
 				let x = TrowbridgeReitz::load(props, region)?;
 				(x.0, Self::TrowbridgeReitz(x.1))
@@ -53,6 +61,12 @@ impl<T: Texture> Load for Lambertian<'_, T> {
 	}
 }
 
+impl Load for ShadowCatcher {
+	fn load(mut props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		Ok((props.name(), Self::new()))
+	}
+}
+
 impl<T: Texture> Load for Emit<'_, T> {
 	fn load(mut props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
 		let tex = props
@@ -92,6 +106,23 @@ impl<T: Texture> Load for Refract<'_, T> {
 	}
 }
 
+impl<T: Texture> Load for RoughDielectric<'_, T> {
+	fn load(mut props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let tex = props
+			.texture("texture")
+			.unwrap_or_else(|| props.default_texture());
+		let roughness = props.float("roughness").unwrap_or(0.5);
+		let eta = props.float("eta").unwrap_or(1.5);
+
+		let name = props.name();
+
+		Ok((
+			name,
+			Self::new(unsafe { &*(&*tex as *const _) }, roughness, eta),
+		))
+	}
+}
+
 impl<T: Texture> Load for TrowbridgeReitz<'_, T> {
 	fn load(mut props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
 		let tex = props
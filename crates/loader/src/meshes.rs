@@ -1,11 +1,12 @@
-use crate::obj::load_obj;
+use crate::obj::{load_obj, Convention};
+use crate::points::load_points;
 use crate::Properties;
 use crate::*;
 use implementations::triangle::MeshData;
 use implementations::triangle::MeshTriangle;
 use implementations::*;
 
-impl<M: Scatter> Load for Vec<AllPrimitives<'_, M>> {
+impl<M: Scatter + Load> Load for Vec<AllPrimitives<'_, M>> {
 	fn load(props: Properties, region: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
 		let kind = match props.text("type") {
 			Some(k) => k,
@@ -14,6 +15,7 @@ impl<M: Scatter> Load for Vec<AllPrimitives<'_, M>> {
 		match kind {
 			"mesh" => mesh(props, region),
 			"aacuboid" => cuboid(props, region),
+			"points" => points(props, region),
 			o => {
 				return Err(LoadErr::MissingRequired(format!(
 					"required a known value for mesh type, found '{o}'"
@@ -114,6 +116,23 @@ fn mesh<'a, M: Scatter>(
 			))
 		}
 	};
-	let prims = load_obj(&filepath, props);
+	let convention = Convention::from_props(&props)?;
+	let prims = load_obj(&filepath, props, convention);
+	Ok((None, prims))
+}
+
+fn points<'a, M: Scatter + Load>(
+	props: Properties,
+	region: &mut Region,
+) -> Result<(Option<String>, Vec<AllPrimitives<'a, M>>), LoadErr> {
+	let filepath = match props.text("points") {
+		Some(c) => c.to_owned(),
+		None => {
+			return Err(LoadErr::MissingRequired(
+				"expected points on point cloud, found nothing".to_string(),
+			))
+		}
+	};
+	let prims = load_points(&filepath, props, region)?;
 	Ok((None, prims))
 }
@@ -33,6 +33,15 @@ impl<T: Texture> Load for Sky<'_, T, AllMaterials<'_, T>> {
 			unsafe { &*(&*mat as *const _) },
 			(res.x as _, res.y as _),
 		);
+		// the sky is only sampled through the portal when there is one
+		let sky = match (
+			props.vec3("portal_corner"),
+			props.vec3("portal_u"),
+			props.vec3("portal_v"),
+		) {
+			(Some(corner), Some(u), Some(v)) => sky.with_portal(Portal::new(corner, u, v)),
+			_ => sky,
+		};
 		Ok((None, sky))
 	}
 }
@@ -1,39 +1,143 @@
 use crate::Float;
+use crate::LoadErr;
 use crate::Properties;
 use crate::Scatter;
+use crate::Vec2;
 use crate::Vec3;
 use implementations::{
-	triangle::{MeshData, MeshTriangle},
+	triangle::{fix_winding, MeshData, MeshTriangle},
 	AllPrimitives,
 };
 use std::sync::Arc;
 
-pub fn load_obj<'a, M: Scatter>(filepath: &str, props: Properties) -> Vec<AllPrimitives<'a, M>> {
+// axis convention of an imported model, the scene is right handed with y up
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Convention {
+	pub z_up: bool,
+	pub left_handed: bool,
+}
+
+impl Convention {
+	// reads "up" (y or z) and "handedness" (right or left)
+	pub fn from_props(props: &Properties) -> Result<Self, LoadErr> {
+		let z_up = match props.text("up") {
+			None | Some("y") => false,
+			Some("z") => true,
+			Some(o) => {
+				return Err(LoadErr::MissingRequired(format!(
+					"expected y or z for up on mesh, found '{o}'"
+				)))
+			}
+		};
+		let left_handed = match props.text("handedness") {
+			None | Some("right") => false,
+			Some("left") => true,
+			Some(o) => {
+				return Err(LoadErr::MissingRequired(format!(
+					"expected right or left for handedness on mesh, found '{o}'"
+				)))
+			}
+		};
+		Ok(Self { z_up, left_handed })
+	}
+
+	// maps a position or normal into scene space, left handed models are mirrored
+	// along their forward axis (z when y is up, y when z is up)
+	pub fn apply(&self, v: Vec3) -> Vec3 {
+		match (self.z_up, self.left_handed) {
+			(false, false) => v,
+			(false, true) => Vec3::new(v.x, v.y, -v.z),
+			(true, false) => Vec3::new(v.x, v.z, -v.y),
+			(true, true) => Vec3::new(v.x, v.z, v.y),
+		}
+	}
+}
+
+pub fn load_obj<'a, M: Scatter>(
+	filepath: &str,
+	props: Properties,
+	convention: Convention,
+) -> Vec<AllPrimitives<'a, M>> {
 	let model = wavefront_obj::obj::parse(&std::fs::read_to_string(filepath).unwrap()).unwrap();
 
+	let (primitives, degenerate) = obj_to_primitives(model, &props, convention);
+	if degenerate != 0 {
+		log::warn!("Skipped {degenerate} degenerate triangle(s) in {filepath}");
+	}
+	if let (Some(name), true) = (props.text("isolate"), primitives.is_empty()) {
+		log::warn!("No object {name} in {filepath}");
+	}
+	primitives
+}
+
+// triangles with (close to) zero area have no well defined normal
+fn is_degenerate(points: [Vec3; 3]) -> bool {
+	let (edge_one, edge_two) = (points[1] - points[0], points[2] - points[0]);
+	edge_one.cross(edge_two).mag() <= Float::EPSILON * edge_one.mag() * edge_two.mag()
+}
+
+// returns the triangles along with the number of degenerate triangles skipped, only
+// the object named by the "isolate" property is kept when it is given, or the object at
+// that index if none has the name, and "fix_winding true" makes the winding of each
+// object's triangles consistent
+fn obj_to_primitives<'a, M: Scatter>(
+	model: wavefront_obj::obj::ObjSet,
+	props: &Properties,
+	convention: Convention,
+) -> (Vec<AllPrimitives<'a, M>>, usize) {
 	let mut primitives: Vec<AllPrimitives<'a, M>> = Vec::new();
+	let mut degenerate = 0;
+	let isolate = props.text("isolate").map(|name| {
+		model
+			.objects
+			.iter()
+			.position(|object| object.name == name)
+			.or_else(|| name.parse::<usize>().ok())
+	});
+	let repair_winding = props.text("fix_winding") == Some("true");
 
-	for object in model.objects {
-		let mesh_data: Arc<MeshData> = Arc::new(MeshData::new(
+	for (i, object) in model.objects.into_iter().enumerate() {
+		if isolate.is_some_and(|index| index != Some(i)) {
+			continue;
+		}
+		let mut mesh_data = MeshData::new(
 			object
 				.vertices
 				.iter()
-				.map(|vertex| vertex_to_vec3(*vertex))
+				.map(|vertex| convention.apply(vertex_to_vec3(*vertex)))
 				.collect(),
 			object
 				.normals
 				.iter()
-				.map(|normal| vertex_to_vec3(*normal))
+				.map(|normal| convention.apply(vertex_to_vec3(*normal)))
 				.collect(),
-		));
+		);
+		mesh_data.uvs = object
+			.tex_vertices
+			.iter()
+			.map(|uv| Vec2::new(uv.u as Float, uv.v as Float))
+			.collect();
+		let mesh_data = Arc::new(mesh_data);
+		let mut triangles = Vec::new();
 
 		for geometric_object in object.geometry {
 			for shape in geometric_object.shapes {
-				if let wavefront_obj::obj::Primitive::Triangle(i1, i2, i3) = shape.primitive {
+				if let wavefront_obj::obj::Primitive::Triangle(i1, mut i2, mut i3) = shape.primitive
+				{
+					// mirroring reverses the winding so swap it back
+					if convention.left_handed {
+						std::mem::swap(&mut i2, &mut i3);
+					}
+
 					if i1.2.is_none() {
 						panic!("Please export obj file with vertex normals!");
 					}
 
+					if is_degenerate([i1.0, i2.0, i3.0].map(|i| mesh_data.vertices[i])) {
+						degenerate += 1;
+						continue;
+					}
+
 					let mat: region::RegionRes<M> = props
 						.lookup_material(
 							geometric_object
@@ -43,23 +147,339 @@ pub fn load_obj<'a, M: Scatter>(filepath: &str, props: Properties) -> Vec<AllPri
 						)
 						.unwrap_or_else(|| props.default_scatter());
 
-					let triangle: AllPrimitives<'a, M> =
-						AllPrimitives::MeshTriangle(MeshTriangle::new(
-							[i1.0, i2.0, i3.0],
-							[i1.2.unwrap(), i2.2.unwrap(), i3.2.unwrap()],
-							unsafe { &*(&*mat as *const _) },
-							mesh_data.clone(),
-						));
+					let mut triangle = MeshTriangle::new(
+						[i1.0, i2.0, i3.0],
+						[i1.2.unwrap(), i2.2.unwrap(), i3.2.unwrap()],
+						unsafe { &*(&*mat as *const _) },
+						mesh_data.clone(),
+					);
+					// texture coordinates are only used when every vertex has one
+					if let (Some(a), Some(b), Some(c)) = (i1.1, i2.1, i3.1) {
+						triangle.uv_indices = Some([a, b, c]);
+					}
 
-					primitives.push(triangle)
+					triangles.push(triangle)
 				}
 			}
 		}
+		if repair_winding {
+			let flipped = fix_winding(&mut triangles);
+			if flipped != 0 {
+				log::info!("Flipped {flipped} triangle(s) in {}", object.name);
+			}
+		}
+		primitives.extend(triangles.into_iter().map(AllPrimitives::MeshTriangle));
 		std::mem::forget(mesh_data);
 	}
-	primitives
+	(primitives, degenerate)
 }
 
 fn vertex_to_vec3(vertex: wavefront_obj::obj::Vertex) -> Vec3 {
 	Vec3::new(vertex.x as Float, vertex.y as Float, vertex.z as Float)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::*;
+	use implementations::{
+		rt_core::{Primitive, Ray},
+		AllMaterials, AllTextures,
+	};
+	use wavefront_obj::obj::{Geometry, ObjSet, Object, Shape, TVertex, Vertex};
+
+	fn vertex(x: f64, y: f64, z: f64) -> Vertex {
+		Vertex { x, y, z }
+	}
+
+	fn triangle(a: usize, b: usize, c: usize) -> Shape {
+		Shape {
+			primitive: wavefront_obj::obj::Primitive::Triangle(
+				(a, None, Some(0)),
+				(b, None, Some(0)),
+				(c, None, Some(0)),
+			),
+			groups: Vec::new(),
+			smoothing_groups: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn degenerate_triangles() {
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let file = "
+texture grey (
+	type solid
+	colour 0.5
+)
+material default (
+	type lambertian
+	texture grey
+	albedo 0.5
+)";
+		let data = parser::from_str(file).unwrap();
+		let textures = load_textures::<AllTextures>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials =
+			load_materials::<AllMaterials<AllTextures>>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, t| lookup.scatter_insert(n, t));
+
+		let model = ObjSet {
+			material_library: None,
+			objects: vec![Object {
+				name: "mesh".to_owned(),
+				vertices: vec![
+					vertex(0.0, 0.0, 0.0),
+					vertex(1.0, 0.0, 0.0),
+					vertex(0.0, 1.0, 0.0),
+					vertex(2.0, 0.0, 0.0),
+				],
+				tex_vertices: Vec::new(),
+				normals: vec![vertex(0.0, 0.0, 1.0)],
+				geometry: vec![Geometry {
+					material_name: None,
+					// the second triangle has collinear points
+					shapes: vec![triangle(0, 1, 2), triangle(0, 1, 3)],
+				}],
+			}],
+		};
+
+		let props = Properties::new(&lookup, &data[1]);
+		let (primitives, degenerate) =
+			obj_to_primitives::<AllMaterials<AllTextures>>(model, &props, Convention::default());
+
+		assert_eq!(degenerate, 1);
+		assert_eq!(primitives.len(), 1);
+		let ray = Ray::new(Vec3::new(0.2, 0.2, 1.0), -Vec3::z(), 0.0);
+		let hit = primitives[0].get_int(&ray).unwrap();
+		assert!(!hit.hit.normal.contains_nan());
+	}
+
+	#[test]
+	fn texture_coordinates() {
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let data = parser::from_str("").unwrap();
+		let textures = load_textures::<AllTextures>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials =
+			load_materials::<AllMaterials<AllTextures>>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, t| lookup.scatter_insert(n, t));
+		let props = Properties::new(&lookup, &Default::default());
+
+		// the same triangle with and without texture coordinates, side by side
+		let textured = Shape {
+			primitive: wavefront_obj::obj::Primitive::Triangle(
+				(0, Some(0), Some(0)),
+				(1, Some(1), Some(0)),
+				(2, Some(2), Some(0)),
+			),
+			groups: Vec::new(),
+			smoothing_groups: Vec::new(),
+		};
+		let model = ObjSet {
+			material_library: None,
+			objects: vec![Object {
+				name: "mesh".to_owned(),
+				vertices: vec![
+					vertex(0.0, 0.0, 0.0),
+					vertex(1.0, 0.0, 0.0),
+					vertex(0.0, 1.0, 0.0),
+					vertex(2.0, 0.0, 0.0),
+					vertex(3.0, 0.0, 0.0),
+					vertex(2.0, 1.0, 0.0),
+				],
+				tex_vertices: vec![
+					TVertex {
+						u: 0.2,
+						v: 0.1,
+						w: 0.0,
+					},
+					TVertex {
+						u: 0.8,
+						v: 0.1,
+						w: 0.0,
+					},
+					TVertex {
+						u: 0.2,
+						v: 0.9,
+						w: 0.0,
+					},
+				],
+				normals: vec![vertex(0.0, 0.0, 1.0)],
+				geometry: vec![Geometry {
+					material_name: None,
+					shapes: vec![textured, triangle(3, 4, 5)],
+				}],
+			}],
+		};
+		let (primitives, _) =
+			obj_to_primitives::<AllMaterials<AllTextures>>(model, &props, Convention::default());
+		assert_eq!(primitives.len(), 2);
+		let uv = |x: Float| {
+			let ray = Ray::new(Vec3::new(x, 0.25, 1.0), -Vec3::z(), 0.0);
+			primitives
+				.iter()
+				.find_map(|primitive| primitive.get_int(&ray))
+				.unwrap()
+				.hit
+				.uv
+				.unwrap()
+		};
+
+		// weights of 0.5, 0.25 and 0.25 on the three vertices' coordinates
+		let textured = uv(0.25);
+		assert!((textured.x - 0.35).abs() < 0.0001, "{textured:?}");
+		assert!((textured.y - 0.3).abs() < 0.0001, "{textured:?}");
+		// without them the triangle keeps its default coordinates
+		let plain = uv(2.25);
+		assert!((plain.x - 0.5).abs() < 0.0001, "{plain:?}");
+		assert!((plain.y - 0.25).abs() < 0.0001, "{plain:?}");
+	}
+
+	#[test]
+	fn up_axis() {
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let data = parser::from_str("").unwrap();
+		let textures = load_textures::<AllTextures>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials =
+			load_materials::<AllMaterials<AllTextures>>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, t| lookup.scatter_insert(n, t));
+		let props = Properties::new(&lookup, &Default::default());
+
+		// a triangle lying on the ground of a z up file, facing up
+		let model = || ObjSet {
+			material_library: None,
+			objects: vec![Object {
+				name: "mesh".to_owned(),
+				vertices: vec![
+					vertex(0.0, 0.0, 0.0),
+					vertex(1.0, 0.0, 0.0),
+					vertex(0.0, 1.0, 0.0),
+				],
+				tex_vertices: Vec::new(),
+				normals: vec![vertex(0.0, 0.0, 1.0)],
+				geometry: vec![Geometry {
+					material_name: None,
+					shapes: vec![triangle(0, 1, 2)],
+				}],
+			}],
+		};
+		let load = |z_up, left_handed| {
+			let convention = Convention { z_up, left_handed };
+			let (primitives, _) =
+				obj_to_primitives::<AllMaterials<AllTextures>>(model(), &props, convention);
+			match &primitives[0] {
+				AllPrimitives::MeshTriangle(triangle) => (
+					triangle.point_indices.map(|i| triangle.mesh.vertices[i]),
+					triangle.mesh.normals[0],
+				),
+				_ => unreachable!(),
+			}
+		};
+
+		// z up is a -90 degree rotation about x away from y up
+		let rotate = |v: Vec3| Vec3::new(v.x, v.z, -v.y);
+		let (y_up_points, y_up_normal) = load(false, false);
+		let (z_up_points, z_up_normal) = load(true, false);
+		assert_eq!(z_up_points, y_up_points.map(rotate));
+		assert_eq!(z_up_normal, rotate(y_up_normal));
+		assert_eq!(z_up_normal, Vec3::y());
+
+		// a left handed file is mirrored along its forward axis and keeps its winding
+		let (points, normal) = load(true, true);
+		assert_eq!(normal, Vec3::y());
+		assert_eq!(points, [Vec3::zero(), Vec3::z(), Vec3::x()]);
+		let (points, _) = load(false, true);
+		assert_eq!(points, [Vec3::zero(), Vec3::y(), Vec3::x()]);
+
+		let obj = parser::from_str("mesh (\n\tup x\n)").unwrap();
+		assert!(Convention::from_props(&Properties::new(&lookup, &obj[0])).is_err());
+	}
+
+	#[test]
+	fn isolate() {
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let file = "
+primitive (
+	type sphere
+	centre 0 0 0
+	radius 1
+)
+mesh (
+	type aacuboid
+	point_one 0 0 0
+	point_two 1 1 1
+)
+mesh (
+	type mesh
+	obj model.obj
+)";
+		let mut data = parser::from_str(file).unwrap();
+		let textures = load_textures::<AllTextures>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials =
+			load_materials::<AllMaterials<AllTextures>>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, t| lookup.scatter_insert(n, t));
+
+		// only the obj mesh is left
+		isolate_object(&mut data, "right");
+		assert_eq!(data.len(), 1);
+		let props = Properties::new(&lookup, &data[0]);
+
+		let object = |name: &str, offset: f64, triangles: usize| Object {
+			name: name.to_owned(),
+			vertices: vec![
+				vertex(offset, 0.0, 0.0),
+				vertex(offset + 1.0, 0.0, 0.0),
+				vertex(offset, 1.0, 0.0),
+				vertex(offset + 1.0, 1.0, 0.0),
+			],
+			tex_vertices: Vec::new(),
+			normals: vec![vertex(0.0, 0.0, 1.0)],
+			geometry: vec![Geometry {
+				material_name: None,
+				shapes: [triangle(0, 1, 2), triangle(1, 3, 2)][..triangles].to_vec(),
+			}],
+		};
+		let model = || ObjSet {
+			material_library: None,
+			objects: vec![object("left", -2.0, 1), object("right", 2.0, 2)],
+		};
+
+		let (primitives, _) =
+			obj_to_primitives::<AllMaterials<AllTextures>>(model(), &props, Convention::default());
+		assert_eq!(primitives.len(), 2);
+		for primitive in primitives {
+			match primitive {
+				AllPrimitives::MeshTriangle(triangle) => {
+					assert!(triangle
+						.point_indices
+						.iter()
+						.all(|&i| triangle.mesh.vertices[i].x >= 2.0))
+				}
+				_ => unreachable!(),
+			}
+		}
+
+		// an index picks the object at that position in the file
+		data[0]
+			.values
+			.insert("isolate", parser::ObjectValue::Text("0"));
+		let props = Properties::new(&lookup, &data[0]);
+		let (primitives, _) =
+			obj_to_primitives::<AllMaterials<AllTextures>>(model(), &props, Convention::default());
+		assert_eq!(primitives.len(), 1);
+		data[0]
+			.values
+			.insert("isolate", parser::ObjectValue::Text("2"));
+		let props = Properties::new(&lookup, &data[0]);
+		let (primitives, _) =
+			obj_to_primitives::<AllMaterials<AllTextures>>(model(), &props, Convention::default());
+		assert!(primitives.is_empty());
+	}
+}
@@ -0,0 +1,165 @@
+use crate::*;
+use implementations::sphere::Sphere;
+use implementations::SolidColour;
+
+// a point cloud position with an optional sRGB colour
+type Point = (Vec3, Option<[u8; 3]>);
+
+pub fn load_points<'a, M: Scatter + Load>(
+	filepath: &str,
+	props: Properties,
+	region: &mut Region,
+) -> Result<Vec<AllPrimitives<'a, M>>, LoadErr> {
+	let data = match std::fs::read_to_string(filepath) {
+		Ok(d) => d,
+		Err(e) => return Err(LoadErr::FileNotRead(filepath.into(), e)),
+	};
+	let points = parse_points(&data)?;
+	log::info!("Loaded {} point(s) from {filepath}", points.len());
+	points_to_primitives(&points, &props, region)
+}
+
+// each line holds "x y z" or "x y z r g b" with colours in 0-255,
+// values can be separated by whitespace or commas
+fn parse_points(data: &str) -> Result<Vec<Point>, LoadErr> {
+	let mut points = Vec::new();
+	for (line_number, line) in data.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+			continue;
+		}
+		let values = line
+			.split(|c: char| c.is_whitespace() || c == ',')
+			.filter(|v| !v.is_empty())
+			.map(|v| v.parse::<Float>())
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| {
+				LoadErr::MissingRequired(format!(
+					"expected numbers on line {} of point cloud, {e}",
+					line_number + 1
+				))
+			})?;
+
+		let colour = match values.len() {
+			3 => None,
+			6 => Some([values[3], values[4], values[5]].map(|v| v.clamp(0.0, 255.0) as u8)),
+			n => {
+				return Err(LoadErr::MissingRequired(format!(
+					"expected 3 or 6 values on line {} of point cloud, found {n}",
+					line_number + 1
+				)))
+			}
+		};
+		points.push((Vec3::new(values[0], values[1], values[2]), colour));
+	}
+	Ok(points)
+}
+
+fn points_to_primitives<'a, M: Scatter + Load>(
+	points: &[Point],
+	props: &Properties,
+	region: &mut Region,
+) -> Result<Vec<AllPrimitives<'a, M>>, LoadErr> {
+	let radius = props.float("radius").unwrap_or(0.01);
+	let albedo = props.float("albedo").unwrap_or(0.5);
+	let mat: region::RegionRes<M> = props
+		.scatter("material")
+		.unwrap_or_else(|| props.default_scatter());
+
+	// points of the same colour share a material
+	let mut colours: HashMap<[u8; 3], RegionRes<M>> = HashMap::new();
+	let mut primitives = Vec::with_capacity(points.len());
+	for &(point, colour) in points {
+		let mat = match colour {
+			Some(colour) => match colours.get(&colour) {
+				Some(mat) => mat.clone(),
+				None => {
+					let mat = colour_material::<M>(colour, albedo, region)?;
+					colours.insert(colour, mat.clone());
+					mat
+				}
+			},
+			None => mat.clone(),
+		};
+		primitives.push(AllPrimitives::Sphere(Sphere::new(point, radius, unsafe {
+			&*(&*mat as *const _)
+		})));
+	}
+	Ok(primitives)
+}
+
+// creates a lambertian material with a solid texture, assumes that M uses
+// the loader's texture type like the default material does
+fn colour_material<M: Scatter + Load>(
+	colour: [u8; 3],
+	albedo: Float,
+	region: &mut Region,
+) -> Result<RegionRes<M>, LoadErr> {
+	use parser::{Object, ObjectKind, ObjectValue};
+
+	let mut lookup = Lookup::new();
+	let texture = TextureType::SolidColour(SolidColour::new(Vec3::from_rgb8(
+		colour[0], colour[1], colour[2],
+	)));
+	lookup.texture_insert("__POINT_TEX", region.alloc(texture).shared());
+
+	let obj = Object {
+		kind: ObjectKind::Material,
+		name: None,
+		values: [
+			("type", ObjectValue::Text("lambertian")),
+			("texture", ObjectValue::Text("__POINT_TEX")),
+			("albedo", ObjectValue::Num1(albedo)),
+		]
+		.into(),
+	};
+	let mat = M::load(Properties::new(&lookup, &obj), region)?.1;
+	Ok(region.alloc(mat).shared())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use implementations::rt_core::Ray;
+
+	#[test]
+	fn point_cloud() {
+		let mut region = Region::new();
+		let mut lookup = Lookup::new();
+		let data = parser::from_str("").unwrap();
+		let textures = load_textures::<AllTextures>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, textures, |n, t| lookup.texture_insert(n, t));
+		let materials =
+			load_materials::<AllMaterials<AllTextures>>(&data, &lookup, &mut region).unwrap();
+		region_insert_with_lookup(&mut region, materials, |n, t| lookup.scatter_insert(n, t));
+
+		let points = parse_points(
+			"# x y z r g b
+0 0 0 255 0 0
+1.0, 2.0, 3.0, 0, 255, 0
+-1 0.5 2
+0 4 0 255 0 0",
+		)
+		.unwrap();
+		assert_eq!(points.len(), 4);
+		assert_eq!(points[2], (Vec3::new(-1.0, 0.5, 2.0), None));
+
+		let props = Properties::new(&lookup, &Default::default());
+		let primitives =
+			points_to_primitives::<AllMaterials<AllTextures>>(&points, &props, &mut region)
+				.unwrap();
+		assert_eq!(primitives.len(), points.len());
+
+		let ray = Ray::new(Vec3::new(1.0, 2.0, -5.0), Vec3::z(), 0.0);
+		let hit = primitives[1].get_int(&ray).unwrap();
+		assert!((hit.hit.point - Vec3::new(1.0, 2.0, 3.0)).mag() <= 0.011);
+		let albedo = primitives[1].material().unwrap().eval_over_scattering_pdf(
+			&hit.hit,
+			Vec3::z(),
+			-Vec3::z(),
+		);
+		assert!(albedo.x == 0.0 && albedo.y > 0.0);
+
+		assert!(parse_points("0 0").is_err());
+	}
+}
@@ -1,5 +1,11 @@
 use crate::Properties;
 use crate::*;
+use implementations::aabox::AABox;
+use implementations::bilinear_patch::BilinearPatch;
+use implementations::cylinder::Cylinder;
+use implementations::disk::Disk;
+use implementations::moving_sphere::MovingSphere;
+use implementations::quad::Quad;
 use implementations::sphere::Sphere;
 use implementations::*;
 
@@ -20,10 +26,157 @@ impl<M: Scatter> Load for Sphere<'_, M> {
 			}
 		};
 
-		Ok((
-			None,
-			Self::new(centre, radius, unsafe { &*(&*mat as *const _) }),
-		))
+		let mut sphere = Self::new(centre, radius, unsafe { &*(&*mat as *const _) });
+		sphere.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		sphere.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, sphere))
+	}
+}
+
+impl<M: Scatter> Load for MovingSphere<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let radius = props.float("radius").unwrap_or(1.0);
+		let (centre0, centre1) = match (props.vec3("centre0"), props.vec3("centre1")) {
+			(Some(c0), Some(c1)) => (c0, c1),
+			_ => {
+				return Err(LoadErr::MissingRequired(
+					"expected centre0 and centre1 on moving sphere, found nothing".to_string(),
+				))
+			}
+		};
+		let time0 = props.float("time0").unwrap_or(0.0);
+		let time1 = props.float("time1").unwrap_or(1.0);
+
+		let mut sphere = Self::new(centre0, centre1, time0, time1, radius, unsafe {
+			&*(&*mat as *const _)
+		});
+		sphere.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		sphere.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, sphere))
+	}
+}
+
+impl<M: Scatter> Load for Cylinder<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let base = match props.vec3("base") {
+			Some(b) => b,
+			None => {
+				return Err(LoadErr::MissingRequired(
+					"expected base on cylinder, found nothing".to_string(),
+				))
+			}
+		};
+		let axis = props.vec3("axis").unwrap_or_else(Vec3::y);
+		let radius = props.float("radius").unwrap_or(1.0);
+		let height = props.float("height").unwrap_or(1.0);
+
+		let mut cylinder = Self::new(base, axis, radius, height, unsafe { &*(&*mat as *const _) });
+		cylinder.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		cylinder.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, cylinder))
+	}
+}
+
+impl<M: Scatter> Load for Disk<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let centre = match props.vec3("centre") {
+			Some(c) => c,
+			None => {
+				return Err(LoadErr::MissingRequired(
+					"expected centre on disk, found nothing".to_string(),
+				))
+			}
+		};
+		let normal = props.vec3("normal").unwrap_or_else(Vec3::y);
+		let radius = props.float("radius").unwrap_or(1.0);
+
+		let mut disk = Self::new(centre, normal, radius, unsafe { &*(&*mat as *const _) })
+			.with_inner_radius(props.float("inner_radius").unwrap_or(0.0));
+		disk.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		disk.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, disk))
+	}
+}
+
+impl<M: Scatter> Load for BilinearPatch<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let mut points = [Vec3::zero(); 4];
+		for (point, name) in points.iter_mut().zip(["p00", "p10", "p01", "p11"]) {
+			*point = match props.vec3(name) {
+				Some(p) => p,
+				None => {
+					return Err(LoadErr::MissingRequired(format!(
+						"expected {name} on bilinear patch, found nothing"
+					)))
+				}
+			};
+		}
+
+		let mut patch = Self::new(points, unsafe { &*(&*mat as *const _) });
+		patch.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		patch.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, patch))
+	}
+}
+
+impl<M: Scatter> Load for Quad<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let (corner, u, v) = match (props.vec3("corner"), props.vec3("u"), props.vec3("v")) {
+			(Some(corner), Some(u), Some(v)) => (corner, u, v),
+			_ => {
+				return Err(LoadErr::MissingRequired(
+					"expected corner, u and v on quad, found nothing".to_string(),
+				))
+			}
+		};
+
+		let mut quad = Self::new(corner, u, v, unsafe { &*(&*mat as *const _) });
+		quad.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		quad.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, quad))
+	}
+}
+
+impl<M: Scatter> Load for AABox<'_, M> {
+	fn load(props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
+		let mat: region::RegionRes<M> = props
+			.scatter("material")
+			.unwrap_or_else(|| props.default_scatter());
+		let (min, max) = match (props.vec3("min"), props.vec3("max")) {
+			(Some(min), Some(max)) => (min, max),
+			_ => {
+				return Err(LoadErr::MissingRequired(
+					"expected min and max on box, found nothing".to_string(),
+				))
+			}
+		};
+
+		let mut aabox = Self::new(min, max, unsafe { &*(&*mat as *const _) });
+		aabox.object_id = props.float("object_id").unwrap_or(0.0) as u32;
+		aabox.layers = props.float("layers").map_or(u32::MAX, |l| l as u32);
+
+		Ok((None, aabox))
 	}
 }
 
@@ -39,6 +192,30 @@ impl<M: Scatter> Load for AllPrimitives<'_, M> {
 				let x = Sphere::load(props, region)?;
 				(x.0, Self::Sphere(x.1))
 			}
+			"moving_sphere" => {
+				let x = MovingSphere::load(props, region)?;
+				(x.0, Self::MovingSphere(x.1))
+			}
+			"bilinear_patch" => {
+				let x = BilinearPatch::load(props, region)?;
+				(x.0, Self::BilinearPatch(x.1))
+			}
+			"cylinder" => {
+				let x = Cylinder::load(props, region)?;
+				(x.0, Self::Cylinder(x.1))
+			}
+			"disk" => {
+				let x = Disk::load(props, region)?;
+				(x.0, Self::Disk(x.1))
+			}
+			"quad" => {
+				let x = Quad::load(props, region)?;
+				(x.0, Self::Quad(x.1))
+			}
+			"box" => {
+				let x = AABox::load(props, region)?;
+				(x.0, Self::AABox(x.1))
+			}
 			"triangle" => todo!(),
 			o => {
 				return Err(LoadErr::MissingRequired(format!(
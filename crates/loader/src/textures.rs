@@ -55,14 +55,27 @@ impl Load for ImageTexture {
 			Some(f) => f,
 			None => return Err(LoadErr::MissingRequired("filename".to_string())),
 		};
-		Ok((name, Self::new(&filename)))
+		let rotation = props.float("rotation").unwrap_or(0.0);
+		Ok((name, Self::new(&filename).with_rotation(rotation)))
 	}
 }
 
 impl Load for Perlin {
 	fn load(mut props: Properties, _: &mut Region) -> Result<(Option<String>, Self), LoadErr> {
 		let name = props.name();
-		Ok((name, Self::new()))
+		let perlin = match props.float("seed") {
+			Some(seed) => Self::seeded(seed as u64),
+			None => Self::new(),
+		};
+		let octaves = props.float("turbulence").unwrap_or(0.0) as u32;
+		let perlin = perlin
+			.with_scale(props.float("scale").unwrap_or(1.0))
+			.with_turbulence(octaves, props.text("marble") == Some("true"))
+			.with_colours(
+				props.vec3("primary").unwrap_or(Vec3::one()),
+				props.vec3("secondary").unwrap_or(Vec3::zero()),
+			);
+		Ok((name, perlin))
 	}
 }
 
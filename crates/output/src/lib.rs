@@ -1,11 +1,16 @@
+use clap::ValueEnum;
 use fern::colors::{Color, ColoredLevelConfig};
-use rt_core::Float;
+use rt_core::{Float, Vec3};
 
+use std::path::{Path, PathBuf};
 use std::process;
 use std::time::Instant;
 
 use std::time::Duration;
 
+mod lut;
+pub use lut::Lut;
+
 pub fn create_logger() {
 	let colors = ColoredLevelConfig::new()
 		.error(Color::Red)
@@ -62,6 +67,35 @@ pub fn get_readable_duration(duration: Duration) -> String {
 	days_string + &hours_string + &minutes_string + &seconds_string
 }
 
+// samples needed before the time remaining is estimated
+const MIN_ETA_SAMPLES: u64 = 3;
+
+pub fn get_remaining_duration(elapsed: Duration, completed: u64, total: u64) -> Option<Duration> {
+	if completed < MIN_ETA_SAMPLES || completed > total {
+		return None;
+	}
+	Duration::try_from_secs_f64(
+		elapsed.as_secs_f64() * (total - completed) as f64 / completed as f64,
+	)
+	.ok()
+}
+
+pub fn get_eta_output(elapsed: Duration, completed: u64, total: u64) -> String {
+	let remaining = get_remaining_duration(elapsed, completed, total);
+	// estimates too long to add to the clock, e.g. from a stalled start, aren't shown
+	let finish = remaining
+		.and_then(|remaining| chrono::Duration::from_std(remaining).ok())
+		.and_then(|remaining| chrono::Local::now().checked_add_signed(remaining));
+	match (remaining, finish) {
+		(Some(remaining), Some(finish)) => format!(
+			"ETA: {} (finishes {})",
+			get_readable_duration(remaining),
+			finish.format("%H:%M:%S")
+		),
+		_ => "ETA: --".to_string(),
+	}
+}
+
 pub fn rgba_to_rgb(data: &[Float]) -> Vec<Float> {
 	data.iter()
 		.enumerate()
@@ -70,48 +104,412 @@ pub fn rgba_to_rgb(data: &[Float]) -> Vec<Float> {
 		.collect::<Vec<_>>()
 }
 
+// whether the colour channels of rgba images are saved multiplied by alpha
+#[derive(Debug, ValueEnum, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+	#[default]
+	Straight,
+	Premultiplied,
+}
+
+// converts straight rgba data into the given alpha convention
+pub fn apply_alpha_mode(rgba: &mut [Float], alpha_mode: AlphaMode) {
+	if alpha_mode == AlphaMode::Straight {
+		return;
+	}
+	for pixel in rgba.chunks_mut(4) {
+		let alpha = pixel[3];
+		pixel[..3].iter_mut().for_each(|v| *v *= alpha);
+	}
+}
+
+// straight rgba layer of the colour with the shadow matte as its alpha, the matte is
+// the same in every channel of the rgb image
+pub fn shadow_matte_rgba(matte: &[Float], colour: Vec3) -> Vec<Float> {
+	matte
+		.chunks(3)
+		.flat_map(|pixel| [colour.x, colour.y, colour.z, pixel[0].clamp(0.0, 1.0)])
+		.collect()
+}
+
+// bilinearly resamples an rgb image, used to display a preview at the output resolution
+pub fn upscale(data: &[Float], from: (u32, u32), to: (u32, u32)) -> Vec<Float> {
+	let (from_width, from_height) = (from.0 as usize, from.1 as usize);
+	let mut output = Vec::with_capacity(3 * (to.0 * to.1) as usize);
+
+	// pixel centres are aligned so the image isn't shifted
+	let source = |i: u32, from: usize, to: u32| -> (usize, usize, Float) {
+		let x = ((i as Float + 0.5) * from as Float / to as Float - 0.5)
+			.clamp(0.0, (from - 1) as Float);
+		let x0 = x.floor() as usize;
+		(x0, (x0 + 1).min(from - 1), x - x0 as Float)
+	};
+
+	for y in 0..to.1 {
+		let (y0, y1, ty) = source(y, from_height, to.1);
+		for x in 0..to.0 {
+			let (x0, x1, tx) = source(x, from_width, to.0);
+			for c in 0..3 {
+				let pixel = |x: usize, y: usize| data[3 * (y * from_width + x) + c];
+				let top = pixel(x0, y0) * (1.0 - tx) + pixel(x1, y0) * tx;
+				let bottom = pixel(x0, y1) * (1.0 - tx) + pixel(x1, y1) * tx;
+				output.push(top * (1.0 - ty) + bottom * ty);
+			}
+		}
+	}
+	output
+}
+
+// averages factor x factor blocks of an rgb image into each pixel, used to bring a
+// supersampled render back to the output resolution
+pub fn downsample(data: &[Float], from: (u32, u32), factor: u32) -> Vec<Float> {
+	let (width, height) = (from.0 / factor, from.1 / factor);
+	let mut output = Vec::with_capacity(3 * (width * height) as usize);
+	let weight = 1.0 / (factor * factor) as Float;
+
+	for y in 0..height {
+		for x in 0..width {
+			let mut pixel = [0.0; 3];
+			for (sx, sy) in (0..factor).flat_map(|i| (0..factor).map(move |j| (i, j))) {
+				let i = 3 * ((y * factor + sy) * from.0 + x * factor + sx) as usize;
+				for (c, v) in pixel.iter_mut().enumerate() {
+					*v += data[i + c];
+				}
+			}
+			output.extend(pixel.map(|v| v * weight));
+		}
+	}
+	output
+}
+
+// scales an image by 2^stops, applied before the tone curve
+pub fn expose(image: &mut [Float], stops: Float) {
+	let scale = (2.0 as Float).powf(stops);
+	image.iter_mut().for_each(|v| *v *= scale);
+}
+
+// curve compressing linear values above one into range before the image is quantised
+#[derive(Debug, ValueEnum, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ToneMap {
+	// values above one are clipped
+	#[default]
+	None,
+	// x / (1 + x)
+	Reinhard,
+	// Narkowicz's fit of the ACES filmic curve
+	AcesFilmic,
+}
+
+impl ToneMap {
+	pub fn map(&self, x: Float) -> Float {
+		match self {
+			ToneMap::None => x,
+			ToneMap::Reinhard => x.max(0.0) / (1.0 + x.max(0.0)),
+			ToneMap::AcesFilmic => {
+				let x = x.max(0.0);
+				(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+			}
+		}
+	}
+
+	// maps every channel of an rgb image
+	pub fn apply(&self, image: &mut [Float]) {
+		if *self != ToneMap::None {
+			image.iter_mut().for_each(|v| *v = self.map(*v));
+		}
+	}
+}
+
+// brings each pixel into [0, 1] by desaturating it towards the grey of the same
+// luminance just far enough, instead of clipping channels on their own which shifts
+// the hue. Pixels brighter than white become white and darker than black black.
+pub fn compress_gamut(image: &mut [Float]) {
+	for pixel in image.chunks_mut(3) {
+		let colour = Vec3::new(pixel[0], pixel[1], pixel[2]);
+		let (min, max) = (colour.component_min(), colour.component_max());
+		if min >= 0.0 && max <= 1.0 {
+			continue;
+		}
+		let luminance = 0.2126 * colour.x + 0.7152 * colour.y + 0.0722 * colour.z;
+		if luminance <= 0.0 || luminance >= 1.0 {
+			pixel.fill(luminance.clamp(0.0, 1.0));
+			continue;
+		}
+		// fraction of the distance from grey kept
+		let mut t: Float = 1.0;
+		if min < 0.0 {
+			t = t.min(luminance / (luminance - min));
+		}
+		if max > 1.0 {
+			t = t.min((1.0 - luminance) / (max - luminance));
+		}
+		let compressed = (luminance * Vec3::one()).lerp(colour, t).clamp(0.0, 1.0);
+		pixel.copy_from_slice(&[compressed.x, compressed.y, compressed.z]);
+	}
+}
+
+// inserts the suffix before the file's extension, e.g. "image.png" becomes "image_even.png"
+pub fn suffixed_path(filename: &str, suffix: &str) -> String {
+	match filename.rsplit_once('.') {
+		Some((stem, extension)) => format!("{stem}_{suffix}.{extension}"),
+		None => format!("{filename}_{suffix}"),
+	}
+}
+
+// e.g. "image_ev-2.png" for -2 stops
+pub fn bracket_path(filename: &str, stops: Float) -> String {
+	suffixed_path(filename, &format!("ev{stops:+}"))
+}
+
+// how the depth pass is saved
+#[derive(Debug, ValueEnum, Copy, Clone, PartialEq, Eq)]
+pub enum DepthOutput {
+	// distances as they are in an exr, misses keep the far depth
+	Raw,
+	// scaled so the nearest hit is black and the furthest white, misses are white
+	Normalised,
+}
+
+impl DepthOutput {
+	// grey rgb image of the per pixel depths
+	pub fn image(&self, depth: &[Float]) -> Vec<Float> {
+		let values = match self {
+			DepthOutput::Raw => depth.to_vec(),
+			DepthOutput::Normalised => {
+				let (min, max) = depth
+					.iter()
+					.filter(|d| d.is_finite())
+					.fold((Float::MAX, Float::MIN), |(min, max), &d| {
+						(min.min(d), max.max(d))
+					});
+				let range = (max - min).max(Float::EPSILON);
+				depth
+					.iter()
+					.map(|&d| {
+						if d.is_finite() {
+							((d - min) / range).clamp(0.0, 1.0)
+						} else {
+							1.0
+						}
+					})
+					.collect()
+			}
+		};
+		values.into_iter().flat_map(|d| [d; 3]).collect()
+	}
+
+	// the image's filename with _depth before the extension, raw depth is always an exr
+	pub fn path(&self, filename: &str) -> String {
+		let path = suffixed_path(filename, "depth");
+		match self {
+			DepthOutput::Raw => Path::new(&path)
+				.with_extension("exr")
+				.to_string_lossy()
+				.into_owned(),
+			DepthOutput::Normalised => path,
+		}
+	}
+}
+
+// bits per channel of saved png, tiff and ppm images, other formats are always 8 bit
+#[derive(Debug, ValueEnum, Copy, Clone, Default, PartialEq, Eq)]
+pub enum BitDepth {
+	#[default]
+	#[value(name = "8")]
+	Eight,
+	#[value(name = "16")]
+	Sixteen,
+}
+
+// 8 bit value of a linear channel, values outside [0, 1] are clipped
+pub fn quantise(value: Float, gamma: Float) -> u8 {
+	(value.powf(1.0 / gamma) * 255.999) as u8
+}
+
+// 16 bit value of a linear channel, values outside [0, 1] are clipped
+pub fn quantise_16(value: Float, gamma: Float) -> u16 {
+	(value.powf(1.0 / gamma) * 65535.999) as u16
+}
+
+// whether images saved with the filename's extension keep linear values, the tone
+// curve, gamma and gamut compression are skipped for them
+pub fn is_linear_format(filename: &str) -> bool {
+	Path::new(filename)
+		.extension()
+		.is_some_and(|extension| extension == "exr")
+}
+
+// the linear image as 32 bit float rgb, values above one are kept
 #[allow(clippy::unnecessary_cast)]
+pub fn save_exr(
+	filename: &str,
+	width: u32,
+	height: u32,
+	image: &[Float],
+) -> image::ImageResult<()> {
+	let data: Vec<f32> = image.iter().map(|&val| val as f32).collect();
+	let image_buf: image::Rgb32FImage = image::ImageBuffer::from_raw(width, height, data)
+		.expect("image data doesn't match its size");
+	image_buf.save(filename)
+}
+
 pub fn save_data_to_image(
 	filename: String,
 	width: u32,
 	height: u32,
 	image: Vec<Float>,
 	gamma: Float,
+	bit_depth: BitDepth,
 ) {
-	let split = filename.split('.').collect::<Vec<_>>();
-	if split.len() != 2 {
-		println!("Invalid filename: {filename}");
-		process::exit(0);
-	}
-
-	let extension = split[1];
+	let extension = match Path::new(&filename).extension() {
+		Some(extension) => extension.to_string_lossy(),
+		None => {
+			println!("Invalid filename: {filename}");
+			process::exit(0);
+		}
+	};
 
-	match extension {
+	match extension.as_ref() {
 		// TODO HDR
-		"png" | "jpg" | "jpeg" | "tiff" | "ppm" | "bmp" => {
-			let data: Vec<u8> = image
+		"png" | "tiff" | "ppm" if bit_depth == BitDepth::Sixteen => {
+			let data: Vec<u16> = image
 				.into_iter()
-				.map(|val| (val.powf(1.0 / gamma) * 255.999) as u8)
+				.map(|val| quantise_16(val, gamma))
 				.collect();
 
+			let image_buf: image::ImageBuffer<image::Rgb<u16>, _> =
+				image::ImageBuffer::from_raw(width, height, data).unwrap();
+			image_buf.save(&filename).unwrap();
+		}
+		"png" | "jpg" | "jpeg" | "tiff" | "ppm" | "bmp" => {
+			if bit_depth == BitDepth::Sixteen {
+				log::warn!(".{extension} images can't be 16 bit, saving 8 bit instead");
+			}
+			let data: Vec<u8> = image.into_iter().map(|val| quantise(val, gamma)).collect();
+
 			image::save_buffer(&filename, &data, width, height, image::ColorType::Rgb8).unwrap();
 		}
-		"exr" => {
-			// gamma is ignored because of exr
-			let data: Vec<f32> = image.into_iter().map(|val| (val as f32)).collect();
+		"exr" => save_exr(&filename, width, height, &image).unwrap(),
+		_ => {
+			log::error!("Unable to save file: (unknown filetype .{extension})");
+			return;
+		}
+	};
+	log::info!("Image {filename} saved");
+}
+
+// saves straight rgba data in the alpha convention, the gamma is only applied to the
+// colour channels. Formats without an alpha channel get the alpha as a grey image.
+#[allow(clippy::unnecessary_cast)]
+pub fn save_rgba_image(
+	filename: String,
+	width: u32,
+	height: u32,
+	mut image: Vec<Float>,
+	gamma: Float,
+	bit_depth: BitDepth,
+	alpha_mode: AlphaMode,
+) {
+	let extension = match Path::new(&filename).extension() {
+		Some(extension) => extension.to_string_lossy().into_owned(),
+		None => {
+			println!("Invalid filename: {filename}");
+			process::exit(0);
+		}
+	};
+	apply_alpha_mode(&mut image, alpha_mode);
+	let gamma = |i: usize| if i % 4 == 3 { 1.0 } else { gamma };
+
+	match extension.as_ref() {
+		"png" | "tiff" if bit_depth == BitDepth::Sixteen => {
+			let data: Vec<u16> = image
+				.iter()
+				.enumerate()
+				.map(|(i, &val)| quantise_16(val, gamma(i)))
+				.collect();
 
-			let image_buf: image::Rgb32FImage =
+			let image_buf: image::ImageBuffer<image::Rgba<u16>, _> =
 				image::ImageBuffer::from_raw(width, height, data).unwrap();
 			image_buf.save(&filename).unwrap();
 		}
+		"png" | "tiff" => {
+			let data: Vec<u8> = image
+				.iter()
+				.enumerate()
+				.map(|(i, &val)| quantise(val, gamma(i)))
+				.collect();
+
+			image::save_buffer(&filename, &data, width, height, image::ColorType::Rgba8).unwrap();
+		}
+		"exr" => {
+			let data: Vec<f32> = image.iter().map(|&val| val as f32).collect();
+			let image_buf: image::Rgba32FImage = image::ImageBuffer::from_raw(width, height, data)
+				.expect("image data doesn't match its size");
+			image_buf.save(&filename).unwrap();
+		}
 		_ => {
-			log::error!("Unable to save file: (unknown filetype .{extension})");
+			log::warn!(".{extension} images have no alpha channel, saving the alpha as grey");
+			let alpha = image.chunks(4).flat_map(|pixel| [pixel[3]; 3]).collect();
+			save_data_to_image(filename, width, height, alpha, 1.0, bit_depth);
 			return;
 		}
 	};
 	log::info!("Image {filename} saved");
 }
 
+// an image's channels as floats and its size, in [0, 1] as stored for 8 and 16 bit
+// formats and linear for exr
+#[allow(clippy::unnecessary_cast)]
+pub fn load_image(filename: &str) -> image::ImageResult<(Vec<Float>, (u32, u32))> {
+	let image = image::open(filename)?.to_rgb32f();
+	let size = image.dimensions();
+	Ok((
+		image.into_raw().into_iter().map(|v| v as Float).collect(),
+		size,
+	))
+}
+
+// per channel comparison of two images of the same size
+pub struct ImageDifference {
+	// absolute difference of each channel times the amplification
+	pub image: Vec<Float>,
+	// mean squared error over every channel
+	pub mse: Float,
+	pub max_error: Float,
+}
+
+impl ImageDifference {
+	pub fn new(a: &[Float], b: &[Float], amplify: Float) -> Self {
+		assert_eq!(a.len(), b.len(), "compared images differ in size");
+		let errors: Vec<Float> = a.iter().zip(b).map(|(a, b)| (a - b).abs()).collect();
+		let mse = errors.iter().map(|e| e * e).sum::<Float>() / errors.len().max(1) as Float;
+		let max_error = errors.iter().fold(0.0, |max: Float, &e| max.max(e));
+		ImageDifference {
+			image: errors.into_iter().map(|e| amplify * e).collect(),
+			mse,
+			max_error,
+		}
+	}
+}
+
+// file in the directory named after the scene and the time, a counter is added if
+// a render with the same name was already saved that second
+pub fn auto_filename(
+	outdir: &Path,
+	scene: &str,
+	extension: &str,
+	time: chrono::DateTime<chrono::Local>,
+) -> PathBuf {
+	let stem = format!("{scene}_{}", time.format("%Y%m%d_%H%M%S"));
+	let mut path = outdir.join(format!("{stem}.{extension}"));
+	let mut i = 1;
+	while path.exists() {
+		path = outdir.join(format!("{stem}_{i}.{extension}"));
+		i += 1;
+	}
+	path
+}
+
 pub fn print_final_statistics(start: Instant, ray_count: u64, samples: u64) {
 	let end = Instant::now();
 	let duration = end.checked_duration_since(start).unwrap();
@@ -134,3 +532,302 @@ pub fn print_render_start(width: u64, height: u64, gamma: f64, samples: Option<u
 	}
 	Instant::now()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn remaining_duration() {
+		let elapsed = Duration::from_secs(30);
+
+		assert_eq!(get_remaining_duration(elapsed, 1, 100), None);
+		assert_eq!(
+			get_remaining_duration(elapsed, 25, 100),
+			Some(Duration::from_secs(90))
+		);
+		assert_eq!(
+			get_remaining_duration(elapsed, 100, 100),
+			Some(Duration::ZERO)
+		);
+		assert_eq!(get_eta_output(elapsed, 1, 100), "ETA: --");
+		// a near zero rate gives an estimate beyond what the clock can hold
+		let slow = Duration::from_secs(1_000_000_000);
+		assert_eq!(get_eta_output(slow, 3, 1_000_000_000), "ETA: --");
+		assert_eq!(get_remaining_duration(slow, 3, u64::MAX), None);
+	}
+
+	#[test]
+	fn alpha_modes() {
+		let pixel = [0.8, 0.4, 0.2, 0.5];
+
+		let mut straight = pixel;
+		apply_alpha_mode(&mut straight, AlphaMode::Straight);
+		assert_eq!(straight, pixel);
+
+		let mut premultiplied = pixel;
+		apply_alpha_mode(&mut premultiplied, AlphaMode::Premultiplied);
+		assert_eq!(premultiplied, [0.4, 0.2, 0.1, 0.5]);
+	}
+
+	#[test]
+	fn rgba_output() {
+		// a half transparent orange shadow over one pixel and none over the other
+		let matte = [0.5, 0.5, 0.5, 0.0, 0.0, 0.0];
+		let rgba = shadow_matte_rgba(&matte, Vec3::new(0.8, 0.4, 0.2));
+		assert_eq!(rgba, [0.8, 0.4, 0.2, 0.5, 0.8, 0.4, 0.2, 0.0]);
+
+		let saved = |alpha_mode| {
+			let path =
+				std::env::temp_dir().join(format!("rt_rgba_{alpha_mode:?}_{}.png", process::id()));
+			let filename = path.to_string_lossy().into_owned();
+			save_rgba_image(
+				filename,
+				2,
+				1,
+				rgba.clone(),
+				1.0,
+				BitDepth::Eight,
+				alpha_mode,
+			);
+			let saved = image::open(&path).unwrap().to_rgba8().into_raw();
+			std::fs::remove_file(&path).unwrap();
+			saved
+		};
+		assert_eq!(
+			saved(AlphaMode::Straight),
+			[204, 102, 51, 127, 204, 102, 51, 0]
+		);
+		assert_eq!(
+			saved(AlphaMode::Premultiplied),
+			[102, 51, 25, 127, 0, 0, 0, 0]
+		);
+	}
+
+	#[test]
+	fn tone_curves() {
+		let image: Vec<Float> = (0..200).map(|i| 0.05 * i as Float).collect();
+		let mut aces = image.clone();
+		ToneMap::AcesFilmic.apply(&mut aces);
+		let quantised: Vec<u8> = aces.iter().map(|&v| quantise(v, 2.2)).collect();
+		assert!(aces.windows(2).all(|w| w[0] <= w[1]));
+		assert!(quantised.windows(2).all(|w| w[0] <= w[1]));
+		// highlights roll off towards white instead of all clipping at one
+		assert!(aces[40] < 1.0 && aces[199] > aces[40]);
+		assert_eq!(*quantised.last().unwrap(), 255);
+		assert!((ToneMap::AcesFilmic.map(0.18) - 0.2669).abs() < 0.001);
+
+		let mut reinhard = image.clone();
+		ToneMap::Reinhard.apply(&mut reinhard);
+		assert!(reinhard.windows(2).all(|w| w[0] < w[1]));
+		assert!((reinhard[20] - 0.5).abs() < 0.00001);
+
+		let mut none = image.clone();
+		ToneMap::None.apply(&mut none);
+		assert_eq!(none, image);
+	}
+
+	#[test]
+	fn gamut_compression() {
+		let hue =
+			|c: &[Float]| ((3.0 as Float).sqrt() * (c[1] - c[2])).atan2(2.0 * c[0] - c[1] - c[2]);
+		let luminance = |c: &[Float]| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+		for colour in [[1.6, 0.7, 0.1], [0.9, 0.3, -0.2], [-0.1, 1.3, 0.4]] {
+			let mut compressed = colour;
+			compress_gamut(&mut compressed);
+			let clamped = colour.map(|v| v.clamp(0.0, 1.0));
+			assert!(compressed.iter().all(|v| (0.0..=1.0).contains(v)));
+
+			let (original, kept, clipped) = (hue(&colour), hue(&compressed), hue(&clamped));
+			assert!(
+				(kept - original).abs() < 0.0001,
+				"{colour:?} {compressed:?}"
+			);
+			assert!((clipped - original).abs() > 0.01);
+			assert!((luminance(&compressed) - luminance(&colour)).abs() < 0.0001);
+		}
+
+		// colours already in gamut are left alone
+		let mut image = [0.2, 0.5, 0.9, 0.0, 0.0, 0.0, 3.0, 2.0, 1.5];
+		compress_gamut(&mut image);
+		assert_eq!(image[..6], [0.2, 0.5, 0.9, 0.0, 0.0, 0.0]);
+		assert_eq!(image[6..], [1.0, 1.0, 1.0]);
+	}
+
+	#[test]
+	fn bilinear_upscale() {
+		// 2x1 black and white image
+		let image = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+		let upscaled = upscale(&image, (2, 1), (4, 2));
+		assert_eq!(upscaled.len(), 4 * 2 * 3);
+		let row: Vec<Float> = upscaled[..12].iter().step_by(3).copied().collect();
+		assert_eq!(row, [0.0, 0.25, 0.75, 1.0]);
+		assert_eq!(upscaled[..12], upscaled[12..]);
+	}
+
+	#[test]
+	fn box_downsample() {
+		// 4x2 image with a white pixel in each 2x2 block
+		let mut image = vec![0.0; 4 * 2 * 3];
+		image[..3].copy_from_slice(&[1.0; 3]);
+		image[3 * 7..].copy_from_slice(&[1.0; 3]);
+		let downsampled = downsample(&image, (4, 2), 2);
+		assert_eq!(downsampled, [0.25; 6]);
+
+		// a factor of 1 leaves the image as it is
+		assert_eq!(downsample(&image, (4, 2), 1), image);
+	}
+
+	#[test]
+	fn auto_filenames() {
+		use chrono::TimeZone;
+
+		let outdir = std::env::temp_dir().join(format!("rt_auto_name_{}", process::id()));
+		std::fs::create_dir_all(&outdir).unwrap();
+		let time = chrono::Local
+			.with_ymd_and_hms(2024, 1, 1, 12, 0, 0)
+			.unwrap();
+
+		let first = auto_filename(&outdir, "cornell", "png", time);
+		assert_eq!(first, outdir.join("cornell_20240101_120000.png"));
+		std::fs::write(&first, []).unwrap();
+
+		// the same second gets a new name
+		let second = auto_filename(&outdir, "cornell", "png", time);
+		assert_eq!(second, outdir.join("cornell_20240101_120000_1.png"));
+		let exr = auto_filename(&outdir, "cornell", "exr", time);
+		assert_eq!(exr, outdir.join("cornell_20240101_120000.exr"));
+		std::fs::remove_dir_all(&outdir).unwrap();
+	}
+
+	#[test]
+	fn exposure_brackets() {
+		let image = [0.01, 0.1, 0.2, 0.05, 0.5, 0.125];
+		let brackets: Vec<Vec<Float>> = [-2.0, 0.0, 2.0]
+			.iter()
+			.map(|&stops| {
+				let mut data = image.to_vec();
+				expose(&mut data, stops);
+				data
+			})
+			.collect();
+		assert_eq!(brackets[1], image);
+		for (i, v) in image.iter().enumerate() {
+			assert!((brackets[0][i] - v / 4.0).abs() < 0.00001);
+			assert!((brackets[2][i] - v * 4.0).abs() < 0.00001);
+		}
+
+		assert_eq!(bracket_path("render.png", -2.0), "render_ev-2.png");
+		assert_eq!(bracket_path("render.png", 0.0), "render_ev+0.png");
+		assert_eq!(bracket_path("render", 1.5), "render_ev+1.5");
+		assert_eq!(suffixed_path("out/render.exr", "odd"), "out/render_odd.exr");
+	}
+
+	#[test]
+	fn depth_output() {
+		let depth = [2.0, 4.0, Float::INFINITY, 3.0];
+		assert_eq!(
+			DepthOutput::Normalised.image(&depth),
+			[0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5, 0.5, 0.5]
+		);
+		assert_eq!(DepthOutput::Raw.image(&depth)[6], Float::INFINITY);
+		assert_eq!(
+			DepthOutput::Raw.path("out/image.png"),
+			"out/image_depth.exr"
+		);
+		assert_eq!(
+			DepthOutput::Normalised.path("out/image.png"),
+			"out/image_depth.png"
+		);
+	}
+
+	#[test]
+	fn linear_exr() {
+		let (width, height) = (7, 5);
+		let image: Vec<Float> = (0..width * height * 3)
+			.map(|i| 0.001 * (i * i) as Float + if i % 3 == 0 { 100.0 } else { 0.0 })
+			.collect();
+		let path = std::env::temp_dir().join(format!("rt_linear_{}.exr", process::id()));
+		let filename = path.to_string_lossy().to_string();
+		assert!(is_linear_format(&filename) && !is_linear_format("render.png"));
+
+		save_exr(&filename, width as u32, height as u32, &image).unwrap();
+		let saved = image::open(&path).unwrap().to_rgb32f();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(saved.dimensions(), (width as u32, height as u32));
+		for (&saved, &expected) in saved.into_raw().iter().zip(&image) {
+			assert!(
+				(saved as Float - expected).abs() <= 0.00001 * expected.max(1.0),
+				"{saved} {expected}"
+			);
+		}
+	}
+
+	#[test]
+	fn sixteen_bit_png() {
+		// a smooth ramp across 1000 pixels
+		let (width, height) = (1000, 2);
+		let gradient: Vec<Float> = (0..width * height * 3)
+			.map(|i| (i / 3 % width) as Float / (width - 1) as Float)
+			.collect();
+		let max_error = |bit_depth, extension: &str| {
+			let path = std::env::temp_dir().join(format!(
+				"rt_gradient_{bit_depth:?}_{}.{extension}",
+				process::id()
+			));
+			let filename = path.to_string_lossy().to_string();
+			save_data_to_image(
+				filename,
+				width as u32,
+				height as u32,
+				gradient.clone(),
+				1.0,
+				bit_depth,
+			);
+			let saved = image::open(&path).unwrap().to_rgb16();
+			std::fs::remove_file(&path).unwrap();
+			saved
+				.into_raw()
+				.iter()
+				.zip(&gradient)
+				.map(|(&v, &expected)| (v as Float / 65535.0 - expected).abs())
+				.fold(0.0, Float::max)
+		};
+
+		let (eight, sixteen) = (
+			max_error(BitDepth::Eight, "png"),
+			max_error(BitDepth::Sixteen, "png"),
+		);
+		assert!(sixteen < 0.0001, "{sixteen}");
+		assert!(sixteen < 0.01 * eight, "{sixteen} {eight}");
+
+		// jpegs are saved with 8 bits instead
+		let jpeg = max_error(BitDepth::Sixteen, "jpg");
+		assert!(jpeg > 0.001 && jpeg < 0.05, "{jpeg}");
+	}
+
+	#[test]
+	fn image_difference() {
+		let image = [0.1, 0.5, 0.9, 0.0, 1.0, 0.25];
+		let same = ImageDifference::new(&image, &image, 10.0);
+		assert!(same.image.iter().all(|&v| v == 0.0));
+		assert_eq!((same.mse, same.max_error), (0.0, 0.0));
+
+		let other = [0.1, 0.25, 0.9, 0.5, 1.0, 0.25];
+		let difference = ImageDifference::new(&image, &other, 2.0);
+		assert_eq!(difference.image, [0.0, 0.5, 0.0, 1.0, 0.0, 0.0]);
+		assert!((difference.mse - (0.0625 + 0.25) / 6.0).abs() < 0.00001);
+		assert_eq!(difference.max_error, 0.5);
+
+		// a saved image compared with itself after loading it back
+		let path = std::env::temp_dir().join(format!("rt_diff_{}.png", process::id()));
+		let filename = path.to_string_lossy().into_owned();
+		save_data_to_image(filename.clone(), 2, 1, image.to_vec(), 1.0, BitDepth::Eight);
+		let (loaded, size) = load_image(&filename).unwrap();
+		assert_eq!(size, (2, 1));
+		let difference = ImageDifference::new(&loaded, &loaded, 1.0);
+		assert_eq!(difference.mse, 0.0);
+		assert!((ImageDifference::new(&loaded, &image, 1.0).max_error) < 1.0 / 255.0);
+		std::fs::remove_file(path).unwrap();
+	}
+}
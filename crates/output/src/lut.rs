@@ -0,0 +1,161 @@
+use rt_core::Float;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+// tone curve read from a 1D LUT, each channel maps through its own (input, output) pairs
+// with linear interpolation between them, inputs outside the table take the end values
+#[derive(Clone, Debug)]
+pub struct Lut {
+	channels: [Vec<(Float, Float)>; 3],
+}
+
+fn invalid(message: String) -> io::Error {
+	io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn parse_floats<'a>(values: impl Iterator<Item = &'a str>) -> io::Result<Vec<Float>> {
+	values
+		.filter(|v| !v.trim().is_empty())
+		.map(|v| {
+			v.trim()
+				.parse::<Float>()
+				.map_err(|_| invalid(format!("expected a number in LUT, found '{v}'")))
+		})
+		.collect()
+}
+
+impl Lut {
+	// .cube files are read as Resolve/Adobe 1D LUTs, anything else as csv rows of
+	// "input,output" or "input,r,g,b"
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let data = std::fs::read_to_string(&path)?;
+		match path.as_ref().extension() {
+			Some(extension) if extension == "cube" => Self::from_cube(&data),
+			_ => Self::from_csv(&data),
+		}
+	}
+
+	pub fn from_cube(data: &str) -> io::Result<Self> {
+		let (mut domain_min, mut domain_max) = ([0.0; 3], [1.0; 3]);
+		let mut outputs = Vec::new();
+		for line in data.lines().map(str::trim) {
+			let keyword = line.split_whitespace().next().unwrap_or_default();
+			match keyword {
+				"" | "TITLE" | "LUT_1D_SIZE" | "LUT_1D_INPUT_RANGE" => (),
+				_ if keyword.starts_with('#') => (),
+				"LUT_3D_SIZE" => return Err(invalid("3D LUTs aren't supported".to_string())),
+				"DOMAIN_MIN" | "DOMAIN_MAX" => {
+					let values = parse_floats(line.split_whitespace().skip(1))?;
+					let domain: [Float; 3] = values
+						.try_into()
+						.map_err(|_| invalid(format!("expected three values for {keyword}")))?;
+					if keyword == "DOMAIN_MIN" {
+						domain_min = domain;
+					} else {
+						domain_max = domain;
+					}
+				}
+				_ => {
+					let values = parse_floats(line.split_whitespace())?;
+					let rgb: [Float; 3] = values
+						.try_into()
+						.map_err(|_| invalid(format!("expected three values on '{line}'")))?;
+					outputs.push(rgb);
+				}
+			}
+		}
+
+		let last = outputs.len().saturating_sub(1).max(1) as Float;
+		let channels = [0, 1, 2].map(|c| {
+			outputs
+				.iter()
+				.enumerate()
+				.map(|(i, rgb)| {
+					let t = i as Float / last;
+					(domain_min[c] + t * (domain_max[c] - domain_min[c]), rgb[c])
+				})
+				.collect()
+		});
+		Self::new(channels)
+	}
+
+	pub fn from_csv(data: &str) -> io::Result<Self> {
+		let mut channels: [Vec<(Float, Float)>; 3] = Default::default();
+		for line in data.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			match parse_floats(line.split(','))?[..] {
+				[input, output] => channels
+					.iter_mut()
+					.for_each(|channel| channel.push((input, output))),
+				[input, r, g, b] => {
+					for (channel, output) in channels.iter_mut().zip([r, g, b]) {
+						channel.push((input, output));
+					}
+				}
+				_ => return Err(invalid(format!("expected two or four values on '{line}'"))),
+			}
+		}
+		Self::new(channels)
+	}
+
+	fn new(mut channels: [Vec<(Float, Float)>; 3]) -> io::Result<Self> {
+		if channels[0].len() < 2 {
+			return Err(invalid("a LUT needs at least two entries".to_string()));
+		}
+		for channel in channels.iter_mut() {
+			channel.sort_by(|a, b| a.0.total_cmp(&b.0));
+		}
+		Ok(Lut { channels })
+	}
+
+	pub fn map(&self, channel: usize, value: Float) -> Float {
+		let entries = &self.channels[channel];
+		let i = entries.partition_point(|(input, _)| *input <= value);
+		if i == 0 {
+			return entries[0].1;
+		}
+		if i == entries.len() {
+			return entries[i - 1].1;
+		}
+		let ((x0, y0), (x1, y1)) = (entries[i - 1], entries[i]);
+		y0 + (value - x0) / (x1 - x0) * (y1 - y0)
+	}
+
+	// maps every channel of an rgb image
+	pub fn apply(&self, image: &mut [Float]) {
+		for (i, value) in image.iter_mut().enumerate() {
+			*value = self.map(i % 3, *value);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn interpolates_entries() {
+		let identity = Lut::from_cube("TITLE \"identity\"\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n").unwrap();
+		let mut image = [0.0, 0.125, 0.3, 0.5, 0.77, 1.0];
+		let original = image;
+		identity.apply(&mut image);
+		for (a, b) in image.iter().zip(original) {
+			assert!((a - b).abs() < 0.00001);
+		}
+
+		// a curve darkening the shadows, interpolated linearly between its entries
+		let curve =
+			Lut::from_cube("# gamma like\nLUT_1D_SIZE 3\n0 0 0\n0.25\t0.25\t0.25\n1 1 1").unwrap();
+		assert!((curve.map(0, 0.75) - 0.625).abs() < 0.00001);
+		assert!((curve.map(1, 0.25) - 0.125).abs() < 0.00001);
+		// values outside the domain are clamped to the ends
+		assert_eq!(curve.map(2, 2.0), 1.0);
+
+		let csv = Lut::from_csv("0,0\n0.5,0.25\n1,1").unwrap();
+		assert!((csv.map(0, 0.75) - 0.625).abs() < 0.00001);
+		let domain = Lut::from_cube("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n0 0 0\n1 1 1").unwrap();
+		assert!((domain.map(0, 1.0) - 0.5).abs() < 0.00001);
+	}
+}
@@ -14,6 +14,30 @@ pub trait AccelerationStructure: Sync {
 
 	fn check_hit(&self, ray: &Ray) -> (SurfaceIntersection<Self::Material>, usize);
 
+	// whether anything is hit closer than max_dist, for shadow and occlusion rays that
+	// don't need to know what was hit so can stop at the first hit found
+	fn occluded(&self, ray: &Ray, max_dist: Float) -> bool {
+		match self.check_hit(ray) {
+			(_, usize::MAX) => false,
+			(intersection, _) => intersection.hit.t < max_dist,
+		}
+	}
+
+	// closest hits of a bundle of coherent rays, must match check_hit for each ray
+	fn check_hit_packet(&self, rays: &[Ray]) -> Vec<(SurfaceIntersection<Self::Material>, usize)> {
+		rays.iter().map(|ray| self.check_hit(ray)).collect()
+	}
+
+	// closest hit of each ray, None where the ray escapes to the sky
+	fn intersect_batch(&self, rays: &[Ray]) -> Vec<Option<Hit>> {
+		rays.iter()
+			.map(|ray| match self.check_hit(ray) {
+				(_, usize::MAX) => None,
+				(intersection, _) => Some(intersection.hit),
+			})
+			.collect()
+	}
+
 	fn get_samplable(&self) -> &[usize] {
 		unimplemented!()
 	}
@@ -21,12 +45,19 @@ pub trait AccelerationStructure: Sync {
 	fn get_object(&self, _index: usize) -> Option<&Self::Object> {
 		unimplemented!()
 	}
+	// changes to an object must keep whether it's a light, and its bounds unless the
+	// structure is refit afterwards
+	fn get_object_mut(&mut self, _index: usize) -> Option<&mut Self::Object> {
+		None
+	}
+	// only lights in one of the layers could have been sampled
 	fn get_pdf_from_index(
 		&self,
 		last_hit: &Hit,
 		light_hit: &Hit,
 		sampled_dir: Vec3,
 		index: usize,
+		layers: u32,
 	) -> Float;
 	fn sky(&self) -> &Self::Sky;
 }
@@ -0,0 +1,92 @@
+use crate::{Float, Vec3};
+
+// linear colours
+impl Vec3 {
+	pub const BLACK: Vec3 = Vec3::splat(0.0);
+	pub const WHITE: Vec3 = Vec3::splat(1.0);
+	pub const GREY: Vec3 = Vec3::splat(0.5);
+	pub const RED: Vec3 = Vec3 {
+		x: 1.0,
+		y: 0.0,
+		z: 0.0,
+	};
+	pub const GREEN: Vec3 = Vec3 {
+		x: 0.0,
+		y: 1.0,
+		z: 0.0,
+	};
+	pub const BLUE: Vec3 = Vec3 {
+		x: 0.0,
+		y: 0.0,
+		z: 1.0,
+	};
+
+	const fn splat(v: Float) -> Self {
+		Vec3 { x: v, y: v, z: v }
+	}
+
+	// sRGB bytes to linear
+	pub fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+		Vec3::new(
+			srgb_to_linear(r as Float / 255.0),
+			srgb_to_linear(g as Float / 255.0),
+			srgb_to_linear(b as Float / 255.0),
+		)
+	}
+
+	// linear to sRGB bytes
+	pub fn to_rgb8(self) -> [u8; 3] {
+		let c = self.clamp(0.0, 1.0);
+		[c.x, c.y, c.z].map(|v| (linear_to_srgb(v) * 255.0).round() as u8)
+	}
+
+	// parses an sRGB hex colour such as "#ff8800", the leading # is optional
+	pub fn from_hex(hex: &str) -> Option<Self> {
+		let hex = hex.strip_prefix('#').unwrap_or(hex);
+		if hex.len() != 6 || !hex.is_ascii() {
+			return None;
+		}
+		let channel = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).ok();
+		Some(Vec3::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+	}
+}
+
+pub fn srgb_to_linear(v: Float) -> Float {
+	if v <= 0.04045 {
+		v / 12.92
+	} else {
+		((v + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+pub fn linear_to_srgb(v: Float) -> Float {
+	if v <= 0.0031308 {
+		v * 12.92
+	} else {
+		1.055 * v.powf(1.0 / 2.4) - 0.055
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hex() {
+		let grey = Vec3::from_hex("#808080").unwrap();
+		assert!((grey.x - 0.2158605).abs() < 0.00001);
+		assert_eq!(grey.x, grey.y);
+		assert_eq!(grey.x, grey.z);
+
+		assert_eq!(Vec3::from_hex("ff8800"), Vec3::from_hex("#FF8800"));
+		assert_eq!(Vec3::from_hex("#ff880"), None);
+		assert_eq!(Vec3::from_hex("#gg8800"), None);
+	}
+
+	#[test]
+	fn rgb8_round_trip() {
+		for v in 0..=255 {
+			assert_eq!(Vec3::from_rgb8(v, 0, 255 - v).to_rgb8(), [v, 0, 255 - v]);
+		}
+	}
+}
@@ -1,14 +1,18 @@
 pub mod acceleration;
+pub mod colour;
 pub mod material;
 pub mod primitive;
 pub mod ray;
+pub mod rotation;
 pub mod sampler;
 pub mod vec;
 
 pub use acceleration::*;
+pub use colour::*;
 pub use material::*;
 pub use primitive::*;
 pub use ray::*;
+pub use rotation::*;
 pub use sampler::*;
 pub use vec::*;
 
@@ -1,8 +1,10 @@
 use crate::{Float, Hit, Ray, Vec3};
+use rand::Rng;
 
-// wo (and ray.direction in scatter_ray) points towards the surface and wi away by convention
+// wo (and ray.direction in scatter_ray) points towards the surface and wi away by convention,
+// random choices are made with the given rng so seeded renders are repeatable
 pub trait Scatter: Sync {
-	fn scatter_ray(&self, _ray: &mut Ray, _hit: &Hit) -> bool {
+	fn scatter_ray<R: Rng>(&self, _ray: &mut Ray, _hit: &Hit, _rng: &mut R) -> bool {
 		true
 	}
 	fn requires_uv(&self) -> bool {
@@ -17,6 +19,9 @@ pub trait Scatter: Sync {
 	fn is_delta(&self) -> bool {
 		false
 	}
+	fn is_shadow_catcher(&self) -> bool {
+		false
+	}
 	fn scattering_pdf(&self, _hit: &Hit, _wo: Vec3, _wi: Vec3) -> Float {
 		0.0
 	}
@@ -27,4 +32,12 @@ pub trait Scatter: Sync {
 	fn get_emission(&self, _hit: &Hit, _wo: Vec3) -> Vec3 {
 		Vec3::zero()
 	}
+	// colour of the surface without any lighting, given to denoisers as the albedo
+	fn base_colour(&self, _hit: &Hit, _wo: Vec3) -> Vec3 {
+		Vec3::one()
+	}
+	// false for parameters that can't be rendered, e.g. negative colours
+	fn is_valid(&self) -> bool {
+		true
+	}
 }
@@ -1,10 +1,15 @@
 use crate::{Float, Ray, Scatter, Vec2, Vec3};
+use rand::Rng;
 
 pub struct Hit {
 	pub t: Float,
 	pub point: Vec3,
 	pub error: Vec3,
+	// shading normal, facing against the ray
 	pub normal: Vec3,
+	// normal of the surface itself on the same side as the ray, rays leaving the hit are
+	// offset along it so they can't start on the wrong side of a smooth shaded surface
+	pub geometric_normal: Vec3,
 	pub uv: Option<Vec2>,
 	pub out: bool,
 }
@@ -33,12 +38,18 @@ where
 				point,
 				error,
 				normal,
+				geometric_normal: normal,
 				uv,
 				out,
 			},
 			material,
 		}
 	}
+
+	pub fn with_geometric_normal(mut self, geometric_normal: Vec3) -> Self {
+		self.hit.geometric_normal = geometric_normal;
+		self
+	}
 }
 
 pub trait Primitive: Sync {
@@ -51,15 +62,29 @@ pub trait Primitive: Sync {
 	fn get_uv(&self, _: Vec3) -> Option<Vec2> {
 		None
 	}
-	fn get_sample(&self) -> Vec3 {
+	fn get_sample<R: Rng>(&self, _rng: &mut R) -> Vec3 {
 		unimplemented!()
 	}
-	fn sample_visible_from_point(&self, _point: Vec3) -> Vec3 {
+	fn sample_visible_from_point<R: Rng>(&self, _point: Vec3, _time: Float, _rng: &mut R) -> Vec3 {
 		unimplemented!()
 	}
+	// point distributed uniformly by area over the surface and the geometric normal there
+	fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3);
 	fn area(&self) -> Float;
 	fn scattering_pdf(&self, _hit_point: Vec3, _wi: Vec3, _sampled_hit: &Hit) -> Float;
 	fn material_is_light(&self) -> bool {
 		false
 	}
+	fn object_id(&self) -> u32 {
+		0
+	}
+	// bitmask of the render layers the primitive is in, rays only hit primitives sharing
+	// one of their layers
+	fn layers(&self) -> u32 {
+		u32::MAX
+	}
+	// the material shared by the whole surface, if there is one
+	fn material(&self) -> Option<&Self::Material> {
+		None
+	}
 }
@@ -7,6 +7,8 @@ pub struct Ray {
 	pub d_inverse: Vec3,
 	pub shear: Vec3,
 	pub time: Float,
+	// render layers the ray can hit
+	pub layers: u32,
 }
 
 impl Ray {
@@ -42,9 +44,20 @@ impl Ray {
 			d_inverse: Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z),
 			shear: Vec3::new(shear_x, shear_y, shear_z),
 			time,
+			layers: u32::MAX,
 		}
 	}
 
+	pub fn with_layers(mut self, layers: u32) -> Self {
+		self.layers = layers;
+		self
+	}
+
+	// ray continuing the same path from a new origin
+	pub fn spawn(&self, origin: Vec3, direction: Vec3) -> Self {
+		Ray::new(origin, direction, self.time).with_layers(self.layers)
+	}
+
 	pub fn at(&self, t: Float) -> Vec3 {
 		self.origin + self.direction * t
 	}
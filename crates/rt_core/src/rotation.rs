@@ -0,0 +1,97 @@
+use crate::{Float, Vec3};
+use std::ops::Mul;
+
+// unit quaternion representing a rotation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rotation {
+	pub w: Float,
+	pub v: Vec3,
+}
+
+impl Rotation {
+	pub fn identity() -> Self {
+		Rotation {
+			w: 1.0,
+			v: Vec3::zero(),
+		}
+	}
+
+	// rotates anticlockwise by angle (radians) when looking down the axis
+	pub fn from_axis_angle(axis: Vec3, angle: Float) -> Self {
+		let (sin, cos) = (0.5 * angle).sin_cos();
+		Rotation {
+			w: cos,
+			v: sin * axis.normalised(),
+		}
+	}
+
+	pub fn inverse(self) -> Self {
+		Rotation {
+			w: self.w,
+			v: -self.v,
+		}
+	}
+
+	pub fn rotate(&self, vec: Vec3) -> Vec3 {
+		let t = 2.0 * self.v.cross(vec);
+		vec + self.w * t + self.v.cross(t)
+	}
+}
+
+impl Default for Rotation {
+	fn default() -> Self {
+		Self::identity()
+	}
+}
+
+// a * b applies b then a
+impl Mul for Rotation {
+	type Output = Self;
+	fn mul(self, rhs: Self) -> Self {
+		Rotation {
+			w: self.w * rhs.w - self.v.dot(rhs.v),
+			v: self.w * rhs.v + rhs.w * self.v + self.v.cross(rhs.v),
+		}
+	}
+}
+
+impl Vec3 {
+	pub fn rotated_around_axis(self, axis: Vec3, angle: Float) -> Self {
+		Rotation::from_axis_angle(axis, angle).rotate(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::FRAC_PI_4;
+
+	fn assert_close(a: Vec3, b: Vec3) {
+		assert!((a - b).mag() < 0.00001, "{a} != {b}");
+	}
+
+	#[test]
+	fn quarter_turn() {
+		assert_close(
+			Vec3::x().rotated_around_axis(Vec3::z(), 2.0 * FRAC_PI_4),
+			Vec3::y(),
+		);
+	}
+
+	#[test]
+	fn composition() {
+		let eighth = Rotation::from_axis_angle(Vec3::z(), FRAC_PI_4);
+		let quarter = Rotation::from_axis_angle(Vec3::z(), 2.0 * FRAC_PI_4);
+		let v = Vec3::new(0.3, -1.2, 0.7);
+
+		assert_close((eighth * eighth).rotate(v), quarter.rotate(v));
+		assert_close(quarter.inverse().rotate(quarter.rotate(v)), v);
+
+		// order of composition matters for different axes
+		let about_x = Rotation::from_axis_angle(Vec3::x(), 2.0 * FRAC_PI_4);
+		assert_close(
+			(quarter * about_x).rotate(v),
+			quarter.rotate(about_x.rotate(v)),
+		);
+	}
+}
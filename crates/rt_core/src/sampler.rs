@@ -1,14 +1,16 @@
 use crate::{Float, Ray, Scatter, SurfaceIntersection, Vec3};
+use rand::Rng;
 
 pub trait NoHit<M: Scatter>: Sync {
 	fn get_colour(&self, ray: &Ray) -> Vec3;
-	fn pdf(&self, _: Vec3) -> Float {
+	// pdf and sample take the point light is gathered at
+	fn pdf(&self, _: Vec3, _: Vec3) -> Float {
 		unimplemented!()
 	}
 	fn can_sample(&self) -> bool {
 		false
 	}
-	fn sample(&self) -> Vec3 {
+	fn sample<R: Rng>(&self, _: Vec3, _: &mut R) -> Vec3 {
 		unimplemented!()
 	}
 	fn get_si(&self, _: &Ray) -> SurfaceIntersection<M> {
@@ -237,6 +237,22 @@ impl Vec3 {
 		)
 	}
 
+	// each component clamped on its own
+	#[inline]
+	pub fn clamp(self, min: Float, max: Float) -> Self {
+		Vec3::new(
+			self.x.clamp(min, max),
+			self.y.clamp(min, max),
+			self.z.clamp(min, max),
+		)
+	}
+
+	// self at t = 0 and other at t = 1
+	#[inline]
+	pub fn lerp(self, other: Self, t: Float) -> Self {
+		self + t * (other - self)
+	}
+
 	#[inline]
 	pub fn contains_nan(&self) -> bool {
 		self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
@@ -371,3 +387,31 @@ impl From<[Float; 2]> for Vec2 {
 		Vec2::new(vec[0], vec[1])
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn component_wise() {
+		let (a, b) = (Vec3::new(1.0, -2.0, 3.0), Vec3::new(-4.0, 5.0, 0.5));
+		assert_eq!(a.component_min(), -2.0);
+		assert_eq!(a.component_max(), 3.0);
+		assert_eq!(b.component_min(), -4.0);
+		assert_eq!(b.component_max(), 5.0);
+		assert_eq!(a.min_by_component(b), Vec3::new(-4.0, -2.0, 0.5));
+		assert_eq!(a.max_by_component(b), Vec3::new(1.0, 5.0, 3.0));
+		assert_eq!(a * b, Vec3::new(-4.0, -10.0, 1.5));
+		assert_eq!(a.clamp(0.0, 1.0), Vec3::new(1.0, 0.0, 1.0));
+		assert_eq!(b.clamp(-1.0, 2.0), Vec3::new(-1.0, 2.0, 0.5));
+	}
+
+	#[test]
+	fn lerp() {
+		let (a, b) = (Vec3::new(1.0, -2.0, 3.0), Vec3::new(-3.0, 6.0, 3.0));
+		assert_eq!(a.lerp(b, 0.0), a);
+		assert_eq!(a.lerp(b, 1.0), b);
+		assert_eq!(a.lerp(b, 0.25), Vec3::new(0.0, 0.0, 3.0));
+		assert_eq!(a.lerp(b, 1.5), Vec3::new(-5.0, 10.0, 3.0));
+	}
+}
@@ -1,5 +1,5 @@
 use crate::parameters::Parameters;
-use crate::scene::Scene;
+use crate::scene::{numbered_path, Scene};
 use implementations::rt_core::*;
 use implementations::*;
 use indicatif::ProgressBar;
@@ -133,6 +133,7 @@ fn render_gui<M, P, C, S, A>(
 				render_options.height as u32,
 				rgba_to_rgb(&*buffer.read().unwrap()),
 				render_options.gamma,
+				BitDepth::Eight,
 			);
 		}
 	});
@@ -144,7 +145,11 @@ fn render_gui<M, P, C, S, A>(
 fn render_tui<M, P, C, S, A>(
 	render_options: RenderOptions,
 	filename: Option<String>,
-	scene: Scene<M, P, C, S, A>,
+	output_size: (u64, u64),
+	stats_out: Option<String>,
+	save_options: SaveOptions,
+	brackets: &[Float],
+	scene: &Scene<M, P, C, S, A>,
 ) where
 	M: Scatter,
 	P: Primitive,
@@ -175,6 +180,13 @@ fn render_tui<M, P, C, S, A>(
 	let progress_bar_output = |sp: &mut Progress, previous: &SamplerProgress, i: u64| -> bool {
 		sp.sampler_progress.samples_completed += 1;
 		sp.sampler_progress.rays_shot += previous.rays_shot;
+		sp.sampler_progress.moments.clone_from(&previous.moments);
+		sp.sampler_progress.halves.clone_from(&previous.halves);
+		sp.sampler_progress.normals.clone_from(&previous.normals);
+		sp.sampler_progress.depth.clone_from(&previous.depth);
+		sp.sampler_progress.albedo.clone_from(&previous.albedo);
+		sp.sampler_progress.direct.clone_from(&previous.direct);
+		sp.sampler_progress.indirect.clone_from(&previous.indirect);
 
 		sp.sampler_progress
 			.current_image
@@ -184,6 +196,15 @@ fn render_tui<M, P, C, S, A>(
 				*pres += (acc - *pres) / i as Float; // since copies first buffer when i=1
 			});
 		sp.bar.set_position(sp.sampler_progress.samples_completed);
+		sp.bar.set_message(format!(
+			"{} noise: {:.4}",
+			get_eta_output(
+				start.elapsed(),
+				sp.sampler_progress.samples_completed,
+				render_options.samples_per_pixel,
+			),
+			previous.noise
+		));
 		if sp.sampler_progress.samples_completed == render_options.samples_per_pixel {
 			sp.bar.finish_and_clear()
 		}
@@ -191,37 +212,382 @@ fn render_tui<M, P, C, S, A>(
 	};
 
 	scene.render(render_options, Some((&mut image, progress_bar_output)));
+	if !image.bar.is_finished() {
+		image.bar.finish_and_clear();
+	}
 
 	let ray_count = image.sampler_progress.rays_shot;
 
 	print_final_statistics(start, ray_count, image.sampler_progress.samples_completed);
 
+	if let Some(path) = stats_out {
+		let moments = &image.sampler_progress.moments;
+		if let Err(e) = save_pixel_stats(&path, render_options.width, moments) {
+			log::error!("failed to write pixel statistics: {e}");
+		}
+	}
+
 	if let Some(filename) = filename {
-		save_data_to_image(
-			filename,
-			render_options.width as u32,
-			render_options.height as u32,
-			image.sampler_progress.current_image,
-			render_options.gamma,
+		let render_size = (render_options.width as u32, render_options.height as u32);
+		let output_size = (output_size.0 as u32, output_size.1 as u32);
+		let resample = |data: &[Float]| {
+			if render_size == output_size {
+				data.to_vec()
+			} else if render_size.0 > output_size.0 {
+				downsample(data, render_size, render_size.0 / output_size.0)
+			} else {
+				upscale(data, render_size, output_size)
+			}
+		};
+		let resize = |data: &[Float]| {
+			let mut data = resample(data);
+			expose(&mut data, render_options.exposure);
+			data
+		};
+
+		// normals are already in [0, 1] so skip the exposure and tone curve
+		if let Some(normals) = &image.sampler_progress.normals {
+			let path = suffixed_path(&filename, "normal");
+			let (width, height) = output_size;
+			save_data_to_image(path, width, height, resample(normals), 1.0, BitDepth::Eight);
+		}
+		if let (Some(depth), Some(mode)) = (&image.sampler_progress.depth, save_options.depth) {
+			let path = mode.path(&filename);
+			let (width, height) = output_size;
+			save_data_to_image(
+				path,
+				width,
+				height,
+				resample(&mode.image(depth)),
+				1.0,
+				BitDepth::Eight,
+			);
+		}
+		// colours without lighting so only the gamma is applied
+		if let Some(albedo) = &image.sampler_progress.albedo {
+			let path = suffixed_path(&filename, "albedo");
+			let (width, height) = output_size;
+			save_data_to_image(
+				path,
+				width,
+				height,
+				resample(albedo),
+				render_options.gamma,
+				save_options.bit_depth,
+			);
+		}
+
+		let lighting = [
+			(&image.sampler_progress.direct, "direct"),
+			(&image.sampler_progress.indirect, "indirect"),
+		];
+		for (light, suffix) in lighting {
+			if let Some(light) = light {
+				let path = suffixed_path(&filename, suffix);
+				save_tone_mapped(
+					path,
+					output_size,
+					resize(light),
+					&render_options,
+					save_options,
+				);
+			}
+		}
+
+		if let Some(halves) = &image.sampler_progress.halves {
+			for (half, suffix) in [(halves.even(), "even"), (halves.odd(), "odd")] {
+				let path = suffixed_path(&filename, suffix);
+				save_tone_mapped(
+					path,
+					output_size,
+					resize(half),
+					&render_options,
+					save_options,
+				);
+			}
+		}
+
+		// the matte is the alpha of a layer to composite over a plate so it isn't exposed
+		if matches!(render_options.render_method, RenderMethod::ShadowMatte) {
+			let matte = resample(&image.sampler_progress.current_image);
+			let (width, height) = output_size;
+			save_rgba_image(
+				filename,
+				width,
+				height,
+				shadow_matte_rgba(&matte, save_options.shadow_colour),
+				render_options.gamma,
+				save_options.bit_depth,
+				save_options.alpha_mode,
+			);
+			return;
+		}
+
+		// id colours are saved as rendered since filtering or a tone curve would blend them
+		if matches!(render_options.render_method, RenderMethod::ObjectId) {
+			if render_size != output_size {
+				log::warn!("object ids are saved at the render size");
+			}
+			let (width, height) = render_size;
+			save_data_to_image(
+				filename,
+				width,
+				height,
+				image.sampler_progress.current_image.clone(),
+				1.0,
+				BitDepth::Eight,
+			);
+			return;
+		}
+
+		let data = resize(&image.sampler_progress.current_image);
+		if brackets.is_empty() {
+			save_tone_mapped(
+				filename.clone(),
+				output_size,
+				data,
+				&render_options,
+				save_options,
+			);
+			if let Some((other, amplify)) = save_options.diff {
+				save_difference(&filename, other, amplify);
+			}
+		} else {
+			for &stops in brackets {
+				let mut exposed = data.clone();
+				expose(&mut exposed, stops);
+				let path = bracket_path(&filename, stops);
+				save_tone_mapped(path, output_size, exposed, &render_options, save_options);
+			}
+		}
+	}
+}
+
+// how the linear image is turned into the saved file
+#[derive(Clone, Copy)]
+struct SaveOptions<'a> {
+	tone_map: ToneMap,
+	// replaces gamma as the tone curve
+	lut: Option<&'a Lut>,
+	bit_depth: BitDepth,
+	depth: Option<DepthOutput>,
+	// image the saved render is compared with and how much the difference is amplified
+	diff: Option<(&'a str, Float)>,
+	alpha_mode: AlphaMode,
+	// colour of the layer saved with a shadow matte as its alpha
+	shadow_colour: Vec3,
+}
+
+fn save_tone_mapped(
+	filename: String,
+	size: (u32, u32),
+	mut data: Vec<Float>,
+	render_options: &RenderOptions,
+	save_options: SaveOptions,
+) {
+	if is_linear_format(&filename) {
+		save_data_to_image(filename, size.0, size.1, data, 1.0, save_options.bit_depth);
+		return;
+	}
+	save_options.tone_map.apply(&mut data);
+	let gamma = match save_options.lut {
+		Some(lut) => {
+			lut.apply(&mut data);
+			1.0
+		}
+		None => render_options.gamma,
+	};
+	if render_options.gamut_compression {
+		compress_gamut(&mut data);
+	}
+	save_data_to_image(
+		filename,
+		size.0,
+		size.1,
+		data,
+		gamma,
+		save_options.bit_depth,
+	);
+}
+
+// compares the saved image with another of the same size as they are stored and
+// saves their difference next to it
+fn save_difference(filename: &str, other: &str, amplify: Float) {
+	let (image, other_image) = match (load_image(filename), load_image(other)) {
+		(Ok(image), Ok(other_image)) => (image, other_image),
+		(Err(e), _) | (_, Err(e)) => {
+			log::error!("failed to load images to compare: {e}");
+			return;
+		}
+	};
+	let ((width, height), other_size) = (image.1, other_image.1);
+	if (width, height) != other_size {
+		log::error!(
+			"can't compare the {width}x{height} image with {other}, which is {}x{}",
+			other_size.0,
+			other_size.1
 		);
+		return;
 	}
+	let difference = ImageDifference::new(&image.0, &other_image.0, amplify);
+	log::info!(
+		"Difference from {other}:\n\tMSE:\t\t{:.6}\n\tMax error:\t{:.6}",
+		difference.mse,
+		difference.max_error
+	);
+	let path = suffixed_path(filename, "diff");
+	save_data_to_image(path, width, height, difference.image, 1.0, BitDepth::Eight);
+}
+
+// json when the path ends in .json, csv otherwise
+fn save_pixel_stats(path: &str, width: u64, moments: &[PixelMoments]) -> std::io::Result<()> {
+	let json = std::path::Path::new(path)
+		.extension()
+		.is_some_and(|extension| extension == "json");
+	let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+	write_pixel_stats(&mut writer, width, moments, json)?;
+	log::info!("Pixel statistics saved to {path}");
+	Ok(())
 }
 
 fn main() {
 	create_logger();
-	let (scene, parameters) = match parameters::process_args() {
+	let (mut scene, parameters) = match parameters::process_args() {
 		Some(data) => data,
 		None => return,
 	};
 
+	for warning in scene.validate() {
+		log::warn!("{warning}");
+	}
+
 	let Parameters {
 		render_options,
 		gui,
 		filename,
+		bake_ao,
+		output_size,
+		turntable,
+		stats_out,
+		all_cameras,
+		lut,
+		tone_map,
+		bit_depth,
+		depth_pass,
+		brackets,
+		stream_output,
+		diff,
+		alpha_mode,
+		shadow_colour,
 	} = parameters;
+	let save_options = SaveOptions {
+		tone_map,
+		lut: lut.as_ref(),
+		bit_depth,
+		depth: depth_pass,
+		diff: diff
+			.as_ref()
+			.map(|(path, amplify)| (path.as_str(), *amplify)),
+		alpha_mode,
+		shadow_colour,
+	};
+
+	if let Some(path) = bake_ao {
+		if let Err(e) = scene.bake_ao(&path, render_options.samples_per_pixel) {
+			log::error!("failed to bake ambient occlusion: {e}");
+		}
+		return;
+	}
+
+	if let Some((frames, orbit)) = turntable {
+		if gui {
+			log::warn!("turntables are rendered without the gui");
+		}
+		let filename = match filename {
+			Some(filename) => filename,
+			None => {
+				log::error!("an output filename is needed for the turntable's frames");
+				return;
+			}
+		};
+		for frame in 0..frames {
+			scene.turntable_frame(frame, frames, &orbit);
+			scene.animation_frame(frame);
+			println!("Frame {}/{frames}", frame + 1);
+			let path = numbered_path(&filename, frame as usize);
+			let stats_out = stats_out
+				.as_ref()
+				.map(|stats_out| numbered_path(stats_out, frame as usize));
+			render_tui(
+				render_options,
+				Some(path),
+				output_size,
+				stats_out,
+				save_options,
+				&brackets,
+				&scene,
+			);
+		}
+		return;
+	}
+
+	if all_cameras {
+		if gui {
+			log::warn!("cameras are rendered without the gui");
+		}
+		let filename = match filename {
+			Some(filename) => filename,
+			None => {
+				log::error!("an output filename is needed for each camera's render");
+				return;
+			}
+		};
+		for camera in 0..scene.camera_count() {
+			scene.select_camera(camera);
+			println!("Camera {}/{}", camera + 1, scene.camera_count());
+			let path = numbered_path(&filename, camera);
+			let stats_out = stats_out
+				.as_ref()
+				.map(|stats_out| numbered_path(stats_out, camera));
+			render_tui(
+				render_options,
+				Some(path),
+				output_size,
+				stats_out,
+				save_options,
+				&brackets,
+				&scene,
+			);
+		}
+		return;
+	}
+
+	if let Some(path) = stream_output {
+		let start = print_render_start(
+			render_options.width,
+			render_options.height,
+			render_options.gamma as f64,
+			Some(render_options.samples_per_pixel),
+		);
+		match scene.render_to_file(render_options, &path) {
+			Ok(ray_count) => {
+				print_final_statistics(start, ray_count, render_options.samples_per_pixel)
+			}
+			Err(e) => log::error!("failed to write {path}: {e}"),
+		}
+		return;
+	}
 
 	if !gui {
-		render_tui(render_options, filename, scene);
+		render_tui(
+			render_options,
+			filename,
+			output_size,
+			stats_out,
+			save_options,
+			&brackets,
+			&scene,
+		);
 	} else {
 		#[cfg(feature = "gui")]
 		render_gui(render_options, filename, scene);
@@ -1,5 +1,10 @@
-use crate::{scene::Scene, Float};
+use crate::{
+	scene::{Orbit, Scene},
+	Float, Vec3,
+};
 use clap::Parser;
+use serde::Deserialize;
+use std::path::Path;
 
 use implementations::{split::SplitType, *};
 use region::Region;
@@ -15,64 +20,731 @@ pub struct Parameters {
 	pub render_options: RenderOptions,
 	pub gui: bool,
 	pub filename: Option<String>,
+	pub bake_ao: Option<String>,
+	// resolution of the saved image, larger than the render's for a preview and smaller
+	// when supersampling
+	pub output_size: (u64, u64),
+	// number of frames and the orbit of a turntable animation
+	pub turntable: Option<(u64, Orbit)>,
+	// per pixel sample counts and variance written after the render
+	pub stats_out: Option<String>,
+	// renders every camera in the scene to numbered output files
+	pub all_cameras: bool,
+	// tone curve used instead of gamma when saving
+	pub lut: Option<output::Lut>,
+	// operator bringing values above one into range before the lut or gamma
+	pub tone_map: output::ToneMap,
+	pub bit_depth: output::BitDepth,
+	// how the depth pass is saved, when there is one
+	pub depth_pass: Option<output::DepthOutput>,
+	// exposures in stops each saved to their own file, empty saves the image as is
+	pub brackets: Vec<Float>,
+	// .pfm file the render is written to a tile at a time instead of being held in memory
+	pub stream_output: Option<String>,
+	// image the saved render is compared with and how much the difference is amplified
+	pub diff: Option<(String, Float)>,
+	pub alpha_mode: output::AlphaMode,
+	// colour of the layer whose alpha is the shadow matte
+	pub shadow_colour: Vec3,
 }
 
 #[derive(Parser, Debug)]
 #[command(about, long_about=None)]
 #[command(name = "Pathtracer")]
 #[command(about = "An experimental pathtracer written in Rust")]
+#[command(args_override_self = true)]
 struct Cli {
 	#[arg(short, long, default_value_t = false)]
 	gui: bool,
-	#[arg(short, long, default_value_t = 128)]
-	samples: u64,
-	#[arg(short = 'x', long, default_value_t = 1920)]
-	width: u64,
-	#[arg(short = 'y', long, default_value_t = 1080)]
-	height: u64,
+	#[arg(short, long)]
+	samples: Option<u64>,
+	#[arg(short = 'x', long)]
+	width: Option<u64>,
+	#[arg(short = 'y', long)]
+	height: Option<u64>,
 	#[arg(short, long)]
 	filepath: String,
 	#[arg(short, long,value_enum, default_value_t = SplitType::Sah)]
 	bvh_type: SplitType,
-	#[arg(short, long,value_enum, default_value_t = RenderMethod::MIS)]
+	#[arg(long, value_enum, default_value_t = TraversalOrder::Axis)]
+	traversal_order: TraversalOrder,
+	#[arg(long, value_enum, default_value_t = SamplerType::Random)]
+	sampler: SamplerType,
+	// fraction of a pixel's mean luminance either side of it within which the adaptive
+	// sampler counts it as converged
+	#[arg(long, default_value_t = 0.01)]
+	adaptive_threshold: Float,
+	#[arg(short, long,value_enum, default_value_t = RenderMethod::MIS, alias = "mode")]
 	render_method: RenderMethod,
+	// how the MIS method counts light reachable by both light and material samples, mis
+	// weights the two and light-samples leaves it all to the light sample
+	#[arg(long, value_enum, default_value_t = LightWeighting::Mis)]
+	light_weighting: LightWeighting,
+	// rays per first hit and the distance they look for occluders in the ao render method
+	#[arg(long, default_value_t = 16)]
+	ao_samples: u64,
+	#[arg(long, default_value_t = Float::INFINITY)]
+	ao_distance: Float,
 	#[arg(short, long)]
 	output: Option<String>,
+	// directory the output is saved in, created if it doesn't exist
+	#[arg(long)]
+	outdir: Option<String>,
+	// names the output after the scene file and the time the render started, keeping the
+	// extension of any given output. Implied by --outdir without --output.
+	#[arg(long, default_value_t = false)]
+	auto_name: bool,
 	#[arg(long, default_value_t = 2.2)]
 	gamma: Float,
+	// stops the saved image is brightened or darkened by
+	#[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+	exposure: Float,
+	// desaturates colours outside [0, 1] towards grey before they're saved instead of
+	// clipping each channel
+	#[arg(long, default_value_t = false)]
+	gamut_compression: bool,
+	// curve compressing highlights before the image is saved, none clips them
+	#[arg(long, value_enum, default_value_t = output::ToneMap::None)]
+	tone_map: output::ToneMap,
+	// whether the colour of rgba output, the shadow matte's layer, is multiplied by alpha
+	#[arg(long, value_enum, default_value_t = output::AlphaMode::Straight)]
+	alpha_mode: output::AlphaMode,
+	// colour of the layer saved with the shadow matte as its alpha
+	#[arg(long, num_args = 3, value_names = ["R", "G", "B"])]
+	shadow_colour: Option<Vec<Float>>,
+	// bits per channel of png, tiff and ppm output
+	#[arg(long, value_enum, default_value_t = output::BitDepth::Eight)]
+	bitdepth: output::BitDepth,
+	// render threads, all cores are used by default
+	#[arg(long)]
+	threads: Option<usize>,
+	// toml file of settings, options on the command line take precedence over it
+	#[arg(long, value_name = "PATH")]
+	config: Option<String>,
+	#[arg(long)]
+	epsilon_converge: Option<Float>,
+	// stops after the sample pass in which the total rays shot reaches N, for comparing
+	// samplers at the same cost
+	#[arg(long, value_name = "N")]
+	max_rays: Option<u64>,
+	#[arg(long, num_args = 6, value_names = ["PX", "PY", "PZ", "NX", "NY", "NZ"])]
+	clip_plane: Option<Vec<Float>>,
+	#[arg(long)]
+	seed: Option<u64>,
+	#[arg(long, value_name = "IPD")]
+	stereo: Option<Float>,
+	// times the camera's shutter opens and closes, objects move over [0, 1]
+	#[arg(long, num_args = 2, value_names = ["OPEN", "CLOSE"])]
+	shutter: Option<Vec<Float>>,
+	#[arg(long, default_value_t = false)]
+	reference: bool,
+	#[arg(long)]
+	sample_mask: Option<String>,
+	#[arg(long, default_value_t = 1)]
+	min_samples: u64,
+	#[arg(long, default_value_t = 50)]
+	max_diffuse_depth: u32,
+	#[arg(long, default_value_t = 50)]
+	max_specular_depth: u32,
+	#[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=8))]
+	packet_size: u64,
+	// contributions after n bounces are clamped to BASE * GROWTH^n
+	#[arg(long, num_args = 2, value_names = ["BASE", "GROWTH"])]
+	clamp: Option<Vec<Float>>,
+	// largest sky radiance seen after a bounce, the visible background isn't clamped
+	#[arg(long, value_name = "MAX")]
+	sky_clamp: Option<Float>,
+	// bitmask of the render layers visible in this pass
+	#[arg(long, value_name = "MASK", default_value_t = u32::MAX)]
+	layers: u32,
+	// renders only the object with this name from the scene's obj files, an integer picks
+	// the object at that index in files without an object of that name
+	#[arg(long, value_name = "OBJECT")]
+	isolate: Option<String>,
+	// joins mesh vertices closer than a cell of this many square pixels in the selected
+	// camera's image, dropping triangles too small to be seen
+	#[arg(long, value_name = "AREA")]
+	lod_threshold: Option<Float>,
+	// simplifies each mesh to about this fraction of its triangles for quicker previews
+	#[arg(long, value_name = "RATIO")]
+	decimate: Option<Float>,
+	// writes each pixel's sample count and luminance variance as csv, or json for a .json path
+	#[arg(long, value_name = "PATH")]
+	stats_out: Option<String>,
+	// renders a tile at a time straight to a .pfm file for images too large to hold in
+	// memory, the output size, exposure and tone curve aren't applied. The adaptive
+	// sampler, --epsilon-converge and --max-rays need the whole image and can't be used.
+	#[arg(long, value_name = "PFM")]
+	stream_output: Option<String>,
+	// writes the scene's meshes with baked ambient occlusion instead of rendering
+	#[arg(long, value_name = "OBJ")]
+	bake_ao: Option<String>,
+	// renders at a fraction of the resolution and upscales the result
+	#[arg(long)]
+	preview_scale: Option<Float>,
+	// renders at N times the resolution and averages N x N blocks into each pixel
+	#[arg(long, value_name = "N", conflicts_with = "preview_scale")]
+	supersample: Option<u64>,
+	// index of the scene's camera to render from, in the order they are in the file
+	#[arg(long, value_name = "INDEX", default_value_t = 0)]
+	camera: usize,
+	// renders from each of the scene's cameras to numbered output files
+	#[arg(long, default_value_t = false, conflicts_with_all = ["camera", "turntable"])]
+	all_cameras: bool,
+	// replaces the selected camera with one framing the whole scene
+	#[arg(long, default_value_t = false, conflicts_with = "all_cameras")]
+	frame_scene: bool,
+	// renders N frames orbiting the scene's centre to numbered output files
+	#[arg(long, value_name = "N")]
+	turntable: Option<u64>,
+	// orbit of the turntable, by default it passes through the scene's camera
+	#[arg(long, num_args = 3, value_names = ["RADIUS", "HEIGHT", "ELEVATION"])]
+	turntable_orbit: Option<Vec<Float>>,
+	// scales the strength of every light from FROM on the turntable's first frame to TO
+	// on its last
+	#[arg(long, num_args = 2, value_names = ["FROM", "TO"], requires = "turntable")]
+	light_keyframes: Option<Vec<Float>>,
+	// traces photons through specular surfaces into a caustic map before rendering
+	#[arg(long, default_value_t = false)]
+	caustics: bool,
+	#[arg(long, default_value_t = 100000)]
+	caustic_photons: u64,
+	// radius around shading points that photons are gathered from
+	#[arg(long, default_value_t = 0.05)]
+	caustic_radius: Float,
+	// box of fog between the two corners, followed by the MIS integrator
+	#[arg(long, num_args = 7, value_names = ["MINX", "MINY", "MINZ", "MAXX", "MAXY", "MAXZ", "DENSITY"])]
+	fog: Option<Vec<Float>>,
+	#[arg(long, num_args = 3, value_names = ["R", "G", "B"])]
+	fog_colour: Option<Vec<Float>>,
+	// square greyscale tile used for pixel jitter, one is generated when no path is given
+	#[arg(long, value_name = "PATH", num_args = 0..=1)]
+	blue_noise: Option<Option<String>>,
+	// 1D LUT (.cube, or csv rows of input,output) applied as the tone curve in place of gamma
+	#[arg(long, value_name = "PATH")]
+	lut: Option<String>,
+	// saves a copy of the image for each exposure in stops, e.g. -2,0,2, with the
+	// exposure added to the file's name
+	#[arg(
+		long,
+		value_name = "STOPS",
+		value_delimiter = ',',
+		allow_hyphen_values = true
+	)]
+	brackets: Option<Vec<Float>>,
+	// also saves the even and odd samples accumulated apart to files ending in _even and
+	// _odd, their difference estimates the image's noise
+	#[arg(long, default_value_t = false)]
+	split_halves: bool,
+	// also saves the normal of each pixel's first hit, remapped to [0, 1], to a file
+	// ending in _normal
+	#[arg(long, default_value_t = false)]
+	normal_pass: bool,
+	// also saves the distance to each pixel's first hit to a file ending in _depth, raw
+	// distances go to an exr and normalised ones to the image's format
+	#[arg(long, value_enum, value_name = "MODE")]
+	depth_pass: Option<output::DepthOutput>,
+	// depth of pixels that see the sky in the depth pass
+	#[arg(long, default_value_t = Float::INFINITY)]
+	depth_far: Float,
+	// also saves the unlit base colour of each pixel's first hit to a file ending in
+	// _albedo, for guiding a denoiser
+	#[arg(long, default_value_t = false)]
+	albedo_pass: bool,
+	// also saves the MIS image's light split into what reaches each pixel straight from an
+	// emitter or after a single bounce and what arrives after further bounces, to files
+	// ending in _direct and _indirect
+	#[arg(long, default_value_t = false)]
+	lighting_pass: bool,
+	// compares the saved image with this one, printing the mean squared and largest
+	// error and saving their difference to a file ending in _diff
+	#[arg(long, value_name = "PATH", conflicts_with = "brackets")]
+	diff: Option<String>,
+	// factor the saved difference is scaled by so small changes show up
+	#[arg(long, default_value_t = 1.0, requires = "diff")]
+	diff_amplify: Float,
+}
+
+// settings read from a render config file, each is the option of the same name and
+// scene is the scene file
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Config {
+	scene: Option<String>,
+	samples: Option<u64>,
+	width: Option<u64>,
+	height: Option<u64>,
+	sampler: Option<String>,
+	render_method: Option<String>,
+	gamma: Option<Float>,
+	lut: Option<String>,
+	exposure: Option<Float>,
+	tone_map: Option<String>,
+	threads: Option<usize>,
+	output: Option<String>,
+	seed: Option<u64>,
+}
+
+impl Config {
+	fn load(path: &str) -> Result<Self, String> {
+		let data = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+		toml::from_str(&data).map_err(|e| format!("{path}: {e}"))
+	}
+
+	// the settings as command line arguments
+	fn args(&self) -> Vec<String> {
+		let options = [
+			("filepath", self.scene.clone()),
+			("samples", self.samples.map(|v| v.to_string())),
+			("width", self.width.map(|v| v.to_string())),
+			("height", self.height.map(|v| v.to_string())),
+			("sampler", self.sampler.clone()),
+			("render-method", self.render_method.clone()),
+			("gamma", self.gamma.map(|v| v.to_string())),
+			("lut", self.lut.clone()),
+			("exposure", self.exposure.map(|v| v.to_string())),
+			("tone-map", self.tone_map.clone()),
+			("threads", self.threads.map(|v| v.to_string())),
+			("output", self.output.clone()),
+			("seed", self.seed.map(|v| v.to_string())),
+		];
+		options
+			.into_iter()
+			.filter_map(|(name, value)| Some(format!("--{name}={}", value?)))
+			.collect()
+	}
+}
+
+const DEFAULT_SAMPLES: u64 = 128;
+const DEFAULT_WIDTH: u64 = 1920;
+const DEFAULT_HEIGHT: u64 = 1080;
+
+// environment overrides (e.g. for CI) that cap values not given on the command line
+fn env_cap(name: &str) -> Option<u64> {
+	std::env::var(name).ok()?.parse().ok()
+}
+
+impl Cli {
+	// the arguments from the --config file are put ahead of those given so the later
+	// values given on the command line replace them
+	fn parse_with_config(args: Vec<String>) -> Result<Self, String> {
+		let path =
+			args.iter()
+				.enumerate()
+				.find_map(|(i, arg)| match arg.strip_prefix("--config") {
+					Some("") => args.get(i + 1).cloned(),
+					Some(value) => value.strip_prefix('=').map(String::from),
+					None => None,
+				});
+		let config = match path {
+			Some(path) => Config::load(&path)?,
+			None => Config::default(),
+		};
+		let (program, given) = args.split_first().ok_or("missing program name")?;
+		Ok(Cli::parse_from(
+			std::iter::once(program.clone())
+				.chain(config.args())
+				.chain(given.iter().cloned()),
+		))
+	}
+
+	fn output_filename(&self) -> Option<String> {
+		if self.outdir.is_none() && !self.auto_name {
+			return self.output.clone();
+		}
+		let outdir = Path::new(self.outdir.as_deref().unwrap_or("."));
+		if self.outdir.is_some() {
+			if let Err(e) = std::fs::create_dir_all(outdir) {
+				log::error!("Unable to create output directory: {e}");
+			}
+		}
+		let path = match &self.output {
+			Some(output) if !self.auto_name => outdir.join(output),
+			output => {
+				let extension = output
+					.as_deref()
+					.and_then(|output| Path::new(output).extension())
+					.map_or("png".into(), |extension| extension.to_string_lossy());
+				let scene = Path::new(&self.filepath).file_stem()?.to_string_lossy();
+				output::auto_filename(outdir, &scene, &extension, chrono::Local::now())
+			}
+		};
+		Some(path.to_string_lossy().into_owned())
+	}
+
+	fn samples(&self) -> u64 {
+		capped_samples(self.samples, env_cap("RT_SAMPLES"))
+	}
+
+	fn dimensions(&self) -> (u64, u64) {
+		capped_dimensions(self.width, self.height, env_cap("RT_MAX_DIM"))
+	}
+}
+
+// the default sample count is capped, a count that was given is kept
+fn capped_samples(samples: Option<u64>, cap: Option<u64>) -> u64 {
+	samples.unwrap_or_else(|| match cap {
+		Some(cap) => DEFAULT_SAMPLES.min(cap),
+		None => DEFAULT_SAMPLES,
+	})
+}
+
+// the default resolution is scaled down so its longest side fits the cap, keeping the
+// aspect ratio, a width or height that was given is kept
+fn capped_dimensions(width: Option<u64>, height: Option<u64>, cap: Option<u64>) -> (u64, u64) {
+	match (width, height) {
+		(None, None) => match cap {
+			Some(cap) if cap < DEFAULT_WIDTH.max(DEFAULT_HEIGHT) => {
+				let scale = cap as Float / DEFAULT_WIDTH.max(DEFAULT_HEIGHT) as Float;
+				(
+					((DEFAULT_WIDTH as Float * scale) as u64).max(1),
+					((DEFAULT_HEIGHT as Float * scale) as u64).max(1),
+				)
+			}
+			_ => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+		},
+		(width, height) => (
+			width.unwrap_or(DEFAULT_WIDTH),
+			height.unwrap_or(DEFAULT_HEIGHT),
+		),
+	}
 }
 
 pub fn process_args() -> Option<(SceneType<'static>, Parameters)> {
-	let cli = Cli::parse();
+	let cli = match Cli::parse_with_config(std::env::args().collect()) {
+		Ok(cli) => cli,
+		Err(e) => {
+			log::error!("failed to load config: {e}");
+			return None;
+		}
+	};
+	if let Some(threads) = cli.threads {
+		if let Err(e) = rayon::ThreadPoolBuilder::new()
+			.num_threads(threads)
+			.build_global()
+		{
+			log::warn!("couldn't set the number of threads: {e}");
+		}
+	}
+	let filename = cli.output_filename();
+	let lut = match cli.lut.as_ref().map(output::Lut::load).transpose() {
+		Ok(lut) => lut,
+		Err(e) => {
+			log::error!("failed to load LUT: {e}");
+			return None;
+		}
+	};
 
 	let mut region = Region::new();
-	let (primitives, camera, sky) = match loader::load_file_full::<
+	let (primitives, cameras, sky, empty_meshes) = match loader::load_file_full::<
 		AllTextures,
 		MaterialType,
 		PrimitiveType,
 		SimpleCamera,
 		SkyType,
-	>(&mut region, &cli.filepath)
-	{
+	>(
+		&mut region,
+		&cli.filepath,
+		cli.isolate.as_deref(),
+		|primitives, cameras| {
+			if let Some(ratio) = cli.decimate {
+				let removed = decimate(primitives, ratio);
+				log::info!("Removed {removed} triangle(s) decimating meshes");
+			}
+			if let (Some(min_area), Some(camera)) = (cli.lod_threshold, cameras.get(cli.camera)) {
+				let removed = simplify_subpixel(primitives, camera, cli.dimensions(), min_area);
+				log::info!("Removed {removed} sub-pixel triangle(s)");
+			}
+		},
+	) {
 		Ok(a) => a,
 		Err(e) => panic!("{e:?}"),
 	};
 
-	let bvh = Bvh::new(primitives, sky, cli.bvh_type);
+	let cameras = cameras.into_iter().map(|camera| {
+		// each eye takes half of the image width
+		let camera = match cli.stereo {
+			Some(ipd) => camera.stereo(ipd),
+			None => camera,
+		};
+		match &cli.shutter {
+			Some(v) => camera.with_shutter(v[0], v[1]),
+			None => camera,
+		}
+	});
+
+	let bvh = Bvh::new(primitives, sky, cli.bvh_type, cli.traversal_order);
 
-	let scene = Scene::new(bvh, camera, region);
+	let caustics = cli.caustics.then(|| {
+		let map = CausticMap::new(&bvh, cli.caustic_photons, cli.caustic_radius, cli.seed);
+		log::info!("caustic map holds {} photons", map.len());
+		&*Box::leak(Box::new(map))
+	});
+
+	let mut scene = Scene::new(bvh, cameras.collect(), region).with_empty_meshes(empty_meshes);
+	if !scene.select_camera(cli.camera) {
+		log::error!(
+			"camera {} was selected but the scene has {}",
+			cli.camera,
+			scene.camera_count()
+		);
+		return None;
+	}
+	if cli.frame_scene {
+		let (width, height) = cli.dimensions();
+		match scene.suggested_camera(width as Float / height as Float) {
+			Some(camera) => scene.set_camera(camera),
+			None => log::warn!("the scene is empty so has no camera framing it"),
+		}
+	}
+	if let (Some(frames), Some(v)) = (cli.turntable, &cli.light_keyframes) {
+		scene.keyframe_lights(v[0], v[1], frames);
+	}
+
+	let turntable = cli.turntable.map(|frames| {
+		let orbit = scene.orbit();
+		let orbit = match &cli.turntable_orbit {
+			Some(v) => Orbit {
+				radius: v[0],
+				height: v[1],
+				elevation: v[2],
+				..orbit
+			},
+			None => orbit,
+		};
+		(frames, orbit)
+	});
+
+	let (width, height) = cli.dimensions();
 
 	let render_ops = RenderOptions {
-		width: cli.width,
-		height: cli.height,
-		samples_per_pixel: cli.samples,
+		sampler: cli.sampler,
+		width,
+		height,
+		samples_per_pixel: cli.samples(),
 		render_method: cli.render_method,
+		light_weighting: cli.light_weighting,
 		gamma: cli.gamma,
+		exposure: cli.exposure,
+		gamut_compression: cli.gamut_compression,
+		epsilon_converge: cli.epsilon_converge,
+		max_rays: cli.max_rays,
+		clip_plane: cli.clip_plane.map(|v| {
+			(
+				Vec3::new(v[0], v[1], v[2]),
+				Vec3::new(v[3], v[4], v[5]).normalised(),
+			)
+		}),
+		seed: cli.seed,
+		sample_mask: cli.sample_mask.map(|path| {
+			let mask = load_sample_mask(&path, width, height);
+			&*Box::leak(mask.into_boxed_slice())
+		}),
+		min_samples: cli.min_samples,
+		max_diffuse_depth: cli.max_diffuse_depth,
+		max_specular_depth: cli.max_specular_depth,
+		russian_roulette: true,
+		packet_size: cli.packet_size,
+		clamp: cli.clamp.map(|v| ClampSchedule::new(v[0], v[1])),
+		sky_clamp: cli.sky_clamp,
+		layers: cli.layers,
+		ao_samples: cli.ao_samples,
+		ao_distance: cli.ao_distance,
+		split_halves: cli.split_halves,
+		normal_pass: cli.normal_pass,
+		depth_pass: cli.depth_pass.is_some(),
+		depth_far: cli.depth_far,
+		albedo_pass: cli.albedo_pass,
+		lighting_pass: cli.lighting_pass,
+		adaptive_threshold: cli.adaptive_threshold,
+		caustics,
+		blue_noise: cli.blue_noise.map(|path| match path {
+			Some(path) => blue_noise::BlueNoise::load(&path),
+			None => blue_noise::BlueNoise::generate(64),
+		}),
+		fog: cli.fog.map(|v| {
+			let colour = match &cli.fog_colour {
+				Some(c) => Vec3::new(c[0], c[1], c[2]),
+				None => Vec3::one(),
+			};
+			FogVolume::new(
+				aabb::AABB::new(Vec3::new(v[0], v[1], v[2]), Vec3::new(v[3], v[4], v[5])),
+				v[6],
+				colour,
+			)
+		}),
+	};
+	if cli.split_halves && matches!(cli.sampler, SamplerType::InPlace) {
+		log::warn!("the in place sampler doesn't keep even and odd samples apart");
+	}
+	if cli.normal_pass && matches!(cli.sampler, SamplerType::InPlace) {
+		log::warn!("the in place sampler doesn't keep a normal pass");
+	}
+	if cli.depth_pass.is_some() && matches!(cli.sampler, SamplerType::InPlace) {
+		log::warn!("the in place sampler doesn't keep a depth pass");
+	}
+	if cli.albedo_pass && matches!(cli.sampler, SamplerType::InPlace) {
+		log::warn!("the in place sampler doesn't keep an albedo pass");
+	}
+	if cli.lighting_pass && matches!(cli.sampler, SamplerType::InPlace) {
+		log::warn!("the in place sampler doesn't keep a lighting pass");
+	}
+	if cli.lighting_pass && !matches!(cli.render_method, RenderMethod::MIS) {
+		log::warn!("only the MIS render method splits direct and indirect light, all of it is saved as direct");
+	}
+	let render_ops = if cli.reference {
+		render_ops.reference()
+	} else {
+		render_ops
+	};
+	let render_ops = match cli.preview_scale {
+		Some(scale) => render_ops.preview(scale),
+		None => render_ops,
+	};
+	let render_ops = match cli.supersample {
+		Some(factor) if factor > 1 => render_ops.supersample(factor),
+		_ => render_ops,
 	};
+
 	let params = Parameters {
 		render_options: render_ops,
 		gui: cli.gui,
-		filename: cli.output,
+		filename,
+		bake_ao: cli.bake_ao,
+		output_size: (width, height),
+		turntable,
+		stats_out: cli.stats_out,
+		all_cameras: cli.all_cameras,
+		lut,
+		tone_map: cli.tone_map,
+		bit_depth: cli.bitdepth,
+		depth_pass: cli.depth_pass,
+		brackets: cli.brackets.unwrap_or_default(),
+		stream_output: cli.stream_output,
+		diff: cli.diff.map(|path| (path, cli.diff_amplify)),
+		alpha_mode: cli.alpha_mode,
+		shadow_colour: match &cli.shadow_colour {
+			Some(c) => Vec3::new(c[0], c[1], c[2]),
+			None => Vec3::zero(),
+		},
 	};
 	Some((scene, params))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn env_overrides() {
+		assert_eq!(capped_samples(None, Some(1)), 1);
+		assert_eq!(capped_dimensions(None, None, Some(192)), (192, 108));
+
+		// caps above the defaults leave them as they are
+		assert_eq!(capped_samples(None, Some(1000)), DEFAULT_SAMPLES);
+		assert_eq!(
+			capped_dimensions(None, None, Some(4000)),
+			(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+		);
+		assert_eq!(capped_samples(None, None), DEFAULT_SAMPLES);
+
+		// command line values take precedence
+		assert_eq!(capped_samples(Some(64), Some(1)), 64);
+		assert_eq!(
+			capped_dimensions(Some(640), None, Some(192)),
+			(640, DEFAULT_HEIGHT)
+		);
+	}
+
+	#[test]
+	fn output_filenames() {
+		let outdir = std::env::temp_dir().join(format!("rt_outdir_{}", std::process::id()));
+		let outdir = outdir.to_string_lossy().into_owned();
+		let filename = |given: &[&str]| {
+			let args = ["pathtracer", "-f", "scenes/cornell.ssml"]
+				.iter()
+				.chain(given);
+			Cli::parse_from(args).output_filename()
+		};
+
+		assert_eq!(filename(&[]), None);
+		assert_eq!(filename(&["-o", "out.exr"]).unwrap(), "out.exr");
+		let joined = filename(&["-o", "out.exr", "--outdir", &outdir]).unwrap();
+		assert_eq!(Path::new(&joined), Path::new(&outdir).join("out.exr"));
+
+		// an output directory on its own names the render automatically
+		let auto = filename(&["--outdir", &outdir]).unwrap();
+		let auto = Path::new(&auto);
+		assert_eq!(auto.parent().unwrap(), Path::new(&outdir));
+		assert!(auto.to_string_lossy().contains("cornell_"));
+		assert_eq!(auto.extension().unwrap(), "png");
+
+		// the given output's extension is kept
+		let auto = filename(&["--outdir", &outdir, "--auto-name", "-o", "out.exr"]).unwrap();
+		assert_eq!(Path::new(&auto).extension().unwrap(), "exr");
+
+		std::fs::remove_dir_all(&outdir).unwrap();
+	}
+
+	#[test]
+	fn light_keyframes_need_turntable() {
+		let args = [
+			"pathtracer",
+			"-f",
+			"scene.ssml",
+			"--light-keyframes",
+			"1",
+			"2",
+		];
+		assert!(Cli::try_parse_from(args).is_err());
+		assert!(Cli::try_parse_from(args.iter().chain(&["--turntable", "4"])).is_ok());
+	}
+
+	#[test]
+	fn config_file() {
+		let path = std::env::temp_dir().join(format!("rt_render_{}.toml", std::process::id()));
+		std::fs::write(
+			&path,
+			"scene = \"scene.ssml\"\nsamples = 64\nwidth = 320\nsampler = \"in-place\"\nexposure = -1.5\ntone_map = \"reinhard\"\n",
+		)
+		.unwrap();
+		let path = path.to_string_lossy().into_owned();
+		let args = |given: &[&str]| {
+			let mut args = vec![
+				"pathtracer".to_string(),
+				"--config".to_string(),
+				path.clone(),
+			];
+			args.extend(given.iter().map(|a| a.to_string()));
+			Cli::parse_with_config(args).unwrap()
+		};
+
+		let cli = args(&[]);
+		assert_eq!(cli.filepath, "scene.ssml");
+		assert_eq!(cli.samples(), 64);
+		assert_eq!(cli.width, Some(320));
+		assert!(matches!(cli.sampler, SamplerType::InPlace));
+		assert_eq!(cli.exposure, -1.5);
+		assert_eq!(cli.tone_map, output::ToneMap::Reinhard);
+
+		// command line values take precedence
+		let cli = args(&[
+			"--samples",
+			"10",
+			"-f",
+			"other.ssml",
+			"--tone-map",
+			"aces-filmic",
+		]);
+		assert_eq!(cli.samples(), 10);
+		assert_eq!(cli.tone_map, output::ToneMap::AcesFilmic);
+		assert_eq!(cli.filepath, "other.ssml");
+		assert_eq!(cli.width, Some(320));
+
+		std::fs::write(&path, "samples = 64\nunknown = 1\n").unwrap();
+		assert!(
+			Cli::parse_with_config(vec!["pathtracer".into(), format!("--config={path}")]).is_err()
+		);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -1,8 +1,15 @@
-use implementations::random_sampler::RandomSampler;
+use crate::parameters::SceneType;
+use implementations::aabb::{AABound, AABB};
 use implementations::rt_core::*;
+use implementations::triangle::{MeshData, MeshTriangle};
 use implementations::*;
+use implementations::{
+	adaptive_sampler::AdaptiveSampler, in_place_sampler::InPlaceSampler,
+	random_sampler::RandomSampler, stratified_sampler::StratifiedSampler,
+};
+use rand::thread_rng;
 use region::Region;
-use std::mem::ManuallyDrop;
+use std::{fmt, fs::File, io::BufWriter, mem::ManuallyDrop, ptr::NonNull, sync::Arc};
 
 pub struct Scene<M, P, C, S, A>
 where
@@ -13,10 +20,19 @@ where
 	A: AccelerationStructure<Object = P, Material = M, Sky = S>,
 {
 	acceleration: A,
-	camera: C,
-	_region: ManuallyDrop<Region>,
+	cameras: Vec<C>,
+	// index of the camera that renders
+	camera: usize,
+	region: ManuallyDrop<Region>,
+	// primitive indices, the material in the region each points to and what that material
+	// is on a given frame
+	animations: Vec<(usize, NonNull<M>, MaterialAnimation<M>)>,
+	// names of the meshes that loaded without any triangles
+	empty_meshes: Vec<String>,
 }
 
+type MaterialAnimation<M> = Box<dyn Fn(u64) -> M>;
+
 impl<M, P, C, S, A> Scene<M, P, C, S, A>
 where
 	M: Scatter,
@@ -25,20 +41,350 @@ where
 	S: NoHit<M>,
 	A: AccelerationStructure<Object = P, Material = M, Sky = S>,
 {
-	pub fn new(acceleration: A, camera: C, region: ManuallyDrop<Region>) -> Self {
+	// renders from the first camera until another is selected
+	pub fn new(acceleration: A, cameras: Vec<C>, region: ManuallyDrop<Region>) -> Self {
+		assert!(!cameras.is_empty(), "a scene needs a camera");
 		Self {
 			acceleration,
-			camera,
-			_region: region,
+			cameras,
+			camera: 0,
+			region,
+			animations: Vec::new(),
+			empty_meshes: Vec::new(),
 		}
 	}
+
+	pub fn with_empty_meshes(mut self, empty_meshes: Vec<String>) -> Self {
+		self.empty_meshes = empty_meshes;
+		self
+	}
+
+	pub fn camera(&self) -> &C {
+		&self.cameras[self.camera]
+	}
+
+	// replaces the selected camera
+	pub fn set_camera(&mut self, camera: C) {
+		self.cameras[self.camera] = camera;
+	}
+
+	pub fn camera_count(&self) -> usize {
+		self.cameras.len()
+	}
+
+	// returns false, leaving the camera unchanged, if there is no camera at the index
+	pub fn select_camera(&mut self, index: usize) -> bool {
+		if index >= self.cameras.len() {
+			return false;
+		}
+		self.camera = index;
+		true
+	}
 	pub fn render<T>(
 		&self,
 		opts: RenderOptions,
 		update: Option<(&mut T, impl Fn(&mut T, &SamplerProgress, u64) -> bool)>,
 	) {
-		let sampler = RandomSampler {};
-		sampler.sample_image(opts, &self.camera, &self.acceleration, update);
+		match opts.sampler {
+			SamplerType::Random => {
+				RandomSampler.sample_image(opts, self.camera(), &self.acceleration, update)
+			}
+			SamplerType::InPlace => {
+				InPlaceSampler.sample_image(opts, self.camera(), &self.acceleration, update)
+			}
+			SamplerType::Stratified => {
+				StratifiedSampler.sample_image(opts, self.camera(), &self.acceleration, update)
+			}
+			SamplerType::Adaptive => {
+				AdaptiveSampler.sample_image(opts, self.camera(), &self.acceleration, update)
+			}
+		}
+	}
+
+	// renders tile by tile to a .pfm file without holding the image, returning the rays shot
+	pub fn render_to_file(&self, opts: RenderOptions, path: &str) -> std::io::Result<u64> {
+		tiled_output::render_to_pfm(opts, self.camera(), &self.acceleration, path)
+	}
+}
+
+// likely mistakes in a scene, indices refer to primitives in the acceleration structure
+#[derive(Debug, PartialEq, Eq)]
+pub enum Warning {
+	CameraInsideGeometry,
+	NothingInView,
+	ZeroAreaPrimitive(usize),
+	ZeroAreaLight(usize),
+	InvalidMaterial(usize),
+	EmptyMesh(String),
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Warning::CameraInsideGeometry => write!(f, "camera is inside opaque geometry"),
+			Warning::NothingInView => write!(f, "nothing is in the centre of the camera's view"),
+			Warning::ZeroAreaPrimitive(i) => write!(f, "primitive {i} has zero area"),
+			Warning::ZeroAreaLight(i) => write!(f, "light {i} has zero area and emits nothing"),
+			Warning::InvalidMaterial(i) => {
+				write!(
+					f,
+					"primitive {i} has a material with negative or NaN values"
+				)
+			}
+			Warning::EmptyMesh(name) => write!(f, "mesh {name} has no triangles"),
+		}
+	}
+}
+
+impl<M, P, C, S, A> Scene<M, P, C, S, A>
+where
+	M: Scatter,
+	P: Primitive<Material = M>,
+	C: Camera,
+	S: NoHit<M>,
+	A: AccelerationStructure<Object = P, Material = M, Sky = S>,
+{
+	pub fn validate(&self) -> Vec<Warning> {
+		let mut warnings = Vec::new();
+
+		let mut index = 0;
+		while let Some(primitive) = self.acceleration.get_object(index) {
+			if primitive.area() <= 0.0 {
+				warnings.push(if primitive.material_is_light() {
+					Warning::ZeroAreaLight(index)
+				} else {
+					Warning::ZeroAreaPrimitive(index)
+				});
+			}
+			if primitive.material().is_some_and(|m| !m.is_valid()) {
+				warnings.push(Warning::InvalidMaterial(index));
+			}
+			index += 1;
+		}
+		warnings.extend(self.empty_meshes.iter().cloned().map(Warning::EmptyMesh));
+
+		let (intersection, index) =
+			self.acceleration
+				.check_hit(&self.camera().get_ray(0.5, 0.5, &mut thread_rng()));
+		if index == usize::MAX {
+			warnings.push(Warning::NothingInView);
+		} else if !intersection.hit.out && !intersection.material.is_delta() {
+			warnings.push(Warning::CameraInsideGeometry);
+		}
+
+		warnings
+	}
+}
+
+impl<'a, M, C, S, A> Scene<M, AllPrimitives<'a, M>, C, S, A>
+where
+	M: Scatter,
+	C: Camera,
+	S: NoHit<M>,
+	A: AccelerationStructure<Object = AllPrimitives<'a, M>, Material = M, Sky = S>,
+{
+	// bakes ambient occlusion for every mesh in the scene, if there is more than
+	// one mesh each gets its own file with the mesh's index appended to the name
+	pub fn bake_ao(&self, filename: &str, samples: u64) -> std::io::Result<()> {
+		let mut meshes: Vec<(*const MeshData, Vec<MeshTriangle<'a, M>>)> = Vec::new();
+		let mut index = 0;
+		while let Some(primitive) = self.acceleration.get_object(index) {
+			if let AllPrimitives::MeshTriangle(triangle) = primitive {
+				let mesh = Arc::as_ptr(&triangle.mesh);
+				let triangle = MeshTriangle::new(
+					triangle.point_indices,
+					triangle.normal_indices,
+					triangle.material,
+					triangle.mesh.clone(),
+				);
+				match meshes.iter_mut().find(|(m, _)| *m == mesh) {
+					Some((_, triangles)) => triangles.push(triangle),
+					None => meshes.push((mesh, vec![triangle])),
+				}
+			}
+			index += 1;
+		}
+
+		if meshes.is_empty() {
+			log::warn!("scene contains no meshes to bake");
+		}
+
+		let count = meshes.len();
+		for (i, (_, triangles)) in meshes.into_iter().enumerate() {
+			let ao = bake::bake_ao(&self.acceleration, &triangles, samples);
+			let path = match count {
+				1 => filename.to_owned(),
+				_ => numbered_path(filename, i),
+			};
+			let mut writer = BufWriter::new(File::create(&path)?);
+			bake::write_obj(&mut writer, &triangles, &ao)?;
+			println!("Baked ambient occlusion to {path}");
+		}
+		Ok(())
+	}
+}
+
+impl<'a, M, S, A> Scene<M, AllPrimitives<'a, M>, SimpleCamera, S, A>
+where
+	M: Scatter,
+	S: NoHit<M>,
+	A: AccelerationStructure<Object = AllPrimitives<'a, M>, Material = M, Sky = S>,
+{
+	// bounds of every primitive in the scene, None when it's empty
+	pub fn bounds(&self) -> Option<AABB> {
+		let mut bounds: Option<AABB> = None;
+		let mut index = 0;
+		while let Some(primitive) = self.acceleration.get_object(index) {
+			AABB::merge(&mut bounds, primitive.get_aabb());
+			index += 1;
+		}
+		bounds
+	}
+
+	// centre of the bounds of every primitive in the scene
+	pub fn centre(&self) -> Vec3 {
+		self.bounds()
+			.map_or(Vec3::zero(), |bounds| 0.5 * (bounds.min + bounds.max))
+	}
+
+	// a camera with the default field of view looking at the centre of the scene from the
+	// front and a little above, far enough back that the sphere around its bounds fills the
+	// narrower side of the image, focused on the centre
+	pub fn suggested_camera(&self, aspect_ratio: Float) -> Option<SimpleCamera> {
+		const FOV: Float = 40.0;
+		let bounds = self.bounds()?;
+		let centre = 0.5 * (bounds.min + bounds.max);
+		let radius = (0.5 * bounds.get_extent().mag()).max(EPSILON);
+
+		// the fov is horizontal so a tall image is narrower across than down
+		let half_width = (0.5 * FOV.to_radians()).tan();
+		let half_angle = half_width.min(half_width / aspect_ratio).atan();
+		let distance = radius / half_angle.sin();
+
+		let origin = centre + distance * Vec3::new(0.0, 0.3, -1.0).normalised();
+		Some(SimpleCamera::new(
+			origin,
+			centre,
+			Vec3::y(),
+			FOV,
+			aspect_ratio,
+			0.0,
+			distance,
+		))
+	}
+
+	// a turntable orbit passing through the current camera position, looking at the height
+	// where the camera's view passes closest to the orbit's axis
+	pub fn orbit(&self) -> Orbit {
+		let camera = self.camera();
+		let offset = camera.origin - self.centre();
+		let forward =
+			camera.lower_left + 0.5 * (camera.horizontal + camera.vertical) - camera.origin;
+
+		let (offset_xz, forward_xz) = (
+			Vec3::new(offset.x, 0.0, offset.z),
+			Vec3::new(forward.x, 0.0, forward.z),
+		);
+		let elevation = match forward_xz.mag_sq() {
+			len_sq if len_sq > 0.0 => {
+				let t = (-offset_xz.dot(forward_xz) / len_sq).max(0.0);
+				offset.y + t * forward.y
+			}
+			_ => 0.0,
+		};
+
+		Orbit {
+			radius: offset_xz.mag(),
+			height: offset.y,
+			elevation,
+			start: offset.z.atan2(offset.x),
+		}
+	}
+
+	// the closure gives the material of the primitive at the index for each frame, the
+	// material has to stay a light or not like the one it replaces. The primitive gets a
+	// material of its own in the region that each frame overwrites.
+	pub fn animate_material(&mut self, index: usize, material: impl Fn(u64) -> M + 'static) {
+		let slot = NonNull::from(&mut *self.region.alloc(material(0)));
+		match self.acceleration.get_object_mut(index) {
+			Some(primitive) => primitive.set_material(unsafe { slot.as_ref() }),
+			None => {
+				log::warn!("no primitive {index} to animate");
+				return;
+			}
+		}
+		self.animations.push((index, slot, Box::new(material)));
+	}
+
+	// writes every animated material for the frame over the last frame's
+	pub fn animation_frame(&mut self, frame: u64) {
+		for (index, slot, material) in &mut self.animations {
+			// the primitive's reference is taken again after the write so no reference from
+			// before it is used, the region lives as long as the scene
+			let material: &'a M = unsafe {
+				*slot.as_ptr() = material(frame);
+				slot.as_ref()
+			};
+			if let Some(primitive) = self.acceleration.get_object_mut(*index) {
+				primitive.set_material(material);
+			}
+		}
+	}
+
+	// moves the camera to the frame of a turntable animation around the scene's centre
+	pub fn turntable_frame(&mut self, frame: u64, frames: u64, orbit: &Orbit) {
+		let centre = self.centre();
+		let angle =
+			orbit.start + std::f64::consts::TAU as Float * frame as Float / frames.max(1) as Float;
+
+		let origin = centre
+			+ Vec3::new(
+				orbit.radius * angle.cos(),
+				orbit.height,
+				orbit.radius * angle.sin(),
+			);
+		let lookat = centre + orbit.elevation * Vec3::y();
+		let camera = &mut self.cameras[self.camera];
+		*camera = camera.looking_from(origin, lookat, Vec3::y());
+	}
+}
+
+impl SceneType<'static> {
+	pub fn keyframe_lights(&mut self, from: Float, to: Float, frames: u64) {
+		let mut lights = Vec::new();
+		let mut index = 0;
+		while let Some(primitive) = self.acceleration.get_object(index) {
+			if let Some(AllMaterials::Emit(emit)) = primitive.material() {
+				lights.push((index, emit.clone()));
+			}
+			index += 1;
+		}
+		for (index, emit) in lights {
+			self.animate_material(index, move |frame| {
+				let t = frame as Float / (frames.max(2) - 1) as Float;
+				let scale = from + t * (to - from);
+				AllMaterials::Emit(Emit::new(emit.texture, scale * emit.strength))
+			});
+		}
+	}
+}
+
+// circle around the y axis through a scene's centre, the camera sits radius from the axis
+// and height above the centre looking at the point elevation above the centre. Start is
+// the angle of the first frame from +x towards +z in radians.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Orbit {
+	pub radius: Float,
+	pub height: Float,
+	pub elevation: Float,
+	pub start: Float,
+}
+
+// inserts the index before the file's extension, e.g. "image.png" becomes "image_3.png"
+pub fn numbered_path(filename: &str, i: usize) -> String {
+	match filename.rsplit_once('.') {
+		Some((stem, extension)) => format!("{stem}_{i}.{extension}"),
+		None => format!("{filename}_{i}"),
 	}
 }
 
@@ -52,6 +398,373 @@ where
 {
 }
 
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use implementations::sphere::Sphere;
+
+	type Tex = AllTextures;
+	type Mat = AllMaterials<'static, Tex>;
+	type Prim = AllPrimitives<'static, Mat>;
+	type SkyType = Sky<'static, Tex, Mat>;
+
+	fn lambertian(colour: Vec3) -> &'static Mat {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(colour))));
+		Box::leak(Box::new(AllMaterials::Lambertian(Lambertian::new(
+			texture, 0.5,
+		))))
+	}
+
+	// the primitives viewed from -z under a black sky
+	fn scene(primitives: Vec<Prim>) -> SceneType<'static> {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(
+			Vec3::zero(),
+		))));
+		let sky_material = Box::leak(Box::new(AllMaterials::Emit(Emit::new(texture, 1.0))));
+		let sky: SkyType = Sky::new(texture, sky_material, (0, 0));
+
+		let mut region = Region::new();
+		let bvh = Bvh::new(
+			region.alloc_slice(&primitives),
+			sky,
+			split::SplitType::Sah,
+			TraversalOrder::Axis,
+		);
+		let camera = SimpleCamera::new(
+			Vec3::new(0.0, 0.0, -5.0),
+			Vec3::zero(),
+			Vec3::y(),
+			40.0,
+			1.0,
+			0.0,
+			5.0,
+		);
+		Scene::new(bvh, vec![camera], region)
+	}
+
+	#[test]
+	fn validate() {
+		let scene = scene(vec![
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(3.0, 0.0, 0.0),
+				0.0,
+				lambertian(Vec3::one()),
+			)),
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::zero(),
+				1.0,
+				lambertian(Vec3::new(0.5, -0.5, 0.5)),
+			)),
+		]);
+
+		let warnings = scene.validate();
+		assert_eq!(warnings.len(), 2);
+		assert!(warnings
+			.iter()
+			.any(|w| matches!(w, Warning::ZeroAreaPrimitive(_))));
+		assert!(warnings
+			.iter()
+			.any(|w| matches!(w, Warning::InvalidMaterial(_))));
+
+		let scene = scene.with_empty_meshes(vec!["teapot.obj".to_owned()]);
+		let warnings = scene.validate();
+		assert_eq!(warnings.len(), 3);
+		assert!(warnings.contains(&Warning::EmptyMesh("teapot.obj".to_owned())));
+	}
+
+	#[test]
+	fn preview() {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(
+			Vec3::one(),
+		))));
+		let light = Box::leak(Box::new(AllMaterials::Emit(Emit::new(texture, 1.0))));
+		let scene = scene(vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::zero(),
+			1.0,
+			light,
+		))]);
+
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: 32,
+			height: 16,
+			..Default::default()
+		}
+		.preview(0.5);
+		assert_eq!((options.width, options.height), (16, 8));
+
+		let mut image = Vec::new();
+		scene.render(
+			options,
+			Some((
+				&mut image,
+				|image: &mut Vec<Float>, progress: &SamplerProgress, _| {
+					image.clone_from(&progress.current_image);
+					false
+				},
+			)),
+		);
+		assert_eq!(image.len(), 16 * 8 * 3);
+
+		let upscaled = output::upscale(&image, (16, 8), (32, 16));
+		assert_eq!(upscaled.len(), 32 * 16 * 3);
+		// the sphere stays in the centre with black sky around it
+		assert_eq!(upscaled[0], 0.0);
+		assert_eq!(upscaled[3 * (8 * 32 + 16)], 1.0);
+	}
+
+	#[test]
+	fn supersample() {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(
+			Vec3::one(),
+		))));
+		let light = Box::leak(Box::new(AllMaterials::Emit(Emit::new(texture, 1.0))));
+		let scene = scene(vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::zero(),
+			1.0,
+			light,
+		))]);
+
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: 32,
+			height: 16,
+			..Default::default()
+		}
+		.supersample(2);
+		assert_eq!((options.width, options.height), (64, 32));
+
+		let mut image = Vec::new();
+		scene.render(
+			options,
+			Some((
+				&mut image,
+				|image: &mut Vec<Float>, progress: &SamplerProgress, _| {
+					image.clone_from(&progress.current_image);
+					false
+				},
+			)),
+		);
+		assert_eq!(image.len(), 4 * 32 * 16 * 3);
+
+		let downsampled = output::downsample(&image, (64, 32), 2);
+		assert_eq!(downsampled.len(), 32 * 16 * 3);
+		for (y, x) in (0..16).flat_map(|y| (0..32).map(move |x| (y, x))) {
+			let block = [(0, 0), (1, 0), (0, 1), (1, 1)]
+				.iter()
+				.map(|(dx, dy)| image[3 * ((2 * y + dy) * 64 + 2 * x + dx)])
+				.sum::<Float>();
+			assert!((downsampled[3 * (y * 32 + x)] - block / 4.0).abs() < 0.0001);
+		}
+		assert_eq!(downsampled[0], 0.0);
+		assert_eq!(downsampled[3 * (8 * 32 + 16)], 1.0);
+		// pixels on the sphere's edge are partly covered
+		assert!(downsampled.iter().any(|&v| v > 0.0 && v < 1.0));
+	}
+
+	#[test]
+	fn all_cameras() {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(
+			Vec3::one(),
+		))));
+		let light = Box::leak(Box::new(AllMaterials::Emit(Emit::new(texture, 1.0))));
+		let mut scene = scene(vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::zero(),
+			1.0,
+			light,
+		))]);
+		// the second camera looks away from the sphere
+		let away =
+			scene
+				.camera()
+				.looking_from(Vec3::new(0.0, 0.0, -5.0), -10.0 * Vec3::z(), Vec3::y());
+		scene.cameras.push(away);
+		assert_eq!(scene.camera_count(), 2);
+		assert!(!scene.select_camera(2));
+
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: 16,
+			height: 16,
+			..Default::default()
+		};
+		let images: Vec<Vec<Float>> = (0..scene.camera_count())
+			.map(|camera| {
+				assert!(scene.select_camera(camera));
+				let mut image = Vec::new();
+				scene.render(
+					options,
+					Some((
+						&mut image,
+						|image: &mut Vec<Float>, progress: &SamplerProgress, _| {
+							image.clone_from(&progress.current_image);
+							false
+						},
+					)),
+				);
+				image
+			})
+			.collect();
+		assert_eq!(images[0][3 * (8 * 16 + 8)], 1.0);
+		assert!(images[1].iter().all(|&v| v == 0.0));
+	}
+
+	#[test]
+	fn keyframed_light() {
+		let texture = Box::leak(Box::new(AllTextures::SolidColour(SolidColour::new(
+			Vec3::one(),
+		))));
+		let light = Box::leak(Box::new(AllMaterials::Emit(Emit::new(texture, 1.0))));
+		// large enough to cover every jittered ray through the pixel that is checked
+		let mut scene = scene(vec![AllPrimitives::Sphere(Sphere::new(
+			Vec3::zero(),
+			2.0,
+			light,
+		))]);
+		scene.animate_material(0, |frame| {
+			AllMaterials::Emit(Emit::new(texture, 1.0 + frame as Float))
+		});
+
+		let options = RenderOptions {
+			samples_per_pixel: 1,
+			width: 8,
+			height: 8,
+			..Default::default()
+		};
+		let mut brightness = Vec::new();
+		for frame in 0..4 {
+			scene.animation_frame(frame);
+			let mut image = Vec::new();
+			scene.render(
+				options,
+				Some((
+					&mut image,
+					|image: &mut Vec<Float>, progress: &SamplerProgress, _| {
+						image.clone_from(&progress.current_image);
+						false
+					},
+				)),
+			);
+			brightness.push(image[3 * (4 * 8 + 4)]);
+		}
+		assert_eq!(brightness, [1.0, 2.0, 3.0, 4.0]);
+	}
+
+	#[test]
+	fn turntable() {
+		let mut scene = scene(vec![
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(1.0, 0.0, 0.0),
+				1.0,
+				lambertian(Vec3::one()),
+			)),
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(3.0, 0.0, 0.0),
+				1.0,
+				lambertian(Vec3::one()),
+			)),
+		]);
+		let centre = scene.centre();
+		assert_eq!(centre, Vec3::new(2.0, 0.0, 0.0));
+
+		// the default orbit passes through the scene's camera, which looks along z at the
+		// centre's height
+		let orbit = scene.orbit();
+		assert!(orbit.elevation.abs() < 0.0001);
+		let camera = scene.camera().origin;
+		scene.turntable_frame(0, 8, &orbit);
+		assert!((scene.camera().origin - camera).mag() < 0.0001);
+
+		let orbit = Orbit {
+			radius: 4.0,
+			height: 1.0,
+			elevation: 0.5,
+			..orbit
+		};
+		let frames = 8;
+		let mut origin = |frame| {
+			scene.turntable_frame(frame, frames, &orbit);
+			scene.camera().origin
+		};
+		let first = origin(0);
+		for frame in 1..frames / 2 {
+			origin(frame);
+		}
+		let opposite = origin(frames / 2);
+
+		for origin in [first, opposite] {
+			let offset = origin - centre;
+			assert!((offset.y - 1.0).abs() < 0.0001);
+			assert!((Vec3::new(offset.x, 0.0, offset.z).mag() - 4.0).abs() < 0.0001);
+		}
+		// half a turn apart the origins mirror each other through the centre's axis
+		let (a, b) = (first - centre, opposite - centre);
+		assert!((a.x + b.x).abs() < 0.0001 && (a.z + b.z).abs() < 0.0001);
+
+		// the camera looks at the point elevation above the centre
+		let forward = scene.camera().lower_left
+			+ 0.5 * (scene.camera().horizontal + scene.camera().vertical)
+			- opposite;
+		let lookat = centre + 0.5 * Vec3::y();
+		assert!((forward.normalised() - (lookat - opposite).normalised()).mag() < 0.0001);
+
+		assert_eq!(numbered_path("turntable.png", 3), "turntable_3.png");
+	}
+
+	#[test]
+	fn suggested_camera() {
+		let scene = scene(vec![
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(10.0, -2.0, 4.0),
+				1.0,
+				lambertian(Vec3::one()),
+			)),
+			AllPrimitives::Sphere(Sphere::new(
+				Vec3::new(16.0, 1.0, 5.0),
+				0.5,
+				lambertian(Vec3::one()),
+			)),
+		]);
+		let bounds = scene.bounds().unwrap();
+		assert_eq!(bounds.min, Vec3::new(9.0, -3.0, 3.0));
+		assert_eq!(bounds.max, Vec3::new(16.5, 1.5, 5.5));
+
+		for aspect_ratio in [16.0 / 9.0, 1.0, 0.5] {
+			let camera = scene.suggested_camera(aspect_ratio).unwrap();
+			assert_eq!(camera.aspect_ratio, aspect_ratio);
+			// the centre of the bounds is in the middle of the image and in focus
+			let (uv, depth) = camera.project(scene.centre()).unwrap();
+			assert!((uv - Vec2::new(0.5, 0.5)).mag() < 0.0001);
+			assert!((depth - 1.0).abs() < 0.0001);
+
+			for i in 0..8 {
+				let corner = Vec3::new(
+					if i & 1 == 0 {
+						bounds.min.x
+					} else {
+						bounds.max.x
+					},
+					if i & 2 == 0 {
+						bounds.min.y
+					} else {
+						bounds.max.y
+					},
+					if i & 4 == 0 {
+						bounds.min.z
+					} else {
+						bounds.max.z
+					},
+				);
+				let (uv, _) = camera.project(corner).unwrap();
+				assert!(
+					(0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y),
+					"{aspect_ratio} {corner:?} {uv:?}"
+				);
+			}
+		}
+	}
+}
+
 /*#[cfg(test)]
 mod tests {
 	use super::*;
@@ -136,7 +849,12 @@ primitive (
 			load_str_full::<Tex, Mat, Prim, SimpleCamera, SkyType>(&mut region, DATA).unwrap();
 
 		let (p, camera, sky) = stuff;
-		let bvh: Bvh<Prim, Mat, SkyType> = Bvh::new(p, sky.clone(), split::SplitType::Sah);
+		let bvh: Bvh<Prim, Mat, SkyType> = Bvh::new(
+			p,
+			sky.clone(),
+			split::SplitType::Sah,
+			TraversalOrder::Axis,
+		);
 
 		let scene: SceneType<'static> = Scene::new(bvh, camera, sky, region);
 